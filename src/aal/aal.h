@@ -32,6 +32,10 @@
 #  define PLATFORM_IS_SPARC
 #endif
 
+#if defined(__wasm__)
+#  define PLATFORM_IS_WASM
+#endif
+
 namespace snmalloc
 {
   /**
@@ -169,6 +173,8 @@ namespace snmalloc
 #  include "aal_powerpc.h"
 #elif defined(PLATFORM_IS_SPARC)
 #  include "aal_sparc.h"
+#elif defined(PLATFORM_IS_WASM)
+#  include "aal_wasm.h"
 #endif
 
 namespace snmalloc
@@ -0,0 +1,44 @@
+#pragma once
+
+#if defined(__wasm64__)
+#  define SNMALLOC_VA_BITS_64
+#else
+#  define SNMALLOC_VA_BITS_32
+#endif
+
+namespace snmalloc
+{
+  /**
+   * WebAssembly architecture abstraction layer.
+   */
+  class AAL_Wasm
+  {
+  public:
+    /**
+     * Bitmap of AalFeature flags
+     */
+    static constexpr uint64_t aal_features =
+      IntegerPointers | NoCpuCycleCounters;
+
+    static constexpr enum AalName aal_name = Wasm;
+
+    /**
+     * WebAssembly memory has no pages that can be protected or decommitted,
+     * so this is only the granularity at which snmalloc manages memory.  The
+     * 64KiB unit in which linear memory grows would waste too much.
+     */
+    static constexpr size_t smallest_page_size = 0x1000;
+
+    /**
+     * WebAssembly has no spin-loop hint.
+     */
+    static inline void pause() {}
+
+    static inline void prefetch(void* ptr)
+    {
+      UNUSED(ptr);
+    }
+  };
+
+  using AAL_Arch = AAL_Wasm;
+} // namespace snmalloc
@@ -0,0 +1,52 @@
+#pragma once
+
+#include "defines.h"
+
+#include <cstddef>
+
+/**
+ * AddressSanitizer support.
+ *
+ * If snmalloc is built with `SNMALLOC_ASAN` defined, freed small and medium
+ * objects are poisoned through ASan's interface, so that a program
+ * instrumented with ASan, such as a Rust binary built with
+ * `-Zsanitizer=address`, reports uses after free.  The program must link the
+ * ASan runtime, which provides these functions.  snmalloc itself must not be
+ * instrumented, as it reads and writes the headers of free objects.
+ * Otherwise, the functions here do nothing.
+ */
+#ifdef SNMALLOC_ASAN
+extern "C" void
+__asan_poison_memory_region(void const volatile* addr, size_t size);
+extern "C" void
+__asan_unpoison_memory_region(void const volatile* addr, size_t size);
+#endif
+
+namespace snmalloc
+{
+  /**
+   * Mark `size` bytes at `p` as inaccessible to instrumented code.
+   */
+  inline void asan_poison(void* p, size_t size)
+  {
+#ifdef SNMALLOC_ASAN
+    __asan_poison_memory_region(p, size);
+#else
+    UNUSED(p);
+    UNUSED(size);
+#endif
+  }
+
+  /**
+   * Mark `size` bytes at `p` as accessible to instrumented code.
+   */
+  inline void asan_unpoison(void* p, size_t size)
+  {
+#ifdef SNMALLOC_ASAN
+    __asan_unpoison_memory_region(p, size);
+#else
+    UNUSED(p);
+    UNUSED(size);
+#endif
+  }
+} // namespace snmalloc
@@ -148,6 +148,22 @@ namespace snmalloc
       debug_check();
     }
 
+    /**
+     * Returns the number of elements in the list, excluding this one.
+     */
+    size_t length()
+    {
+      size_t n = 0;
+      Ptr<CDLLNode> item = this->get_next();
+
+      while (item != Ptr<CDLLNode>(this))
+      {
+        n++;
+        item = item->get_next();
+      }
+      return n;
+    }
+
     /**
      * Checks the lists invariants
      *   x->next->prev = x
@@ -160,6 +160,17 @@ namespace snmalloc
       }
     }
 
+    /**
+     * Apply `f` to each element of the list, from head to tail.  `f` must
+     * not modify the list.
+     */
+    template<typename F>
+    void for_each(F f)
+    {
+      for (Ptr<T> curr = head; curr != Terminator(); curr = curr->next)
+        f(curr);
+    }
+
     void debug_check_contains(Ptr<T> item)
     {
 #ifndef NDEBUG
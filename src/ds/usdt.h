@@ -0,0 +1,33 @@
+#pragma once
+
+/**
+ * Statically defined tracing (USDT) probes.
+ *
+ * If snmalloc is built with `SNMALLOC_USDT` defined, the `SNMALLOC_PROBE*`
+ * macros emit probes in the `snmalloc` provider using `<sys/sdt.h>`, which
+ * can be attached to with bpftrace, SystemTap or DTrace.  An unattached probe
+ * is a single no-op instruction.  Otherwise, the macros expand to nothing and
+ * their arguments are not evaluated.
+ *
+ * The probes are:
+ *  - `slab_new(sizeclass)`: a new slab was assigned to a small sizeclass.
+ *  - `mediumslab_new(sizeclass)`: a new chunk was used for a medium
+ *    sizeclass.
+ *  - `large_alloc(size)`: a large allocation was made.
+ *  - `os_reserve(base, size)`: address space was reserved from the platform.
+ *  - `os_commit(base, size)`: memory was committed for use.
+ *  - `os_decommit(base, size)`: memory was returned to the platform.
+ *  - `remote_post(from, to, count)`: `count` remote deallocations were sent
+ *    from the allocator with identity `from` to the one with identity `to`.
+ */
+#ifdef SNMALLOC_USDT
+#  include <sys/sdt.h>
+#  define SNMALLOC_PROBE1(name, a) DTRACE_PROBE1(snmalloc, name, a)
+#  define SNMALLOC_PROBE2(name, a, b) DTRACE_PROBE2(snmalloc, name, a, b)
+#  define SNMALLOC_PROBE3(name, a, b, c) \
+    DTRACE_PROBE3(snmalloc, name, a, b, c)
+#else
+#  define SNMALLOC_PROBE1(name, a)
+#  define SNMALLOC_PROBE2(name, a, b)
+#  define SNMALLOC_PROBE3(name, a, b, c)
+#endif
@@ -1,5 +1,6 @@
 #include "../ds/address.h"
 #include "../ds/flaglock.h"
+#include "../ds/usdt.h"
 #include "../pal/pal.h"
 #include "arenamap.h"
 
@@ -43,6 +44,51 @@ namespace snmalloc
      */
     std::atomic_flag spin_lock = ATOMIC_FLAG_INIT;
 
+    /**
+     * Total address space obtained from the platform, provided at
+     * construction, or donated, in bytes.  This never decreases.
+     */
+    std::atomic<size_t> reserved_bytes{0};
+
+    /**
+     * The lowest address and one past the highest address of the address
+     * space obtained, so that the pagemap can be walked over just that range.
+     */
+    std::atomic<address_t> lowest{~address_t(0)};
+    std::atomic<address_t> highest{0};
+
+    /**
+     * True once memory has been donated, after which large requests are
+     * served from `ranges` if possible even if the platform could align them.
+     */
+    std::atomic<bool> has_donations{false};
+
+    /**
+     * Account for a block of address space obtained from the platform.
+     */
+    void note_reserved(CapPtr<void, CBChunk> base, size_t size)
+    {
+      SNMALLOC_PROBE2(os_reserve, base.unsafe_capptr, size);
+      note_range(base, size);
+    }
+
+    /**
+     * Account for a block of address space, however it was obtained.
+     */
+    void note_range(CapPtr<void, CBChunk> base, size_t size)
+    {
+      reserved_bytes += size;
+
+      address_t start = address_cast(base);
+      address_t end = start + size;
+      address_t l = lowest.load(std::memory_order_relaxed);
+      while ((start < l) && !lowest.compare_exchange_weak(l, start))
+      {}
+      address_t h = highest.load(std::memory_order_relaxed);
+      while ((end > h) && !highest.compare_exchange_weak(h, end))
+      {}
+    }
+
     /**
      * Checks a block satisfies its invariant.
      */
@@ -70,14 +116,13 @@ namespace snmalloc
         return;
       }
 
+      // Add to linked list.  The link is written even at the end of the
+      // list, as donated or unreserved memory need not read as zero.
+      commit_block(base, sizeof(void*));
+      *(base.template as_static<CapPtr<void, CBChunk>>().unsafe_capptr) =
+        ranges[align_bits][1];
       if (ranges[align_bits][1] != nullptr)
-      {
-        // Add to linked list.
-        commit_block(base, sizeof(void*));
-        *(base.template as_static<CapPtr<void, CBChunk>>().unsafe_capptr) =
-          ranges[align_bits][1];
         check_block(ranges[align_bits][1], align_bits);
-      }
 
       // Update head of list
       ranges[align_bits][1] = base;
@@ -195,9 +240,16 @@ namespace snmalloc
       if constexpr (
         pal_supports<AlignedAllocation, PAL> && !aal_supports<StrictProvenance>)
       {
-        if (size >= PAL::minimum_alloc_size)
-          return CapPtr<void, CBChunk>(
+        if (
+          (size >= PAL::minimum_alloc_size) &&
+          !has_donations.load(std::memory_order_relaxed))
+        {
+          auto res = CapPtr<void, CBChunk>(
             PAL::template reserve_aligned<committed>(size));
+          if (res != nullptr)
+            note_reserved(res, size);
+          return res;
+        }
       }
 
       CapPtr<void, CBChunk> res;
@@ -228,9 +280,10 @@ namespace snmalloc
             {
               /*
                * We will have handled the case where size >= minimum_alloc_size
-               * above, so we are left to handle only small things here.
+               * above, so we are left to handle only small things here, unless
+               * memory has been donated.
                */
-              block_size = PAL::minimum_alloc_size;
+              block_size = bits::max(size, PAL::minimum_alloc_size);
             }
 
             void* block_raw = PAL::template reserve_aligned<false>(block_size);
@@ -277,6 +330,7 @@ namespace snmalloc
           {
             return nullptr;
           }
+          note_reserved(block, block_size);
           add_range(block, block_size);
 
           // still holding lock so guaranteed to succeed.
@@ -336,10 +390,70 @@ namespace snmalloc
      * of memory.
      */
     AddressSpaceManager(CapPtr<void, CBChunk> base, size_t length)
+    : reserved_bytes(length),
+      lowest(address_cast(base)),
+      highest(address_cast(base) + length)
     {
       add_range(base, length);
     }
 
+    /**
+     * Return a block obtained from `reserve` or `reserve_with_left_over` of
+     * `size` bytes, so that its address space can be handed out again.  It
+     * must be zeroed and need not be committed.
+     */
+    void unreserve(CapPtr<void, CBChunk> base, size_t size)
+    {
+      FlagLock lock(spin_lock);
+      add_range(base, size);
+    }
+
+    /**
+     * Add `length` bytes at `base`, which the caller has mapped, to the
+     * memory that this address-space manager hands out, as if it had been
+     * reserved from the platform.  The range is trimmed to whole pages.  It
+     * need not be zeroed, but must never be unmapped.  Returns false if no
+     * whole page remains.
+     */
+    bool donate(CapPtr<void, CBChunk> base, size_t length)
+    {
+      auto start = pointer_align_up<OS_PAGE_SIZE, void>(base);
+      auto end =
+        pointer_align_down<OS_PAGE_SIZE, void>(pointer_offset(base, length));
+      if (address_cast(end) <= address_cast(start))
+        return false;
+
+      size_t size = pointer_diff(start, end);
+      FlagLock lock(spin_lock);
+      note_range(start, size);
+      add_range(start, size);
+      has_donations.store(true, std::memory_order_relaxed);
+      return true;
+    }
+
+    /**
+     * Returns the total address space, in bytes, that this address-space
+     * manager has obtained.  Address space is never returned, so this is also
+     * the high-water mark.
+     */
+    size_t reserved()
+    {
+      return reserved_bytes.load(std::memory_order_relaxed);
+    }
+
+    /**
+     * Returns the lowest address and one past the highest address of the
+     * address space that this address-space manager has obtained, or an
+     * empty range if it has none.  Not all of the range need be reserved.
+     */
+    std::pair<address_t, address_t> reserved_range()
+    {
+      address_t h = highest.load(std::memory_order_relaxed);
+      if (h == 0)
+        return {0, 0};
+      return {lowest.load(std::memory_order_relaxed), h};
+    }
+
     /**
      * Move assignment operator.  This should only be used during initialisation
      * of the system.  There should be no concurrency.
@@ -351,6 +465,18 @@ namespace snmalloc
       if (other.spin_lock.test_and_set())
         abort();
       ranges = other.ranges;
+      reserved_bytes.store(
+        other.reserved_bytes.load(std::memory_order_relaxed),
+        std::memory_order_relaxed);
+      lowest.store(
+        other.lowest.load(std::memory_order_relaxed),
+        std::memory_order_relaxed);
+      highest.store(
+        other.highest.load(std::memory_order_relaxed),
+        std::memory_order_relaxed);
+      has_donations.store(
+        other.has_donations.load(std::memory_order_relaxed),
+        std::memory_order_relaxed);
       return *this;
     }
   };
@@ -16,6 +16,7 @@
 #include "remoteallocator.h"
 #include "sizeclasstable.h"
 #include "slab.h"
+#include "threadstats.h"
 
 #include <array>
 #include <functional>
@@ -39,6 +40,24 @@ namespace snmalloc
     OnePastEnd
   };
 
+  /**
+   * Occupancy of the slabs of a single sizeclass owned by an allocator.
+   */
+  struct SlabOccupancy
+  {
+    /**
+     * Slabs with no free objects available to the allocator.  This includes
+     * the slab currently being bump allocated from, and slabs whose free
+     * objects have been taken into the allocator's fast free list.
+     */
+    size_t full = 0;
+
+    /**
+     * Slabs with free objects, but at least one live object.
+     */
+    size_t partial = 0;
+  };
+
   // This class is just used so that the free lists are the first entry
   // in the allocator and hence has better code gen.
   // It contains a free list per small size class.  These are used for
@@ -109,12 +128,44 @@ namespace snmalloc
      */
     CapPtr<void, CBChunk> bump_ptrs[NUM_SMALL_CLASSES] = {nullptr};
 
+    /**
+     * Number of slabs (small sizeclasses) or Mediumslabs (medium
+     * sizeclasses) currently owned by this allocator, per sizeclass.  Only
+     * updated on slow paths, and read by other threads for statistics.
+     */
+    StatCounter slab_count[NUM_SIZECLASSES];
+
+    /**
+     * Large allocations made by this allocator, less those it has freed.
+     * Large allocations are often freed by a different allocator, so only
+     * the total over all allocators is meaningful; it wraps below zero.
+     */
+    size_t large_live = 0;
+
+    /**
+     * Counters for the thread that owns this allocator.
+     */
+    ThreadStats thread_stats_;
+
   public:
     Stats& stats()
     {
       return large_allocator.stats;
     }
 
+    const ThreadStats& thread_stats()
+    {
+      return thread_stats_;
+    }
+
+    /**
+     * Zero the thread counters, when the allocator passes to a new thread.
+     */
+    void reset_thread_stats()
+    {
+      thread_stats_ = {};
+    }
+
     template<class MP, class Alloc>
     friend class AllocPool;
 
@@ -132,7 +183,10 @@ namespace snmalloc
       void* result = external_alloc::aligned_alloc(
         natural_alignment(size), round_size(size));
       if constexpr (zero_mem == YesZero)
-        memset(result, 0, size);
+      {
+        if (result != nullptr)
+          memset(result, 0, size);
+      }
       return result;
 #else
       constexpr sizeclass_t sizeclass = size_to_sizeclass_const(size);
@@ -170,7 +224,10 @@ namespace snmalloc
       void* result = external_alloc::aligned_alloc(
         natural_alignment(size), round_size(size));
       if constexpr (zero_mem == YesZero)
-        memset(result, 0, size);
+      {
+        if (result != nullptr)
+          memset(result, 0, size);
+      }
       return result;
 #else
       // Perform the - 1 on size, so that zero wraps around and ends up on
@@ -363,6 +420,82 @@ namespace snmalloc
 #endif
     }
 
+    /**
+     * Shrink the large allocation `p_raw` from `old_size` to `new_size` bytes
+     * without moving it, returning the memory past `new_size` to the
+     * platform.  Both sizes must be larger than the largest sizeclass.
+     *
+     * The whole chunks past the new large class are split off and returned
+     * to the large stacks decommitted, and the pages past `new_size` in the
+     * chunk that remains are zeroed, which releases them on platforms that
+     * zero by remapping.  The allocation must then be freed with `new_size`.
+     * The chunks split off are counted as freed by this thread.
+     */
+    void large_shrink(void* p_raw, size_t old_size, size_t new_size)
+    {
+#ifdef SNMALLOC_PASS_THROUGH
+      UNUSED(p_raw);
+      UNUSED(old_size);
+      UNUSED(new_size);
+#else
+      SNMALLOC_ASSERT(new_size <= old_size);
+      SNMALLOC_ASSERT(new_size > sizeclass_to_size(NUM_SIZECLASSES - 1));
+
+      if (NeedsInitialisation(this))
+      {
+        InitThreadAllocator([p_raw, old_size, new_size](void* alloc) {
+          reinterpret_cast<Allocator*>(alloc)->large_shrink(
+            p_raw, old_size, new_size);
+          return nullptr;
+        });
+        return;
+      }
+
+      size_t old_bits =
+        bits::max(bits::next_pow2_bits(old_size), SUPERSLAB_BITS);
+      size_t new_bits =
+        bits::max(bits::next_pow2_bits(new_size), SUPERSLAB_BITS);
+
+      auto p_ret = CapPtr<void, CBAllocE>(p_raw);
+      auto p_auth = large_allocator.capptr_amplify(p_ret);
+
+      check_client_pagemap(
+        chunkmap().get(address_cast(p_ret)) == old_bits,
+        "Claimed large shrink with wrong size class",
+        p_raw,
+        NUM_SIZECLASSES + old_bits - SUPERSLAB_BITS);
+
+      size_t rsize = bits::one_at_bit(new_bits);
+      auto slab = Aal::capptr_bound<Largeslab, CBChunk>(
+        p_auth, bits::one_at_bit(old_bits));
+
+      if (new_bits != old_bits)
+      {
+        chunkmap().clear_large_size(slab, bits::one_at_bit(old_bits));
+        chunkmap().set_large_size(slab, rsize);
+        stats().large_dealloc(old_bits - SUPERSLAB_BITS);
+        stats().large_alloc(new_bits - SUPERSLAB_BITS);
+        thread_stats_.dealloc(bits::one_at_bit(old_bits) - rsize);
+
+        // Each chunk split off is the same size as everything before it.
+        for (size_t chunk_bits = new_bits; chunk_bits < old_bits; chunk_bits++)
+        {
+          size_t chunk_size = bits::one_at_bit(chunk_bits);
+          auto chunk = Aal::capptr_bound<Largeslab, CBChunk>(
+            pointer_offset(p_auth, chunk_size), chunk_size);
+          large_allocator.dealloc_decommitted(
+            chunk, chunk_bits - SUPERSLAB_BITS);
+        }
+      }
+
+      size_t kept = bits::align_up(new_size, OS_PAGE_SIZE);
+      size_t used = bits::min(bits::align_up(old_size, OS_PAGE_SIZE), rsize);
+      if (kept < used)
+        pal_zero<typename MemoryProvider::Pal, true>(
+          pointer_offset(slab, kept), used - kept);
+#endif
+    }
+
     template<Boundary location = Start>
     void* external_pointer(void* p_raw)
     {
@@ -503,6 +636,126 @@ namespace snmalloc
       return public_state()->trunc_id();
     }
 
+    /**
+     * Return the truncated ID (see `get_trunc_id`) of the allocator that owns
+     * the slab containing `p_raw`.
+     *
+     * Large allocations are not owned by any allocator, and nor is memory
+     * that was not allocated by snmalloc; for both of these, this returns 0,
+     * which is never a valid allocator ID.
+     */
+    size_t get_owner_trunc_id(const void* p_raw)
+    {
+#ifdef SNMALLOC_PASS_THROUGH
+      UNUSED(p_raw);
+      return 0;
+#else
+      uint8_t chunkmap_slab_kind = chunkmap().get(address_cast(p_raw));
+      if (
+        (chunkmap_slab_kind != CMSuperslab) &&
+        (chunkmap_slab_kind != CMMediumslab))
+        return 0;
+
+      auto p_ret = CapPtr<void, CBAllocE>(const_cast<void*>(p_raw));
+      auto p_auth = large_allocator.capptr_amplify(p_ret);
+
+      // Superslabs and Mediumslabs share the Allocslab header.
+      return Superslab::get(p_auth)->get_allocator()->trunc_id();
+#endif
+    }
+
+    /**
+     * Returns true if `p_raw` points into a chunk that this allocator's heap
+     * is currently using, so that a pointer from an unknown source can be
+     * passed to `dealloc` if it is the start of a live allocation.  This is a
+     * single pagemap lookup and does not check that the object is live.
+     */
+    bool owns(const void* p_raw)
+    {
+#ifdef SNMALLOC_PASS_THROUGH
+      UNUSED(p_raw);
+      return false;
+#else
+      return chunkmap().get(address_cast(p_raw)) != CMNotOurs;
+#endif
+    }
+
+    /**
+     * Return the approximate number of messages waiting in this allocator's
+     * incoming message queue, and the high-water mark of that number.
+     *
+     * An allocator only processes its queue when it allocates or frees, so a
+     * thread that receives remote frees but never allocates will see this
+     * grow without bound.
+     */
+    std::pair<size_t, size_t> remote_queue_depth()
+    {
+      return {
+        public_state()->queue_depth.load(std::memory_order_relaxed),
+        public_state()->peak_queue_depth.load(std::memory_order_relaxed)};
+    }
+
+    /**
+     * Return the number of full and partially full slabs of the given
+     * sizeclass owned by this allocator.  Slabs whose objects have all been
+     * freed are returned to their superslab, so are counted by
+     * `unused_slabs` rather than here.
+     *
+     * This walks the allocator's internal lists, so must only be called by
+     * the thread that owns the allocator.
+     */
+    SlabOccupancy slab_occupancy(sizeclass_t sizeclass)
+    {
+      SlabOccupancy result;
+
+      if (sizeclass < NUM_SMALL_CLASSES)
+      {
+        result.partial = small_classes[sizeclass].length();
+      }
+      else if (sizeclass < NUM_SIZECLASSES)
+      {
+        medium_classes[sizeclass - NUM_SMALL_CLASSES].for_each(
+          [&result](auto) { result.partial++; });
+      }
+      else
+      {
+        return result;
+      }
+
+      SNMALLOC_ASSERT(slab_count[sizeclass] >= result.partial);
+      result.full = slab_count[sizeclass] - result.partial;
+      return result;
+    }
+
+    /**
+     * Return the number of slabs of the given sizeclass owned by this
+     * allocator, full or not.  Other threads may call this, but the result is
+     * then only approximate.
+     */
+    size_t slabs(sizeclass_t sizeclass)
+    {
+      return slab_count[sizeclass];
+    }
+
+    /**
+     * Return the number of empty slabs retained by this allocator in
+     * partially used superslabs.  These are not assigned to any sizeclass and
+     * can only be reused by this allocator.
+     *
+     * This walks the allocator's internal lists, so must only be called by
+     * the thread that owns the allocator.
+     */
+    size_t unused_slabs()
+    {
+      size_t result = 0;
+      auto count = [&result](CapPtr<Superslab, CBChunk> super) {
+        result += super->unused_slabs();
+      };
+      super_available.for_each(count);
+      super_only_short_available.for_each(count);
+      return result;
+    }
+
   private:
     using alloc_id_t = typename Remote::alloc_id_t;
 
@@ -616,6 +869,117 @@ namespace snmalloc
         }
       }
 
+      flush_small_caches();
+
+      for (auto& small_class : small_classes)
+      {
+        test(small_class);
+      }
+
+      for (auto& medium_class : medium_classes)
+      {
+        test(medium_class);
+      }
+
+      test(super_available);
+      test(super_only_short_available);
+
+      // Place the static stub message on the queue.
+      init_message_queue();
+      public_state()->queue_depth.store(0, std::memory_order_relaxed);
+    }
+
+    /**
+     * Large allocations made by this allocator, less those it has freed.
+     * Only the total over all allocators is meaningful.
+     */
+    size_t debug_large_live()
+    {
+      return large_live;
+    }
+
+    /**
+     * Return everything this allocator is caching to where other allocators
+     * can reuse it: objects freed by other threads are processed, objects
+     * freed by this thread for other allocators are posted to them, and the
+     * bump allocators and fast free lists are returned to their slabs.  Slabs
+     * and superslabs that become empty are released.  Objects that are still
+     * live, including the stub of the message queue, keep their slabs owned
+     * by this allocator.
+     *
+     * Returns the bytes in chunks handed back to the memory provider.
+     *
+     * This must only be called by the thread that owns the allocator.
+     */
+    size_t flush()
+    {
+      size_t returned = large_allocator.returned_bytes;
+
+      flush_small_caches();
+
+      while (has_messages())
+        handle_message_queue_inner();
+
+      stats().remote_post();
+      remote_cache.post<Allocator>(this, get_trunc_id());
+
+      return large_allocator.returned_bytes - returned;
+    }
+
+    /**
+     * Process every object that other allocators have freed for this one,
+     * and post the objects this one has freed for others, so that neither is
+     * left for a later allocation or deallocation to do.  Unlike `flush`,
+     * the bump allocators and free lists are kept.
+     *
+     * Returns the number of objects processed.
+     *
+     * This must only be called by the thread that owns the allocator.
+     */
+    size_t flush_remote()
+    {
+      size_t processed = 0;
+      while (has_messages())
+        processed += handle_message_queue_inner();
+
+      if (remote_cache.capacity < REMOTE_CACHE)
+      {
+        stats().remote_post();
+        remote_cache.post<Allocator>(this, get_trunc_id());
+      }
+
+      return processed;
+    }
+
+    template<Boundary location>
+    static CapPtr<void, CBAllocE> external_pointer(
+      CapPtr<void, CBAllocE> p_ret,
+      sizeclass_t sizeclass,
+      CapPtr<void, CBAllocE> end_point)
+    {
+      size_t rsize = sizeclass_to_size(sizeclass);
+
+      auto end_point_correction = location == End ?
+        pointer_offset_signed(end_point, -1) :
+        (location == OnePastEnd ?
+           end_point :
+           pointer_offset_signed(end_point, -static_cast<ptrdiff_t>(rsize)));
+
+      size_t offset_from_end =
+        pointer_diff(p_ret, pointer_offset_signed(end_point, -1));
+
+      size_t end_to_end = round_by_sizeclass(sizeclass, offset_from_end);
+
+      return pointer_offset_signed(
+        end_point_correction, -static_cast<ptrdiff_t>(end_to_end));
+    }
+
+    /**
+     * Return the objects in the bump allocators and fast free lists to their
+     * slabs.
+     */
+    void flush_small_caches()
+    {
       // Dump bump allocators back into memory
       for (size_t i = 0; i < NUM_SMALL_CLASSES; i++)
       {
@@ -653,44 +1017,8 @@ namespace snmalloc
             auto curr = small_fast_free_lists[i].take(entropy);
             small_dealloc_offseted_inner(super, slab, curr, i);
           } while (!small_fast_free_lists[i].empty());
-
-          test(small_classes[i]);
         }
       }
-
-      for (auto& medium_class : medium_classes)
-      {
-        test(medium_class);
-      }
-
-      test(super_available);
-      test(super_only_short_available);
-
-      // Place the static stub message on the queue.
-      init_message_queue();
-    }
-
-    template<Boundary location>
-    static CapPtr<void, CBAllocE> external_pointer(
-      CapPtr<void, CBAllocE> p_ret,
-      sizeclass_t sizeclass,
-      CapPtr<void, CBAllocE> end_point)
-    {
-      size_t rsize = sizeclass_to_size(sizeclass);
-
-      auto end_point_correction = location == End ?
-        pointer_offset_signed(end_point, -1) :
-        (location == OnePastEnd ?
-           end_point :
-           pointer_offset_signed(end_point, -static_cast<ptrdiff_t>(rsize)));
-
-      size_t offset_from_end =
-        pointer_diff(p_ret, pointer_offset_signed(end_point, -1));
-
-      size_t end_to_end = round_by_sizeclass(sizeclass, offset_from_end);
-
-      return pointer_offset_signed(
-        end_point_correction, -static_cast<ptrdiff_t>(end_to_end));
     }
 
     void init_message_queue()
@@ -716,6 +1044,7 @@ namespace snmalloc
         auto p_auth = large_allocator.template capptr_amplify<Remote>(p);
         auto super = Superslab::get(p_auth);
         auto sizeclass = p->sizeclass();
+        thread_stats_.remote_receive();
         dealloc_not_large_local(super, Remote::clear(p), sizeclass);
       }
       else
@@ -755,32 +1084,44 @@ namespace snmalloc
       if (likely(sizeclass < NUM_SMALL_CLASSES))
       {
         SNMALLOC_ASSERT(super->get_kind() == Super);
-        check_client(
+        check_client_dealloc(
           super->get_kind() == Super,
-          "Heap Corruption: Sizeclass of remote dealloc corrupt.");
+          "Heap Corruption: Sizeclass of remote dealloc corrupt.",
+          p.unsafe_capptr,
+          sizeclass);
         auto slab = Metaslab::get_slab(Aal::capptr_rebound(super.as_void(), p));
-        check_client(
+        check_client_dealloc(
           super->get_meta(slab)->sizeclass() == sizeclass,
-          "Heap Corruption: Sizeclass of remote dealloc corrupt.");
+          "Heap Corruption: Sizeclass of remote dealloc corrupt.",
+          p.unsafe_capptr,
+          sizeclass);
         small_dealloc_offseted(super, slab, p, sizeclass);
       }
       else
       {
         auto medium = super.template as_reinterpret<Mediumslab>();
         SNMALLOC_ASSERT(medium->get_kind() == Medium);
-        check_client(
+        check_client_dealloc(
           medium->get_kind() == Medium,
-          "Heap Corruption: Sizeclass of remote dealloc corrupt.");
-        check_client(
+          "Heap Corruption: Sizeclass of remote dealloc corrupt.",
+          p.unsafe_capptr,
+          sizeclass);
+        check_client_dealloc(
           medium->get_sizeclass() == sizeclass,
-          "Heap Corruption: Sizeclass of remote dealloc corrupt.");
+          "Heap Corruption: Sizeclass of remote dealloc corrupt.",
+          p.unsafe_capptr,
+          sizeclass);
         medium_dealloc_local(medium, p, sizeclass);
       }
     }
 
-    SNMALLOC_SLOW_PATH void handle_message_queue_inner()
+    /**
+     * Handle up to `REMOTE_BATCH` messages, returning the number handled.
+     */
+    SNMALLOC_SLOW_PATH size_t handle_message_queue_inner()
     {
-      for (size_t i = 0; i < REMOTE_BATCH; i++)
+      size_t i = 0;
+      for (; i < REMOTE_BATCH; i++)
       {
         auto r = message_queue().dequeue();
 
@@ -789,13 +1130,15 @@ namespace snmalloc
 
         handle_dealloc_remote(r.first);
       }
+      public_state()->note_processed(i);
 
       // Our remote queues may be larger due to forwarding remote frees.
       if (likely(remote_cache.capacity > 0))
-        return;
+        return i;
 
       stats().remote_post();
       remote_cache.post<Allocator>(this, get_trunc_id());
+      return i;
     }
 
     /**
@@ -924,7 +1267,9 @@ namespace snmalloc
       {
         stats().alloc_request(size);
         stats().sizeclass_alloc(sizeclass);
+        thread_stats_.alloc(sizeclass_to_size(sizeclass));
         auto p = fl.take(entropy);
+        asan_unpoison(p.unsafe_capptr, sizeclass_to_size(sizeclass));
         if constexpr (zero_mem == YesZero)
         {
           pal_zero<typename MemoryProvider::Pal>(
@@ -968,6 +1313,7 @@ namespace snmalloc
       {
         stats().alloc_request(size);
         stats().sizeclass_alloc(sizeclass);
+        thread_stats_.alloc(rsize);
 
         auto meta = sl.get_next().template as_static<Metaslab>();
         auto& ffl = small_fast_free_lists[sizeclass];
@@ -990,6 +1336,7 @@ namespace snmalloc
       {
         stats().alloc_request(size);
         stats().sizeclass_alloc(sizeclass);
+        thread_stats_.alloc(sizeclass_to_size(sizeclass));
         return small_alloc_new_free_list<zero_mem>(sizeclass);
       }
       return small_alloc_first_alloc<zero_mem>(sizeclass, size);
@@ -1050,10 +1397,11 @@ namespace snmalloc
       Slab::alloc_new_list(bp, ffl, rsize, entropy);
 
       auto p = ffl.take(entropy);
+      asan_unpoison(p.unsafe_capptr, rsize);
 
       if constexpr (zero_mem == YesZero)
       {
-        pal_zero<typename MemoryProvider::Pal>(p, sizeclass_to_size(sizeclass));
+        pal_zero<typename MemoryProvider::Pal>(p, rsize);
       }
 
       // TODO: Should this be zeroing the next pointer?
@@ -1074,6 +1422,8 @@ namespace snmalloc
       auto slab = alloc_slab(sizeclass);
       if (slab == nullptr)
         return nullptr;
+      slab_count[sizeclass]++;
+      SNMALLOC_PROBE1(slab_new, sizeclass);
       bp = pointer_offset(
         slab, get_initial_offset(sizeclass, Metaslab::is_short(slab)));
 
@@ -1086,9 +1436,11 @@ namespace snmalloc
       CapPtr<void, CBAllocE> p_ret,
       sizeclass_t sizeclass)
     {
-      check_client(
+      check_client_pagemap(
         chunkmap().get(address_cast(p_ret)) == CMSuperslab,
-        "Claimed small deallocation is not in a Superslab");
+        "Claimed small deallocation is not in a Superslab",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       small_dealloc_checked_chunkmap(super, p_auth, p_ret, sizeclass);
     }
@@ -1100,9 +1452,11 @@ namespace snmalloc
       sizeclass_t sizeclass)
     {
       auto slab = Metaslab::get_slab(p_auth);
-      check_client(
+      check_client_dealloc(
         sizeclass == super->get_meta(slab)->sizeclass(),
-        "Claimed small deallocation with mismatching size class");
+        "Claimed small deallocation with mismatching size class",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       small_dealloc_checked_sizeclass(super, slab, p_auth, p_ret, sizeclass);
     }
@@ -1114,13 +1468,31 @@ namespace snmalloc
       CapPtr<void, CBAllocE> p_ret,
       sizeclass_t sizeclass)
     {
-      check_client(
+      check_client_dealloc(
         Slab::get_meta(slab)->is_start_of_object(address_cast(p_ret)),
-        "Not deallocating start of an object");
+        "Not deallocating start of an object",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       small_dealloc_start(super, slab, p_auth, p_ret, sizeclass);
     }
 
+    /**
+     * Poison the object `p` of `sizeclass` that is being freed for
+     * AddressSanitizer, apart from the header that the allocator writes into
+     * free objects, which it must be able to access.  The object is
+     * unpoisoned when it is allocated again, and its chunk when that is
+     * returned to the large allocator.
+     */
+    static void
+    asan_poison_freed(CapPtr<void, CBAlloc> p, sizeclass_t sizeclass)
+    {
+      static_assert(sizeof(Remote) <= MIN_ALLOC_SIZE);
+      asan_poison(
+        pointer_offset(p, sizeof(Remote)).unsafe_capptr,
+        sizeclass_to_size(sizeclass) - sizeof(Remote));
+    }
+
     SNMALLOC_FAST_PATH void small_dealloc_start(
       CapPtr<Superslab, CBChunkD> super,
       CapPtr<Slab, CBChunkD> slab,
@@ -1135,9 +1507,11 @@ namespace snmalloc
 
       auto p =
         Aal::capptr_bound<void, CBAlloc>(p_auth, sizeclass_to_size(sizeclass));
+      asan_poison_freed(p, sizeclass);
 
       if (likely(target == public_state()))
       {
+        thread_stats_.dealloc(sizeclass_to_size(sizeclass));
         small_dealloc_offseted(super, slab, p, sizeclass);
       }
       else
@@ -1179,6 +1553,7 @@ namespace snmalloc
       if (likely(a == Superslab::NoSlabReturn))
         return;
       stats().sizeclass_dealloc_slab(sizeclass);
+      slab_count[sizeclass]--;
 
       if (a == Superslab::NoStatusChange)
         return;
@@ -1218,6 +1593,7 @@ namespace snmalloc
           super_available.remove(super_slab);
 
           chunkmap().clear_slab(super_slab);
+          asan_unpoison(super_slab.unsafe_capptr, SUPERSLAB_SIZE);
           large_allocator.dealloc(
             super_slab.template as_reinterpret<Largeslab>(), 0);
           stats().superslab_push();
@@ -1274,6 +1650,8 @@ namespace snmalloc
 
         Mediumslab::init(newslab, public_state(), sizeclass, rsize);
         chunkmap().set_slab(newslab);
+        slab_count[sizeclass]++;
+        SNMALLOC_PROBE1(mediumslab_new, sizeclass);
 
         auto newslab_export = capptr_export(newslab);
 
@@ -1286,6 +1664,7 @@ namespace snmalloc
 
       stats().alloc_request(size);
       stats().sizeclass_alloc(sizeclass);
+      thread_stats_.alloc(rsize);
 
       return p;
     }
@@ -1297,9 +1676,11 @@ namespace snmalloc
       CapPtr<void, CBAllocE> p_ret,
       sizeclass_t sizeclass)
     {
-      check_client(
+      check_client_pagemap(
         chunkmap().get(address_cast(p_ret)) == CMMediumslab,
-        "Claimed medium deallocation is not in a Mediumslab");
+        "Claimed medium deallocation is not in a Mediumslab",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       medium_dealloc_checked_chunkmap(slab, p_auth, p_ret, sizeclass);
     }
@@ -1311,9 +1692,11 @@ namespace snmalloc
       CapPtr<void, CBAllocE> p_ret,
       sizeclass_t sizeclass)
     {
-      check_client(
+      check_client_dealloc(
         slab->get_sizeclass() == sizeclass,
-        "Claimed medium deallocation of the wrong sizeclass");
+        "Claimed medium deallocation of the wrong sizeclass",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       medium_dealloc_checked_sizeclass(slab, p_auth, p_ret, sizeclass);
     }
@@ -1325,10 +1708,12 @@ namespace snmalloc
       CapPtr<void, CBAllocE> p_ret,
       sizeclass_t sizeclass)
     {
-      check_client(
+      check_client_dealloc(
         is_multiple_of_sizeclass(
           sizeclass, address_cast(slab) + SUPERSLAB_SIZE - address_cast(p_ret)),
-        "Not deallocating start of an object");
+        "Not deallocating start of an object",
+        p_ret.unsafe_capptr,
+        sizeclass);
 
       medium_dealloc_start(slab, p_auth, p_ret, sizeclass);
     }
@@ -1349,9 +1734,13 @@ namespace snmalloc
       // mediumslabs store free objects by offset rather than pointer.
       auto p =
         Aal::capptr_bound<void, CBAlloc>(p_auth, sizeclass_to_size(sizeclass));
+      asan_poison_freed(p, sizeclass);
 
       if (likely(target == public_state()))
+      {
+        thread_stats_.dealloc(sizeclass_to_size(sizeclass));
         medium_dealloc_local(slab, p, sizeclass);
+      }
       else
       {
         remote_dealloc(target, p, sizeclass);
@@ -1384,9 +1773,11 @@ namespace snmalloc
         }
 
         chunkmap().clear_slab(slab_bounded);
+        asan_unpoison(slab_bounded.unsafe_capptr, SUPERSLAB_SIZE);
         large_allocator.dealloc(
           slab_bounded.template as_reinterpret<Largeslab>(), 0);
         stats().superslab_push();
+        slab_count[sizeclass]--;
       }
       else if (was_full)
       {
@@ -1412,7 +1803,10 @@ namespace snmalloc
 
       size_t size_bits = bits::next_pow2_bits(size);
       size_t large_class = size_bits - SUPERSLAB_BITS;
-      SNMALLOC_ASSERT(large_class < NUM_LARGE_CLASSES);
+
+      // No address space can hold a larger request.
+      if (unlikely(large_class >= NUM_LARGE_CLASSES))
+        return nullptr;
 
       size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
       // For superslab size, we always commit the whole range.
@@ -1424,9 +1818,12 @@ namespace snmalloc
       if (likely(p != nullptr))
       {
         chunkmap().set_large_size(p, size);
+        large_live++;
 
         stats().alloc_request(size);
         stats().large_alloc(large_class);
+        thread_stats_.alloc(rsize);
+        SNMALLOC_PROBE1(large_alloc, size);
       }
       return capptr_export(Aal::capptr_bound<void, CBAlloc>(p, rsize));
     }
@@ -1440,9 +1837,11 @@ namespace snmalloc
       // This also catches some "not deallocating start of an object" cases: if
       // we're so far from the start that our actual chunkmap slab kind is not a
       // legitimate large class
-      check_client(
+      check_client_pagemap(
         chunkmap().get(address_cast(p_ret)) == claimed_chunkmap_slab_kind,
-        "Claimed large deallocation with wrong size class");
+        "Claimed large deallocation with wrong size class",
+        p_ret.unsafe_capptr,
+        NUM_SIZECLASSES + claimed_chunkmap_slab_kind - SUPERSLAB_BITS);
 
       // round up as we would if we had had to look up the chunkmap_slab_kind
       size_t rsize = bits::one_at_bit(claimed_chunkmap_slab_kind);
@@ -1457,9 +1856,11 @@ namespace snmalloc
       size_t size,
       uint8_t chunkmap_slab_kind)
     {
-      check_client(
+      check_client_dealloc(
         address_cast(Superslab::get(p_auth)) == address_cast(p_ret),
-        "Not deallocating start of an object");
+        "Not deallocating start of an object",
+        p_ret.unsafe_capptr,
+        NUM_SIZECLASSES + chunkmap_slab_kind - SUPERSLAB_BITS);
       SNMALLOC_ASSERT(bits::one_at_bit(chunkmap_slab_kind) >= SUPERSLAB_SIZE);
 
       large_dealloc_start(p_auth, p_ret, size, chunkmap_slab_kind);
@@ -1488,8 +1889,10 @@ namespace snmalloc
       auto slab = Aal::capptr_bound<Largeslab, CBChunk>(p_auth, size);
 
       chunkmap().clear_large_size(slab, size);
+      large_live--;
 
       stats().large_dealloc(large_class);
+      thread_stats_.dealloc(size);
 
       // Initialise in order to set the correct SlabKind.
       slab->init();
@@ -1511,6 +1914,7 @@ namespace snmalloc
       if (remote_cache.capacity > 0)
       {
         stats().remote_free(sizeclass);
+        thread_stats_.remote_send(sizeclass_to_size(sizeclass));
         remote_cache.dealloc<Allocator>(target->trunc_id(), p, sizeclass);
         return;
       }
@@ -1549,6 +1953,7 @@ namespace snmalloc
       handle_message_queue();
 
       stats().remote_free(sizeclass);
+      thread_stats_.remote_send(sizeclass_to_size(sizeclass));
       remote_cache.dealloc<Allocator>(target->trunc_id(), p_auth, sizeclass);
 
       stats().remote_post();
@@ -5,25 +5,108 @@
 namespace snmalloc
 {
 // The CHECK_CLIENT macro is used to turn on minimal checking of the client
-// calling the API correctly.
+// calling the API correctly.  It enables all of the individual hardening
+// options below, which can also be enabled separately:
+//
+//  - SNMALLOC_CHECK_FREELIST randomises the order of free lists and encodes
+//    their pointers, so that corruption of a free object, for example by a
+//    use after free or a double free, is likely to be detected.
+//  - SNMALLOC_CHECK_DEALLOC checks that a deallocated pointer is the start of
+//    an object, and that its size and the sizeclass recorded for a remote
+//    deallocation match the allocator's metadata.
+//  - SNMALLOC_CHECK_PAGEMAP checks deallocations against the pagemap, and
+//    rederives the owner of a remote deallocation from the pagemap rather
+//    than trusting the value stored in the freed object.
+//  - SNMALLOC_CHECK_BOUNDS checks the sizes that callers pass to the Rust
+//    shim's sized entry points, such as the old size given to
+//    `rust_realloc`, against the allocation, before copying or clearing
+//    that many bytes of it.
 #if !defined(NDEBUG) && !defined(CHECK_CLIENT)
 #  define CHECK_CLIENT
 #endif
 
-  SNMALLOC_FAST_PATH void check_client_impl(bool test, const char* const str)
-  {
 #ifdef CHECK_CLIENT
+#  ifndef SNMALLOC_CHECK_FREELIST
+#    define SNMALLOC_CHECK_FREELIST
+#  endif
+#  ifndef SNMALLOC_CHECK_DEALLOC
+#    define SNMALLOC_CHECK_DEALLOC
+#  endif
+#  ifndef SNMALLOC_CHECK_PAGEMAP
+#    define SNMALLOC_CHECK_PAGEMAP
+#  endif
+#  ifndef SNMALLOC_CHECK_BOUNDS
+#    define SNMALLOC_CHECK_BOUNDS
+#  endif
+#endif
+
+  /**
+   * Callback invoked when a client check fails, before the process is
+   * aborted, so that the application can log the failure.  It is passed the
+   * pointer that failed the check, or null if there is none, the sizeclass
+   * claimed for it (`SIZE_MAX` if unknown), and the reason for the failure.
+   * Large sizeclasses are numbered after the small and medium ones, one per
+   * power of two from `SUPERSLAB_SIZE`.
+   *
+   * The heap may be corrupt when the callback runs, so it should do as
+   * little as possible.  A check that fails while it is running aborts
+   * without calling it again.
+   */
+  using ClientCheckReport =
+    void (*)(const void* p, size_t sizeclass, const char* reason);
+
+  inline std::atomic<ClientCheckReport> client_check_report{nullptr};
+
+  /**
+   * Install `report` to be called when a client check fails, or remove it if
+   * null.
+   */
+  inline void set_client_check_report(ClientCheckReport report)
+  {
+    client_check_report.store(report, std::memory_order_relaxed);
+  }
+
+  [[noreturn]] SNMALLOC_SLOW_PATH inline void
+  client_check_failed(const char* const str, const void* p, size_t sizeclass)
+  {
+    static thread_local bool reporting = false;
+    auto report = client_check_report.load(std::memory_order_relaxed);
+    if ((report != nullptr) && !reporting)
+    {
+      reporting = true;
+      report(p, sizeclass, str);
+    }
+    error(str);
+  }
+
+  SNMALLOC_FAST_PATH void check_client_impl(
+    bool test,
+    const char* const str,
+    const void* p = nullptr,
+    size_t sizeclass = SIZE_MAX)
+  {
     if (unlikely(!test))
-      error(str);
+      client_check_failed(str, p, sizeclass);
+  }
+#ifdef SNMALLOC_CHECK_FREELIST
+#  define check_client_freelist(test, ...) check_client_impl(test, __VA_ARGS__)
 #else
-    UNUSED(test);
-    UNUSED(str);
+#  define check_client_freelist(test, ...)
 #endif
-  }
-#ifdef CHECK_CLIENT
-#  define check_client(test, str) check_client_impl(test, str)
+#ifdef SNMALLOC_CHECK_DEALLOC
+#  define check_client_dealloc(test, ...) check_client_impl(test, __VA_ARGS__)
+#else
+#  define check_client_dealloc(test, ...)
+#endif
+#ifdef SNMALLOC_CHECK_PAGEMAP
+#  define check_client_pagemap(test, ...) check_client_impl(test, __VA_ARGS__)
 #else
-#  define check_client(test, str)
+#  define check_client_pagemap(test, ...)
+#endif
+#ifdef SNMALLOC_CHECK_BOUNDS
+#  define check_client_bounds(test, ...) check_client_impl(test, __VA_ARGS__)
+#else
+#  define check_client_bounds(test, ...)
 #endif
 
   // 0 intermediate bits results in power of 2 small allocs. 1 intermediate
@@ -49,12 +132,16 @@ namespace snmalloc
   // Handle at most this many object from the remote dealloc queue at a time.
   static constexpr size_t REMOTE_BATCH =
 #ifdef USE_REMOTE_BATCH
-    REMOTE_BATCH
+    USE_REMOTE_BATCH
 #else
     4096
 #endif
     ;
 
+#if defined(SNMALLOC_USE_LARGE_CHUNKS) && defined(SNMALLOC_USE_SMALL_CHUNKS)
+#  error SNMALLOC_USE_LARGE_CHUNKS and SNMALLOC_USE_SMALL_CHUNKS are exclusive
+#endif
+
   // Specifies smaller slab and super slab sizes for address space
   // constrained scenarios.
   static constexpr size_t USE_LARGE_CHUNKS =
@@ -111,9 +198,22 @@ namespace snmalloc
 
   static constexpr size_t PAGE_ALIGNED_SIZE = OS_PAGE_SIZE << INTERMEDIATE_BITS;
 
-  // Minimum allocation size is space for two pointers.
+  // Every allocation is aligned to at least this.  By default it is space
+  // for two pointers, which is also the minimum, as free objects hold two
+  // pointers, but it can be raised, for example to a cache line or the
+  // alignment of wide vector types.
   static_assert(bits::next_pow2_const(sizeof(void*)) == sizeof(void*));
-  static constexpr size_t MIN_ALLOC_SIZE = 2 * sizeof(void*);
+  static constexpr size_t MIN_ALIGNMENT =
+#ifdef USE_MIN_ALIGNMENT
+    USE_MIN_ALIGNMENT
+#else
+    2 * sizeof(void*)
+#endif
+    ;
+
+  // Minimum allocation size is the minimum alignment.  Every sizeclass is a
+  // multiple of this, so every object is aligned to it.
+  static constexpr size_t MIN_ALLOC_SIZE = MIN_ALIGNMENT;
   static constexpr size_t MIN_ALLOC_BITS = bits::ctz_const(MIN_ALLOC_SIZE);
 
   // Slabs are 64 KiB unless constrained to 16 or even 8 KiB
@@ -153,4 +253,11 @@ namespace snmalloc
     "SLAB_COUNT must be a power of 2");
   static_assert(
     SLAB_COUNT <= (UINT8_MAX + 1), "SLAB_COUNT must fit in a uint8_t");
+  static_assert(REMOTE_CACHE > 0, "REMOTE_CACHE must be positive");
+  static_assert(REMOTE_BATCH > 0, "REMOTE_BATCH must be positive");
+  static_assert(
+    bits::next_pow2_const(MIN_ALIGNMENT) == MIN_ALIGNMENT,
+    "MIN_ALIGNMENT must be a power of two");
+  static_assert(
+    MIN_ALIGNMENT <= 4096, "MIN_ALIGNMENT must be no more than 4096");
 } // namespace snmalloc
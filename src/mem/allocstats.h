@@ -3,6 +3,7 @@
 #include "../ds/bits.h"
 #include "../mem/sizeclass.h"
 
+#include <atomic>
 #include <cstdint>
 
 #ifdef USE_SNMALLOC_STATS
@@ -15,14 +16,68 @@
 
 namespace snmalloc
 {
+  /**
+   * Counts of the allocations and deallocations made so far, and of the
+   * bytes they covered, rounded up to their sizeclass.
+   */
+  struct AllocTotals
+  {
+    size_t allocations = 0;
+    size_t deallocations = 0;
+    size_t bytes_allocated = 0;
+    size_t bytes_freed = 0;
+  };
+
+  /**
+   * A counter that only the thread owning it updates, but which other threads
+   * may read while it changes, as `stats_snapshot` does.  It is a relaxed
+   * atomic, so updating it costs the same as updating a plain integer.
+   */
+  class StatCounter
+  {
+    std::atomic<size_t> value{0};
+
+  public:
+    operator size_t() const
+    {
+      return value.load(std::memory_order_relaxed);
+    }
+
+    StatCounter& operator=(size_t n)
+    {
+      value.store(n, std::memory_order_relaxed);
+      return *this;
+    }
+
+    StatCounter& operator+=(size_t n)
+    {
+      return *this = *this + n;
+    }
+
+    StatCounter& operator-=(size_t n)
+    {
+      return *this = *this - n;
+    }
+
+    void operator++(int)
+    {
+      *this += 1;
+    }
+
+    void operator--(int)
+    {
+      *this -= 1;
+    }
+  };
+
   template<size_t N, size_t LARGE_N>
   struct AllocStats
   {
     struct CurrentMaxPair
     {
-      size_t current = 0;
-      size_t max = 0;
-      size_t used = 0;
+      StatCounter current;
+      StatCounter max;
+      StatCounter used;
 
       void inc()
       {
@@ -129,17 +184,19 @@ namespace snmalloc
 
     Stats sizeclass[N];
 
-    size_t large_pop_count[LARGE_N] = {0};
-    size_t large_push_count[LARGE_N] = {0};
-
-    size_t remote_freed = 0;
-    size_t remote_posted = 0;
-    size_t remote_received = 0;
-    size_t superslab_push_count = 0;
-    size_t superslab_pop_count = 0;
-    size_t superslab_fresh_count = 0;
-    size_t segment_count = 0;
-    size_t bucketed_requests[TOTAL_BUCKETS] = {};
+    StatCounter large_pop_count[LARGE_N];
+    StatCounter large_push_count[LARGE_N];
+
+    StatCounter remote_freed;
+    StatCounter remote_posted;
+    StatCounter remote_received;
+    StatCounter superslab_push_count;
+    StatCounter superslab_pop_count;
+    StatCounter superslab_fresh_count;
+    StatCounter segment_count;
+    StatCounter fresh_zero_count;
+    StatCounter fresh_zero_bytes;
+    StatCounter bucketed_requests[TOTAL_BUCKETS];
 #endif
 
     void alloc_request(size_t size)
@@ -261,6 +318,20 @@ namespace snmalloc
 #endif
     }
 
+    /**
+     * A zeroed allocation of `size` bytes was served from fresh memory, which
+     * is already zero, so it was not cleared.
+     */
+    void fresh_zero(size_t size)
+    {
+      UNUSED(size);
+
+#ifdef USE_SNMALLOC_STATS
+      fresh_zero_count++;
+      fresh_zero_bytes += size;
+#endif
+    }
+
     void remote_free(sizeclass_t sc)
     {
       UNUSED(sc);
@@ -273,7 +344,7 @@ namespace snmalloc
     void remote_post()
     {
 #ifdef USE_SNMALLOC_STATS
-      remote_posted = remote_freed;
+      remote_posted = static_cast<size_t>(remote_freed);
 #endif
     }
 
@@ -286,6 +357,74 @@ namespace snmalloc
 #endif
     }
 
+    /**
+     * Returns the number of bytes in live objects, rounded up to their
+     * sizeclass, or zero if statistics are not enabled.
+     */
+    size_t live_bytes()
+    {
+#ifdef USE_SNMALLOC_STATS
+      size_t result = 0;
+      for (size_t i = 0; i < N; i++)
+        result += sizeclass[i].count.current * sizeclass_to_size(i);
+
+      // Large allocations may be freed by a different allocator, so the
+      // difference may only be meaningful once aggregated.
+      for (size_t i = 0; i < LARGE_N; i++)
+        result += (large_pop_count[i] - large_push_count[i]) *
+          (bits::one_at_bit(SUPERSLAB_BITS) << i);
+
+      return result;
+#else
+      return 0;
+#endif
+    }
+
+    /**
+     * Returns the number of live objects in sizeclass `sc` and the number
+     * ever allocated, or zeros if statistics are not enabled.
+     */
+    std::pair<size_t, size_t> sizeclass_counts(sizeclass_t sc)
+    {
+#ifdef USE_SNMALLOC_STATS
+      return {sizeclass[sc].count.current, sizeclass[sc].count.used};
+#else
+      UNUSED(sc);
+      return {0, 0};
+#endif
+    }
+
+    /**
+     * Returns the allocations and deallocations made so far, or zeros if
+     * statistics are not enabled.  A large allocation resized in place
+     * counts as a deallocation and an allocation.
+     */
+    AllocTotals totals()
+    {
+      AllocTotals result;
+#ifdef USE_SNMALLOC_STATS
+      for (size_t i = 0; i < N; i++)
+      {
+        auto& count = sizeclass[i].count;
+        size_t size = sizeclass_to_size(static_cast<sizeclass_t>(i));
+        result.allocations += count.used;
+        result.deallocations += count.used - count.current;
+        result.bytes_allocated += count.used * size;
+        result.bytes_freed += (count.used - count.current) * size;
+      }
+
+      for (size_t i = 0; i < LARGE_N; i++)
+      {
+        size_t size = bits::one_at_bit(SUPERSLAB_BITS) << i;
+        result.allocations += large_pop_count[i];
+        result.deallocations += large_push_count[i];
+        result.bytes_allocated += large_pop_count[i] * size;
+        result.bytes_freed += large_push_count[i] * size;
+      }
+#endif
+      return result;
+    }
+
     void add(AllocStats<N, LARGE_N>& that)
     {
       UNUSED(that);
@@ -310,6 +449,8 @@ namespace snmalloc
       superslab_push_count += that.superslab_push_count;
       superslab_fresh_count += that.superslab_fresh_count;
       segment_count += that.segment_count;
+      fresh_zero_count += that.fresh_zero_count;
+      fresh_zero_bytes += that.fresh_zero_bytes;
 #endif
     }
 
@@ -336,7 +477,9 @@ namespace snmalloc
             << "Superslab pop"
             << "Superslab push"
             << "Superslab fresh"
-            << "Segments" << csv.endl;
+            << "Segments"
+            << "Fresh zero"
+            << "Fresh zero bytes" << csv.endl;
 
         csv << "BucketedStats"
             << "DumpID"
@@ -410,7 +553,7 @@ namespace snmalloc
       csv << "GlobalStats" << dumpid << allocatorid << remote_freed
           << remote_posted << remote_received << superslab_pop_count
           << superslab_push_count << superslab_fresh_count << segment_count
-          << csv.endl;
+          << fresh_zero_count << fresh_zero_bytes << csv.endl;
     }
 #endif
   };
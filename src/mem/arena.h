@@ -0,0 +1,157 @@
+#pragma once
+
+#include "globalalloc.h"
+
+namespace snmalloc
+{
+  /**
+   * A region allocator, for parsers, compilers and other programs that
+   * allocate many objects with a common lifetime and free them together.
+   *
+   * Objects are bump-allocated from chunks taken from the default memory
+   * provider, as large allocations are, and are not freed individually:
+   * `reset` returns every chunk at once.  The provider counts the chunks in
+   * its statistics, and caches or decommits them on return according to its
+   * retention policy, as it does any other chunk.
+   *
+   * The arena must only be used by one thread at a time.  Its objects must
+   * not be passed to `free`, and their destructors are not run.
+   */
+  class Arena
+  {
+    /**
+     * The header at the start of each chunk.
+     */
+    struct Chunk
+    {
+      Chunk* next;
+      size_t large_class;
+    };
+
+    LargeAlloc<GlobalVirtual> large_allocator;
+
+    Chunk* chunks = nullptr;
+    char* bump = nullptr;
+    char* limit = nullptr;
+    size_t min_large_class;
+    size_t chunk_bytes = 0;
+    size_t allocated_bytes = 0;
+
+    SNMALLOC_SLOW_PATH void* alloc_slow(size_t size, size_t alignment)
+    {
+      size_t max_size = bits::one_at_bit(SUPERSLAB_BITS)
+        << (NUM_LARGE_CLASSES - 1);
+      if ((size > max_size) || (alignment > max_size))
+        return nullptr;
+
+      // Leave room for the header and for aligning the object after it.
+      size_t needed =
+        bits::max(size + alignment + sizeof(Chunk), SUPERSLAB_SIZE);
+      size_t large_class = bits::max(
+        min_large_class, bits::next_pow2_bits(needed) - SUPERSLAB_BITS);
+      if (large_class >= NUM_LARGE_CLASSES)
+        return nullptr;
+
+      size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+      auto p = large_allocator.alloc(large_class, rsize, rsize);
+      if (p == nullptr)
+        return nullptr;
+
+      chunks = new (p.unsafe_capptr) Chunk{chunks, large_class};
+      chunk_bytes += rsize;
+      bump = reinterpret_cast<char*>(chunks) + sizeof(Chunk);
+      limit = reinterpret_cast<char*>(chunks) + rsize;
+      return alloc(size, alignment);
+    }
+
+  public:
+    /**
+     * Create an arena that takes chunks of at least `chunk_size` bytes,
+     * rounded up to a power of two of at least `SUPERSLAB_SIZE`.
+     */
+    explicit Arena(size_t chunk_size = SUPERSLAB_SIZE)
+    : large_allocator(default_memory_provider()),
+      min_large_class(
+        bits::next_pow2_bits(bits::max(chunk_size, SUPERSLAB_SIZE)) -
+        SUPERSLAB_BITS)
+    {}
+
+    Arena(const Arena&) = delete;
+    Arena& operator=(const Arena&) = delete;
+
+    ~Arena()
+    {
+      reset();
+    }
+
+    /**
+     * Allocate `size` bytes aligned to `alignment`, which must be a power of
+     * two.  Returns null if the memory cannot be obtained.
+     */
+    SNMALLOC_FAST_PATH void*
+    alloc(size_t size, size_t alignment = MIN_ALIGNMENT)
+    {
+      char* p = pointer_align_up<char>(bump, alignment);
+      if (likely(
+            (bump != nullptr) && (p <= limit) &&
+            (size <= static_cast<size_t>(limit - p))))
+      {
+        bump = p + size;
+        allocated_bytes += size;
+        return p;
+      }
+      return alloc_slow(size, alignment);
+    }
+
+    /**
+     * Construct a `T` from `args` in the arena.  Its destructor is never run.
+     * Returns null if the memory cannot be obtained.
+     */
+    template<typename T, typename... Args>
+    T* make(Args&&... args)
+    {
+      void* p = alloc(sizeof(T), alignof(T));
+      if (p == nullptr)
+        return nullptr;
+      return new (p) T(std::forward<Args>(args)...);
+    }
+
+    /**
+     * Free everything allocated from the arena, returning its chunks to the
+     * default memory provider.  The arena can be used again afterwards.
+     */
+    void reset()
+    {
+      while (chunks != nullptr)
+      {
+        Chunk* c = chunks;
+        chunks = c->next;
+        size_t large_class = c->large_class;
+        auto slab =
+          CapPtr<Largeslab, CBChunk>(reinterpret_cast<Largeslab*>(c));
+        slab->init();
+        large_allocator.dealloc(slab, large_class);
+      }
+      bump = nullptr;
+      limit = nullptr;
+      chunk_bytes = 0;
+      allocated_bytes = 0;
+    }
+
+    /**
+     * The bytes allocated since the arena was created or last reset.
+     */
+    size_t allocated() const
+    {
+      return allocated_bytes;
+    }
+
+    /**
+     * The bytes in the chunks that the arena holds.
+     */
+    size_t footprint() const
+    {
+      return chunk_bytes;
+    }
+  };
+} // namespace snmalloc
@@ -0,0 +1,269 @@
+#pragma once
+
+#include "globalalloc.h"
+
+#include <fcntl.h>
+#include <sys/mman.h>
+#include <unistd.h>
+
+namespace snmalloc
+{
+  /**
+   * A pointer into a `FileHeap`, stored as its offset from the start of the
+   * file.  Unlike a raw pointer, this is meaningful wherever the file is
+   * mapped, so can be stored in the heap to build data structures that are
+   * read back from the file later, by this or another process.
+   *
+   * Offset zero is within the file's header, so is used for null.
+   */
+  template<typename T>
+  class FileOffset
+  {
+    uint64_t offset = 0;
+
+  public:
+    constexpr FileOffset() = default;
+
+    constexpr explicit FileOffset(uint64_t offset) : offset(offset) {}
+
+    uint64_t value() const
+    {
+      return offset;
+    }
+
+    bool is_null() const
+    {
+      return offset == 0;
+    }
+
+    /**
+     * Convert to a pointer, given the start of a mapping of the file.
+     */
+    T* get(void* base) const
+    {
+      if (offset == 0)
+        return nullptr;
+
+      return pointer_offset<T>(base, static_cast<size_t>(offset));
+    }
+  };
+
+  /**
+   * A heap whose memory is a shared mapping of a file, so that objects
+   * allocated in it are written back to the file.  This is for persistent
+   * caches and experiments with memory-mapped data stores.
+   *
+   * The heap has its own allocator, confined to the file, which must only be
+   * used by one thread at a time.  Objects should be freed through the heap,
+   * not through the thread-local allocator.
+   *
+   * The allocator's metadata contains raw pointers, so the heap cannot be
+   * reopened for allocation once closed: `open` always starts a new heap.
+   * The data can still be read back by mapping the file and following
+   * `FileOffset`s from the root recorded in the `Header`.
+   *
+   * This is only available on POSIX platforms.
+   */
+  class FileHeap
+  {
+  public:
+    /**
+     * The start of the file.  The rest of the file is the heap's state and
+     * the memory it allocates from, which are only meaningful while it is
+     * open.
+     */
+    struct Header
+    {
+      static constexpr char MAGIC[8] = {'S', 'N', 'F', 'H', 'E', 'A', 'P', '1'};
+
+      char magic[8];
+
+      /**
+       * Size of the file in bytes.
+       */
+      uint64_t size;
+
+      /**
+       * Offset of the object set with `set_root`, or zero if none.
+       */
+      uint64_t root;
+    };
+
+  private:
+    static bool never_init(void*)
+    {
+      return false;
+    }
+
+    static void* no_op_init(function_ref<void*(void*)>)
+    {
+      error("FileHeap allocators do not need initialisation");
+    }
+
+    using Pal = PALNoAlloc<DefaultPal>;
+
+    /**
+     * Confines amplification to the mapping of the file.
+     */
+    struct ArenaMap
+    {
+      CapPtr<void, CBArena> arena_root;
+
+      template<typename T = void, typename U, capptr_bounds B>
+      SNMALLOC_FAST_PATH CapPtr<T, CBArena> capptr_amplify(CapPtr<U, B> r)
+      {
+        return Aal::capptr_rebound<T>(arena_root, r);
+      }
+    };
+
+    using MemoryProvider = MemoryProviderStateMixin<Pal, ArenaMap>;
+
+    using HeapAlloc = Allocator<never_init, no_op_init, MemoryProvider>;
+
+    Header header;
+
+    int fd;
+
+    MemoryProvider state;
+
+    HeapAlloc allocator;
+
+    FileHeap(int fd, size_t size)
+    : fd(fd),
+      state(
+        pointer_offset(CapPtr<void, CBChunk>(this), sizeof(FileHeap)),
+        size - sizeof(FileHeap)),
+      allocator(state)
+    {
+      memcpy(header.magic, Header::MAGIC, sizeof(header.magic));
+      header.size = size;
+      header.root = 0;
+      state.arenamap().arena_root = CapPtr<void, CBArena>(this);
+    }
+
+  public:
+    /**
+     * The smallest size of file that `open` accepts.  Part of a superslab
+     * can be lost to alignment, so this allows for two.
+     */
+    static constexpr size_t min_size()
+    {
+      return sizeof(FileHeap) + 2 * SUPERSLAB_SIZE;
+    }
+
+    /**
+     * Create a heap in the file at `path`, which is created if necessary and
+     * truncated to `size` bytes, rounded up to a whole number of pages.
+     * Returns null if the file cannot be created or mapped, or if `size` is
+     * less than `min_size()`.
+     */
+    static FileHeap* open(const char* path, size_t size)
+    {
+      size = bits::align_up(size, OS_PAGE_SIZE);
+      if (size < min_size())
+        return nullptr;
+
+      int fd = ::open(path, O_RDWR | O_CREAT | O_TRUNC, 0600);
+      if (fd < 0)
+        return nullptr;
+
+      void* p = MAP_FAILED;
+      if (ftruncate(fd, static_cast<off_t>(size)) == 0)
+        p = mmap(nullptr, size, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+
+      if (p == MAP_FAILED)
+      {
+        ::close(fd);
+        return nullptr;
+      }
+
+      return new (p) FileHeap(fd, size);
+    }
+
+    /**
+     * Write the heap back to the file and unmap it.  `heap` must not be used
+     * afterwards, nor any pointers into it.
+     */
+    static void close(FileHeap* heap)
+    {
+      heap->sync();
+
+      int fd = heap->fd;
+      size_t size = static_cast<size_t>(heap->header.size);
+      heap->~FileHeap();
+      munmap(heap, size);
+      ::close(fd);
+    }
+
+    /**
+     * Write all modified pages of the heap back to the file, waiting for the
+     * writes to complete.  Returns false if this fails.
+     */
+    bool sync()
+    {
+      return msync(this, static_cast<size_t>(header.size), MS_SYNC) == 0;
+    }
+
+    /**
+     * Allocate `size` bytes from the file, returning null if it is full.
+     */
+    void* alloc(size_t size)
+    {
+      return allocator.alloc(size);
+    }
+
+    /**
+     * Free an object allocated by `alloc`.
+     */
+    void dealloc(void* p)
+    {
+      allocator.dealloc(p);
+    }
+
+    /**
+     * Returns true if `p` points into the file.
+     */
+    bool contains(const void* p)
+    {
+      return (p >= this) &&
+        (p < pointer_offset(this, static_cast<size_t>(header.size)));
+    }
+
+    /**
+     * Convert a pointer into the heap to a `FileOffset`.
+     */
+    template<typename T>
+    FileOffset<T> offset_of(T* p)
+    {
+      if (p == nullptr)
+        return {};
+
+      SNMALLOC_ASSERT(contains(p));
+      return FileOffset<T>(pointer_diff(this, p));
+    }
+
+    /**
+     * Convert a `FileOffset` back to a pointer into this heap.
+     */
+    template<typename T>
+    T* resolve(FileOffset<T> offset)
+    {
+      return offset.get(this);
+    }
+
+    /**
+     * Record the object from which to find the rest of the persistent data.
+     */
+    template<typename T>
+    void set_root(T* p)
+    {
+      header.root = offset_of(p).value();
+    }
+
+    template<typename T>
+    T* root()
+    {
+      return resolve(FileOffset<T>(header.root));
+    }
+  };
+} // namespace snmalloc
@@ -15,7 +15,7 @@
 
 namespace snmalloc
 {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
   static constexpr std::size_t PRESERVE_BOTTOM_BITS = 16;
 #endif
 
@@ -26,7 +26,7 @@ namespace snmalloc
   template<typename T, capptr_bounds B>
   inline static address_t initial_key(CapPtr<T, B> slab)
   {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
     /**
      * This file assumes that SLAB_BITS is smaller than 16.  In multiple
      * places it uses uint16_t to represent the offset into a slab.
@@ -73,14 +73,14 @@ namespace snmalloc
      * There are two definitions of encode() below, which use std::enable_if_t
      * to gate on do_encode.
      */
-#ifndef CHECK_CLIENT
+#ifndef SNMALLOC_CHECK_FREELIST
     static constexpr bool do_encode = false;
 #else
     static constexpr bool do_encode = aal_supports<IntegerPointers, Aal>;
 #endif
 
   public:
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
     template<typename T = FreeObject>
     static std::enable_if_t<do_encode, CapPtr<T, CBAlloc>> encode(
       uint16_t local_key, CapPtr<T, CBAlloc> next_object, LocalEntropy& entropy)
@@ -165,13 +165,13 @@ namespace snmalloc
   class FreeListIter
   {
     CapPtr<FreeObject, CBAlloc> curr = nullptr;
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
     address_t prev = 0;
 #endif
 
     uint16_t get_prev()
     {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       return prev & 0xffff;
 #else
       return 0;
@@ -186,13 +186,14 @@ namespace snmalloc
      */
     void update_cursor(CapPtr<FreeObject, CBAlloc> next)
     {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
 #  ifndef NDEBUG
       if (next != nullptr)
       {
-        check_client(
+        check_client_freelist(
           !different_slab(prev, next),
-          "Heap corruption - free list corrupted!");
+          "Heap corruption - free list corrupted!",
+          next.unsafe_capptr);
       }
 #  endif
       prev = address_cast(curr);
@@ -203,7 +204,7 @@ namespace snmalloc
   public:
     FreeListIter(CapPtr<FreeObject, CBAlloc> head)
     : curr(head)
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       ,
       prev(initial_key(head))
 #endif
@@ -234,9 +235,11 @@ namespace snmalloc
      */
     CapPtr<FreeObject, CBAlloc> take(LocalEntropy& entropy)
     {
-#ifdef CHECK_CLIENT
-      check_client(
-        !different_slab(prev, curr), "Heap corruption - free list corrupted!");
+#ifdef SNMALLOC_CHECK_FREELIST
+      check_client_freelist(
+        !different_slab(prev, curr),
+        "Heap corruption - free list corrupted!",
+        curr.unsafe_capptr);
 #endif
       auto c = curr;
       update_cursor(curr->read_next(get_prev(), entropy));
@@ -280,7 +283,7 @@ namespace snmalloc
     // In the empty case end[i] == &head[i]
     // This enables branch free enqueuing.
     EncodeFreeObjectReference* end[LENGTH];
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
     // The bottom 16 bits of the previous pointer
     uint16_t prev[LENGTH];
     // The bottom 16 bits of the current pointer
@@ -293,7 +296,7 @@ namespace snmalloc
 
     uint16_t get_prev(uint32_t index)
     {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       return prev[index];
 #else
       UNUSED(index);
@@ -303,7 +306,7 @@ namespace snmalloc
 
     uint16_t get_curr(uint32_t index)
     {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       return curr[index];
 #else
       UNUSED(index);
@@ -328,7 +331,7 @@ namespace snmalloc
       SNMALLOC_ASSERT(empty());
       for (size_t i = 0; i < LENGTH; i++)
       {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
         prev[i] = HEAD_KEY;
         curr[i] = initial_key(p) & 0xffff;
 #else
@@ -372,7 +375,7 @@ namespace snmalloc
 
       end[index]->store(n, get_prev(index), entropy);
       end[index] = &(n->next_object);
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       prev[index] = curr[index];
       curr[index] = address_cast(n) & 0xffff;
 #endif
@@ -396,7 +399,10 @@ namespace snmalloc
         while (end[i] != iter)
         {
           CapPtr<FreeObject, CBAlloc> next = iter->read(local_prev, entropy);
-          check_client(!different_slab(next, prev_obj), "Heap corruption");
+          check_client_freelist(
+            !different_slab(next, prev_obj),
+            "Heap corruption",
+            next.unsafe_capptr);
           local_prev = local_curr;
           local_curr = address_cast(next) & 0xffff;
           count++;
@@ -453,12 +459,12 @@ namespace snmalloc
           // and extend the first list to cover all of the second.
           if (preserve_queue && h != nullptr)
           {
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
             prev[0] = prev[1];
             curr[0] = curr[1];
 #endif
             end[0] = end[1];
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
             prev[1] = HEAD_KEY;
             curr[1] = initial_key(h) & 0xffff;
 #endif
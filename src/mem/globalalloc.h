@@ -40,6 +40,24 @@ namespace snmalloc
     }
 
   public:
+    /**
+     * Call `f` on every allocator created by this pool, whether or not it is
+     * currently owned by a thread.  Allocators are never freed, so this is
+     * safe to call concurrently with allocation, but the state of allocators
+     * owned by other threads may be changing as it is inspected.
+     */
+    template<typename F>
+    void for_each_allocator(F f)
+    {
+      auto* alloc = Parent::iterate();
+
+      while (alloc != nullptr)
+      {
+        f(alloc);
+        alloc = Parent::iterate(alloc);
+      }
+    }
+
     void aggregate_stats(Stats& stats)
     {
       auto* alloc = Parent::iterate();
@@ -90,7 +108,7 @@ namespace snmalloc
           alloc = Parent::extract(alloc);
         }
 
-        restore(first, last);
+        Parent::restore(first, last);
       }
 #endif
     }
@@ -134,12 +152,22 @@ namespace snmalloc
         }
       }
 
+      // Large allocations are not owned by an allocator, and are often freed
+      // by a different one from that which allocated them.
+      size_t large_live = 0;
+      for (alloc = Parent::iterate(); alloc != nullptr;
+           alloc = Parent::iterate(alloc))
+        large_live += alloc->debug_large_live();
+
       if (result != nullptr)
       {
-        *result = okay;
+        *result = okay && (large_live == 0);
         return;
       }
 
+      if (large_live != 0)
+        error("debug_check_empty: found live large allocations");
+
       if (!okay)
       {
         alloc = Parent::iterate();
@@ -191,6 +219,351 @@ namespace snmalloc
       AllocPool<GlobalVirtual, Alloc>::make>::get();
   }
 
+  /**
+   * A breakdown of the memory used by the global allocator.
+   */
+  struct MemoryBreakdown
+  {
+    /**
+     * Address space reserved from the platform.
+     */
+    size_t reserved;
+
+    /**
+     * Memory committed to hold allocator metadata, objects, and free memory
+     * that has not been returned to the platform.
+     */
+    size_t committed;
+
+    /**
+     * Bytes in live objects, rounded up to their sizeclass.  This is only
+     * tracked if statistics are enabled; otherwise it is the memory in chunks
+     * currently held by allocators, which is an upper bound.
+     */
+    size_t live;
+
+    /**
+     * Total memory returned to the platform so far, and the number of
+     * operations that returned it.
+     */
+    size_t released;
+    size_t releases;
+
+    /**
+     * Memory in chunks returned by allocators when their threads were torn
+     * down or, if built with `SNMALLOC_RELEASE_ON_THREAD_EXIT`, exited.
+     */
+    size_t reclaimed;
+  };
+
+  /**
+   * Returns the current memory breakdown.  The fields are read independently
+   * and so may be mutually inconsistent if other threads are allocating.
+   */
+  inline MemoryBreakdown memory_breakdown()
+  {
+    auto& mp = default_memory_provider();
+    MemoryBreakdown result;
+    result.reserved = mp.reserved_bytes();
+    result.committed = mp.committed_bytes();
+    result.released = mp.released_bytes();
+    result.releases = mp.releases();
+    result.reclaimed = mp.thread_exit_reclaimed_bytes();
+#ifdef USE_SNMALLOC_STATS
+    Stats stats;
+    current_alloc_pool()->aggregate_stats(stats);
+    result.live = stats.live_bytes();
+#else
+    result.live = mp.memory_usage().first;
+#endif
+    return result;
+  }
+
+  /**
+   * Returns the bytes of the heap backed by huge pages, or zero if the
+   * platform cannot tell.  This reads the platform's accounting of the whole
+   * address space, so is slow.
+   */
+  template<typename PAL = GlobalVirtual::Pal>
+  inline size_t huge_page_bytes()
+  {
+    if constexpr (pal_supports<HugePageQuery, PAL>)
+      return PAL::huge_page_bytes();
+    else
+      return 0;
+  }
+
+  /**
+   * Counts of the objects and slabs of one sizeclass, summed over all
+   * allocators.  The object counts are only tracked if statistics are
+   * enabled; otherwise they, and `free`, are zero.
+   */
+  struct SizeclassStats
+  {
+    /**
+     * Objects currently allocated.
+     */
+    size_t live;
+
+    /**
+     * Objects allocated so far.
+     */
+    size_t total;
+
+    /**
+     * Slabs holding objects of the sizeclass.  Unlike the object counts,
+     * this is tracked in every build.
+     */
+    size_t slabs;
+
+    /**
+     * Unused object slots in those slabs.  This slightly overstates them, as
+     * the first slab in a superslab holds fewer objects than the others.
+     */
+    size_t free;
+  };
+
+  /**
+   * All of the allocator's statistics, gathered at one time.
+   */
+  struct StatsSnapshot
+  {
+    MemoryBreakdown memory;
+
+    /**
+     * High-water mark of the memory in chunks handed out to allocators.
+     */
+    size_t peak;
+
+    /**
+     * Number of allocators created, whether or not they are owned by a
+     * thread.
+     */
+    size_t allocators;
+
+    /**
+     * Messages waiting in the queues of all allocators.
+     */
+    size_t remote_queue_depth;
+
+    /**
+     * Allocations and deallocations so far.  These are only tracked if
+     * statistics are enabled; otherwise they are zero.
+     */
+    AllocTotals totals;
+
+    SizeclassStats sizeclasses[NUM_SIZECLASSES];
+  };
+
+  /**
+   * Fill `result` with the current statistics.  This walks every allocator,
+   * so is much slower than `memory_breakdown`.  The allocators may be in use
+   * while they are inspected, and their counters are read one at a time, so
+   * the fields may be mutually inconsistent.
+   */
+  inline void stats_snapshot(StatsSnapshot& result)
+  {
+    result.memory = memory_breakdown();
+    result.peak = default_memory_provider().memory_usage().second;
+    result.allocators = 0;
+    result.remote_queue_depth = 0;
+
+    Stats stats;
+    size_t slabs[NUM_SIZECLASSES] = {0};
+    current_alloc_pool()->for_each_allocator([&](Alloc* a) {
+      result.allocators++;
+      result.remote_queue_depth += a->remote_queue_depth().first;
+      stats.add(a->stats());
+      for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES; sc++)
+        slabs[sc] += a->slabs(sc);
+    });
+    result.totals = stats.totals();
+
+    for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES; sc++)
+    {
+      auto counts = stats.sizeclass_counts(sc);
+      size_t free = 0;
+#ifdef USE_SNMALLOC_STATS
+      size_t slots = slabs[sc] * sizeclass_to_slab_capacity(sc);
+      if (slots > counts.first)
+        free = slots - counts.first;
+#endif
+      result.sizeclasses[sc] = {counts.first, counts.second, slabs[sc], free};
+    }
+  }
+
+  /**
+   * The change in the statistics between two snapshots, such as what a phase
+   * of a benchmark allocated.  As with the snapshots, the allocation counts
+   * are only tracked if statistics are enabled.
+   */
+  struct StatsDelta
+  {
+    /**
+     * Allocations and deallocations made between the snapshots.
+     */
+    AllocTotals totals;
+
+    /**
+     * Change in the bytes in live objects and in committed memory, which may
+     * be negative.
+     */
+    ptrdiff_t live;
+    ptrdiff_t committed;
+
+    /**
+     * Increase in the high-water mark.
+     */
+    size_t peak;
+  };
+
+  /**
+   * Returns the change in the statistics from `before` to `after`, which must
+   * have been taken in that order.
+   */
+  inline StatsDelta
+  stats_delta(const StatsSnapshot& before, const StatsSnapshot& after)
+  {
+    StatsDelta result;
+    result.totals.allocations =
+      after.totals.allocations - before.totals.allocations;
+    result.totals.deallocations =
+      after.totals.deallocations - before.totals.deallocations;
+    result.totals.bytes_allocated =
+      after.totals.bytes_allocated - before.totals.bytes_allocated;
+    result.totals.bytes_freed =
+      after.totals.bytes_freed - before.totals.bytes_freed;
+    result.live = static_cast<ptrdiff_t>(after.memory.live) -
+      static_cast<ptrdiff_t>(before.memory.live);
+    result.committed = static_cast<ptrdiff_t>(after.memory.committed) -
+      static_cast<ptrdiff_t>(before.memory.committed);
+    result.peak = after.peak - before.peak;
+    return result;
+  }
+
+  /**
+   * Call `f(base, kind)` for each chunk of the default memory provider that
+   * holds a superslab, a medium slab, or the start of a large allocation,
+   * in increasing order of address.  `kind` is the chunk's
+   * `ChunkMapSuperslabKind`, which for a large allocation is the log2 of its
+   * size.  This walks the chunkmap over all of the address space reserved so
+   * far; chunks allocated or freed by other threads meanwhile may be missed.
+   */
+  template<typename F>
+  inline void for_each_chunk(F f)
+  {
+#ifndef SNMALLOC_PASS_THROUGH
+    auto range = default_memory_provider().reserved_range();
+    address_t a = bits::align_up(range.first, SUPERSLAB_SIZE);
+    while (a < range.second)
+    {
+      uint8_t kind = SNMALLOC_DEFAULT_CHUNKMAP::get(a);
+      size_t step = SUPERSLAB_SIZE;
+      if ((kind >= CMLargeMin) && (kind <= CMLargeMax))
+        step = bits::one_at_bit(kind);
+      else if ((kind != CMSuperslab) && (kind != CMMediumslab))
+        kind = CMNotOurs;
+      if (kind != CMNotOurs)
+        f(a, kind);
+      a += step;
+    }
+#else
+    UNUSED(f);
+#endif
+  }
+
+  /**
+   * Make requests for new chunks from the default memory provider fail once
+   * the memory it has handed out, the peak in `StatsSnapshot`, would exceed
+   * `bytes`, or remove the limit if `bytes` is zero.  As chunks are cached
+   * and reused rather than returned, this bounds the heap's footprint.
+   * Metadata allocated when the allocator starts is counted but not limited.
+   */
+  inline void set_memory_limit(size_t bytes)
+  {
+    default_memory_provider().set_memory_limit(bytes);
+  }
+
+  /**
+   * Install `handler` to be called before a request would exceed the memory
+   * limit; see `MemoryLimitHandler`.
+   */
+  inline void set_memory_limit_handler(MemoryLimitHandler handler)
+  {
+    default_memory_provider().set_memory_limit_handler(handler);
+  }
+
+  /**
+   * Reserve `size` bytes of address space from the default memory provider,
+   * aligned to the next power of two at or above `size`, for the caller to
+   * commit and decommit a page at a time with the PAL.  `size` must be a
+   * multiple of the page size.  The memory reads as zero when first
+   * committed.  Returns null if the address space is exhausted.
+   */
+  inline void* reserve_address_space(size_t size)
+  {
+    return default_memory_provider().reserve_uncommitted(size);
+  }
+
+  /**
+   * Return address space from `reserve_address_space`, with the same `size`,
+   * to the default memory provider, discarding its contents.
+   */
+  inline void unreserve_address_space(void* p, size_t size)
+  {
+    default_memory_provider().unreserve(p, size);
+  }
+
+  /**
+   * Give the default memory provider `length` bytes at `base`, which the
+   * caller has mapped, to allocate from before asking the platform for more;
+   * see `AddressSpaceManager::donate`.  The memory is never returned.
+   * Returns false if the range holds no whole page or, as with pass-through,
+   * donations are not supported.
+   */
+  inline bool donate_range(void* base, size_t length)
+  {
+#ifndef SNMALLOC_PASS_THROUGH
+    return default_memory_provider().donate(base, length);
+#else
+    UNUSED(base);
+    UNUSED(length);
+    return false;
+#endif
+  }
+
+  /**
+   * Set how many freed chunks of `large_class` are kept committed for reuse
+   * by the default memory provider.
+   */
+  inline void set_large_retention(size_t large_class, size_t count)
+  {
+    default_memory_provider().set_large_retention(large_class, count);
+  }
+
+  /**
+   * Set the retention of every large class to that of `strategy`, so that the
+   * default memory provider decommits freed chunks as if it had been built
+   * with that strategy.  On platforms without low-memory notifications, the
+   * chunks that a lazy strategy retains are only decommitted by
+   * `release_free_memory`.
+   */
+  inline void set_decommit_strategy(DecommitStrategy strategy)
+  {
+    for (size_t lc = 0; lc < NUM_LARGE_CLASSES; lc++)
+      set_large_retention(
+        lc, decommit_on_dealloc(lc, strategy) ? 0 : SIZE_MAX);
+  }
+
+  /**
+   * Returns statistics for the default memory provider's cache of freed
+   * chunks of `large_class`.
+   */
+  inline LargeCacheStats large_cache_stats(size_t large_class)
+  {
+    return default_memory_provider().large_cache_stats(large_class);
+  }
+
   template<class MemoryProvider, class Alloc>
   inline AllocPool<MemoryProvider, Alloc>* make_alloc_pool(MemoryProvider& mp)
   {
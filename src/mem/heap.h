@@ -0,0 +1,222 @@
+#pragma once
+
+#include "globalalloc.h"
+
+namespace snmalloc
+{
+  /**
+   * A heap with its own allocator and its own region of memory, in the
+   * manner of Windows' `HeapCreate`, for isolating tenants or requests from
+   * one another and tearing down everything they allocated at once.
+   *
+   * The region is a single large chunk taken from the default memory
+   * provider, with the heap's state at its start.  The heap's allocator only
+   * allocates from the rest of the chunk, and `destroy` returns the whole
+   * chunk, decommitted, to the default memory provider.
+   *
+   * The heap must only be used by one thread at a time.  Objects should be
+   * freed through the heap, not through the thread-local allocator.
+   *
+   * Because the heap's state and objects share no pages with anything else,
+   * the whole chunk can be made read-only with `freeze`, for data built at
+   * startup that should not change afterwards.
+   */
+  class Heap
+  {
+    static bool never_init(void*)
+    {
+      return false;
+    }
+
+    static void* no_op_init(function_ref<void*(void*)>)
+    {
+      error("Heap allocators do not need initialisation");
+    }
+
+    using Pal = PALNoAlloc<DefaultPal>;
+
+    /**
+     * Confines amplification to the heap's chunk.
+     */
+    struct ArenaMap
+    {
+      CapPtr<void, CBArena> arena_root;
+
+      template<typename T = void, typename U, capptr_bounds B>
+      SNMALLOC_FAST_PATH CapPtr<T, CBArena> capptr_amplify(CapPtr<U, B> r)
+      {
+        return Aal::capptr_rebound<T>(arena_root, r);
+      }
+    };
+
+    using MemoryProvider = MemoryProviderStateMixin<Pal, ArenaMap>;
+
+    using HeapAlloc = Allocator<never_init, no_op_init, MemoryProvider>;
+
+    size_t large_class;
+
+    MemoryProvider state;
+
+    HeapAlloc allocator;
+
+    Heap(size_t large_class)
+    : large_class(large_class),
+      state(
+        pointer_offset(CapPtr<void, CBChunk>(this), sizeof(Heap)),
+        size() - sizeof(Heap)),
+      allocator(state)
+    {
+      state.arenamap().arena_root = CapPtr<void, CBArena>(this);
+    }
+
+    size_t size()
+    {
+      return bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+    }
+
+    /**
+     * Destroy `heap`, mark its chunk as no longer in use in the chunkmap, and
+     * return its memory, apart from the first page, to the platform.  Returns
+     * the large class of the chunk.
+     */
+    static size_t release(Heap* heap)
+    {
+      size_t large_class = heap->large_class;
+      size_t size = heap->size();
+      heap->~Heap();
+      asan_unpoison(heap, size);
+
+      GlobalChunkmap::pagemap().set_range(
+        address_cast(heap), CMNotOurs, size >> SUPERSLAB_BITS);
+      snmalloc::Pal::notify_not_using(
+        pointer_offset(heap, OS_PAGE_SIZE), size - OS_PAGE_SIZE);
+      return large_class;
+    }
+
+  public:
+    /**
+     * The largest capacity that a heap can have.
+     */
+    static constexpr size_t max_capacity()
+    {
+      return (bits::one_at_bit(SUPERSLAB_BITS) << (NUM_LARGE_CLASSES - 1)) -
+        sizeof(Heap);
+    }
+
+    /**
+     * Create a heap that can allocate at least `capacity` bytes of memory,
+     * of which part is used by the allocator's metadata and lost to
+     * alignment.  Returns null if `capacity` is more than `max_capacity()`
+     * or the memory cannot be obtained.
+     */
+    static Heap* create(size_t capacity)
+    {
+      if (capacity > max_capacity())
+        return nullptr;
+
+      // Part of a superslab can be lost to alignment, so allow for two.
+      size_t size = bits::next_pow2(
+        bits::max(capacity, 2 * SUPERSLAB_SIZE) + sizeof(Heap));
+      size_t large_class = bits::next_pow2_bits(size) - SUPERSLAB_BITS;
+
+      auto& mp = default_memory_provider();
+      auto p = mp.pop_large_stack(large_class);
+      if (p == nullptr)
+      {
+        p = mp.template reserve<false>(large_class);
+        if (p == nullptr)
+          return nullptr;
+        snmalloc::Pal::notify_using<NoZero>(p.unsafe_capptr, size);
+      }
+      else if (
+        p.template as_static<Baseslab>().unsafe_capptr->get_kind() ==
+        Decommitted)
+      {
+        snmalloc::Pal::notify_using<NoZero>(
+          pointer_offset(p.unsafe_capptr, OS_PAGE_SIZE), size - OS_PAGE_SIZE);
+      }
+
+      return new (p.unsafe_capptr) Heap(large_class);
+    }
+
+    /**
+     * Free everything allocated from `heap` and return its memory to the
+     * default memory provider.  `heap` must not be used afterwards, nor any
+     * pointers into it.
+     */
+    static void destroy(Heap* heap)
+    {
+      size_t large_class = release(heap);
+      auto p = CapPtr<Largeslab, CBChunk>(
+        new (static_cast<void*>(heap)) Decommittedslab());
+      default_memory_provider().push_large_stack(p, large_class);
+    }
+
+    /**
+     * Free everything allocated from the heap at once, and return its memory
+     * to the platform, leaving the heap empty and ready for reuse.  No
+     * pointers into the heap may be used afterwards.
+     */
+    void destroy_all()
+    {
+      size_t lc = release(this);
+      snmalloc::Pal::notify_using<NoZero>(
+        pointer_offset(this, OS_PAGE_SIZE),
+        (bits::one_at_bit(SUPERSLAB_BITS) << lc) - OS_PAGE_SIZE);
+      new (this) Heap(lc);
+    }
+
+    /**
+     * Allocate `size` bytes from the heap, returning null if it is full.
+     */
+    void* alloc(size_t size)
+    {
+      return allocator.alloc(size);
+    }
+
+    /**
+     * Free an object of `size` bytes allocated by `alloc`.
+     */
+    void dealloc(void* p, size_t size)
+    {
+      allocator.dealloc(p, size);
+    }
+
+    /**
+     * Make all of the heap's memory, including its allocator's state,
+     * read-only, so that writes to its objects fault.  Nothing may be
+     * allocated from, freed to or destroyed with the heap until `unfreeze` is
+     * called.  Returns false, leaving the heap writable, if the platform
+     * cannot protect pages or protecting them fails.
+     */
+    template<typename PAL = snmalloc::Pal>
+    bool freeze()
+    {
+      if constexpr (pal_supports<PageProtection, PAL>)
+      {
+        if (PAL::make_read_only(this, size()))
+          return true;
+        unfreeze();
+      }
+      return false;
+    }
+
+    /**
+     * Make the memory of a heap frozen by `freeze` writable again.
+     */
+    template<typename PAL = snmalloc::Pal>
+    void unfreeze()
+    {
+      if constexpr (pal_supports<PageProtection, PAL>)
+        PAL::make_read_write(this, size());
+    }
+
+    /**
+     * Returns true if `p` points into the heap's memory.
+     */
+    bool contains(const void* p)
+    {
+      return (p >= this) && (p < pointer_offset(this, size()));
+    }
+  };
+} // namespace snmalloc
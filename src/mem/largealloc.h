@@ -3,6 +3,7 @@
 #include "../ds/flaglock.h"
 #include "../ds/helpers.h"
 #include "../ds/mpmcstack.h"
+#include "../ds/usdt.h"
 #include "../pal/pal.h"
 #include "address_space.h"
 #include "allocstats.h"
@@ -59,6 +60,52 @@ namespace snmalloc
     }
   };
 
+  /**
+   * Returns true if, under `strategy`, a chunk of the given large class is
+   * decommitted, apart from its first page, when it is returned to the large
+   * stack.  The default strategy can be overridden at run time with
+   * `set_large_retention` or `set_decommit_strategy`.
+   */
+  constexpr bool decommit_on_dealloc(
+    size_t large_class, DecommitStrategy strategy = decommit_strategy)
+  {
+    return (strategy != DecommitNone) &&
+      (large_class != 0 || strategy == DecommitSuper);
+  }
+
+  /**
+   * Called with the size of a chunk that the backend needs and the memory
+   * limit that taking it would exceed; see `set_memory_limit`.  Returns true
+   * to try again, for instance after raising the limit, or false to fail the
+   * allocation.  It is called in the middle of an allocation, so must not
+   * allocate.
+   */
+  using MemoryLimitHandler = bool (*)(size_t size, size_t limit);
+
+  /**
+   * Statistics for the cache of freed chunks of one large class.
+   */
+  struct LargeCacheStats
+  {
+    /**
+     * Freed chunks waiting for reuse that are still committed.
+     */
+    size_t retained_committed;
+    /**
+     * Maximum number of freed chunks kept committed; further chunks are
+     * decommitted when they are freed.
+     */
+    size_t retention_limit;
+    /**
+     * Allocations served from the cache.
+     */
+    size_t hits;
+    /**
+     * Allocations that needed fresh address space.
+     */
+    size_t misses;
+  };
+
   // This represents the state that the large allcoator needs to add to the
   // global state of the allocator.  This is currently stored in the memory
   // provider, so we add this in.
@@ -99,6 +146,32 @@ namespace snmalloc
      */
     std::atomic<size_t> available_large_chunks_in_bytes{0};
 
+    /**
+     * Memory in large_stacks that has been decommitted.
+     */
+    std::atomic<size_t> decommitted_large_chunks_in_bytes{0};
+
+    /**
+     * Cumulative memory returned to the platform, and the number of
+     * operations that returned it.
+     */
+    std::atomic<size_t> released_bytes_total{0};
+    std::atomic<size_t> release_count{0};
+
+    /**
+     * Cumulative memory in chunks returned by allocators flushed when their
+     * thread exited.
+     */
+    std::atomic<size_t> thread_exit_reclaimed_total{0};
+
+    /**
+     * Limit on `peak_memory_used_bytes` for chunks requested through
+     * `reserve`, or zero for none, so that it is correct in memory that is
+     * zeroed rather than constructed.
+     */
+    std::atomic<size_t> memory_limit{0};
+    std::atomic<MemoryLimitHandler> memory_limit_handler{nullptr};
+
     /**
      * Stack of large allocations that have been returned for reuse.
      */
@@ -107,6 +180,40 @@ namespace snmalloc
       MPMCStack<Largeslab, RequiresInit, CapPtrCBChunk, AtomicCapPtrCBChunk>>
       large_stack;
 
+    /**
+     * Number of chunks in each large_stack that are still committed.
+     */
+    ModArray<NUM_LARGE_CLASSES, std::atomic<size_t>> committed_large_chunks;
+
+    /**
+     * Run-time overrides of the number of committed chunks to retain in each
+     * large_stack.  Zero means use the default from `decommit_on_dealloc`;
+     * otherwise the limit is one less than the value.  This encoding keeps
+     * the default correct in memory that is zeroed rather than constructed.
+     */
+    ModArray<NUM_LARGE_CLASSES, std::atomic<size_t>> large_retention_override;
+
+    /**
+     * True if this provider was given memory at construction rather than
+     * taking it from the PAL.  False in memory that is zeroed rather than
+     * constructed, which is how `make` creates the default provider.
+     */
+    bool external_memory = false;
+
+    /**
+     * True once memory that may have been written to has joined the address
+     * space: donated memory, or, on a platform without lazy commit, memory
+     * returned by `unreserve`, whose pages may survive being decommitted.
+     * After that, fresh memory is no longer known to be zero.
+     */
+    std::atomic<bool> recycled_memory{false};
+
+    /**
+     * Counts of pops from each large_stack that did and did not find a chunk.
+     */
+    ModArray<NUM_LARGE_CLASSES, std::atomic<size_t>> large_stack_hits;
+    ModArray<NUM_LARGE_CLASSES, std::atomic<size_t>> large_stack_misses;
+
   public:
     using Pal = PAL;
 
@@ -121,24 +228,106 @@ namespace snmalloc
       auto p = large_stack[large_class].pop();
       if (p != nullptr)
       {
+        large_stack_hits[large_class]++;
         const size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
         available_large_chunks_in_bytes -= rsize;
+        if (
+          p.template as_static<Baseslab>().unsafe_capptr->get_kind() ==
+          Decommitted)
+          decommitted_large_chunks_in_bytes -= rsize - OS_PAGE_SIZE;
+        else
+          committed_large_chunks[large_class]--;
+      }
+      else
+      {
+        large_stack_misses[large_class]++;
       }
       return p;
     }
 
     /**
      * Push `slab` onto the large-allocation stack associated with the size
-     * class specified by `large_class`.  Always succeeds.
+     * class specified by `large_class`.  Always succeeds.  If `slab` is still
+     * committed, it must have been accounted for by `retain_committed`.
      */
     SNMALLOC_FAST_PATH void
     push_large_stack(CapPtr<Largeslab, CBChunk> slab, size_t large_class)
     {
       const size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
       available_large_chunks_in_bytes += rsize;
+      if (slab->get_kind() == Decommitted)
+      {
+        // Chunks are only pushed decommitted if dealloc() just did so.
+        decommitted_large_chunks_in_bytes += rsize - OS_PAGE_SIZE;
+        released(rsize - OS_PAGE_SIZE);
+      }
       large_stack[large_class].push(slab);
     }
 
+    /**
+     * Returns the number of freed chunks of `large_class` that are kept
+     * committed for reuse.
+     */
+    size_t large_retention(size_t large_class)
+    {
+      size_t v = large_retention_override[large_class].load(
+        std::memory_order_relaxed);
+      if (v != 0)
+        return v - 1;
+      return decommit_on_dealloc(large_class) ? 0 : SIZE_MAX;
+    }
+
+    /**
+     * Set the number of freed chunks of `large_class` that are kept committed
+     * for reuse.  Chunks already retained beyond a lowered limit stay
+     * committed until they are reused or a low-memory notification arrives.
+     */
+    void set_large_retention(size_t large_class, size_t count)
+    {
+      large_retention_override[large_class].store(
+        bits::min(count, SIZE_MAX - 1) + 1, std::memory_order_relaxed);
+    }
+
+    /**
+     * Decommit every freed chunk that is being kept committed for reuse,
+     * whatever the retention limits, as a low-memory notification would.
+     * Returns the bytes returned to the platform, which is zero if another
+     * thread is already decommitting.
+     */
+    size_t decommit_cached()
+    {
+      if (lazy_decommit_guard.test_and_set())
+        return 0;
+      size_t result = decommit_large_stacks(false);
+      lazy_decommit_guard.clear();
+      return result;
+    }
+
+    /**
+     * Called before a chunk of `large_class` is pushed onto the large stack.
+     * Returns true, and counts it as retained, if it should stay committed;
+     * otherwise the caller must decommit it.
+     */
+    bool retain_committed(size_t large_class)
+    {
+      size_t limit = large_retention(large_class);
+      if (committed_large_chunks[large_class]++ < limit)
+        return true;
+      committed_large_chunks[large_class]--;
+      return false;
+    }
+
+    /**
+     * Returns statistics for the cache of freed chunks of `large_class`.
+     */
+    LargeCacheStats large_cache_stats(size_t large_class)
+    {
+      return {committed_large_chunks[large_class],
+              large_retention(large_class),
+              large_stack_hits[large_class],
+              large_stack_misses[large_class]};
+    }
+
     /**
      * Default constructor.  This constructs a memory provider that doesn't yet
      * own any memory, but which can claim memory from the PAL.
@@ -151,8 +340,20 @@ namespace snmalloc
      * allocate memory, if the initial reservation is sufficient.
      */
     MemoryProviderStateMixin(CapPtr<void, CBChunk> start, size_t len)
-    : address_space(start, len)
+    : address_space(start, len), external_memory(true)
     {}
+
+    /**
+     * Returns true if memory from `reserve` is known to be zero.  This is the
+     * case for memory from the PAL, but not for memory provided by the
+     * embedder, at construction or by `donate`, which may have been written
+     * to.
+     */
+    bool fresh_memory_is_zero()
+    {
+      return !external_memory &&
+        !recycled_memory.load(std::memory_order_relaxed);
+    }
     /**
      * Make a new memory provide for this PAL.
      */
@@ -190,15 +391,33 @@ namespace snmalloc
     }
 
   private:
+    void released(size_t size)
+    {
+      released_bytes_total += size;
+      release_count++;
+    }
+
     SNMALLOC_SLOW_PATH void lazy_decommit()
     {
       // If another thread is try to do lazy decommit, let it continue.  If
       // we try to parallelise this, we'll most likely end up waiting on the
       // same page table locks.
-      if (!lazy_decommit_guard.test_and_set())
+      if (lazy_decommit_guard.test_and_set())
       {
         return;
       }
+      decommit_large_stacks(true);
+      lazy_decommit_guard.clear();
+    }
+
+    /**
+     * Decommit the chunks waiting on the large stacks, apart from the first
+     * page of each.  If `while_low_memory` is set, this stops once the
+     * platform no longer reports low memory.  Returns the bytes decommitted.
+     */
+    size_t decommit_large_stacks(bool while_low_memory)
+    {
+      size_t total = 0;
       // When we hit low memory, iterate over size classes and decommit all of
       // the memory that we can.  Start with the small size classes so that we
       // hit cached superslabs first.
@@ -207,9 +426,12 @@ namespace snmalloc
       for (size_t large_class = 0; large_class < NUM_LARGE_CLASSES;
            large_class++)
       {
-        if (!PAL::expensive_low_memory_check())
+        if constexpr (pal_supports<LowMemoryNotification, PAL>)
         {
-          break;
+          if (while_low_memory && !PAL::expensive_low_memory_check())
+          {
+            break;
+          }
         }
         size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
         size_t decommit_size = rsize - OS_PAGE_SIZE;
@@ -223,6 +445,14 @@ namespace snmalloc
           {
             PAL::notify_not_using(
               pointer_offset(slab.unsafe_capptr, OS_PAGE_SIZE), decommit_size);
+            SNMALLOC_PROBE2(
+              os_decommit,
+              pointer_offset(slab.unsafe_capptr, OS_PAGE_SIZE),
+              decommit_size);
+            decommitted_large_chunks_in_bytes += decommit_size;
+            committed_large_chunks[large_class]--;
+            released(decommit_size);
+            total += decommit_size;
           }
           // Once we've removed these from the stack, there will be no
           // concurrent accesses and removal should have established a
@@ -234,7 +464,7 @@ namespace snmalloc
           slab = next;
         }
       }
-      lazy_decommit_guard.clear();
+      return total;
     }
 
     class LowMemoryNotificationObject : public PalNotificationObject
@@ -278,13 +508,61 @@ namespace snmalloc
       return new (p.unsafe_capptr) T(std::forward<Args...>(args)...);
     }
 
+    /**
+     * Count `size` more bytes of chunks as used, unless that would exceed the
+     * memory limit and the limit handler does not ask to try again.
+     */
+    bool take_within_limit(size_t size)
+    {
+      size_t used = peak_memory_used_bytes.load(std::memory_order_relaxed);
+      while (true)
+      {
+        size_t limit = memory_limit.load(std::memory_order_relaxed);
+        if ((limit != 0) && (used + size > limit))
+        {
+          auto handler =
+            memory_limit_handler.load(std::memory_order_relaxed);
+          if ((handler == nullptr) || !handler(size, limit))
+            return false;
+          used = peak_memory_used_bytes.load(std::memory_order_relaxed);
+          continue;
+        }
+        if (peak_memory_used_bytes.compare_exchange_weak(used, used + size))
+          return true;
+      }
+    }
+
     template<bool committed>
     CapPtr<Largeslab, CBChunk> reserve(size_t large_class) noexcept
     {
       size_t size = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
-      peak_memory_used_bytes += size;
-      return address_space.template reserve<committed>(size, arena_map)
-        .template as_static<Largeslab>();
+      if (!take_within_limit(size))
+        return nullptr;
+      auto p = address_space.template reserve<committed>(size, arena_map)
+                 .template as_static<Largeslab>();
+      if (p == nullptr)
+        peak_memory_used_bytes -= size;
+      return p;
+    }
+
+    /**
+     * Fail requests for new chunks that would take the memory used by this
+     * provider, as reported by `memory_usage`, over `bytes`, or remove the
+     * limit if `bytes` is zero.  Chunks already in use or cached for reuse
+     * are not affected.
+     */
+    void set_memory_limit(size_t bytes)
+    {
+      memory_limit.store(bytes, std::memory_order_relaxed);
+    }
+
+    /**
+     * Install `handler` to be called when the memory limit would be
+     * exceeded, or remove it if null.
+     */
+    void set_memory_limit_handler(MemoryLimitHandler handler)
+    {
+      memory_limit_handler.store(handler, std::memory_order_relaxed);
     }
 
     /**
@@ -298,6 +576,130 @@ namespace snmalloc
       return {peak - avail, peak};
     }
 
+    /**
+     * Returns the address space, in bytes, reserved from the platform.
+     */
+    size_t reserved_bytes()
+    {
+      return address_space.reserved();
+    }
+
+    /**
+     * Reserve `size` bytes of address space, aligned to the next power of two
+     * at or above `size`, without committing it, for a caller that commits
+     * and decommits it itself.  `size` must be a multiple of the page size.
+     * Returns null if the address space is exhausted.
+     */
+    void* reserve_uncommitted(size_t size)
+    {
+      return address_space.template reserve_with_left_over<false>(
+        size, arena_map)
+        .unsafe_capptr;
+    }
+
+    /**
+     * Return the address space from `reserve_uncommitted` with the same
+     * `size`.  Its pages are discarded, so that it reads as zero when it is
+     * handed out again.  With lazy commit, discarding them is cheapest done by
+     * zeroing, which maps fresh pages; otherwise the pages are decommitted,
+     * which not every platform zeroes, so fresh memory is then zeroed when it
+     * is committed again.
+     */
+    void unreserve(void* p, size_t size)
+    {
+      if constexpr (pal_supports<LazyCommit, PAL>)
+      {
+        PAL::template notify_using<YesZero>(p, size);
+      }
+      else
+      {
+        PAL::notify_not_using(p, size);
+        recycled_memory.store(true, std::memory_order_relaxed);
+      }
+      address_space.unreserve(CapPtr<void, CBChunk>(p), size);
+    }
+
+    /**
+     * Add `length` bytes at `base`, mapped by the embedder, to the memory
+     * that this provider hands out; see `AddressSpaceManager::donate`.  Large
+     * zeroed allocations then clear fresh memory, as it may be from the
+     * range and hold old data.  This is not supported with strict provenance,
+     * where the range would also need registering with the arena map, and
+     * returns false.
+     */
+    bool donate(void* base, size_t length)
+    {
+      if constexpr (aal_supports<StrictProvenance>)
+      {
+        UNUSED(base);
+        UNUSED(length);
+        return false;
+      }
+      else
+      {
+        // Set first, so that no chunk of the range is taken as zero.
+        recycled_memory.store(true, std::memory_order_relaxed);
+        return address_space.donate(CapPtr<void, CBChunk>(base), length);
+      }
+    }
+
+    /**
+     * Returns the range of addresses that the address space reserved from
+     * the platform lies within.
+     */
+    std::pair<address_t, address_t> reserved_range()
+    {
+      return address_space.reserved_range();
+    }
+
+    /**
+     * Returns an estimate of the memory, in bytes, that is committed.  This
+     * counts all memory handed out by the address space manager, except for
+     * chunks that have been decommitted while waiting for reuse.  The
+     * platform may not back committed memory until it is first touched.
+     */
+    size_t committed_bytes()
+    {
+      size_t decommitted = decommitted_large_chunks_in_bytes;
+      size_t used = peak_memory_used_bytes;
+      return used - decommitted;
+    }
+
+    /**
+     * Returns the total memory, in bytes, returned to the platform so far.
+     */
+    size_t released_bytes()
+    {
+      return released_bytes_total;
+    }
+
+    /**
+     * Returns the number of operations that returned memory to the platform.
+     */
+    size_t releases()
+    {
+      return release_count;
+    }
+
+    /**
+     * Record memory in chunks returned by an allocator flushed when its
+     * thread exited or was torn down.
+     */
+    void reclaimed_on_thread_exit(size_t size)
+    {
+      thread_exit_reclaimed_total += size;
+    }
+
+    /**
+     * Returns the total memory, in bytes, reclaimed from allocators when
+     * their threads exited, if built with `SNMALLOC_RELEASE_ON_THREAD_EXIT`,
+     * or were torn down.
+     */
+    size_t thread_exit_reclaimed_bytes()
+    {
+      return thread_exit_reclaimed_total;
+    }
+
     template<typename T, typename U, capptr_bounds B>
     SNMALLOC_FAST_PATH CapPtr<T, CBArena> capptr_amplify(CapPtr<U, B> r)
     {
@@ -321,6 +723,12 @@ namespace snmalloc
 
     MemoryProvider& memory_provider;
 
+    /**
+     * Bytes in chunks that this allocator has returned to the memory
+     * provider.
+     */
+    size_t returned_bytes = 0;
+
     LargeAlloc(MemoryProvider& mp) : memory_provider(mp) {}
 
     template<ZeroMem zero_mem = NoZero>
@@ -338,19 +746,31 @@ namespace snmalloc
         p = memory_provider.template reserve<false>(large_class);
         if (p == nullptr)
           return nullptr;
-        MemoryProvider::Pal::template notify_using<zero_mem>(
-          p.unsafe_capptr, rsize);
+        stats.superslab_fresh();
+
+        // Fresh memory from the PAL is already zero, so a zeroed allocation
+        // can skip clearing it, which for a large request is the main cost.
+        if ((zero_mem == YesZero) && memory_provider.fresh_memory_is_zero())
+        {
+          MemoryProvider::Pal::template notify_using<NoZero>(
+            p.unsafe_capptr, rsize);
+          stats.fresh_zero(rsize);
+        }
+        else
+        {
+          MemoryProvider::Pal::template notify_using<zero_mem>(
+            p.unsafe_capptr, rsize);
+        }
+        SNMALLOC_PROBE2(os_commit, p.unsafe_capptr, rsize);
       }
       else
       {
         stats.superslab_pop();
 
-        // Cross-reference alloc.h's large_dealloc decommitment condition.
+        // Chunks are marked as decommitted by dealloc() or lazy_decommit().
         bool decommitted =
-          ((decommit_strategy == DecommitSuperLazy) &&
-           (p.template as_static<Baseslab>().unsafe_capptr->get_kind() ==
-            Decommitted)) ||
-          (large_class > 0) || (decommit_strategy == DecommitSuper);
+          p.template as_static<Baseslab>().unsafe_capptr->get_kind() ==
+          Decommitted;
 
         if (decommitted)
         {
@@ -365,6 +785,10 @@ namespace snmalloc
           MemoryProvider::Pal::template notify_using<zero_mem>(
             pointer_offset(p.unsafe_capptr, OS_PAGE_SIZE),
             rsize - OS_PAGE_SIZE);
+          SNMALLOC_PROBE2(
+            os_commit,
+            pointer_offset(p.unsafe_capptr, OS_PAGE_SIZE),
+            rsize - OS_PAGE_SIZE);
         }
         else
         {
@@ -391,18 +815,36 @@ namespace snmalloc
           "without low memory notifications");
       }
 
-      size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
-
-      // Cross-reference largealloc's alloc() decommitted condition.
-      if (
-        (decommit_strategy != DecommitNone) &&
-        (large_class != 0 || decommit_strategy == DecommitSuper))
+      if (!memory_provider.retain_committed(large_class))
       {
-        MemoryProvider::Pal::notify_not_using(
-          pointer_offset(p, OS_PAGE_SIZE).unsafe_capptr, rsize - OS_PAGE_SIZE);
+        dealloc_decommitted(p, large_class);
+        return;
       }
 
       stats.superslab_push();
+      returned_bytes += bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+      memory_provider.push_large_stack(p, large_class);
+    }
+
+    /**
+     * Return `p` to the large stack decommitted, apart from its first page,
+     * whatever the retention policy for `large_class`.
+     */
+    void dealloc_decommitted(CapPtr<Largeslab, CBChunk> p, size_t large_class)
+    {
+      size_t rsize = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+
+      // Mark decommitted chunks so that alloc() knows to recommit them.
+      MemoryProvider::Pal::notify_not_using(
+        pointer_offset(p, OS_PAGE_SIZE).unsafe_capptr, rsize - OS_PAGE_SIZE);
+      SNMALLOC_PROBE2(
+        os_decommit,
+        pointer_offset(p, OS_PAGE_SIZE).unsafe_capptr,
+        rsize - OS_PAGE_SIZE);
+      p = CapPtr<Largeslab, CBChunk>(new (p.unsafe_capptr) Decommittedslab());
+
+      stats.superslab_push();
+      returned_bytes += rsize;
       memory_provider.push_large_stack(p, large_class);
     }
 
@@ -1,5 +1,6 @@
 #pragma once
 
+#include "../ds/asan.h"
 #include "../ds/dllist.h"
 #include "allocconfig.h"
 #include "allocslab.h"
@@ -113,10 +114,10 @@ namespace snmalloc
       auto p = pointer_offset(self, (static_cast<size_t>(index) << 8));
       self->free--;
 
+      asan_unpoison(p.unsafe_capptr, size);
+
       if constexpr (zero_mem == YesZero)
         pal_zero<PAL>(Aal::capptr_rebound(self->self_chunk, p), size);
-      else
-        UNUSED(size);
 
       return Aal::capptr_bound<void, CBAllocE>(p, size);
     }
@@ -1,5 +1,6 @@
 #pragma once
 
+#include "../ds/asan.h"
 #include "../ds/cdllist.h"
 #include "../ds/dllist.h"
 #include "../ds/helpers.h"
@@ -51,7 +52,7 @@ namespace snmalloc
      *
      *  Spare 32bits are used for the fields in MetaslabEnd.
      */
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
     FreeListBuilder<true, MetaslabEnd> free_queue;
 #else
     FreeListBuilder<false, MetaslabEnd> free_queue;
@@ -188,6 +189,8 @@ namespace snmalloc
 
       self->debug_slab_invariant(meta, entropy);
 
+      asan_unpoison(p.unsafe_capptr, rsize);
+
       if constexpr (zero_mem == YesZero)
       {
         if (rsize < PAGE_ALIGNED_SIZE)
@@ -195,10 +198,6 @@ namespace snmalloc
         else
           pal_zero<PAL, true>(Aal::capptr_rebound(self.as_void(), p), rsize);
       }
-      else
-      {
-        UNUSED(rsize);
-      }
 
       // TODO: Should this be zeroing the FreeObject state?
       return capptr_export(p.as_void());
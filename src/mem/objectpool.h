@@ -0,0 +1,224 @@
+#pragma once
+
+#include "threadalloc.h"
+
+namespace snmalloc
+{
+  /**
+   * Where a `FixedSizePoolTemplate` takes its objects from when it refills,
+   * and returns them to when it is trimmed: the calling thread's allocator.
+   * The shims provide their own, so that pool objects are traced, profiled
+   * and passed to the hooks like their other allocations.
+   */
+  struct ThreadAllocPoolSource
+  {
+    static void* alloc(size_t alignment, size_t size)
+    {
+      return ThreadAlloc::get()->alloc(aligned_size(alignment, size));
+    }
+
+    static void dealloc(void* p, size_t alignment, size_t size)
+    {
+      ThreadAlloc::get()->dealloc(p, aligned_size(alignment, size));
+    }
+  };
+
+  /**
+   * A cache of free objects of one size class, for programs that allocate
+   * and free many objects of the same size, such as the entities of a game
+   * or the connections of a server, and want to skip even the allocator's
+   * fast path.
+   *
+   * When empty, the pool is refilled from `Source` a batch at a time, so its
+   * objects are ordinary snmalloc allocations: they are found in the
+   * pagemap, `alloc_size` and the bounds checks work on them, and the
+   * statistics count them as allocated while they are cached.  Cached
+   * objects are freed by `trim` and when the pool is destroyed.
+   *
+   * The pool must only be used by one thread at a time.  Its objects may be
+   * passed to other threads, but must be returned with `release`, not freed.
+   */
+  template<typename Source = ThreadAllocPoolSource>
+  class FixedSizePoolTemplate
+  {
+    struct Slot
+    {
+      Slot* next;
+    };
+
+    Slot* head = nullptr;
+    size_t object_alignment;
+    size_t requested_size;
+    size_t object_size;
+    size_t refill_count;
+    size_t cached_count = 0;
+
+    SNMALLOC_SLOW_PATH bool refill()
+    {
+      for (size_t i = 0; i < refill_count; i++)
+      {
+        void* p = Source::alloc(object_alignment, requested_size);
+        if (p == nullptr)
+          break;
+        release(p);
+      }
+      return head != nullptr;
+    }
+
+  public:
+    /**
+     * The default batch: enough objects to fill a small slab, up to 64.
+     */
+    static constexpr size_t default_batch(size_t size)
+    {
+      return bits::max<size_t>(1, bits::min<size_t>(64, SLAB_SIZE / size));
+    }
+
+    /**
+     * Create a pool of objects of `size` bytes aligned to `alignment`, which
+     * must be a power of two, refilled `batch` objects at a time, or
+     * `default_batch` objects if `batch` is zero.
+     */
+    FixedSizePoolTemplate(size_t size, size_t alignment = 1, size_t batch = 0)
+    : object_alignment(alignment),
+      requested_size(size),
+      object_size(round_size(
+        aligned_size(alignment, bits::max(size, sizeof(Slot))))),
+      refill_count(batch == 0 ? default_batch(object_size) : batch)
+    {}
+
+    FixedSizePoolTemplate(const FixedSizePoolTemplate&) = delete;
+    FixedSizePoolTemplate& operator=(const FixedSizePoolTemplate&) = delete;
+
+    ~FixedSizePoolTemplate()
+    {
+      trim();
+    }
+
+    /**
+     * Take an object from the pool, refilling it if it is empty.  The
+     * contents are undefined.  Returns null if the memory cannot be
+     * obtained.
+     */
+    SNMALLOC_FAST_PATH void* acquire()
+    {
+      if (unlikely(head == nullptr) && !refill())
+        return nullptr;
+      Slot* s = head;
+      head = s->next;
+      cached_count--;
+      return s;
+    }
+
+    /**
+     * Return to the pool an object from `acquire`.
+     */
+    SNMALLOC_FAST_PATH void release(void* p)
+    {
+      auto s = static_cast<Slot*>(p);
+      s->next = head;
+      head = s;
+      cached_count++;
+    }
+
+    /**
+     * Free the cached objects to `Source`.  Returns the number freed.
+     */
+    size_t trim()
+    {
+      if (head == nullptr)
+        return 0;
+      size_t count = cached_count;
+      while (head != nullptr)
+      {
+        Slot* s = head;
+        head = s->next;
+        Source::dealloc(s, object_alignment, requested_size);
+      }
+      cached_count = 0;
+      return count;
+    }
+
+    /**
+     * The size of the pool's objects, which is a size class.
+     */
+    size_t size() const
+    {
+      return object_size;
+    }
+
+    /**
+     * The number of objects taken from the allocator when the pool is empty.
+     */
+    size_t batch() const
+    {
+      return refill_count;
+    }
+
+    /**
+     * The number of free objects in the pool.
+     */
+    size_t cached() const
+    {
+      return cached_count;
+    }
+  };
+
+  using FixedSizePool = FixedSizePoolTemplate<>;
+
+  /**
+   * A `FixedSizePoolTemplate` of objects of type `T`, constructed by
+   * `acquire` and destroyed by `release`.
+   */
+  template<typename T, typename Source = ThreadAllocPoolSource>
+  class ObjectPool
+  {
+    FixedSizePoolTemplate<Source> pool;
+
+  public:
+    /**
+     * Create a pool refilled `batch` objects at a time, or the default for
+     * the size of `T` if `batch` is zero.
+     */
+    explicit ObjectPool(size_t batch = 0) : pool(sizeof(T), alignof(T), batch)
+    {}
+
+    /**
+     * Construct a `T` from `args` in an object from the pool.  Returns null
+     * if the memory cannot be obtained.
+     */
+    template<typename... Args>
+    T* acquire(Args&&... args)
+    {
+      void* p = pool.acquire();
+      if (p == nullptr)
+        return nullptr;
+      return new (p) T(std::forward<Args>(args)...);
+    }
+
+    /**
+     * Destroy `p`, which came from `acquire`, and return it to the pool.
+     */
+    void release(T* p)
+    {
+      p->~T();
+      pool.release(p);
+    }
+
+    /**
+     * Free the cached objects; see `FixedSizePoolTemplate::trim`.
+     */
+    size_t trim()
+    {
+      return pool.trim();
+    }
+
+    /**
+     * The number of free objects in the pool.
+     */
+    size_t cached() const
+    {
+      return pool.cached();
+    }
+  };
+} // namespace snmalloc
@@ -512,7 +512,7 @@ namespace snmalloc
         external_pagemap = T::cast_to_pagemap(raw_pagemap, c);
         if (!external_pagemap)
         {
-          Pal::error("Incorrect ABI of global pagemap.");
+          error("Incorrect ABI of global pagemap.");
         }
       }
       return *external_pagemap;
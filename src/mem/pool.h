@@ -28,7 +28,7 @@ namespace snmalloc
 
     std::atomic_flag lock = ATOMIC_FLAG_INIT;
     MPMCStack<T, PreZeroed> stack;
-    T* list = nullptr;
+    std::atomic<T*> list{nullptr};
 
     Pool(MemoryProvider& m) : memory_provider(m) {}
 
@@ -62,8 +62,9 @@ namespace snmalloc
               std::forward<Args...>(args)...);
 
       FlagLock f(lock);
-      p->list_next = list;
-      list = p;
+      p->list_next = list.load(std::memory_order_relaxed);
+      // Published for `iterate`, which does not take the lock.
+      list.store(p, std::memory_order_release);
 
       p->set_in_use();
       return p;
@@ -107,7 +108,7 @@ namespace snmalloc
     T* iterate(T* p = nullptr)
     {
       if (p == nullptr)
-        return list;
+        return list.load(std::memory_order_acquire);
 
       return p->list_next;
     }
@@ -1,6 +1,7 @@
 #pragma once
 
 #include "../ds/mpscq.h"
+#include "../ds/usdt.h"
 #include "../mem/allocconfig.h"
 #include "../mem/freelist.h"
 #include "../mem/sizeclass.h"
@@ -8,7 +9,7 @@
 
 #include <atomic>
 
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_PAGEMAP
 #  define SNMALLOC_DONT_CACHE_ALLOCATOR_PTR
 #endif
 
@@ -111,6 +112,36 @@ namespace snmalloc
     sizeof(Remote) <= MIN_ALLOC_SIZE,
     "Needs to be able to fit in smallest allocation.");
 
+  /**
+   * Callback invoked when posting to an allocator's message queue makes the
+   * number of waiting messages reach `remote_queue_alarm_threshold`.  It is
+   * passed the truncated ID of the receiving allocator and the new depth.
+   *
+   * The callback runs on the posting thread, inside the allocator, and so
+   * must not allocate or free memory.
+   */
+  using RemoteQueueAlarm = void (*)(size_t alloc_id, size_t depth);
+
+  inline std::atomic<RemoteQueueAlarm> remote_queue_alarm{nullptr};
+
+  /**
+   * Depth at which `remote_queue_alarm` is invoked.  Zero disables the alarm.
+   */
+  inline std::atomic<size_t> remote_queue_alarm_threshold{0};
+
+  /**
+   * Install `alarm` to be called whenever an allocator's incoming message
+   * queue grows to `threshold` messages.  Passing a `threshold` of zero, or a
+   * null `alarm`, disables the alarm.
+   */
+  inline void set_remote_queue_alarm(size_t threshold, RemoteQueueAlarm alarm)
+  {
+    remote_queue_alarm_threshold.store(0, std::memory_order_relaxed);
+    remote_queue_alarm.store(alarm, std::memory_order_relaxed);
+    remote_queue_alarm_threshold.store(
+      alarm == nullptr ? 0 : threshold, std::memory_order_relaxed);
+  }
+
   struct RemoteAllocator
   {
     using alloc_id_t = Remote::alloc_id_t;
@@ -119,12 +150,56 @@ namespace snmalloc
     alignas(CACHELINE_SIZE)
       MPSCQ<Remote, CapPtrCBAlloc, AtomicCapPtrCBAlloc> message_queue;
 
+    /**
+     * Number of messages that have been posted to `message_queue` and not yet
+     * dequeued by the owning allocator, and the high-water mark of that
+     * number.  These are updated with relaxed atomics outside of the fast
+     * paths, so are approximate while messages are in flight.
+     */
+    std::atomic<size_t> queue_depth{0};
+    std::atomic<size_t> peak_queue_depth{0};
+
     alloc_id_t trunc_id()
     {
       return static_cast<alloc_id_t>(
                reinterpret_cast<uintptr_t>(&message_queue)) &
         ~SIZECLASS_MASK;
     }
+
+    /**
+     * Record that `n` messages are about to be enqueued on this allocator's
+     * message queue.  This must happen before the enqueue, so that the owner
+     * never observes more dequeued messages than have been counted.
+     */
+    void note_posted(size_t n)
+    {
+      size_t depth = queue_depth.fetch_add(n, std::memory_order_relaxed) + n;
+
+      size_t peak = peak_queue_depth.load(std::memory_order_relaxed);
+      while ((depth > peak) &&
+             !peak_queue_depth.compare_exchange_weak(
+               peak, depth, std::memory_order_relaxed))
+      {}
+
+      size_t threshold =
+        remote_queue_alarm_threshold.load(std::memory_order_relaxed);
+      if (unlikely(
+            (threshold != 0) && (depth >= threshold) &&
+            ((depth - n) < threshold)))
+      {
+        auto alarm = remote_queue_alarm.load(std::memory_order_relaxed);
+        if (alarm != nullptr)
+          alarm(trunc_id(), depth);
+      }
+    }
+
+    /**
+     * Record that the owning allocator has dequeued `n` messages.
+     */
+    void note_processed(size_t n)
+    {
+      queue_depth.fetch_sub(n, std::memory_order_relaxed);
+    }
   };
 
   /*
@@ -143,9 +218,15 @@ namespace snmalloc
 
     CapPtr<Remote, CBAlloc> last{&head};
 
+    /*
+     * Number of Remote objects on this list.
+     */
+    size_t count{0};
+
     void clear()
     {
       last = CapPtr<Remote, CBAlloc>(&head);
+      count = 0;
     }
 
     bool empty()
@@ -197,6 +278,7 @@ namespace snmalloc
       RemoteList* l = &list[get_slot<Alloc>(target_id, 0)];
       l->last->non_atomic_next = r;
       l->last = r;
+      l->count++;
     }
 
     template<typename Alloc>
@@ -225,7 +307,10 @@ namespace snmalloc
             auto first_auth =
               allocator->large_allocator.template capptr_amplify<Remote>(first);
             auto super = Superslab::get(first_auth);
-            super->get_allocator()->message_queue.enqueue(first, l->last);
+            auto target = super->get_allocator();
+            target->note_posted(l->count);
+            SNMALLOC_PROBE3(remote_post, id, target->trunc_id(), l->count);
+            target->message_queue.enqueue(first, l->last);
             l->clear();
           }
         }
@@ -253,6 +338,7 @@ namespace snmalloc
           RemoteList* l = &list[slot];
           l->last->non_atomic_next = r;
           l->last = r;
+          l->count++;
 
           r = r->non_atomic_next;
         }
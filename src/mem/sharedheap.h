@@ -0,0 +1,558 @@
+#pragma once
+
+#include "globalalloc.h"
+
+#ifndef _WIN32
+#  include <fcntl.h>
+#  include <sys/mman.h>
+#  include <unistd.h>
+#endif
+
+namespace snmalloc
+{
+  /**
+   * A heap in a shared memory segment, which cooperating processes can all
+   * allocate from and free to, for passing data between them without
+   * copying.
+   *
+   * The heap's state, including its own allocator and a chunkmap covering
+   * only the segment, is kept at the start of the segment.  Every process
+   * maps the segment at the same address, so the allocator's metadata and
+   * pointers stored in the heap are valid in all of them.  Calls to the heap
+   * are serialised by a lock in the segment, which is held only for the
+   * duration of each call.  If a process dies while holding it, the other
+   * processes will wait for it forever.
+   *
+   * Objects in the heap must only be freed through the heap.  Memory freed
+   * to the heap is not returned to the operating system until the segment is
+   * destroyed.  The segment is accessed through a `SharedHeapHandle`.
+   */
+  class SharedHeap
+  {
+  public:
+    /**
+     * The start of the segment, read by `SharedHeapHandle::attach` to find
+     * where to map the rest.
+     */
+    struct Header
+    {
+      static constexpr char MAGIC[8] = {'S', 'N', 'S', 'H', 'E', 'A', 'P', '1'};
+
+      char magic[8];
+
+      /**
+       * Size of the segment in bytes.
+       */
+      uint64_t size;
+
+      /**
+       * Address at which every process maps the segment.
+       */
+      uint64_t base;
+    };
+
+  private:
+    friend class SharedHeapHandle;
+
+    static bool never_init(void*)
+    {
+      return false;
+    }
+
+    static void* no_op_init(function_ref<void*(void*)>)
+    {
+      error("SharedHeap allocators do not need initialisation");
+    }
+
+    using Pal = PALNoAlloc<DefaultPal>;
+
+    /**
+     * Confines amplification to the segment.
+     */
+    struct ArenaMap
+    {
+      CapPtr<void, CBArena> arena_root;
+
+      template<typename T = void, typename U, capptr_bounds B>
+      SNMALLOC_FAST_PATH CapPtr<T, CBArena> capptr_amplify(CapPtr<U, B> r)
+      {
+        return Aal::capptr_rebound<T>(arena_root, r);
+      }
+    };
+
+    /**
+     * A chunkmap covering only the segment, with its entries in the segment
+     * after the heap's state, so that every process sees the chunks that any
+     * of them allocated.  This provides the interface of `DefaultChunkMap`.
+     */
+    class ChunkMap
+    {
+      size_t first = 0;
+      size_t count = 0;
+      uint8_t* entries = nullptr;
+
+      void set(address_t p, uint8_t x, size_t n = 1)
+      {
+        size_t i = (p >> SUPERSLAB_BITS) - first;
+        SNMALLOC_ASSERT((i < count) && (n <= count - i));
+        memset(entries + i, x, n);
+      }
+
+    public:
+      ChunkMap() = default;
+
+      ChunkMap(address_t base, size_t size, uint8_t* entries)
+      : first(base >> SUPERSLAB_BITS),
+        count(((base + size - 1) >> SUPERSLAB_BITS) - first + 1),
+        entries(entries)
+      {}
+
+      /**
+       * The number of entries needed for a segment of `size` bytes.
+       */
+      static constexpr size_t entries_for(size_t size)
+      {
+        return (size >> SUPERSLAB_BITS) + 2;
+      }
+
+      uint8_t get(address_t p)
+      {
+        size_t i = (p >> SUPERSLAB_BITS) - first;
+        return (i < count) ? entries[i] : static_cast<uint8_t>(CMNotOurs);
+      }
+
+      void set_slab(CapPtr<Superslab, CBChunk> slab)
+      {
+        set(address_cast(slab), CMSuperslab);
+      }
+
+      void set_slab(CapPtr<Mediumslab, CBChunk> slab)
+      {
+        set(address_cast(slab), CMMediumslab);
+      }
+
+      void clear_slab(CapPtr<Superslab, CBChunk> slab)
+      {
+        SNMALLOC_ASSERT(get(address_cast(slab)) == CMSuperslab);
+        set(address_cast(slab), CMNotOurs);
+      }
+
+      void clear_slab(CapPtr<Mediumslab, CBChunk> slab)
+      {
+        SNMALLOC_ASSERT(get(address_cast(slab)) == CMMediumslab);
+        set(address_cast(slab), CMNotOurs);
+      }
+
+      void set_large_size(CapPtr<Largeslab, CBChunk> p, size_t size)
+      {
+        size_t size_bits = bits::next_pow2_bits(size);
+        set(address_cast(p), static_cast<uint8_t>(size_bits));
+        auto ss = address_cast(p) + SUPERSLAB_SIZE;
+        for (size_t i = 0; i < size_bits - SUPERSLAB_BITS; i++)
+        {
+          size_t run = bits::one_at_bit(i);
+          set(ss, static_cast<uint8_t>(CMLargeRangeMin + i), run);
+          ss = ss + SUPERSLAB_SIZE * run;
+        }
+      }
+
+      void clear_large_size(CapPtr<Largeslab, CBChunk> p, size_t size)
+      {
+        SNMALLOC_ASSERT(get(address_cast(p)) == bits::next_pow2_bits(size));
+        set(
+          address_cast(p), CMNotOurs, bits::next_pow2(size) >> SUPERSLAB_BITS);
+      }
+    };
+
+    using MemoryProvider = MemoryProviderStateMixin<Pal, ArenaMap>;
+
+    using HeapAlloc =
+      Allocator<never_init, no_op_init, MemoryProvider, ChunkMap>;
+
+    Header header;
+
+    std::atomic_flag lock = ATOMIC_FLAG_INIT;
+
+    std::atomic<void*> root_object{nullptr};
+
+    MemoryProvider state;
+
+    HeapAlloc allocator;
+
+    /**
+     * The bytes at the start of the segment used for the heap's state and
+     * chunkmap.
+     */
+    static constexpr size_t state_size(size_t size)
+    {
+      return bits::align_up(
+        sizeof(SharedHeap) + ChunkMap::entries_for(size), OS_PAGE_SIZE);
+    }
+
+    SharedHeap(size_t size)
+    : state(
+        pointer_offset(CapPtr<void, CBChunk>(this), state_size(size)),
+        size - state_size(size)),
+      allocator(
+        state,
+        ChunkMap(
+          address_cast(this),
+          size,
+          pointer_offset<uint8_t>(this, sizeof(SharedHeap))))
+    {
+      memcpy(header.magic, Header::MAGIC, sizeof(header.magic));
+      header.size = size;
+      header.base = address_cast(this);
+      state.arenamap().arena_root = CapPtr<void, CBArena>(this);
+    }
+
+  public:
+    /**
+     * The smallest segment that `SharedHeapHandle::create` accepts.  Part of
+     * a superslab can be lost to alignment, so this allows for two.
+     */
+    static constexpr size_t min_size()
+    {
+      return state_size(4 * SUPERSLAB_SIZE) + 2 * SUPERSLAB_SIZE;
+    }
+
+    /**
+     * Allocate `size` bytes from the segment, returning null if it is full.
+     */
+    void* alloc(size_t size)
+    {
+      FlagLock f(lock);
+      return allocator.alloc(size);
+    }
+
+    /**
+     * Free an object allocated by `alloc` in any process.
+     */
+    void dealloc(void* p)
+    {
+      FlagLock f(lock);
+      allocator.dealloc(p);
+    }
+
+    /**
+     * Returns true if `p` points into the segment.
+     */
+    bool contains(const void* p)
+    {
+      return (p >= this) &&
+        (p < pointer_offset(this, static_cast<size_t>(header.size)));
+    }
+
+    /**
+     * Record an object from which other processes can find the data in the
+     * heap.
+     */
+    void set_root(void* p)
+    {
+      root_object.store(p, std::memory_order_release);
+    }
+
+    template<typename T>
+    T* root()
+    {
+      return static_cast<T*>(root_object.load(std::memory_order_acquire));
+    }
+  };
+
+  /**
+   * A process's mapping of a `SharedHeap` segment, which is unmapped when the
+   * handle is destroyed or `detach` is called.
+   *
+   * On POSIX platforms, segments are named `shm_open` objects, or on Linux
+   * can be anonymous `memfd`s, passed to other processes by inheritance or
+   * over a socket.  On Windows, they are named file mappings backed by the
+   * paging file, committed in full when created.
+   */
+  class SharedHeapHandle
+  {
+    SharedHeap* heap = nullptr;
+#ifdef _WIN32
+    HANDLE mapping = nullptr;
+#else
+    int fd = -1;
+#endif
+
+#ifdef _WIN32
+    static SharedHeapHandle create_mapping(HANDLE mapping, size_t size)
+    {
+      SharedHeapHandle h;
+      h.mapping = mapping;
+      void* p = MapViewOfFile(mapping, FILE_MAP_ALL_ACCESS, 0, 0, size);
+      if (p != nullptr)
+        h.heap = new (p) SharedHeap(size);
+      return h;
+    }
+#else
+    static SharedHeapHandle create_fd(int fd, size_t size)
+    {
+      SharedHeapHandle h;
+      h.fd = fd;
+      if (ftruncate(fd, static_cast<off_t>(size)) != 0)
+        return h;
+      void* p = mmap(nullptr, size, PROT_READ | PROT_WRITE, MAP_SHARED, fd, 0);
+      if (p != MAP_FAILED)
+        h.heap = new (p) SharedHeap(size);
+      return h;
+    }
+#endif
+
+  public:
+    SharedHeapHandle() = default;
+
+    SharedHeapHandle(const SharedHeapHandle&) = delete;
+    SharedHeapHandle& operator=(const SharedHeapHandle&) = delete;
+
+    SharedHeapHandle(SharedHeapHandle&& other) noexcept
+    {
+      *this = std::move(other);
+    }
+
+    SharedHeapHandle& operator=(SharedHeapHandle&& other) noexcept
+    {
+      detach();
+      heap = other.heap;
+      other.heap = nullptr;
+#ifdef _WIN32
+      mapping = other.mapping;
+      other.mapping = nullptr;
+#else
+      fd = other.fd;
+      other.fd = -1;
+#endif
+      return *this;
+    }
+
+    ~SharedHeapHandle()
+    {
+      detach();
+    }
+
+    /**
+     * Create a segment of `size` bytes, rounded up to a whole number of
+     * pages, named `name`, which must not already exist, and start a heap in
+     * it.  The handle is empty if the segment cannot be created or mapped,
+     * or if `size` is less than `SharedHeap::min_size()`.
+     */
+    static SharedHeapHandle create(const char* name, size_t size)
+    {
+      size = bits::align_up(size, OS_PAGE_SIZE);
+      if (size < SharedHeap::min_size())
+        return {};
+
+#ifdef _WIN32
+      HANDLE mapping = CreateFileMappingA(
+        INVALID_HANDLE_VALUE,
+        nullptr,
+        PAGE_READWRITE,
+        static_cast<DWORD>(static_cast<uint64_t>(size) >> 32),
+        static_cast<DWORD>(size),
+        name);
+      if (mapping == nullptr)
+        return {};
+      if (GetLastError() == ERROR_ALREADY_EXISTS)
+      {
+        CloseHandle(mapping);
+        return {};
+      }
+      return create_mapping(mapping, size);
+#else
+      int fd = shm_open(name, O_RDWR | O_CREAT | O_EXCL, 0600);
+      if (fd < 0)
+        return {};
+      SharedHeapHandle h = create_fd(fd, size);
+      if (!h)
+      {
+        shm_unlink(name);
+        return {};
+      }
+      return h;
+#endif
+    }
+
+#ifdef __linux__
+    /**
+     * As `create`, for an anonymous segment, which other processes attach to
+     * with `attach_fd` after inheriting or being sent `get_fd()`.
+     */
+    static SharedHeapHandle create_anonymous(size_t size)
+    {
+      size = bits::align_up(size, OS_PAGE_SIZE);
+      if (size < SharedHeap::min_size())
+        return {};
+
+      int fd = memfd_create("snmalloc-shared-heap", MFD_CLOEXEC);
+      if (fd < 0)
+        return {};
+      return create_fd(fd, size);
+    }
+#endif
+
+    /**
+     * Map the segment named `name`, created by `create` in this or another
+     * process.  The handle is empty if it does not exist or cannot be mapped
+     * at the address where the other processes have mapped it.
+     */
+    static SharedHeapHandle attach(const char* name)
+    {
+#ifdef _WIN32
+      HANDLE mapping = OpenFileMappingA(FILE_MAP_ALL_ACCESS, FALSE, name);
+      if (mapping == nullptr)
+        return {};
+      SharedHeapHandle h;
+      h.mapping = mapping;
+
+      SharedHeap::Header header;
+      void* p = MapViewOfFile(mapping, FILE_MAP_READ, 0, 0, sizeof(header));
+      if (p == nullptr)
+        return {};
+      memcpy(&header, p, sizeof(header));
+      UnmapViewOfFile(p);
+      if (
+        memcmp(header.magic, SharedHeap::Header::MAGIC, sizeof(header.magic)) !=
+        0)
+        return {};
+
+      void* base = reinterpret_cast<void*>(header.base);
+      p = MapViewOfFileEx(
+        mapping,
+        FILE_MAP_ALL_ACCESS,
+        0,
+        0,
+        static_cast<size_t>(header.size),
+        base);
+      if (p != base)
+      {
+        if (p != nullptr)
+          UnmapViewOfFile(p);
+        return {};
+      }
+      h.heap = static_cast<SharedHeap*>(p);
+      return h;
+#else
+      int fd = shm_open(name, O_RDWR, 0);
+      if (fd < 0)
+        return {};
+      return attach_fd(fd);
+#endif
+    }
+
+#ifndef _WIN32
+    /**
+     * As `attach`, for the segment open as `fd`, which the handle takes
+     * ownership of.
+     */
+    static SharedHeapHandle attach_fd(int fd)
+    {
+      SharedHeapHandle h;
+      h.fd = fd;
+
+      SharedHeap::Header header;
+      if (
+        (pread(fd, &header, sizeof(header), 0) != sizeof(header)) ||
+        (memcmp(
+           header.magic, SharedHeap::Header::MAGIC, sizeof(header.magic)) !=
+         0))
+        return {};
+
+      void* base = reinterpret_cast<void*>(header.base);
+      int flags = MAP_SHARED;
+#  ifdef MAP_FIXED_NOREPLACE
+      flags |= MAP_FIXED_NOREPLACE;
+#  endif
+      void* p = mmap(
+        base,
+        static_cast<size_t>(header.size),
+        PROT_READ | PROT_WRITE,
+        flags,
+        fd,
+        0);
+      if (p != base)
+      {
+        if (p != MAP_FAILED)
+          munmap(p, static_cast<size_t>(header.size));
+        return {};
+      }
+      h.heap = static_cast<SharedHeap*>(p);
+      return h;
+    }
+
+    /**
+     * The descriptor of the segment, for passing to other processes.
+     */
+    int get_fd() const
+    {
+      return fd;
+    }
+
+    /**
+     * Remove the name of the segment `name`, so that no more processes can
+     * attach to it.  It is destroyed when the last handle is detached.
+     */
+    static bool remove(const char* name)
+    {
+      return shm_unlink(name) == 0;
+    }
+#else
+    /**
+     * Named file mappings are destroyed when the last handle is closed, so
+     * there is nothing to remove.
+     */
+    static bool remove(const char*)
+    {
+      return true;
+    }
+#endif
+
+    /**
+     * Unmap the segment, leaving the handle empty.  No pointers into the
+     * segment may be used afterwards in this process.
+     */
+    void detach()
+    {
+      if (heap != nullptr)
+      {
+        size_t size = static_cast<size_t>(heap->header.size);
+#ifdef _WIN32
+        UnmapViewOfFile(heap);
+        UNUSED(size);
+#else
+        munmap(heap, size);
+#endif
+        heap = nullptr;
+      }
+#ifdef _WIN32
+      if (mapping != nullptr)
+      {
+        CloseHandle(mapping);
+        mapping = nullptr;
+      }
+#else
+      if (fd >= 0)
+      {
+        close(fd);
+        fd = -1;
+      }
+#endif
+    }
+
+    explicit operator bool() const
+    {
+      return heap != nullptr;
+    }
+
+    SharedHeap* get() const
+    {
+      return heap;
+    }
+
+    SharedHeap* operator->() const
+    {
+      return heap;
+    }
+  };
+} // namespace snmalloc
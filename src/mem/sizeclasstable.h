@@ -151,6 +151,18 @@ namespace snmalloc
       .medium_slab_slots[(sizeclass - NUM_SMALL_CLASSES)];
   }
 
+  /**
+   * The number of objects of a small or medium sizeclass in one of its slabs.
+   * The first slab in a superslab of small objects holds fewer, as it also
+   * holds the superslab's header.
+   */
+  constexpr static inline size_t sizeclass_to_slab_capacity(sizeclass_t sc)
+  {
+    if (sc < NUM_SMALL_CLASSES)
+      return get_slab_capacity(sc, false);
+    return medium_slab_free(sc);
+  }
+
   inline static size_t round_by_sizeclass(sizeclass_t sc, size_t offset)
   {
     // Only works up to certain offsets, exhaustively tested upto
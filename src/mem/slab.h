@@ -46,7 +46,7 @@ namespace snmalloc
 
       b.open(bumpptr);
 
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       // Structure to represent the temporary list elements
       struct PreAllocObject
       {
@@ -173,7 +173,7 @@ namespace snmalloc
         return Superslab::NoSlabReturn;
       }
 
-#ifdef CHECK_CLIENT
+#ifdef SNMALLOC_CHECK_FREELIST
       size_t count = 1;
       // Check free list is well-formed on platforms with
       // integers as pointers.
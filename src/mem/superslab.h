@@ -175,6 +175,15 @@ namespace snmalloc
       return (used >= ((SLAB_COUNT - 1) << 1));
     }
 
+    /**
+     * Returns the number of slabs, including the short slab, that are not
+     * currently assigned to any sizeclass.
+     */
+    size_t unused_slabs()
+    {
+      return (SLAB_COUNT - 1) - (used >> 1) + (1 - (used & 1));
+    }
+
     Status get_status()
     {
       if (!is_almost_full())
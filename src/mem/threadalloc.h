@@ -120,6 +120,13 @@ namespace snmalloc
       auto& per_thread = get_reference();
       if (per_thread != get_GlobalPlaceHolder())
       {
+#  if defined(SNMALLOC_RELEASE_ON_THREAD_EXIT) && \
+    !defined(SNMALLOC_PASS_THROUGH)
+        // Return the cached state now, rather than leaving it stranded until
+        // another thread reuses this allocator.
+        size_t reclaimed = per_thread->flush();
+        default_memory_provider().reclaimed_on_thread_exit(reclaimed);
+#  endif
         current_alloc_pool()->release(per_thread);
         destructor_has_run = true;
         per_thread = get_GlobalPlaceHolder();
@@ -204,6 +211,25 @@ namespace snmalloc
       return alloc;
 #  endif
     }
+
+    /**
+     * Flush the calling thread's allocator and return it to the pool now,
+     * rather than when the thread exits, for runtimes that manage their own
+     * threads or unload code that allocated.  If the thread allocates again,
+     * it acquires an allocator as before, and is still cleaned up on exit.
+     */
+    static void teardown()
+    {
+      auto& per_thread = get_reference();
+      if (per_thread == get_GlobalPlaceHolder())
+        return;
+#  ifndef SNMALLOC_PASS_THROUGH
+      size_t reclaimed = per_thread->flush();
+      default_memory_provider().reclaimed_on_thread_exit(reclaimed);
+#  endif
+      current_alloc_pool()->release(per_thread);
+      per_thread = get_GlobalPlaceHolder();
+    }
   };
 
   /**
@@ -277,6 +303,7 @@ namespace snmalloc
     if (local_alloc == get_GlobalPlaceHolder())
     {
       local_alloc = current_alloc_pool()->acquire();
+      local_alloc->reset_thread_stats();
     }
     auto result = f(local_alloc);
     // Check if we have already run the destructor for the TLS.  If so,
@@ -297,6 +324,46 @@ namespace snmalloc
     return existing == get_GlobalPlaceHolder();
   }
 #endif
+
+  inline ThreadStats ThreadStats::current()
+  {
+    return ThreadAlloc::get()->thread_stats();
+  }
+
+  /**
+   * Return as much free memory to the platform as possible, in the manner of
+   * `malloc_trim(0)`: the calling thread's allocator is flushed, the objects
+   * queued for allocators that no thread owns are processed, and then every
+   * freed chunk kept committed for reuse is decommitted.  Memory held by
+   * other threads' allocators is not affected.  Returns the bytes returned to
+   * the platform.
+   */
+  inline size_t release_free_memory()
+  {
+#ifdef SNMALLOC_PASS_THROUGH
+    return 0;
+#else
+    ThreadAlloc::get()->flush();
+    current_alloc_pool()->cleanup_unused();
+    return default_memory_provider().decommit_cached();
+#endif
+  }
+
+  /**
+   * Process the objects that other threads have freed for the calling
+   * thread's allocator, and post those it has freed for other threads, so
+   * that latency-sensitive threads can do this work at points they choose
+   * rather than in whichever allocation or deallocation would otherwise do
+   * it.  Returns the number of objects processed.
+   */
+  inline size_t flush_remote()
+  {
+#ifdef SNMALLOC_PASS_THROUGH
+    return 0;
+#else
+    return ThreadAlloc::get()->flush_remote();
+#endif
+  }
 } // namespace snmalloc
 #ifdef SNMALLOC_USE_THREAD_CLEANUP
 /**
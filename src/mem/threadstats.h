@@ -0,0 +1,85 @@
+#pragma once
+
+#include "../ds/defines.h"
+
+#include <cstddef>
+
+namespace snmalloc
+{
+  /**
+   * Counters of the memory allocated and freed by one thread, for attributing
+   * memory churn to particular threads.
+   *
+   * The counters are kept by each allocator, and reset when a thread takes an
+   * allocator from the pool, so they cover the current thread since it first
+   * allocated.  Sizes are of the memory used, after rounding to a sizeclass.
+   * They are only maintained if built with `SNMALLOC_THREAD_STATS`, and are
+   * zero otherwise.
+   */
+  struct ThreadStats
+  {
+    /**
+     * Bytes allocated by this thread.
+     */
+    size_t bytes_allocated = 0;
+
+    /**
+     * Bytes freed by this thread, wherever they were allocated.
+     */
+    size_t bytes_freed = 0;
+
+    /**
+     * Objects freed by this thread that were allocated by another.
+     */
+    size_t remote_frees_sent = 0;
+
+    /**
+     * Objects allocated by this thread that were freed by another, and have
+     * been returned to this thread.  An allocator only processes returned
+     * objects when it allocates or frees, so this can lag behind the other
+     * threads' `remote_frees_sent`.
+     */
+    size_t remote_frees_received = 0;
+
+    void alloc(size_t size)
+    {
+#ifdef SNMALLOC_THREAD_STATS
+      bytes_allocated += size;
+#else
+      UNUSED(size);
+#endif
+    }
+
+    void dealloc(size_t size)
+    {
+#ifdef SNMALLOC_THREAD_STATS
+      bytes_freed += size;
+#else
+      UNUSED(size);
+#endif
+    }
+
+    void remote_send(size_t size)
+    {
+#ifdef SNMALLOC_THREAD_STATS
+      bytes_freed += size;
+      remote_frees_sent++;
+#else
+      UNUSED(size);
+#endif
+    }
+
+    void remote_receive()
+    {
+#ifdef SNMALLOC_THREAD_STATS
+      remote_frees_received++;
+#endif
+    }
+
+    /**
+     * The counters for the calling thread.  This initialises the thread's
+     * allocator if it has not yet allocated.
+     */
+    static ThreadStats current();
+  };
+} // namespace snmalloc
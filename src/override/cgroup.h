@@ -0,0 +1,214 @@
+#pragma once
+
+/**
+ * Detection of the memory limits of the cgroup (v2) that the process runs
+ * in, so that a containerised service returns freed memory promptly rather
+ * than being killed for holding memory that it could have decommitted.
+ *
+ * The limits are read from `memory.max` and `memory.high` in the process's
+ * cgroup and each of its ancestors below the cgroup2 mount, the lowest of
+ * each being the one in effect.  `cgroup::apply` then makes freed chunks be
+ * decommitted as soon as they are freed, with `MADV_DONTNEED` where the
+ * platform offers the choice, and, if asked to, bounds the heap's footprint
+ * by `memory.max`.  Only Linux has cgroups; elsewhere no limits are found.
+ */
+#include "../snmalloc.h"
+
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+
+namespace snmalloc::cgroup
+{
+  /**
+   * Memory limits in bytes, or zero where there is none.
+   */
+  struct Limits
+  {
+    size_t max;
+    size_t high;
+  };
+
+  /**
+   * Parse the contents of a `memory.max` or `memory.high` file from `s`
+   * into `limit`, which is zero for `max`, meaning no limit.  Returns false
+   * if it is not recognised.
+   */
+  inline bool parse_limit(const char* s, size_t& limit)
+  {
+    if (strncmp(s, "max", 3) == 0)
+    {
+      limit = 0;
+      return true;
+    }
+    char* end;
+    unsigned long long value = strtoull(s, &end, 10);
+    if ((end == s) || ((*end != '\0') && (*end != '\n')))
+      return false;
+    limit = static_cast<size_t>(bits::min<unsigned long long>(value, SIZE_MAX));
+    return true;
+  }
+
+  /**
+   * Read the first line of the file at `path` into `buf`.  Returns false if
+   * it cannot be read.
+   */
+  inline bool read_line(const char* path, char* buf, size_t len)
+  {
+    FILE* f = fopen(path, "r");
+    if (f == nullptr)
+      return false;
+    bool result = fgets(buf, static_cast<int>(len), f) != nullptr;
+    fclose(f);
+    return result;
+  }
+
+  /**
+   * Lower `limit` to that in the file at `path`, if it has one.
+   */
+  inline void lower_to_file(const char* path, size_t& limit)
+  {
+    char buf[64];
+    size_t value;
+    if (
+      read_line(path, buf, sizeof(buf)) && parse_limit(buf, value) &&
+      (value != 0) && ((limit == 0) || (value < limit)))
+      limit = value;
+  }
+
+  /**
+   * Read the limits in effect for the cgroup at `path` in the cgroup2
+   * hierarchy mounted at `root`: the lowest of those set on it and its
+   * ancestors.
+   */
+  inline Limits read_limits(const char* root, const char* path)
+  {
+    Limits limits = {0, 0};
+    char dir[1024];
+    size_t root_len = strlen(root);
+    int len = snprintf(dir, sizeof(dir), "%s%s", root, path);
+    if ((len < 0) || (static_cast<size_t>(len) >= sizeof(dir)))
+      return limits;
+
+    char file[sizeof(dir) + 16];
+    size_t dir_len = static_cast<size_t>(len);
+    while (dir_len > 0 && dir[dir_len - 1] == '/')
+      dir_len--;
+    while (true)
+    {
+      dir[dir_len] = '\0';
+      snprintf(file, sizeof(file), "%s/memory.max", dir);
+      lower_to_file(file, limits.max);
+      snprintf(file, sizeof(file), "%s/memory.high", dir);
+      lower_to_file(file, limits.high);
+
+      // Move to the parent, stopping at the root of the hierarchy.
+      char* slash = strrchr(dir, '/');
+      if ((slash == nullptr) || (static_cast<size_t>(slash - dir) < root_len))
+        break;
+      dir_len = static_cast<size_t>(slash - dir);
+    }
+    return limits;
+  }
+
+  /**
+   * Find where the cgroup2 hierarchy is mounted, from `/proc/self/mountinfo`,
+   * and the process's cgroup in it, from `/proc/self/cgroup`.  Returns false
+   * if there is none, as when only cgroup v1 is in use.
+   */
+  inline bool
+  find(char* root, size_t root_len, char* path, size_t path_len)
+  {
+    char line[1024];
+    bool found = false;
+    FILE* f = fopen("/proc/self/mountinfo", "r");
+    if (f == nullptr)
+      return false;
+    while (!found && (fgets(line, sizeof(line), f) != nullptr))
+    {
+      // The filesystem type follows a " - " separator; the mount point is
+      // the fifth field.
+      if (strstr(line, " - cgroup2 ") == nullptr)
+        continue;
+      char* field = line;
+      for (int i = 0; (i < 4) && (field != nullptr); i++)
+      {
+        field = strchr(field, ' ');
+        if (field != nullptr)
+          field++;
+      }
+      char* end = (field == nullptr) ? nullptr : strchr(field, ' ');
+      if ((end != nullptr) && (static_cast<size_t>(end - field) < root_len))
+      {
+        memcpy(root, field, static_cast<size_t>(end - field));
+        root[end - field] = '\0';
+        found = true;
+      }
+    }
+    fclose(f);
+    if (!found)
+      return false;
+
+    // The cgroup2 entry is the one with hierarchy ID 0 and no controllers.
+    found = false;
+    f = fopen("/proc/self/cgroup", "r");
+    if (f == nullptr)
+      return false;
+    while (!found && (fgets(line, sizeof(line), f) != nullptr))
+    {
+      if (strncmp(line, "0::", 3) != 0)
+        continue;
+      size_t len = strcspn(line + 3, "\n");
+      if (len < path_len)
+      {
+        memcpy(path, line + 3, len);
+        path[len] = '\0';
+        found = true;
+      }
+    }
+    fclose(f);
+    return found;
+  }
+
+  /**
+   * Returns the limits in effect for the calling process, which are zero if
+   * it is not in a cgroup2 hierarchy.
+   */
+  inline Limits current()
+  {
+#ifdef __linux__
+    char root[512];
+    char path[512];
+    if (find(root, sizeof(root), path, sizeof(path)))
+      return read_limits(root, path);
+#endif
+    return {0, 0};
+  }
+
+  /**
+   * If the calling process has a cgroup memory limit, decommit freed chunks
+   * as soon as they are freed, advising the platform to drop their pages at
+   * once.  If `limit_heap` is true, also limit the heap to `memory.max`.
+   * That limit counts the address space that the heap has reserved, not
+   * the memory that is resident, so it makes programs that rely on
+   * overcommit or on large reservations that are only partly touched fail
+   * to allocate.  Returns false, changing nothing, if there is no limit or
+   * with `SNMALLOC_PASS_THROUGH`.
+   */
+  inline bool apply(bool limit_heap = false)
+  {
+#ifdef SNMALLOC_PASS_THROUGH
+    UNUSED(limit_heap);
+    return false;
+#else
+    Limits limits = current();
+    if ((limits.max == 0) && (limits.high == 0))
+      return false;
+    set_decommit_strategy(DecommitSuper);
+    set_decommit_advice(AdviseDontNeed);
+    if (limit_heap && (limits.max != 0))
+      set_memory_limit(limits.max);
+    return true;
+#endif
+  }
+} // namespace snmalloc::cgroup
@@ -0,0 +1,64 @@
+#pragma once
+
+/**
+ * Per-thread counts of the allocations and deallocations made through the
+ * shims, for tests that check that a piece of code does not allocate, or
+ * allocates no more than expected.
+ *
+ * If the shims are built with `SNMALLOC_COUNT_ALLOCATIONS` defined, each
+ * thread keeps running totals, which a test can read before and after the
+ * code under test; `counting::Scope` does this.  Reallocations count as an
+ * allocation and a deallocation when they move the block, and as neither
+ * when they resize it in place.  Allocations that fail, returning null, are
+ * not counted.  Otherwise, the counting macros expand to nothing.
+ */
+#ifdef SNMALLOC_COUNT_ALLOCATIONS
+#  include <cstddef>
+
+namespace snmalloc::counting
+{
+  struct Counts
+  {
+    size_t allocations = 0;
+    size_t deallocations = 0;
+  };
+
+  inline thread_local Counts thread_counts;
+
+  /**
+   * The allocations and deallocations made by the current thread since the
+   * scope was created.
+   */
+  class Scope
+  {
+    Counts start = thread_counts;
+
+  public:
+    size_t allocations() const
+    {
+      return thread_counts.allocations - start.allocations;
+    }
+
+    size_t deallocations() const
+    {
+      return thread_counts.deallocations - start.deallocations;
+    }
+  };
+
+  /**
+   * Count an allocation that returned `p`, unless it failed.
+   */
+  inline void count_alloc(const void* p)
+  {
+    if (p != nullptr)
+      thread_counts.allocations++;
+  }
+} // namespace snmalloc::counting
+
+#  define SNMALLOC_COUNT_ALLOC(p) snmalloc::counting::count_alloc(p)
+#  define SNMALLOC_COUNT_DEALLOC() \
+    snmalloc::counting::thread_counts.deallocations++
+#else
+#  define SNMALLOC_COUNT_ALLOC(p) ((void)0)
+#  define SNMALLOC_COUNT_DEALLOC() ((void)0)
+#endif
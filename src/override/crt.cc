@@ -0,0 +1,126 @@
+/**
+ * Replaces the allocation functions of the static Microsoft C runtime with
+ * snmalloc, so that C code linked into the same binary shares its heap.
+ *
+ * This forwards to the `sn_`-prefixed functions from `malloc.cc` (which
+ * `rust.cc` includes), and must be linked ahead of the CRT.  The CRT defines
+ * each function alongside a `_base` variant in the same object file and calls
+ * the `_base` variants internally, so both are replaced here; missing either
+ * would pull in the CRT's object and cause duplicate symbol errors.
+ *
+ * Only the static CRT (/MT) can be replaced this way.  The DLL CRT binds its
+ * own internal calls when it is built.
+ */
+#if !defined(_WIN32)
+#  error crt.cc replaces the Microsoft C runtime allocator
+#endif
+#ifdef _DLL
+#  error crt.cc requires the static C runtime (/MT)
+#endif
+
+#include <cstddef>
+#include <cstdint>
+#include <cstring>
+
+extern "C"
+{
+  void* sn_malloc(size_t size);
+  void* sn_calloc(size_t nmemb, size_t size);
+  void* sn_realloc(void* ptr, size_t size);
+  void sn_free(void* ptr);
+  size_t sn_malloc_usable_size(void* ptr);
+
+  void* __cdecl _malloc_base(size_t size)
+  {
+    return sn_malloc(size);
+  }
+
+  void* __cdecl _calloc_base(size_t nmemb, size_t size)
+  {
+    return sn_calloc(nmemb, size);
+  }
+
+  void* __cdecl _realloc_base(void* ptr, size_t size)
+  {
+    return sn_realloc(ptr, size);
+  }
+
+  void __cdecl _free_base(void* ptr)
+  {
+    sn_free(ptr);
+  }
+
+  size_t __cdecl _msize_base(void* ptr)
+  {
+    return sn_malloc_usable_size(ptr);
+  }
+
+  /**
+   * Resize without moving: succeeds only if `size` fits in the existing
+   * block.
+   */
+  void* __cdecl _expand_base(void* ptr, size_t size)
+  {
+    return size <= sn_malloc_usable_size(ptr) ? ptr : nullptr;
+  }
+
+  /**
+   * As `realloc`, but bytes beyond the old `nmemb * size` are zeroed.
+   *
+   * The old size is not known, only the old usable size, which may be
+   * larger.  So everything past `nmemb * size` is kept zero: `calloc`
+   * zeroes the whole block, and this zeroes the tail of the block it
+   * returns.  The next call then only has to zero from the old usable size.
+   * Blocks from `malloc` or `realloc` are zeroed from their usable size,
+   * which is what `_msize` reports for them.
+   */
+  void* __cdecl _recalloc_base(void* ptr, size_t nmemb, size_t size)
+  {
+    if ((size != 0) && (nmemb > SIZE_MAX / size))
+      return nullptr;
+    size_t sz = nmemb * size;
+    size_t old_sz = ptr == nullptr ? 0 : sn_malloc_usable_size(ptr);
+    auto p = static_cast<char*>(sn_realloc(ptr, sz));
+    if (p != nullptr)
+    {
+      size_t from = old_sz < sz ? old_sz : sz;
+      memset(p + from, 0, sn_malloc_usable_size(p) - from);
+    }
+    return p;
+  }
+
+  void* __cdecl malloc(size_t size)
+  {
+    return _malloc_base(size);
+  }
+
+  void* __cdecl calloc(size_t nmemb, size_t size)
+  {
+    return _calloc_base(nmemb, size);
+  }
+
+  void* __cdecl realloc(void* ptr, size_t size)
+  {
+    return _realloc_base(ptr, size);
+  }
+
+  void __cdecl free(void* ptr)
+  {
+    _free_base(ptr);
+  }
+
+  size_t __cdecl _msize(void* ptr)
+  {
+    return _msize_base(ptr);
+  }
+
+  void* __cdecl _expand(void* ptr, size_t size)
+  {
+    return _expand_base(ptr, size);
+  }
+
+  void* __cdecl _recalloc(void* ptr, size_t nmemb, size_t size)
+  {
+    return _recalloc_base(ptr, nmemb, size);
+  }
+}
@@ -0,0 +1,502 @@
+#pragma once
+
+/**
+ * Allocation accounting in the format of Valgrind's DHAT.
+ *
+ * If the shims are built with `SNMALLOC_DHAT` defined, every allocation made
+ * through them is attributed to its call stack, and `dhat::dump` writes the
+ * totals, lifetimes and peaks of each call stack as JSON, which the DHAT
+ * viewer (`dh_view.html`) reads.  If the `SNMALLOC_DHAT_FILE` environment
+ * variable names a file, the output is also written there at exit.
+ * Otherwise, the DHAT macros expand to nothing and their arguments are not
+ * evaluated.
+ *
+ * This records a backtrace for every allocation and serialises all
+ * allocations and frees on a lock, so it is for finding allocation hot
+ * spots in test runs, not for production.  Memory accesses are not tracked,
+ * and an allocation that is resized in place keeps its original size.
+ */
+#ifdef SNMALLOC_DHAT
+#  ifdef _WIN32
+#    error SNMALLOC_DHAT is only supported on POSIX platforms
+#  endif
+
+#  include "../ds/flaglock.h"
+#  include "../snmalloc.h"
+#  include "filewriter.h"
+
+#  include <algorithm>
+#  include <atomic>
+#  include <chrono>
+#  include <cstdlib>
+#  include <cstring>
+#  if defined(BACKTRACE_HEADER)
+#    include BACKTRACE_HEADER
+#  else
+#    include <execinfo.h>
+#  endif
+
+namespace snmalloc::dhat
+{
+  /**
+   * Frames recorded for each call stack, after those of the recorder itself.
+   */
+  static constexpr size_t MAX_FRAMES = 32;
+
+  /**
+   * Blocks that live for fewer than this many microseconds are reported as
+   * short-lived by the viewer.
+   */
+  static constexpr size_t SHORT_LIVED = 10;
+
+  /**
+   * The totals for one allocation call stack, in DHAT's terms a program
+   * point.
+   */
+  struct ProgramPoint
+  {
+    ProgramPoint* next;
+    size_t depth;
+    void* frames[MAX_FRAMES];
+
+    size_t total_bytes;
+    size_t total_blocks;
+
+    /**
+     * Sum of the lifetimes of the freed blocks, in microseconds.
+     */
+    uint64_t total_lifetime;
+
+    size_t curr_bytes;
+    size_t curr_blocks;
+    size_t max_bytes;
+    size_t max_blocks;
+
+    /**
+     * `curr_bytes` and `curr_blocks` at the global peak numbered
+     * `peak_generation`.
+     */
+    size_t peak_bytes;
+    size_t peak_blocks;
+    size_t peak_generation;
+
+    /**
+     * Lifetimes so far of the live blocks, while writing the output.
+     */
+    uint64_t live_lifetime;
+  };
+
+  /**
+   * A live allocation.
+   */
+  struct Block
+  {
+    Block* next;
+    void* address;
+    size_t size;
+    uint64_t start;
+    ProgramPoint* pp;
+  };
+
+  /**
+   * Set while recording, to ignore allocations made by `backtrace`.
+   */
+  inline thread_local bool busy = false;
+
+  class Recorder
+  {
+    static constexpr size_t PP_BITS = 12;
+    static constexpr size_t BLOCK_BITS = 16;
+
+    /**
+     * Frames of the recorder to drop from each backtrace.
+     */
+    static constexpr size_t SKIP_FRAMES = 1;
+
+    std::atomic_flag lock = ATOMIC_FLAG_INIT;
+    ProgramPoint* pps[bits::one_at_bit(PP_BITS)] = {};
+    Block* blocks[bits::one_at_bit(BLOCK_BITS)] = {};
+
+    /**
+     * Microseconds are measured from the first allocation.
+     */
+    bool started = false;
+    std::chrono::steady_clock::time_point start;
+
+    size_t curr_bytes = 0;
+    size_t curr_blocks = 0;
+    size_t peak_bytes = 0;
+    size_t peak_blocks = 0;
+    uint64_t peak_time = 0;
+
+    /**
+     * Incremented at each new global peak.  A program point whose
+     * `peak_generation` is older has not changed since the peak, so its
+     * current values are those at the peak.
+     */
+    size_t generation = 0;
+
+    static size_t hash(uint64_t v, size_t bits)
+    {
+      return static_cast<size_t>((v * 0x9E3779B97F4A7C15) >> (64 - bits));
+    }
+
+    uint64_t now()
+    {
+      return static_cast<uint64_t>(
+        std::chrono::duration_cast<std::chrono::microseconds>(
+          std::chrono::steady_clock::now() - start)
+          .count());
+    }
+
+    void snapshot(ProgramPoint* pp)
+    {
+      if (pp->peak_generation != generation)
+      {
+        pp->peak_bytes = pp->curr_bytes;
+        pp->peak_blocks = pp->curr_blocks;
+        pp->peak_generation = generation;
+      }
+    }
+
+    /**
+     * Find or create the program point for a call stack.  Must be called
+     * with the lock held.
+     */
+    ProgramPoint* program_point(void** frames, size_t depth)
+    {
+      uint64_t h = depth;
+      for (size_t i = 0; i < depth; i++)
+        h = (h * 31) + static_cast<uint64_t>(address_cast(frames[i]));
+
+      auto& head = pps[hash(h, PP_BITS)];
+      for (auto pp = head; pp != nullptr; pp = pp->next)
+      {
+        if (
+          (pp->depth == depth) &&
+          (memcmp(pp->frames, frames, depth * sizeof(void*)) == 0))
+          return pp;
+      }
+
+      auto pp = static_cast<ProgramPoint*>(
+        ThreadAlloc::get_noncachable()->alloc<YesZero>(sizeof(ProgramPoint)));
+      if (pp == nullptr)
+        return nullptr;
+      pp->depth = depth;
+      memcpy(pp->frames, frames, depth * sizeof(void*));
+      pp->peak_generation = generation;
+      pp->next = head;
+      head = pp;
+      return pp;
+    }
+
+    SNMALLOC_SLOW_PATH void record(void* p, size_t size)
+    {
+      void* frames[MAX_FRAMES + SKIP_FRAMES];
+      auto depth = static_cast<size_t>(
+        backtrace(frames, static_cast<int>(MAX_FRAMES + SKIP_FRAMES)));
+      depth = depth > SKIP_FRAMES ? depth - SKIP_FRAMES : 0;
+
+      auto block = static_cast<Block*>(
+        ThreadAlloc::get_noncachable()->alloc(sizeof(Block)));
+      if (block == nullptr)
+        return;
+
+      FlagLock f(lock);
+      if (!started)
+      {
+        started = true;
+        start = std::chrono::steady_clock::now();
+      }
+
+      auto pp = program_point(frames + SKIP_FRAMES, depth);
+      if (pp == nullptr)
+      {
+        ThreadAlloc::get_noncachable()->dealloc(block, sizeof(Block));
+        return;
+      }
+
+      snapshot(pp);
+      pp->total_bytes += size;
+      pp->total_blocks++;
+      pp->curr_bytes += size;
+      pp->curr_blocks++;
+      if (pp->curr_bytes > pp->max_bytes)
+      {
+        pp->max_bytes = pp->curr_bytes;
+        pp->max_blocks = pp->curr_blocks;
+      }
+
+      curr_bytes += size;
+      curr_blocks++;
+      uint64_t t = now();
+      if (curr_bytes > peak_bytes)
+      {
+        peak_bytes = curr_bytes;
+        peak_blocks = curr_blocks;
+        peak_time = t;
+        generation++;
+        snapshot(pp);
+      }
+
+      *block = {nullptr, p, size, t, pp};
+      auto& head = blocks[hash(address_cast(p), BLOCK_BITS)];
+      block->next = head;
+      head = block;
+    }
+
+    SNMALLOC_SLOW_PATH void forget(void* p)
+    {
+      Block* found = nullptr;
+      {
+        FlagLock f(lock);
+        auto& head = blocks[hash(address_cast(p), BLOCK_BITS)];
+        Block* prev = nullptr;
+        for (auto b = head; b != nullptr; prev = b, b = b->next)
+        {
+          if (b->address == p)
+          {
+            if (prev == nullptr)
+              head = b->next;
+            else
+              prev->next = b->next;
+            found = b;
+            break;
+          }
+        }
+        if (found == nullptr)
+          return;
+
+        auto pp = found->pp;
+        snapshot(pp);
+        pp->curr_bytes -= found->size;
+        pp->curr_blocks--;
+        pp->total_lifetime += now() - found->start;
+        curr_bytes -= found->size;
+        curr_blocks--;
+      }
+      ThreadAlloc::get_noncachable()->dealloc(found, sizeof(Block));
+    }
+
+    static void write_string(FileWriter& w, const char* s)
+    {
+      w.write("\"", 1);
+      for (; *s != '\0'; s++)
+      {
+        if ((*s == '"') || (*s == '\\'))
+          w.write("\\", 1);
+        if (static_cast<unsigned char>(*s) >= 0x20)
+          w.write(s, 1);
+      }
+      w.write("\"", 1);
+    }
+
+    static void write_command(FileWriter& w)
+    {
+      char cmd[256] = "unknown";
+#  ifdef __linux__
+      int fd = ::open("/proc/self/cmdline", O_RDONLY | O_CLOEXEC);
+      if (fd >= 0)
+      {
+        ssize_t len = ::read(fd, cmd, sizeof(cmd) - 1);
+        cmd[len > 0 ? len : 0] = '\0';
+        ::close(fd);
+      }
+#  endif
+      write_string(w, cmd);
+    }
+
+  public:
+    /**
+     * The recorder must be constant initialised, because allocations may be
+     * made before dynamic initialisers run.
+     */
+    constexpr Recorder() = default;
+
+    void alloc(void* p, size_t size)
+    {
+      if ((p == nullptr) || busy)
+        return;
+      busy = true;
+      record(p, size);
+      busy = false;
+    }
+
+    void dealloc(void* p)
+    {
+      if ((p == nullptr) || busy)
+        return;
+      forget(p);
+    }
+
+    /**
+     * Call `f(address, size)` for each live allocation.  Allocations and
+     * frees by the calling thread are not recorded meanwhile, so `f` must not
+     * free memory allocated before the call.
+     */
+    template<typename F>
+    void for_each_live(F f)
+    {
+      busy = true;
+      {
+        FlagLock l(lock);
+        for (auto b : blocks)
+        {
+          for (; b != nullptr; b = b->next)
+            f(b->address, b->size);
+        }
+      }
+      busy = false;
+    }
+
+    /**
+     * Write the program points to `path`.  Returns false if the file cannot
+     * be written.
+     */
+    bool dump(const char* path)
+    {
+      int fd = FileWriter::open(path);
+      if (fd < 0)
+        return false;
+
+      // Symbolising the frames may allocate.
+      busy = true;
+      FileWriter w(fd);
+      {
+        FlagLock f(lock);
+        uint64_t end = started ? now() : 0;
+
+        // The frame table holds each distinct return address once, after
+        // DHAT's root entry.
+        size_t frames = 0;
+        for (auto head : pps)
+        {
+          for (auto pp = head; pp != nullptr; pp = pp->next)
+            frames += pp->depth;
+        }
+        auto a = ThreadAlloc::get_noncachable();
+        auto addresses = static_cast<void**>(
+          a->alloc(bits::max(frames, size_t(1)) * sizeof(void*)));
+        if (addresses == nullptr)
+        {
+          busy = false;
+          w.close();
+          return false;
+        }
+        frames = 0;
+        for (auto head : pps)
+        {
+          for (auto pp = head; pp != nullptr; pp = pp->next)
+          {
+            for (size_t i = 0; i < pp->depth; i++)
+              addresses[frames++] = pp->frames[i];
+          }
+        }
+        std::sort(addresses, addresses + frames);
+        frames = static_cast<size_t>(
+          std::unique(addresses, addresses + frames) - addresses);
+
+        // Blocks still live count up to the end.
+        for (auto b : blocks)
+        {
+          for (; b != nullptr; b = b->next)
+            b->pp->live_lifetime += end - b->start;
+        }
+
+        w.write(
+          "{\"dhatFileVersion\":2,\"mode\":\"rust-heap\","
+          "\"verb\":\"Allocated\",\"bklt\":true,\"bkacc\":false,"
+          "\"tu\":\"\xC2\xB5s\",\"Mtu\":\"s\",");
+        w.print("\"tuth\":%zu,\"cmd\":", SHORT_LIVED);
+        write_command(w);
+        w.print(
+          ",\"pid\":%d,\"tg\":%llu,\"te\":%llu,\"pps\":[",
+          static_cast<int>(getpid()),
+          static_cast<unsigned long long>(peak_time),
+          static_cast<unsigned long long>(end));
+
+        bool first = true;
+        for (auto head : pps)
+        {
+          for (auto pp = head; pp != nullptr; pp = pp->next)
+          {
+            snapshot(pp);
+            uint64_t lifetime = pp->total_lifetime + pp->live_lifetime;
+            pp->live_lifetime = 0;
+
+            w.write(first ? "\n{" : ",\n{");
+            first = false;
+            w.print(
+              "\"tb\":%zu,\"tbk\":%zu,\"tl\":%llu,",
+              pp->total_bytes,
+              pp->total_blocks,
+              static_cast<unsigned long long>(lifetime));
+            w.print(
+              "\"mb\":%zu,\"mbk\":%zu,\"gb\":%zu,\"gbk\":%zu,",
+              pp->max_bytes,
+              pp->max_blocks,
+              pp->peak_bytes,
+              pp->peak_blocks);
+            w.print(
+              "\"eb\":%zu,\"ebk\":%zu,\"fs\":[",
+              pp->curr_bytes,
+              pp->curr_blocks);
+            for (size_t i = 0; i < pp->depth; i++)
+            {
+              auto index = std::lower_bound(
+                             addresses, addresses + frames, pp->frames[i]) -
+                addresses + 1;
+              w.print(i == 0 ? "%td" : ",%td", index);
+            }
+            w.write("]}");
+          }
+        }
+
+        w.write("\n],\"ftbl\":[\n\"[root]\"");
+        char** symbols =
+          backtrace_symbols(addresses, static_cast<int>(frames));
+        for (size_t i = 0; i < frames; i++)
+        {
+          w.write(",\n");
+          if (symbols != nullptr)
+            write_string(w, symbols[i]);
+          else
+          {
+            char name[32];
+            snprintf(name, sizeof(name), "%p", addresses[i]);
+            write_string(w, name);
+          }
+        }
+        w.write("\n]}\n");
+        free(symbols);
+        a->dealloc(addresses);
+      }
+      busy = false;
+      return w.close();
+    }
+
+    ~Recorder()
+    {
+      const char* path = getenv("SNMALLOC_DHAT_FILE");
+      if ((path != nullptr) && (*path != '\0'))
+        dump(path);
+    }
+  };
+
+  inline Recorder recorder;
+
+  /**
+   * Write the totals for each allocation call stack so far.
+   */
+  inline bool dump(const char* path)
+  {
+    return recorder.dump(path);
+  }
+} // namespace snmalloc::dhat
+
+#  define SNMALLOC_DHAT_ALLOC(p, size) snmalloc::dhat::recorder.alloc(p, size)
+#  define SNMALLOC_DHAT_DEALLOC(p) snmalloc::dhat::recorder.dealloc(p)
+#else
+#  define SNMALLOC_DHAT_ALLOC(p, size)
+#  define SNMALLOC_DHAT_DEALLOC(p)
+#endif
@@ -0,0 +1,184 @@
+#pragma once
+
+/**
+ * Injection of allocation failures, for testing how an application handles
+ * running out of memory.
+ *
+ * If the shims are built with `SNMALLOC_FAILURE_INJECTION` defined, each
+ * allocation through them first asks `failure::injector` whether it should
+ * fail, and if so returns null as if memory were exhausted.  Failures can be
+ * requested for every Nth allocation, for just the Nth allocation, for a
+ * random fraction of allocations, for allocations larger than a given size,
+ * and for every allocation made by a thread while it is inside a failure
+ * scope.  Nothing fails until one of these is configured.
+ *
+ * Otherwise, `SNMALLOC_INJECT_FAILURE` is always false, and its argument is
+ * not evaluated.  This is intended for test builds only: it adds an atomic
+ * load to every allocation.
+ */
+#ifdef SNMALLOC_FAILURE_INJECTION
+#  include <atomic>
+#  include <cstddef>
+#  include <cstdint>
+
+namespace snmalloc::failure
+{
+  class Injector
+  {
+    std::atomic<size_t> every{0};
+    std::atomic<size_t> count{0};
+    std::atomic<size_t> nth{0};
+    std::atomic<size_t> nth_count{0};
+    std::atomic<size_t> per_million{0};
+    std::atomic<uint64_t> seed{0};
+    std::atomic<uint64_t> draws{0};
+    std::atomic<size_t> above{SIZE_MAX};
+    std::atomic<size_t> injected{0};
+
+    static inline thread_local size_t scope_depth = 0;
+
+    /**
+     * The next pseudo-random number, from SplitMix64 applied to the seed and
+     * the number of draws so far.
+     */
+    uint64_t draw()
+    {
+      uint64_t z = seed.load(std::memory_order_relaxed) +
+        ((draws.fetch_add(1) + 1) * 0x9e3779b97f4a7c15);
+      z = (z ^ (z >> 30)) * 0xbf58476d1ce4e5b9;
+      z = (z ^ (z >> 27)) * 0x94d049bb133111eb;
+      return z ^ (z >> 31);
+    }
+
+  public:
+    constexpr Injector() = default;
+
+    /**
+     * Fail every `n`th allocation, counted across all threads from this
+     * call.  Zero stops failing by count.
+     */
+    void fail_every(size_t n)
+    {
+      count.store(0, std::memory_order_relaxed);
+      every.store(n, std::memory_order_relaxed);
+    }
+
+    /**
+     * Fail only the `n`th allocation, counted across all threads from this
+     * call.  Zero cancels a failure that has not happened yet.
+     */
+    void fail_nth(size_t n)
+    {
+      nth_count.store(0, std::memory_order_relaxed);
+      nth.store(n, std::memory_order_relaxed);
+    }
+
+    /**
+     * Fail each allocation with a probability of `rate` in a million.  The
+     * choices are a function of `seed` and of the order of allocations, so
+     * a single-threaded run fails the same allocations each time.  Zero
+     * stops failing at random.
+     */
+    void fail_random(size_t rate, uint64_t s)
+    {
+      draws.store(0, std::memory_order_relaxed);
+      seed.store(s, std::memory_order_relaxed);
+      per_million.store(rate, std::memory_order_relaxed);
+    }
+
+    /**
+     * Fail every allocation of more than `size` bytes.  `SIZE_MAX` stops
+     * failing by size.
+     */
+    void fail_above(size_t size)
+    {
+      above.store(size, std::memory_order_relaxed);
+    }
+
+    /**
+     * Fail every allocation by the calling thread until the matching
+     * `exit_scope`.  Scopes may be nested.
+     */
+    static void enter_scope()
+    {
+      scope_depth++;
+    }
+
+    static void exit_scope()
+    {
+      if (scope_depth > 0)
+        scope_depth--;
+    }
+
+    /**
+     * The number of allocations failed so far.
+     */
+    size_t failures()
+    {
+      return injected.load(std::memory_order_relaxed);
+    }
+
+    /**
+     * Stop failing allocations, other than within scopes, and reset the count
+     * of failures.
+     */
+    void reset()
+    {
+      fail_every(0);
+      fail_nth(0);
+      fail_random(0, 0);
+      fail_above(SIZE_MAX);
+      injected.store(0, std::memory_order_relaxed);
+    }
+
+    bool should_fail(size_t size)
+    {
+      bool fail =
+        (scope_depth > 0) || (size > above.load(std::memory_order_relaxed));
+
+      size_t n = every.load(std::memory_order_relaxed);
+      if ((n != 0) && (((count.fetch_add(1) + 1) % n) == 0))
+        fail = true;
+
+      size_t target = nth.load(std::memory_order_relaxed);
+      if ((target != 0) && ((nth_count.fetch_add(1) + 1) == target))
+        fail = true;
+
+      size_t rate = per_million.load(std::memory_order_relaxed);
+      if ((rate != 0) && ((draw() % 1000000) < rate))
+        fail = true;
+
+      if (fail)
+        injected++;
+      return fail;
+    }
+  };
+
+  inline Injector injector;
+
+  /**
+   * Fails every allocation by the current thread while in scope.
+   */
+  class Scope
+  {
+  public:
+    Scope()
+    {
+      Injector::enter_scope();
+    }
+
+    ~Scope()
+    {
+      Injector::exit_scope();
+    }
+
+    Scope(const Scope&) = delete;
+    Scope& operator=(const Scope&) = delete;
+  };
+} // namespace snmalloc::failure
+
+#  define SNMALLOC_INJECT_FAILURE(size) \
+    snmalloc::failure::injector.should_fail(size)
+#else
+#  define SNMALLOC_INJECT_FAILURE(size) false
+#endif
@@ -0,0 +1,90 @@
+#pragma once
+
+#ifdef _WIN32
+#  error FileWriter is only supported on POSIX platforms
+#endif
+
+#include "../ds/bits.h"
+
+#include <cstdio>
+#include <cstring>
+#include <fcntl.h>
+#include <unistd.h>
+
+namespace snmalloc
+{
+  /**
+   * Buffered writes to a file, for the diagnostic files written by the
+   * shims.  It does not allocate, so can be used while the allocator is
+   * locked.  Errors are remembered and reported by `close`.
+   */
+  class FileWriter
+  {
+    int fd;
+    bool failed = false;
+    size_t count = 0;
+    char buffer[4096];
+
+  public:
+    explicit FileWriter(int fd) : fd(fd) {}
+
+    /**
+     * Create or truncate `path` for writing, returning -1 on failure.
+     */
+    static int open(const char* path)
+    {
+      return ::open(path, O_WRONLY | O_CREAT | O_TRUNC | O_CLOEXEC, 0644);
+    }
+
+    void flush()
+    {
+      const char* p = buffer;
+      while (count > 0 && !failed)
+      {
+        ssize_t written = ::write(fd, p, count);
+        if (written <= 0)
+          failed = true;
+        else
+        {
+          p += written;
+          count -= static_cast<size_t>(written);
+        }
+      }
+      count = 0;
+    }
+
+    void write(const char* s, size_t len)
+    {
+      while (len > 0)
+      {
+        if (count == sizeof(buffer))
+          flush();
+        size_t n = bits::min(len, sizeof(buffer) - count);
+        memcpy(buffer + count, s, n);
+        count += n;
+        s += n;
+        len -= n;
+      }
+    }
+
+    void write(const char* s)
+    {
+      write(s, strlen(s));
+    }
+
+    template<typename... Args>
+    void print(const char* format, Args... args)
+    {
+      char line[128];
+      int len = snprintf(line, sizeof(line), format, args...);
+      if (len > 0)
+        write(line, bits::min(static_cast<size_t>(len), sizeof(line) - 1));
+    }
+
+    bool close()
+    {
+      flush();
+      return (::close(fd) == 0) && !failed;
+    }
+  };
+} // namespace snmalloc
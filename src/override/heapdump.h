@@ -0,0 +1,214 @@
+#pragma once
+
+/**
+ * A snapshot of the heap as JSON, for tools that compare the heap at two
+ * points of a run.
+ *
+ * `heapdump::dump` writes the memory totals, the sizeclass table with the
+ * objects and slabs of each class, the cache of freed large chunks, and each
+ * chunk in use, with what it holds.  In debug builds of the shims with
+ * `SNMALLOC_DHAT`, which records every allocation, it also lists the address
+ * and size of each live allocation; otherwise the allocator does not know
+ * them.  The output is passed to a callback a buffer at a time, so that it
+ * can go to a file, a socket or a string.
+ *
+ * The snapshot is not atomic: other threads may allocate and free while it is
+ * taken, and the chunk list is best read while they are quiet.
+ */
+#include "../snmalloc.h"
+#include "dhat.h"
+
+#include <cstdio>
+#include <cstring>
+
+namespace snmalloc::heapdump
+{
+  /**
+   * Receives `len` bytes of the output.  Returns false to stop the dump.
+   */
+  using WriteFn = bool (*)(const char* data, size_t len, void* context);
+
+  /**
+   * Buffers the output for a `WriteFn`, without allocating, so that writing
+   * cannot change what is being described.
+   */
+  class Writer
+  {
+    WriteFn fn;
+    void* context;
+    bool failed = false;
+    size_t count = 0;
+    char buffer[4096];
+
+  public:
+    Writer(WriteFn fn, void* context) : fn(fn), context(context) {}
+
+    void flush()
+    {
+      if ((count > 0) && !failed)
+        failed = !fn(buffer, count, context);
+      count = 0;
+    }
+
+    void write(const char* s)
+    {
+      for (size_t len = strlen(s); len > 0;)
+      {
+        if (count == sizeof(buffer))
+          flush();
+        size_t n = bits::min(len, sizeof(buffer) - count);
+        memcpy(buffer + count, s, n);
+        count += n;
+        s += n;
+        len -= n;
+      }
+    }
+
+    template<typename... Args>
+    void print(const char* format, Args... args)
+    {
+      char line[128];
+      int len = snprintf(line, sizeof(line), format, args...);
+      if (len > 0)
+        write(line);
+    }
+
+    /**
+     * Write any buffered output, and return false if any write failed.
+     */
+    bool close()
+    {
+      flush();
+      return !failed;
+    }
+  };
+
+  inline void write_sizeclasses(Writer& w, StatsSnapshot& snapshot)
+  {
+    w.write("\"sizeclasses\":[");
+    for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES; sc++)
+    {
+      auto& s = snapshot.sizeclasses[sc];
+      w.print(
+        "%s\n{\"size\":%zu,\"slab_size\":%zu,\"objects_per_slab\":%zu,",
+        sc == 0 ? "" : ",",
+        sizeclass_to_size(sc),
+        sc < NUM_SMALL_CLASSES ? SLAB_SIZE : SUPERSLAB_SIZE,
+        sizeclass_to_slab_capacity(sc));
+      w.print(
+        "\"live\":%zu,\"total\":%zu,\"slabs\":%zu,\"free\":%zu}",
+        s.live,
+        s.total,
+        s.slabs,
+        s.free);
+    }
+    w.write("\n],");
+  }
+
+  inline void write_large_cache(Writer& w)
+  {
+    w.write("\"large_cache\":[");
+    for (size_t lc = 0; lc < NUM_LARGE_CLASSES; lc++)
+    {
+      auto stats = large_cache_stats(lc);
+      w.print(
+        "%s\n{\"size\":%zu,\"retained\":%zu,\"limit\":%zu,",
+        lc == 0 ? "" : ",",
+        bits::one_at_bit(SUPERSLAB_BITS + lc),
+        stats.retained_committed,
+        stats.retention_limit);
+      w.print("\"hits\":%zu,\"misses\":%zu}", stats.hits, stats.misses);
+    }
+    w.write("\n],");
+  }
+
+  /**
+   * Write each chunk in use.  Only the chunks of the calling thread's
+   * allocator are described in full: another thread may free one of its
+   * chunks, or reuse a medium slab for another sizeclass, while it is being
+   * read, so for those only the kind is given.  The owner in a chunk's
+   * header is on a page that is never decommitted, and only tells whether
+   * the chunk is the calling thread's, which cannot change meanwhile.
+   */
+  inline void write_chunks(Writer& w)
+  {
+    w.write("\"chunks\":[");
+    auto a = ThreadAlloc::get_noncachable();
+    size_t self = a->get_trunc_id();
+    bool first = true;
+    for_each_chunk([&](address_t base, uint8_t kind) {
+      void* p = reinterpret_cast<void*>(base);
+      w.print("%s\n{\"address\":%zu,", first ? "" : ",", base);
+      first = false;
+      if ((kind == CMSuperslab) || (kind == CMMediumslab))
+      {
+        bool own = a->get_owner_trunc_id(p) == self;
+        w.write(
+          kind == CMSuperslab ? "\"kind\":\"superslab\"" :
+                                "\"kind\":\"medium\"");
+        if (own && (kind == CMMediumslab))
+          w.print(",\"object_size\":%zu", a->alloc_size(p));
+        if (own)
+          w.print(",\"owner\":%zu", self);
+        w.write("}");
+      }
+      else
+      {
+        w.print("\"kind\":\"large\",\"size\":%zu}", bits::one_at_bit(kind));
+      }
+    });
+    w.write("\n]");
+  }
+
+  inline void write_live(Writer& w)
+  {
+#if defined(SNMALLOC_DHAT) && !defined(NDEBUG)
+    w.write(",\"live\":[");
+    bool first = true;
+    dhat::recorder.for_each_live([&](void* p, size_t size) {
+      w.print(
+        "%s\n{\"address\":%zu,\"size\":%zu}",
+        first ? "" : ",",
+        static_cast<size_t>(address_cast(p)),
+        size);
+      first = false;
+    });
+    w.write("\n]");
+#else
+    UNUSED(w);
+#endif
+  }
+
+  /**
+   * Write a snapshot of the heap as a JSON object to `fn`.  Returns false if
+   * `fn` did.
+   */
+  inline bool dump(WriteFn fn, void* context)
+  {
+    StatsSnapshot snapshot;
+    stats_snapshot(snapshot);
+
+    Writer w(fn, context);
+    w.print(
+      "{\"version\":1,\"chunk_size\":%zu,\"slab_size\":%zu,",
+      SUPERSLAB_SIZE,
+      SLAB_SIZE);
+    w.print(
+      "\"reserved\":%zu,\"committed\":%zu,\"live_bytes\":%zu,",
+      snapshot.memory.reserved,
+      snapshot.memory.committed,
+      snapshot.memory.live);
+    w.print(
+      "\"released\":%zu,\"peak\":%zu,\"allocators\":%zu,",
+      snapshot.memory.released,
+      snapshot.peak,
+      snapshot.allocators);
+    w.print("\"remote_queue_depth\":%zu,", snapshot.remote_queue_depth);
+    write_sizeclasses(w, snapshot);
+    write_large_cache(w);
+    write_chunks(w);
+    write_live(w);
+    w.write("}\n");
+    return w.close();
+  }
+} // namespace snmalloc::heapdump
@@ -0,0 +1,98 @@
+#pragma once
+
+/**
+ * Callbacks on each allocation and deallocation made through the shims, so
+ * that leak detectors, tracing layers and custom profilers can be built on
+ * snmalloc without forking it.
+ *
+ * If the shims are built with `SNMALLOC_HOOKS` defined, `hooks::set`
+ * installs an `on_alloc(ptr, size)` and an `on_dealloc(ptr, size)` callback,
+ * either of which may be null.  They are called on the thread making the
+ * call, after an allocation and before a deallocation, with the size
+ * requested; for `free`, which is not told the size, the usable size is
+ * passed instead.  Reallocations call both when they move the block, and
+ * neither when they resize it in place.  Allocations made by a callback are
+ * not reported to it.  Otherwise, the hook macros expand to nothing.
+ *
+ * `hooks::set_min_size` limits the callbacks to blocks of at least a given
+ * size, so that, for example, a tracing layer can log unexpectedly large
+ * allocations in production without being called for every small one.  The
+ * size is rounded to its sizeclass before it is compared, so that a block
+ * is either reported both when it is allocated and when it is freed, or
+ * not at all.
+ */
+#ifdef SNMALLOC_HOOKS
+#  include "../snmalloc.h"
+
+#  include <atomic>
+#  include <cstddef>
+
+namespace snmalloc::hooks
+{
+  using Callback = void (*)(void* p, size_t size);
+
+  inline std::atomic<Callback> on_alloc{nullptr};
+  inline std::atomic<Callback> on_dealloc{nullptr};
+
+  /**
+   * The smallest block that is reported to the callbacks.
+   */
+  inline std::atomic<size_t> min_size{0};
+
+  /**
+   * Set while a callback runs on this thread, so that its own allocations
+   * are not reported, which would recurse.
+   */
+  inline thread_local bool busy = false;
+
+  /**
+   * Install the callbacks, replacing any installed before.  Null removes a
+   * callback.
+   */
+  inline void set(Callback alloc, Callback dealloc)
+  {
+    on_alloc.store(alloc, std::memory_order_release);
+    on_dealloc.store(dealloc, std::memory_order_release);
+  }
+
+  /**
+   * Only report blocks whose sizeclass is at least `size` bytes.  Zero
+   * reports every block.
+   */
+  inline void set_min_size(size_t size)
+  {
+    min_size.store(size, std::memory_order_relaxed);
+  }
+
+  /**
+   * Call `hook`, if installed, for `p`, which is ignored if null, if its
+   * sizeclass is at least `min_size` bytes.  The size is only computed, by
+   * calling `size`, if a hook is installed.
+   */
+  template<typename F>
+  inline void report(const std::atomic<Callback>& hook, void* p, F size)
+  {
+    Callback f = hook.load(std::memory_order_acquire);
+    if ((f == nullptr) || (p == nullptr) || busy)
+      return;
+
+    size_t s = size();
+    if (round_size(s) < min_size.load(std::memory_order_relaxed))
+      return;
+
+    busy = true;
+    f(p, s);
+    busy = false;
+  }
+} // namespace snmalloc::hooks
+
+#  define SNMALLOC_HOOK_ALLOC(p, size) \
+    snmalloc::hooks::report( \
+      snmalloc::hooks::on_alloc, p, [&]() -> size_t { return size; })
+#  define SNMALLOC_HOOK_DEALLOC(p, size) \
+    snmalloc::hooks::report( \
+      snmalloc::hooks::on_dealloc, p, [&]() -> size_t { return size; })
+#else
+#  define SNMALLOC_HOOK_ALLOC(p, size) ((void)0)
+#  define SNMALLOC_HOOK_DEALLOC(p, size) ((void)0)
+#endif
@@ -9,4 +9,11 @@ void get_malloc_info_v1(malloc_info_v1* stats)
   auto next_memory_usage = default_memory_provider().memory_usage();
   stats->current_memory_usage = next_memory_usage.first;
   stats->peak_memory_usage = next_memory_usage.second;
-}
\ No newline at end of file
+}
+void get_malloc_info_v2(malloc_info_v2* stats)
+{
+  auto breakdown = memory_breakdown();
+  stats->reserved_address_space = breakdown.reserved;
+  stats->committed_memory = breakdown.committed;
+  stats->live_bytes = breakdown.live;
+}
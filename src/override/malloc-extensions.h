@@ -32,3 +32,34 @@ struct malloc_info_v1
  * from snmalloc.
  */
 void get_malloc_info_v1(malloc_info_v1* stats);
+
+/**
+ * Structure for returning a breakdown of the memory used by snmalloc, from
+ * address space down to the bytes in live objects.
+ */
+struct malloc_info_v2
+{
+  /**
+   * Address space reserved from the operating system.  This is never
+   * returned, so only grows.
+   */
+  size_t reserved_address_space;
+
+  /**
+   * Memory that snmalloc has committed and not subsequently decommitted.
+   */
+  size_t committed_memory;
+
+  /**
+   * Bytes in live objects, rounded up to their sizeclass.  This is only
+   * precise if snmalloc is built with USE_SNMALLOC_STATS; otherwise it is the
+   * memory in chunks currently in use by allocators, an upper bound.
+   */
+  size_t live_bytes;
+};
+
+/**
+ * Populates a malloc_info_v2 structure for the latest values
+ * from snmalloc.
+ */
+void get_malloc_info_v2(malloc_info_v2* stats);
@@ -1,5 +1,12 @@
 #include "../mem/slowalloc.h"
 #include "../snmalloc.h"
+#include "counting.h"
+#include "dhat.h"
+#include "failure.h"
+#include "hooks.h"
+#include "premain.h"
+#include "profile.h"
+#include "trace.h"
 
 #include <errno.h>
 #include <string.h>
@@ -24,7 +31,7 @@ using namespace snmalloc;
 
 extern "C"
 {
-  void SNMALLOC_NAME_MANGLE(check_start)(void* ptr)
+  static void SNMALLOC_NAME_MANGLE(check_start)(void* ptr)
   {
 #if !defined(NDEBUG) && !defined(SNMALLOC_PASS_THROUGH)
     if (ThreadAlloc::get_noncachable()->external_pointer<Start>(ptr) != ptr)
@@ -43,11 +50,29 @@ extern "C"
 
   SNMALLOC_EXPORT void* SNMALLOC_NAME_MANGLE(malloc)(size_t size)
   {
-    return ThreadAlloc::get_noncachable()->alloc(size);
+    if (SNMALLOC_INJECT_FAILURE(size))
+    {
+      errno = ENOMEM;
+      return nullptr;
+    }
+    void* p = ThreadAlloc::get_noncachable()->alloc(size);
+    SNMALLOC_TRACE_RECORD(Alloc, p, nullptr, size, 0);
+    SNMALLOC_COUNT_ALLOC(p);
+    SNMALLOC_PROFILE_ALLOC(p, size);
+    SNMALLOC_DHAT_ALLOC(p, size);
+    SNMALLOC_HOOK_ALLOC(p, size);
+    return p;
   }
 
   SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(free)(void* ptr)
   {
+    SNMALLOC_TRACE_RECORD(Free, ptr, nullptr, 0, 0);
+    if (ptr != nullptr)
+      SNMALLOC_COUNT_DEALLOC();
+    SNMALLOC_PROFILE_DEALLOC(ptr);
+    SNMALLOC_DHAT_DEALLOC(ptr);
+    SNMALLOC_HOOK_DEALLOC(
+      ptr, ThreadAlloc::get_noncachable()->alloc_size(ptr));
     SNMALLOC_NAME_MANGLE(check_start)(ptr);
     ThreadAlloc::get_noncachable()->dealloc(ptr);
   }
@@ -57,6 +82,50 @@ extern "C"
     SNMALLOC_NAME_MANGLE(free)(ptr);
   }
 
+  /**
+   * C23 sized deallocation.  `size` must be the size most recently requested
+   * for `ptr` from `malloc`, `calloc` (the product of its arguments) or
+   * `realloc`.  Knowing the size skips looking up the sizeclass in the
+   * pagemap, so the size is trusted and is not checked against the pagemap,
+   * even with `CHECK_CLIENT`.  Debug builds only check that `ptr` is the
+   * start of an allocation.
+   */
+  SNMALLOC_EXPORT void
+    SNMALLOC_NAME_MANGLE(free_sized)(void* ptr, size_t size)
+  {
+    if ((ptr == nullptr) || (size == 0))
+    {
+      SNMALLOC_NAME_MANGLE(free)(ptr);
+      return;
+    }
+    SNMALLOC_TRACE_RECORD(Free, ptr, nullptr, size, 0);
+    SNMALLOC_COUNT_DEALLOC();
+    SNMALLOC_PROFILE_DEALLOC(ptr);
+    SNMALLOC_DHAT_DEALLOC(ptr);
+    SNMALLOC_HOOK_DEALLOC(ptr, size);
+    SNMALLOC_NAME_MANGLE(check_start)(ptr);
+    ThreadAlloc::get_noncachable()->dealloc(ptr, size);
+  }
+
+  /**
+   * C23 sized deallocation of memory from `aligned_alloc` or `memalign`,
+   * which must be passed the same `alignment` and `size`.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(free_aligned_sized)(
+    void* ptr, size_t alignment, size_t size)
+  {
+    if (ptr == nullptr)
+      return;
+    SNMALLOC_TRACE_RECORD(Free, ptr, nullptr, size, alignment);
+    SNMALLOC_COUNT_DEALLOC();
+    SNMALLOC_PROFILE_DEALLOC(ptr);
+    SNMALLOC_DHAT_DEALLOC(ptr);
+    SNMALLOC_HOOK_DEALLOC(ptr, size);
+    SNMALLOC_NAME_MANGLE(check_start)(ptr);
+    ThreadAlloc::get_noncachable()->dealloc(
+      ptr, size ? aligned_size(alignment, size) : alignment);
+  }
+
   SNMALLOC_EXPORT void* SNMALLOC_NAME_MANGLE(calloc)(size_t nmemb, size_t size)
   {
     bool overflow = false;
@@ -66,7 +135,18 @@ extern "C"
       errno = ENOMEM;
       return nullptr;
     }
-    return ThreadAlloc::get_noncachable()->alloc<ZeroMem::YesZero>(sz);
+    if (SNMALLOC_INJECT_FAILURE(sz))
+    {
+      errno = ENOMEM;
+      return nullptr;
+    }
+    void* p = ThreadAlloc::get_noncachable()->alloc<ZeroMem::YesZero>(sz);
+    SNMALLOC_TRACE_RECORD(Alloc, p, nullptr, sz, 0);
+    SNMALLOC_COUNT_ALLOC(p);
+    SNMALLOC_PROFILE_ALLOC(p, sz);
+    SNMALLOC_DHAT_ALLOC(p, sz);
+    SNMALLOC_HOOK_ALLOC(p, sz);
+    return p;
   }
 
   SNMALLOC_EXPORT
@@ -76,6 +156,30 @@ extern "C"
     return ThreadAlloc::get_noncachable()->alloc_size(ptr);
   }
 
+  /**
+   * Return the usable size of a `malloc` of `size` bytes, so that callers
+   * that can use extra space, such as growable buffers, can ask for all of
+   * it up front.  This is the macOS interface.
+   */
+  SNMALLOC_EXPORT size_t SNMALLOC_NAME_MANGLE(malloc_good_size)(size_t size)
+  {
+#ifdef SNMALLOC_PASS_THROUGH
+    // The underlying allocator does not promise to round the size up.
+    return size;
+#else
+    return round_size(size);
+#endif
+  }
+
+  /**
+   * The usable size of the allocation at `ptr`, like `malloc_usable_size`.
+   * This is the macOS interface.
+   */
+  SNMALLOC_EXPORT size_t SNMALLOC_NAME_MANGLE(malloc_size)(const void* ptr)
+  {
+    return ThreadAlloc::get_noncachable()->alloc_size(ptr);
+  }
+
   SNMALLOC_EXPORT void* SNMALLOC_NAME_MANGLE(realloc)(void* ptr, size_t size)
   {
     if (size == (size_t)-1)
@@ -103,22 +207,54 @@ extern "C"
       // snmallocs alignment guarantees can be broken by realloc in pass-through
       // this is not exercised, by existing clients, but is tested.
       if (pointer_align_up(ptr, natural_alignment(size)) == ptr)
+      {
+        SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, size, 0);
         return ptr;
+      }
 #else
+      SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, size, 0);
       return ptr;
 #endif
     }
-    void* p = SNMALLOC_NAME_MANGLE(malloc)(size);
+    if (SNMALLOC_INJECT_FAILURE(size))
+    {
+      errno = ENOMEM;
+      return nullptr;
+    }
+    void* p = ThreadAlloc::get_noncachable()->alloc(size);
     if (p != nullptr)
     {
       SNMALLOC_NAME_MANGLE(check_start)(p);
       sz = bits::min(size, sz);
       memcpy(p, ptr, sz);
-      SNMALLOC_NAME_MANGLE(free)(ptr);
+      SNMALLOC_TRACE_RECORD(Realloc, p, ptr, size, 0);
+      SNMALLOC_COUNT_ALLOC(p);
+      SNMALLOC_COUNT_DEALLOC();
+      SNMALLOC_PROFILE_ALLOC(p, size);
+      SNMALLOC_DHAT_ALLOC(p, size);
+      SNMALLOC_HOOK_ALLOC(p, size);
+      SNMALLOC_PROFILE_DEALLOC(ptr);
+      SNMALLOC_DHAT_DEALLOC(ptr);
+      SNMALLOC_HOOK_DEALLOC(
+        ptr, ThreadAlloc::get_noncachable()->alloc_size(ptr));
+      ThreadAlloc::get_noncachable()->dealloc(ptr);
     }
     return p;
   }
 
+  /**
+   * As `realloc`, but frees `ptr` if it cannot be resized, so that callers
+   * do not leak it when they overwrite it with the result.  This is the BSD
+   * interface.
+   */
+  SNMALLOC_EXPORT void* SNMALLOC_NAME_MANGLE(reallocf)(void* ptr, size_t size)
+  {
+    void* p = SNMALLOC_NAME_MANGLE(realloc)(ptr, size);
+    if ((p == nullptr) && (size != 0))
+      SNMALLOC_NAME_MANGLE(free)(ptr);
+    return p;
+  }
+
 #if !defined(__FreeBSD__) && !defined(__OpenBSD__)
   SNMALLOC_EXPORT void*
     SNMALLOC_NAME_MANGLE(reallocarray)(void* ptr, size_t nmemb, size_t size)
@@ -143,14 +279,20 @@ extern "C"
       return nullptr;
     }
 
-    if ((size + alignment) < size)
+    if (((size + alignment) < size) || SNMALLOC_INJECT_FAILURE(size))
     {
       errno = ENOMEM;
       return nullptr;
     }
 
-    return SNMALLOC_NAME_MANGLE(malloc)(
+    void* p = ThreadAlloc::get_noncachable()->alloc(
       size ? aligned_size(alignment, size) : alignment);
+    SNMALLOC_TRACE_RECORD(Alloc, p, nullptr, size, alignment);
+    SNMALLOC_COUNT_ALLOC(p);
+    SNMALLOC_PROFILE_ALLOC(p, size);
+    SNMALLOC_DHAT_ALLOC(p, size);
+    SNMALLOC_HOOK_ALLOC(p, size);
+    return p;
   }
 
   SNMALLOC_EXPORT void*
@@ -212,6 +354,144 @@ extern "C"
     return ENOENT;
   }
 
+  /**
+   * Set up the allocator's global state and the calling thread's allocator,
+   * so that the first allocation does not pay for it.  Calling this again, or
+   * after other allocations, is harmless.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_init)(void)
+  {
+    auto a = ThreadAlloc::get();
+    a->dealloc(a->alloc(1));
+  }
+
+#ifndef SNMALLOC_EXTERNAL_THREAD_ALLOC
+  /**
+   * Flush the calling thread's allocator and return it to the pool, without
+   * waiting for the thread to exit; see `ThreadAllocCommon::teardown`.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_thread_teardown)(void)
+  {
+    ThreadAlloc::teardown();
+  }
+#endif
+
+#ifdef SNMALLOC_COUNT_ALLOCATIONS
+  /**
+   * Report the allocations and deallocations made by the current thread so
+   * far; see `counting.h`.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_thread_allocation_counts)(
+    size_t* allocations, size_t* deallocations)
+  {
+    *allocations = counting::thread_counts.allocations;
+    *deallocations = counting::thread_counts.deallocations;
+  }
+#endif
+
+#ifdef SNMALLOC_PROFILING
+  /**
+   * Control the heap profiler; see `profile.h`.  A rate of zero disables
+   * sampling.  `snmalloc_profile_dump` and `snmalloc_profile_dump_pprof`
+   * return 0 on success and -1 if the file cannot be written.
+   */
+  SNMALLOC_EXPORT void
+    SNMALLOC_NAME_MANGLE(snmalloc_profile_set_rate)(size_t rate)
+  {
+    profile::set_rate(rate);
+  }
+
+  SNMALLOC_EXPORT int
+    SNMALLOC_NAME_MANGLE(snmalloc_profile_dump)(const char* path)
+  {
+    return profile::dump(path) ? 0 : -1;
+  }
+
+  SNMALLOC_EXPORT int
+    SNMALLOC_NAME_MANGLE(snmalloc_profile_dump_pprof)(const char* path)
+  {
+    return profile::dump_pprof(path) ? 0 : -1;
+  }
+#endif
+
+#ifdef SNMALLOC_HOOKS
+  /**
+   * Install callbacks on each allocation and deallocation; see `hooks.h`.
+   * Either may be null.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_set_alloc_hooks)(
+    void (*on_alloc)(void*, size_t), void (*on_dealloc)(void*, size_t))
+  {
+    hooks::set(on_alloc, on_dealloc);
+  }
+
+  /**
+   * Only call the hooks for blocks of at least `size` bytes.
+   */
+  SNMALLOC_EXPORT void
+    SNMALLOC_NAME_MANGLE(snmalloc_set_alloc_hooks_min_size)(size_t size)
+  {
+    hooks::set_min_size(size);
+  }
+#endif
+
+#ifdef SNMALLOC_DHAT
+  /**
+   * Write the allocation totals for each call stack so far as DHAT JSON; see
+   * `dhat.h`.  Returns 0 on success and -1 if the file cannot be written.
+   */
+  SNMALLOC_EXPORT int SNMALLOC_NAME_MANGLE(snmalloc_dhat_dump)(const char* path)
+  {
+    return dhat::dump(path) ? 0 : -1;
+  }
+#endif
+
+#ifdef SNMALLOC_FAILURE_INJECTION
+  /**
+   * Configure failure injection; see `failure.h`.
+   */
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_every)(size_t n)
+  {
+    failure::injector.fail_every(n);
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_nth)(size_t n)
+  {
+    failure::injector.fail_nth(n);
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_random)(
+    size_t per_million, uint64_t seed)
+  {
+    failure::injector.fail_random(per_million, seed);
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_above)(size_t size)
+  {
+    failure::injector.fail_above(size);
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_scope_enter)(void)
+  {
+    failure::Injector::enter_scope();
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_scope_exit)(void)
+  {
+    failure::Injector::exit_scope();
+  }
+
+  SNMALLOC_EXPORT size_t SNMALLOC_NAME_MANGLE(snmalloc_failures)(void)
+  {
+    return failure::injector.failures();
+  }
+
+  SNMALLOC_EXPORT void SNMALLOC_NAME_MANGLE(snmalloc_fail_reset)(void)
+  {
+    failure::injector.reset();
+  }
+#endif
+
 #ifdef SNMALLOC_EXPOSE_PAGEMAP
   /**
    * Export the pagemap.  The return value is a pointer to the pagemap
@@ -272,3 +552,9 @@ extern "C"
   }
 #endif
 }
+
+#ifdef SNMALLOC_INIT_BEFORE_MAIN
+SNMALLOC_BEFORE_MAIN(
+  SNMALLOC_NAME_MANGLE(snmalloc_init_before_main),
+  SNMALLOC_NAME_MANGLE(snmalloc_init))
+#endif
@@ -0,0 +1,147 @@
+#pragma once
+
+/**
+ * Purging of free memory when the operating system reports that it is short
+ * of memory, so that a long-running service shrinks under pressure as it
+ * would with the platform's allocator.
+ *
+ * `memory_pressure::start` starts listening, once per process:
+ *
+ *  - On Linux, a thread waits on a pressure stall information (PSI) trigger
+ *    on `/proc/pressure/memory`, which fires when tasks stall waiting for
+ *    memory for more than 100ms in a 2s window.  This needs a kernel with
+ *    PSI enabled.
+ *  - On macOS, a dispatch source for memory pressure warnings runs the purge
+ *    on a global queue.
+ *  - On Windows, the PAL already registers for memory resource
+ *    notifications when the allocator starts, and decommits cached chunks
+ *    while memory is low, so nothing more is needed.
+ *
+ * Other platforms have no listener.
+ */
+#include "../snmalloc.h"
+
+#if defined(__linux__)
+#  include <errno.h>
+#  include <fcntl.h>
+#  include <poll.h>
+#  include <pthread.h>
+#  include <unistd.h>
+#elif defined(__APPLE__)
+#  include <dispatch/dispatch.h>
+#endif
+
+namespace snmalloc::memory_pressure
+{
+  /**
+   * The number of times that `purge` has been called.
+   */
+  inline std::atomic<size_t> purges{0};
+
+  /**
+   * Return free memory to the platform in response to pressure.  This is
+   * `release_free_memory` without the flush of the calling thread's
+   * allocator, as the listener has none.  Returns the bytes returned.
+   */
+  inline size_t purge()
+  {
+    purges.fetch_add(1, std::memory_order_relaxed);
+#ifdef SNMALLOC_PASS_THROUGH
+    return 0;
+#else
+    current_alloc_pool()->cleanup_unused();
+    return default_memory_provider().decommit_cached();
+#endif
+  }
+
+#if defined(__linux__)
+  /**
+   * Body of the listener thread, which owns the trigger's file descriptor.
+   */
+  inline void* listen(void* arg)
+  {
+    pollfd fd = {static_cast<int>(reinterpret_cast<intptr_t>(arg)), POLLPRI, 0};
+    while (true)
+    {
+      if (poll(&fd, 1, -1) < 0)
+      {
+        if (errno == EINTR)
+          continue;
+        break;
+      }
+      // The trigger is destroyed if the file's cgroup goes away.
+      if ((fd.revents & POLLERR) != 0)
+        break;
+      if ((fd.revents & POLLPRI) != 0)
+        purge();
+    }
+    close(fd.fd);
+    return nullptr;
+  }
+
+  inline bool start_listener()
+  {
+    int fd = open("/proc/pressure/memory", O_RDWR | O_NONBLOCK | O_CLOEXEC);
+    if (fd < 0)
+      return false;
+
+    // Unprivileged processes may only use windows that are multiples of 2s.
+    static constexpr char trigger[] = "some 100000 2000000";
+    pthread_t thread;
+    if (
+      (write(fd, trigger, sizeof(trigger)) < 0) ||
+      (pthread_create(
+         &thread,
+         nullptr,
+         listen,
+         reinterpret_cast<void*>(static_cast<intptr_t>(fd))) != 0))
+    {
+      close(fd);
+      return false;
+    }
+    pthread_detach(thread);
+    return true;
+  }
+#elif defined(__APPLE__)
+  inline void on_pressure(void*)
+  {
+    purge();
+  }
+
+  inline bool start_listener()
+  {
+    // The source is never cancelled, so is deliberately leaked.
+    dispatch_source_t source = dispatch_source_create(
+      DISPATCH_SOURCE_TYPE_MEMORYPRESSURE,
+      0,
+      DISPATCH_MEMORYPRESSURE_WARN | DISPATCH_MEMORYPRESSURE_CRITICAL,
+      dispatch_get_global_queue(QOS_CLASS_UTILITY, 0));
+    if (source == nullptr)
+      return false;
+    dispatch_source_set_event_handler_f(source, on_pressure);
+    dispatch_resume(source);
+    return true;
+  }
+#else
+  inline bool start_listener()
+  {
+    return pal_supports<LowMemoryNotification, Pal>;
+  }
+#endif
+
+  /**
+   * Start purging free memory when the platform reports memory pressure.
+   * Only the first call has any effect.  Returns false if the platform
+   * cannot report pressure, and with `SNMALLOC_PASS_THROUGH`, where the
+   * platform's allocator responds to it itself.
+   */
+  inline bool start()
+  {
+#ifdef SNMALLOC_PASS_THROUGH
+    return false;
+#else
+    static bool started = start_listener();
+    return started;
+#endif
+  }
+} // namespace snmalloc::memory_pressure
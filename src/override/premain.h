@@ -0,0 +1,41 @@
+#pragma once
+
+/**
+ * Registration of a function to run before `main`, ahead of ordinary static
+ * constructors, for initialising the allocator early when built with
+ * `SNMALLOC_INIT_BEFORE_MAIN`.
+ *
+ * `SNMALLOC_BEFORE_MAIN(name, fn)` defines an object named after `name` that
+ * arranges for `fn`, a `void()` function, to be called.  With GCC and Clang
+ * this is a constructor of the highest priority available to applications.
+ * With MSVC it is an entry in `.CRT$XCT`, which the CRT runs before the
+ * `.CRT$XCU` entries for C++ dynamic initialisers.
+ */
+#if defined(_MSC_VER) && !defined(__clang__)
+#  pragma section(".CRT$XCT", read)
+#  ifdef _M_IX86
+#    define SNMALLOC_BEFORE_MAIN_SYMBOL(name) "_" #name
+#  else
+#    define SNMALLOC_BEFORE_MAIN_SYMBOL(name) #name
+#  endif
+// The entry is otherwise unreferenced, so the linker must be told to keep it.
+#  define SNMALLOC_BEFORE_MAIN(name, fn) \
+    extern "C" __declspec(allocate(".CRT$XCT")) void (*const name)(void) = \
+      fn; \
+    __pragma(comment(linker, "/include:" SNMALLOC_BEFORE_MAIN_SYMBOL(name)))
+#elif defined(__APPLE__)
+// Mach-O does not support constructor priorities; constructors in the
+// allocator's object still run before those of objects linked after it.
+#  define SNMALLOC_BEFORE_MAIN(name, fn) \
+    __attribute__((constructor)) static void name() \
+    { \
+      fn(); \
+    }
+#else
+// Priorities up to 100 are reserved for the implementation.
+#  define SNMALLOC_BEFORE_MAIN(name, fn) \
+    __attribute__((constructor(101))) static void name() \
+    { \
+      fn(); \
+    }
+#endif
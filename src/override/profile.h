@@ -0,0 +1,755 @@
+#pragma once
+
+/**
+ * Sampling heap profiler.
+ *
+ * If the shims are built with `SNMALLOC_PROFILING` defined, allocations made
+ * through them are sampled, on average once every `rate` bytes, and the call
+ * stack of each sampled allocation is recorded until it is freed.
+ * `profile::dump` writes the live samples to a file in the heap profile
+ * format of gperftools, which `pprof` reads.  Otherwise, the profiling macros
+ * expand to nothing and their arguments are not evaluated.
+ *
+ * The rate is read from the `SNMALLOC_PROFILE_RATE` environment variable at
+ * the first allocation, and can be changed with `profile::set_rate`; zero,
+ * the default, disables sampling.  A thread that is not sampling only checks
+ * for a change of rate once every `DISABLED_INTERVAL` bytes.
+ *
+ * As with jemalloc's profiler, the interval between samples is exponentially
+ * distributed, so the probability of sampling an allocation depends only on
+ * its size, and `pprof` scales the samples back up to estimate the whole
+ * heap.  An allocation that is resized in place keeps the size it was
+ * sampled with.
+ */
+#ifdef SNMALLOC_PROFILING
+#  ifdef _WIN32
+#    error SNMALLOC_PROFILING is only supported on POSIX platforms
+#  endif
+
+#  include "../ds/flaglock.h"
+#  include "../snmalloc.h"
+#  include "filewriter.h"
+
+#  include <algorithm>
+#  include <atomic>
+#  include <cmath>
+#  include <cstdio>
+#  include <cstdlib>
+#  include <cstring>
+#  include <fcntl.h>
+#  include <unistd.h>
+#  if defined(BACKTRACE_HEADER)
+#    include BACKTRACE_HEADER
+#  else
+#    include <execinfo.h>
+#  endif
+
+namespace snmalloc::profile
+{
+  /**
+   * Frames recorded for each sample, after those of the profiler itself.
+   */
+  static constexpr size_t MAX_FRAMES = 32;
+
+  /**
+   * Bytes allocated between checks for a new rate while not sampling.
+   */
+  static constexpr size_t DISABLED_INTERVAL = 1 << 20;
+
+  struct Sample
+  {
+    Sample* next;
+    void* address;
+    size_t size;
+    size_t depth;
+    void* frames[MAX_FRAMES];
+  };
+
+  /**
+   * Field numbers from `profile.proto` in the `pprof` repository.
+   */
+  namespace pprof
+  {
+    // Profile
+    static constexpr uint64_t SampleType = 1;
+    static constexpr uint64_t SampleField = 2;
+    static constexpr uint64_t MappingField = 3;
+    static constexpr uint64_t LocationField = 4;
+    static constexpr uint64_t StringTable = 6;
+    static constexpr uint64_t PeriodType = 11;
+    static constexpr uint64_t Period = 12;
+
+    // ValueType
+    static constexpr uint64_t ValueTypeType = 1;
+    static constexpr uint64_t ValueTypeUnit = 2;
+
+    // Sample
+    static constexpr uint64_t SampleLocationId = 1;
+    static constexpr uint64_t SampleValue = 2;
+
+    // Mapping
+    static constexpr uint64_t MappingId = 1;
+    static constexpr uint64_t MappingStart = 2;
+    static constexpr uint64_t MappingLimit = 3;
+    static constexpr uint64_t MappingOffset = 4;
+    static constexpr uint64_t MappingFilename = 5;
+
+    // Location
+    static constexpr uint64_t LocationId = 1;
+    static constexpr uint64_t LocationMappingId = 2;
+    static constexpr uint64_t LocationAddress = 3;
+  } // namespace pprof
+
+  struct ThreadState
+  {
+    /**
+     * Bytes to allocate before the next sample.
+     */
+    size_t countdown = 0;
+    uint64_t random = 0;
+    bool active = false;
+    /**
+     * Set while recording a sample, to ignore allocations made by
+     * `backtrace`.
+     */
+    bool busy = false;
+  };
+
+  inline thread_local ThreadState thread_state;
+
+  class Profiler
+  {
+    static constexpr size_t TABLE_BITS = 14;
+    static constexpr size_t RATE_UNSET = ~size_t(0);
+
+    /**
+     * Frames of the profiler and the shim to drop from each backtrace.
+     */
+    static constexpr size_t SKIP_FRAMES = 2;
+
+    std::atomic<size_t> rate{RATE_UNSET};
+
+    /**
+     * Live samples, chained by address.  A free only takes the lock if its
+     * bucket is non-empty.
+     */
+    std::atomic_flag lock = ATOMIC_FLAG_INIT;
+    size_t live = 0;
+    std::atomic<Sample*> table[bits::one_at_bit(TABLE_BITS)] = {};
+
+    static size_t bucket(void* p)
+    {
+      uint64_t a = static_cast<uint64_t>(address_cast(p));
+      return static_cast<size_t>(
+        (a * 0x9E3779B97F4A7C15) >> (64 - TABLE_BITS));
+    }
+
+    size_t current_rate()
+    {
+      size_t r = rate.load(std::memory_order_relaxed);
+      if (likely(r != RATE_UNSET))
+        return r;
+
+      size_t from_env = 0;
+      const char* s = getenv("SNMALLOC_PROFILE_RATE");
+      if (s != nullptr)
+        from_env = static_cast<size_t>(strtoull(s, nullptr, 0));
+      rate.compare_exchange_strong(r, from_env, std::memory_order_relaxed);
+      return rate.load(std::memory_order_relaxed);
+    }
+
+    /**
+     * Bytes until the next sample, drawn from an exponential distribution
+     * with mean `r`.
+     */
+    static size_t next_interval(ThreadState& t, size_t r)
+    {
+      // xorshift64*
+      t.random ^= t.random >> 12;
+      t.random ^= t.random << 25;
+      t.random ^= t.random >> 27;
+      uint64_t x = t.random * 0x2545F4914F6CDD1D;
+
+      // Uniform in (0, 1].
+      double u = static_cast<double>((x >> 11) + 1) * 0x1p-53;
+      double interval = -std::log(u) * static_cast<double>(r);
+      return static_cast<size_t>(interval) + 1;
+    }
+
+    SNMALLOC_SLOW_PATH void record(void* p, size_t size)
+    {
+      void* frames[MAX_FRAMES + SKIP_FRAMES];
+      auto depth = static_cast<size_t>(
+        backtrace(frames, static_cast<int>(MAX_FRAMES + SKIP_FRAMES)));
+
+      auto s = static_cast<Sample*>(
+        ThreadAlloc::get_noncachable()->alloc(sizeof(Sample)));
+      if (s == nullptr)
+        return;
+
+      s->address = p;
+      s->size = size;
+      s->depth = 0;
+      for (size_t i = SKIP_FRAMES; i < depth; i++)
+        s->frames[s->depth++] = frames[i];
+
+      auto& head = table[bucket(p)];
+      FlagLock f(lock);
+      s->next = head.load(std::memory_order_relaxed);
+      head.store(s, std::memory_order_relaxed);
+      live++;
+    }
+
+    SNMALLOC_SLOW_PATH void alloc_slow(void* p, size_t size)
+    {
+      auto& t = thread_state;
+      // Capturing a backtrace may allocate.
+      if (t.busy)
+        return;
+
+      size_t r = current_rate();
+      if (r == 0)
+      {
+        t.active = false;
+        t.countdown = DISABLED_INTERVAL;
+        return;
+      }
+
+      if (!t.active)
+      {
+        // Start sampling afresh, rather than sampling this allocation just
+        // because the rate changed.
+        t.active = true;
+        if (t.random == 0)
+          t.random = static_cast<uint64_t>(address_cast(&t)) | 1;
+        t.countdown = next_interval(t, r);
+        if (size < t.countdown)
+        {
+          t.countdown -= size;
+          return;
+        }
+      }
+
+      t.countdown = next_interval(t, r);
+      if (p == nullptr)
+        return;
+
+      t.busy = true;
+      record(p, size);
+      t.busy = false;
+    }
+
+    SNMALLOC_SLOW_PATH void dealloc_slow(void* p)
+    {
+      Sample* found = nullptr;
+      {
+        FlagLock f(lock);
+        auto& head = table[bucket(p)];
+        Sample* prev = nullptr;
+        for (auto s = head.load(std::memory_order_relaxed); s != nullptr;
+             prev = s, s = s->next)
+        {
+          if (s->address == p)
+          {
+            if (prev == nullptr)
+              head.store(s->next, std::memory_order_relaxed);
+            else
+              prev->next = s->next;
+            live--;
+            found = s;
+            break;
+          }
+        }
+      }
+
+      if (found != nullptr)
+        ThreadAlloc::get_noncachable()->dealloc(found, sizeof(Sample));
+    }
+
+    /**
+     * An encoded protocol buffer message, small enough to build on the stack
+     * before writing it with its length.
+     */
+    struct Message
+    {
+      static constexpr uint64_t VARINT = 0;
+      static constexpr uint64_t LENGTH_DELIMITED = 2;
+
+      size_t len = 0;
+      char data[512];
+
+      void clear()
+      {
+        len = 0;
+      }
+
+      void varint(uint64_t v)
+      {
+        for (; v >= 0x80; v >>= 7)
+          data[len++] = static_cast<char>(v | 0x80);
+        data[len++] = static_cast<char>(v);
+      }
+
+      void uint(uint64_t number, uint64_t v)
+      {
+        varint((number << 3) | VARINT);
+        varint(v);
+      }
+
+      /**
+       * Add `m` as a nested message, or as a packed repeated field.
+       */
+      void field(uint64_t number, const Message& m)
+      {
+        varint((number << 3) | LENGTH_DELIMITED);
+        varint(m.len);
+        memcpy(data + len, m.data, m.len);
+        len += m.len;
+      }
+    };
+
+    /**
+     * Writes protocol buffer fields to a file.
+     */
+    class Writer : public FileWriter
+    {
+    public:
+      using FileWriter::FileWriter;
+
+      void field(uint64_t number, const char* s, size_t len)
+      {
+        Message header;
+        header.varint((number << 3) | Message::LENGTH_DELIMITED);
+        header.varint(len);
+        write(header.data, header.len);
+        write(s, len);
+      }
+
+      void field(uint64_t number, const Message& m)
+      {
+        field(number, m.data, m.len);
+      }
+
+    };
+
+    /**
+     * Reads the executable mappings of the process, in address order, from
+     * `/proc/self/maps`.  Elsewhere there are none, so `pprof` needs the
+     * binaries to be given to it.
+     */
+    class Mappings
+    {
+      int fd = -1;
+      size_t start = 0;
+      size_t end = 0;
+      char buffer[4096 + 256];
+
+    public:
+      Mappings()
+      {
+#  ifdef __linux__
+        fd = ::open("/proc/self/maps", O_RDONLY | O_CLOEXEC);
+#  endif
+      }
+
+      ~Mappings()
+      {
+        if (fd >= 0)
+          ::close(fd);
+      }
+
+      bool next(
+        uintptr_t& map_start,
+        uintptr_t& map_end,
+        uintptr_t& offset,
+        const char*& filename)
+      {
+        while (fd >= 0)
+        {
+          char* line = buffer + start;
+          char* eol = static_cast<char*>(memchr(line, '\n', end - start));
+          if (eol == nullptr)
+          {
+            // Move the partial line to the front and read more.
+            memmove(buffer, line, end - start);
+            end -= start;
+            start = 0;
+            ssize_t len = ::read(fd, buffer + end, sizeof(buffer) - 1 - end);
+            if (len <= 0)
+            {
+              ::close(fd);
+              fd = -1;
+              return false;
+            }
+            end += static_cast<size_t>(len);
+            continue;
+          }
+          *eol = '\0';
+          start = static_cast<size_t>(eol + 1 - buffer);
+
+          char perms[5];
+          int path = 0;
+          if (
+            (sscanf(
+               line,
+               "%zx-%zx %4s %zx %*s %*s %n",
+               &map_start,
+               &map_end,
+               perms,
+               &offset,
+               &path) >= 4) &&
+            (perms[2] == 'x'))
+          {
+            filename = line + path;
+            return true;
+          }
+        }
+        return false;
+      }
+    };
+
+    static void write_location(
+      Writer& w, uint64_t id, uintptr_t address, uint64_t mapping_id)
+    {
+      // Frames are return addresses; point into the call instruction.
+      Message m;
+      m.uint(pprof::LocationId, id);
+      if (mapping_id != 0)
+        m.uint(pprof::LocationMappingId, mapping_id);
+      m.uint(pprof::LocationAddress, address - 1);
+      w.field(pprof::LocationField, m);
+    }
+
+    /**
+     * The number of allocations that a sample of `size` bytes stands for,
+     * at a mean of `r` bytes between samples.
+     */
+    static double sample_weight(size_t size, size_t r)
+    {
+      if ((r == 0) || (size == 0))
+        return 1;
+      return 1 /
+        (1 - std::exp(-static_cast<double>(size) / static_cast<double>(r)));
+    }
+
+    /**
+     * Copy the live samples, so that the table is not locked while they are
+     * written, sorted by call stack.  Returns null if there is no memory;
+     * otherwise the result must be freed with `release`.
+     */
+    Sample* collect(size_t& count)
+    {
+      count = 0;
+      FlagLock f(lock);
+      auto samples = static_cast<Sample*>(ThreadAlloc::get_noncachable()->alloc(
+        bits::max(live, size_t(1)) * sizeof(Sample)));
+      if (samples == nullptr)
+        return nullptr;
+      for (auto& head : table)
+      {
+        for (auto s = head.load(std::memory_order_relaxed); s != nullptr;
+             s = s->next)
+          samples[count++] = *s;
+      }
+      std::sort(samples, samples + count, stack_less);
+      return samples;
+    }
+
+    static void release(Sample* samples)
+    {
+      ThreadAlloc::get_noncachable()->dealloc(samples);
+    }
+
+    static bool same_stack(const Sample& a, const Sample& b)
+    {
+      return (a.depth == b.depth) &&
+        (memcmp(a.frames, b.frames, a.depth * sizeof(void*)) == 0);
+    }
+
+    static bool stack_less(const Sample& a, const Sample& b)
+    {
+      if (a.depth != b.depth)
+        return a.depth < b.depth;
+      return memcmp(a.frames, b.frames, a.depth * sizeof(void*)) < 0;
+    }
+
+  public:
+    /**
+     * The profiler must be constant initialised, because allocations may be
+     * made before dynamic initialisers run.
+     */
+    constexpr Profiler() = default;
+
+    SNMALLOC_FAST_PATH void alloc(void* p, size_t size)
+    {
+      auto& t = thread_state;
+      if (likely(size < t.countdown))
+      {
+        t.countdown -= size;
+        return;
+      }
+      alloc_slow(p, size);
+    }
+
+    SNMALLOC_FAST_PATH void dealloc(void* p)
+    {
+      if (likely(table[bucket(p)].load(std::memory_order_relaxed) == nullptr))
+        return;
+      dealloc_slow(p);
+    }
+
+    void set_rate(size_t r)
+    {
+      rate.store(r, std::memory_order_relaxed);
+    }
+
+    /**
+     * Write the live samples to `path`, grouped by call stack, followed by
+     * the process's memory map for symbolisation.  Returns false if the file
+     * cannot be written.
+     */
+    bool dump(const char* path)
+    {
+      size_t count;
+      Sample* samples = collect(count);
+      if (samples == nullptr)
+        return false;
+
+      size_t total = 0;
+      for (size_t i = 0; i < count; i++)
+        total += samples[i].size;
+
+      int fd = FileWriter::open(path);
+      if (fd < 0)
+      {
+        release(samples);
+        return false;
+      }
+
+      // Only live objects are tracked, so the cumulative counts are zero.
+      FileWriter w(fd);
+      w.print(
+        "heap profile: %zu: %zu [0: 0] @ heap_v2/%zu\n",
+        count,
+        total,
+        current_rate());
+      for (size_t i = 0; i < count;)
+      {
+        size_t objects = 0;
+        size_t bytes = 0;
+        size_t j = i;
+        for (; (j < count) && same_stack(samples[i], samples[j]); j++)
+        {
+          objects++;
+          bytes += samples[j].size;
+        }
+        w.print("%zu: %zu [0: 0] @", objects, bytes);
+        for (size_t k = 0; k < samples[i].depth; k++)
+          w.print(" %p", samples[i].frames[k]);
+        w.write("\n", 1);
+        i = j;
+      }
+
+#  ifdef __linux__
+      w.write("\nMAPPED_LIBRARIES:\n", 19);
+      int maps = ::open("/proc/self/maps", O_RDONLY | O_CLOEXEC);
+      if (maps >= 0)
+      {
+        char buffer[4096];
+        ssize_t len;
+        while ((len = ::read(maps, buffer, sizeof(buffer))) > 0)
+          w.write(buffer, static_cast<size_t>(len));
+        ::close(maps);
+      }
+#  endif
+
+      release(samples);
+      return w.close();
+    }
+
+    /**
+     * Write the live samples to `path` as a `pprof` protocol buffer, with
+     * the sample values scaled up to estimate the whole heap.  Returns false
+     * if the file cannot be written.
+     */
+    bool dump_pprof(const char* path)
+    {
+      size_t count;
+      Sample* samples = collect(count);
+      if (samples == nullptr)
+        return false;
+
+      int fd = FileWriter::open(path);
+      if (fd < 0)
+      {
+        release(samples);
+        return false;
+      }
+
+      // The string table, in order: each string is referred to by index.
+      enum : uint64_t
+      {
+        Empty,
+        Objects,
+        Count,
+        Space,
+        Bytes,
+        FirstFilename
+      };
+      Writer w(fd);
+      for (auto str : {"", "objects", "count", "space", "bytes"})
+        w.field(pprof::StringTable, str, strlen(str));
+
+      Message m;
+      m.uint(pprof::ValueTypeType, Objects);
+      m.uint(pprof::ValueTypeUnit, Count);
+      w.field(pprof::SampleType, m);
+      m.clear();
+      m.uint(pprof::ValueTypeType, Space);
+      m.uint(pprof::ValueTypeUnit, Bytes);
+      w.field(pprof::SampleType, m);
+      w.field(pprof::PeriodType, m);
+      size_t r = current_rate();
+      m.clear();
+      m.uint(pprof::Period, r);
+      w.write(m.data, m.len);
+
+      // Locations are the distinct return addresses, numbered from one.
+      size_t frames = 0;
+      for (size_t i = 0; i < count; i++)
+        frames += samples[i].depth;
+      auto addresses = static_cast<uintptr_t*>(
+        ThreadAlloc::get_noncachable()->alloc(
+          bits::max(frames, size_t(1)) * sizeof(uintptr_t)));
+      if (addresses == nullptr)
+      {
+        release(samples);
+        ::close(fd);
+        return false;
+      }
+      frames = 0;
+      for (size_t i = 0; i < count; i++)
+      {
+        for (size_t k = 0; k < samples[i].depth; k++)
+          addresses[frames++] = address_cast(samples[i].frames[k]);
+      }
+      std::sort(addresses, addresses + frames);
+      size_t locations = static_cast<size_t>(
+        std::unique(addresses, addresses + frames) - addresses);
+      auto location_id = [&](void* frame) {
+        auto a = address_cast(frame);
+        return static_cast<uint64_t>(
+          std::lower_bound(addresses, addresses + locations, a) - addresses +
+          1);
+      };
+
+      for (size_t i = 0; i < count;)
+      {
+        double objects = 0;
+        double bytes = 0;
+        size_t j = i;
+        for (; (j < count) && same_stack(samples[i], samples[j]); j++)
+        {
+          double weight = sample_weight(samples[j].size, r);
+          objects += weight;
+          bytes += weight * static_cast<double>(samples[j].size);
+        }
+
+        Message ids;
+        for (size_t k = 0; k < samples[i].depth; k++)
+          ids.varint(location_id(samples[i].frames[k]));
+        Message values;
+        values.varint(static_cast<uint64_t>(objects + 0.5));
+        values.varint(static_cast<uint64_t>(bytes + 0.5));
+
+        m.clear();
+        m.field(pprof::SampleLocationId, ids);
+        m.field(pprof::SampleValue, values);
+        w.field(pprof::SampleField, m);
+        i = j;
+      }
+
+      // The executable mappings, so that `pprof` can find the binaries to
+      // symbolise the locations.
+      uint64_t next_filename = FirstFilename;
+      uint64_t mapping_id = 0;
+      size_t next_location = 0;
+      Mappings maps;
+      uintptr_t start;
+      uintptr_t end;
+      uintptr_t offset;
+      const char* filename;
+      while (maps.next(start, end, offset, filename))
+      {
+        // Locations before this mapping are not in any mapping.
+        for (;
+             (next_location < locations) && (addresses[next_location] < start);
+             next_location++)
+          write_location(w, next_location + 1, addresses[next_location], 0);
+
+        if (
+          (next_location == locations) || (addresses[next_location] >= end))
+          continue;
+
+        mapping_id++;
+        size_t len = strlen(filename);
+        if (len > 0)
+          w.field(pprof::StringTable, filename, len);
+        m.clear();
+        m.uint(pprof::MappingId, mapping_id);
+        m.uint(pprof::MappingStart, start);
+        m.uint(pprof::MappingLimit, end);
+        m.uint(pprof::MappingOffset, offset);
+        m.uint(pprof::MappingFilename, len > 0 ? next_filename++ : Empty);
+        w.field(pprof::MappingField, m);
+
+        for (; (next_location < locations) && (addresses[next_location] < end);
+             next_location++)
+          write_location(
+            w, next_location + 1, addresses[next_location], mapping_id);
+      }
+      for (; next_location < locations; next_location++)
+        write_location(w, next_location + 1, addresses[next_location], 0);
+
+      ThreadAlloc::get_noncachable()->dealloc(addresses);
+      release(samples);
+      return w.close();
+    }
+  };
+
+  inline Profiler profiler;
+
+  /**
+   * Set the mean number of bytes between samples, or disable sampling if
+   * `rate` is zero.  Each thread starts using the new rate after its next
+   * sample, or after `DISABLED_INTERVAL` bytes if it is not sampling.
+   */
+  inline void set_rate(size_t rate)
+  {
+    profiler.set_rate(rate);
+  }
+
+  /**
+   * Write a heap profile of the sampled allocations that are still live.
+   */
+  inline bool dump(const char* path)
+  {
+    return profiler.dump(path);
+  }
+
+  /**
+   * As `dump`, but in the protocol buffer format of `pprof`.
+   */
+  inline bool dump_pprof(const char* path)
+  {
+    return profiler.dump_pprof(path);
+  }
+} // namespace snmalloc::profile
+
+#  define SNMALLOC_PROFILE_ALLOC(p, size) \
+    snmalloc::profile::profiler.alloc(p, size)
+#  define SNMALLOC_PROFILE_DEALLOC(p) snmalloc::profile::profiler.dealloc(p)
+#else
+#  define SNMALLOC_PROFILE_ALLOC(p, size)
+#  define SNMALLOC_PROFILE_DEALLOC(p)
+#endif
@@ -0,0 +1,166 @@
+#pragma once
+
+/**
+ * Configuration of the allocator from the environment, so that a deployed
+ * program can be tuned without rebuilding it.
+ *
+ * `runtime_config::apply` first tunes decommit for any cgroup memory limit,
+ * as `cgroup::apply` does, unless `SNMALLOC_CGROUP` is `0`; if it is `limit`,
+ * the heap is also limited to `memory.max`.  It then reads the
+ * following variables, which take precedence, and leaves the setting alone
+ * if a variable is unset or empty:
+ *
+ *  - `SNMALLOC_DECOMMIT`: when freed chunks are returned to the OS; one of
+ *    `none`, `eager` or `lazy`.  See `set_decommit_strategy`.
+ *  - `SNMALLOC_DECOMMIT_ADVICE`: how the pages of those chunks are returned
+ *    on platforms that offer a choice; one of `none`, `free` or `dontneed`.
+ *    See `set_decommit_advice`.
+ *  - `SNMALLOC_ERROR_VERBOSITY`: what is printed before aborting on a fatal
+ *    error; one of `silent`, `message` or `backtrace`, or `0` to `2`.
+ *  - `SNMALLOC_STATS_FILE`: a file to which a heap dump, as written by
+ *    `heapdump::dump`, is written when the process exits.
+ *  - `SNMALLOC_MEMORY_PRESSURE`: if set to anything other than `0`, free
+ *    memory is purged when the platform reports memory pressure.  See
+ *    `memory_pressure::start`.
+ *
+ * Unrecognised values are ignored.  The Rust shim applies these before
+ * `main`; each has a setter, so that a program can also apply them itself.
+ */
+#include "cgroup.h"
+#include "heapdump.h"
+#include "memory-pressure.h"
+
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+
+namespace snmalloc::runtime_config
+{
+  /**
+   * Parse a decommit strategy named `s` into `strategy`.  Returns false if it
+   * is not recognised.
+   */
+  inline bool parse_decommit(const char* s, DecommitStrategy& strategy)
+  {
+    if (strcmp(s, "none") == 0)
+      strategy = DecommitNone;
+    else if (strcmp(s, "eager") == 0)
+      strategy = DecommitSuper;
+    else if (strcmp(s, "lazy") == 0)
+      strategy = DecommitSuperLazy;
+    else
+      return false;
+    return true;
+  }
+
+  /**
+   * Parse a decommit advice named `s` into `advice`.  Returns false if it is
+   * not recognised.
+   */
+  inline bool parse_advice(const char* s, DecommitAdvice& advice)
+  {
+    if (strcmp(s, "none") == 0)
+      advice = AdviseNothing;
+    else if (strcmp(s, "free") == 0)
+      advice = AdviseFree;
+    else if (strcmp(s, "dontneed") == 0)
+      advice = AdviseDontNeed;
+    else
+      return false;
+    return true;
+  }
+
+  /**
+   * Parse an error verbosity, by name or number, from `s` into `verbosity`.
+   * Returns false if it is not recognised.
+   */
+  inline bool parse_verbosity(const char* s, ErrorVerbosity& verbosity)
+  {
+    if ((strcmp(s, "silent") == 0) || (strcmp(s, "0") == 0))
+      verbosity = ErrorSilent;
+    else if ((strcmp(s, "message") == 0) || (strcmp(s, "1") == 0))
+      verbosity = ErrorMessage;
+    else if ((strcmp(s, "backtrace") == 0) || (strcmp(s, "2") == 0))
+      verbosity = ErrorBacktrace;
+    else
+      return false;
+    return true;
+  }
+
+  /**
+   * The file for `dump_at_exit`, or empty for none.  This is kept here,
+   * rather than allocated, so that it is still valid when the process exits.
+   */
+  inline char stats_file[1024];
+  inline std::atomic_flag stats_file_lock = ATOMIC_FLAG_INIT;
+
+  inline bool write_file(const char* data, size_t len, void* context)
+  {
+    return fwrite(data, 1, len, static_cast<FILE*>(context)) == len;
+  }
+
+  inline void dump_at_exit()
+  {
+    FlagLock lock(stats_file_lock);
+    if (stats_file[0] == '\0')
+      return;
+    FILE* f = fopen(stats_file, "w");
+    if (f == nullptr)
+      return;
+    heapdump::dump(write_file, f);
+    fclose(f);
+  }
+
+  /**
+   * Write a heap dump to `path` when the process exits, or do not if `path`
+   * is null or empty.  Returns false if `path` is too long.
+   */
+  inline bool set_stats_file(const char* path)
+  {
+    if (path == nullptr)
+      path = "";
+    size_t len = strlen(path);
+    if (len >= sizeof(stats_file))
+      return false;
+
+    static int registered = atexit(dump_at_exit);
+    UNUSED(registered);
+
+    FlagLock lock(stats_file_lock);
+    memcpy(stats_file, path, len + 1);
+    return true;
+  }
+
+  /**
+   * Apply the settings in the environment.
+   */
+  inline void apply()
+  {
+    const char* s = getenv("SNMALLOC_CGROUP");
+    if ((s == nullptr) || (strcmp(s, "0") != 0))
+      cgroup::apply((s != nullptr) && (strcmp(s, "limit") == 0));
+
+    s = getenv("SNMALLOC_DECOMMIT");
+    DecommitStrategy strategy;
+    if ((s != nullptr) && parse_decommit(s, strategy))
+      set_decommit_strategy(strategy);
+
+    s = getenv("SNMALLOC_DECOMMIT_ADVICE");
+    DecommitAdvice advice;
+    if ((s != nullptr) && parse_advice(s, advice))
+      set_decommit_advice(advice);
+
+    s = getenv("SNMALLOC_ERROR_VERBOSITY");
+    ErrorVerbosity verbosity;
+    if ((s != nullptr) && parse_verbosity(s, verbosity))
+      set_error_verbosity(verbosity);
+
+    s = getenv("SNMALLOC_STATS_FILE");
+    if ((s != nullptr) && (*s != '\0'))
+      set_stats_file(s);
+
+    s = getenv("SNMALLOC_MEMORY_PRESSURE");
+    if ((s != nullptr) && (*s != '\0') && (strcmp(s, "0") != 0))
+      memory_pressure::start();
+  }
+} // namespace snmalloc::runtime_config
@@ -0,0 +1,14 @@
+/**
+ * A hardened copy of the Rust shim, built with `CHECK_CLIENT` and exporting
+ * `rust_checked_*` entry points, so that it can be linked alongside the plain
+ * `rust_*` shim and each binary can choose which one to use.
+ */
+#ifndef CHECK_CLIENT
+#  define CHECK_CLIENT
+#endif
+#define SNMALLOC_NAME_MANGLE(a) sn_checked_##a
+#define SNMALLOC_RUST_NAME(a) rust_checked_##a
+// Redefine the namespace, so that this copy of snmalloc, including its global
+// state, is independent of the plain shim if both are linked.
+#define snmalloc snmalloc_checked
+#include "rust.cc"
@@ -0,0 +1,19 @@
+/**
+ * The Rust shim over a single region of memory provided by the caller, with
+ * no interaction with the operating system, for embedded and `no_std`
+ * targets.  `rust_init_with_region` must be called before the first
+ * allocation.
+ */
+#define SNMALLOC_MEMORY_PROVIDER PALFixedRegion
+#include "rust.cc"
+
+/**
+ * Serve all allocations from the `len` bytes at `base`.  Returns false, and
+ * ignores the region, if one has already been provided.  The region must
+ * stay valid for the rest of the program, and cannot be given back.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(init_with_region)(void* base, size_t len)
+{
+  return PALFixedRegion::set_region(base, len);
+}
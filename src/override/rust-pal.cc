@@ -0,0 +1,18 @@
+/**
+ * The Rust shim with platform operations supplied by the embedding program,
+ * for platforms, hypervisors or test harnesses that snmalloc has no PAL for.
+ * `rust_init_with_pal` must be called before the first allocation.
+ */
+#define SNMALLOC_PAL PALHooks
+#include "rust.cc"
+
+/**
+ * Use the function pointers in `hooks` for all platform operations.  The
+ * table is copied.  Returns false, ignoring `hooks`, if hooks have already
+ * been provided or `hooks` has no `reserve` hook.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(init_with_pal)(const PalHooks* hooks)
+{
+  return PALHooks::set_hooks(*hooks);
+}
@@ -0,0 +1,8 @@
+/**
+ * The Rust shim with unprefixed C allocation functions (`malloc`, `free`,
+ * `realloc` and so on), for building a shared library that can be loaded
+ * with `LD_PRELOAD` to run unmodified binaries on snmalloc.  Rust code in the
+ * same process then shares the heap through the `rust_*` entry points.
+ */
+#define SNMALLOC_RUST_LIBC_API
+#include "rust.cc"
@@ -0,0 +1,16 @@
+/**
+ * The hardened half of the runtime-selectable Rust shim; see
+ * `rust-select.cc`.
+ */
+#ifndef CHECK_CLIENT
+#  define CHECK_CLIENT
+#endif
+// Only the selected copy is initialised before main, by `rust-select.cc`.
+#undef SNMALLOC_INIT_BEFORE_MAIN
+#define SNMALLOC_RUST_SELECT_COPY
+#define SNMALLOC_NAME_MANGLE(a) sn_checks_##a
+#define SNMALLOC_RUST_NAME(a) rust_checks_##a
+// Redefine the namespace, so that this copy of snmalloc, including its global
+// state, is independent of the fast copy.
+#define snmalloc snmalloc_checks
+#include "rust.cc"
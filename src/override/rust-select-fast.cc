@@ -0,0 +1,10 @@
+/**
+ * The fast (unchecked) half of the runtime-selectable Rust shim; see
+ * `rust-select.cc`.
+ */
+// Only the selected copy is initialised before main, by `rust-select.cc`.
+#undef SNMALLOC_INIT_BEFORE_MAIN
+#define SNMALLOC_RUST_SELECT_COPY
+#define SNMALLOC_NAME_MANGLE(a) sn_fast_##a
+#define SNMALLOC_RUST_NAME(a) rust_fast_##a
+#include "rust.cc"
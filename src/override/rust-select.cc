@@ -0,0 +1,992 @@
+/**
+ * Rust shim that contains both a fast and a hardened (`CHECK_CLIENT`) copy
+ * of snmalloc, and chooses between them, or the system allocator, at runtime.
+ *
+ * The two copies are built by `rust-select-fast.cc` and
+ * `rust-select-checks.cc` with prefixed entry points and, for the hardened
+ * copy, a renamed namespace, so they share no state.  This file provides the
+ * usual `rust_*` entry points and forwards each call to the selected copy.
+ *
+ * The choice is made once, before the first call that needs it, and cannot be
+ * changed afterwards because memory from one copy cannot be freed by the
+ * other.  It can be made explicitly with `rust_select_checks` or
+ * `rust_select_system`.  Otherwise it is made from the environment: the
+ * system allocator is used if `SNMALLOC_SYSTEM_ALLOCATOR` is set to anything
+ * other than `0`, and failing that the hardened copy is used if
+ * `SNMALLOC_CHECKS` is.
+ *
+ * With the system allocator, only the functions that manage ordinary memory
+ * use it.  The snmalloc-specific functions (I/O and pinned buffers, and the
+ * introspection functions) still go to the fast copy, which then reports no
+ * allocations other than those buffers.  Failure injection and allocation
+ * counting, if built in, do not apply to the system allocator.
+ */
+#include "../ds/defines.h"
+#include "premain.h"
+
+#include <atomic>
+#include <cstddef>
+#include <cstdint>
+#include <cstdlib>
+#include <cstring>
+#ifdef _WIN32
+#  include <malloc.h>
+#endif
+
+#ifndef SNMALLOC_EXPORT
+#  define SNMALLOC_EXPORT
+#endif
+
+struct RustRemoteQueueInfo;
+struct RustSlabOccupancy;
+struct RustSizeclassInfo;
+struct RustPinHooks;
+struct RustLargeCacheInfo;
+struct RustStats;
+struct RustSizeclassStats;
+struct RustThreadStats;
+struct RustLocalHandle;
+struct RustHeap;
+struct RustArena;
+struct RustPool;
+using RustRemoteQueueAlarm = void (*)(size_t, size_t);
+using RustErrorHandler = void (*)(const char*);
+using RustOomHandler = void (*)(size_t, size_t);
+using RustMemoryLimitHandler = bool (*)(size_t, size_t);
+using RustWriteFn = bool (*)(const char*, size_t, void*);
+using RustCheckFailureHandler = void (*)(const void*, size_t, const char*);
+
+#define SNMALLOC_RUST_DECLARE(ret, name, ...) \
+  extern "C" ret rust_fast_##name(__VA_ARGS__); \
+  extern "C" ret rust_checks_##name(__VA_ARGS__)
+
+SNMALLOC_RUST_DECLARE(void*, alloc, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, alloc_zeroed, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, alloc_filled, size_t, size_t, uint8_t);
+SNMALLOC_RUST_DECLARE(void, dealloc, void*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, realloc, void*, size_t, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, realloc_zeroed, void*, size_t, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void, init);
+SNMALLOC_RUST_DECLARE(RustLocalHandle*, local_handle);
+SNMALLOC_RUST_DECLARE(void*, handle_alloc, RustLocalHandle*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(
+  void, handle_dealloc, RustLocalHandle*, void*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(RustHeap*, heap_create, size_t);
+SNMALLOC_RUST_DECLARE(void*, heap_alloc, RustHeap*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void, heap_dealloc, RustHeap*, void*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(bool, heap_freeze, RustHeap*);
+SNMALLOC_RUST_DECLARE(void, heap_unfreeze, RustHeap*);
+SNMALLOC_RUST_DECLARE(void, heap_destroy_all, RustHeap*);
+SNMALLOC_RUST_DECLARE(void, heap_destroy, RustHeap*);
+SNMALLOC_RUST_DECLARE(RustArena*, arena_create, size_t);
+SNMALLOC_RUST_DECLARE(void*, arena_alloc, RustArena*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void, arena_reset, RustArena*);
+SNMALLOC_RUST_DECLARE(void, arena_usage, RustArena*, size_t*, size_t*);
+SNMALLOC_RUST_DECLARE(void, arena_destroy, RustArena*);
+SNMALLOC_RUST_DECLARE(RustPool*, pool_create, size_t, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, pool_acquire, RustPool*);
+SNMALLOC_RUST_DECLARE(void, pool_release, RustPool*, void*);
+SNMALLOC_RUST_DECLARE(size_t, pool_trim, RustPool*);
+SNMALLOC_RUST_DECLARE(void, pool_destroy, RustPool*);
+SNMALLOC_RUST_DECLARE(void*, alloc_locked, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void, dealloc_locked, void*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void, thread_teardown);
+SNMALLOC_RUST_DECLARE(size_t, sizeclass_of, size_t, size_t*);
+SNMALLOC_RUST_DECLARE(size_t, sizeclass_of_ptr, const void*, size_t*);
+SNMALLOC_RUST_DECLARE(size_t, sizeclass_table, RustSizeclassInfo*, size_t);
+SNMALLOC_RUST_DECLARE(bool, resize_in_place, void*, size_t, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, shrink, void*, size_t, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, allocation_start, const void*);
+SNMALLOC_RUST_DECLARE(bool, owns, const void*);
+SNMALLOC_RUST_DECLARE(bool, allocation_bounds, const void*, void**, void**);
+SNMALLOC_RUST_DECLARE(void*, memcpy_checked, void*, const void*, size_t);
+SNMALLOC_RUST_DECLARE(size_t, usable_size, size_t, size_t);
+SNMALLOC_RUST_DECLARE(void*, alloc_excess, size_t, size_t, size_t*);
+SNMALLOC_RUST_DECLARE(size_t, min_alignment);
+SNMALLOC_RUST_DECLARE(size_t, chunk_size);
+SNMALLOC_RUST_DECLARE(void*, io_buffer_alloc, size_t);
+SNMALLOC_RUST_DECLARE(void, io_buffer_dealloc, void*, size_t);
+SNMALLOC_RUST_DECLARE(void, set_pin_hooks, const RustPinHooks*);
+SNMALLOC_RUST_DECLARE(void*, pinned_alloc, size_t);
+SNMALLOC_RUST_DECLARE(void, pinned_dealloc, void*, size_t);
+SNMALLOC_RUST_DECLARE(size_t, page_size);
+SNMALLOC_RUST_DECLARE(void*, reserve, size_t, size_t);
+SNMALLOC_RUST_DECLARE(bool, commit, void*, size_t);
+SNMALLOC_RUST_DECLARE(bool, decommit, void*, size_t);
+SNMALLOC_RUST_DECLARE(void, unreserve, void*, size_t, size_t);
+SNMALLOC_RUST_DECLARE(size_t, current_allocator_id);
+SNMALLOC_RUST_DECLARE(size_t, allocator_id_of, const void*);
+SNMALLOC_RUST_DECLARE(void, remote_queue_depth, size_t*, size_t*);
+SNMALLOC_RUST_DECLARE(
+  size_t, remote_queue_info, RustRemoteQueueInfo*, size_t);
+SNMALLOC_RUST_DECLARE(
+  void, set_remote_queue_alarm, size_t, RustRemoteQueueAlarm);
+SNMALLOC_RUST_DECLARE(void, set_error_handler, RustErrorHandler);
+SNMALLOC_RUST_DECLARE(bool, set_decommit_strategy, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_decommit_advice, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_page_move_threshold, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_error_verbosity, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_stats_file, const char*);
+SNMALLOC_RUST_DECLARE(void, configure_from_env);
+SNMALLOC_RUST_DECLARE(void, set_oom_handler, RustOomHandler);
+SNMALLOC_RUST_DECLARE(
+  void, set_check_failure_handler, RustCheckFailureHandler);
+SNMALLOC_RUST_DECLARE(
+  size_t, slab_occupancy, RustSlabOccupancy*, size_t, size_t*);
+SNMALLOC_RUST_DECLARE(
+  size_t, large_cache_info, RustLargeCacheInfo*, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_large_retention, size_t, size_t);
+SNMALLOC_RUST_DECLARE(bool, donate_range, void*, size_t);
+SNMALLOC_RUST_DECLARE(bool, set_memory_limit, size_t);
+SNMALLOC_RUST_DECLARE(void, set_memory_limit_handler, RustMemoryLimitHandler);
+SNMALLOC_RUST_DECLARE(void, memory_breakdown, size_t*, size_t*, size_t*);
+SNMALLOC_RUST_DECLARE(void, memory_released, size_t*, size_t*);
+SNMALLOC_RUST_DECLARE(size_t, release_free_memory);
+SNMALLOC_RUST_DECLARE(bool, start_memory_pressure_listener);
+SNMALLOC_RUST_DECLARE(bool, cgroup_limits, size_t*, size_t*);
+SNMALLOC_RUST_DECLARE(size_t, huge_page_bytes);
+SNMALLOC_RUST_DECLARE(size_t, flush);
+SNMALLOC_RUST_DECLARE(bool, debug_check_empty);
+SNMALLOC_RUST_DECLARE(uint64_t, stats_refresh);
+SNMALLOC_RUST_DECLARE(
+  size_t, stats_read, RustStats*, RustSizeclassStats*, size_t);
+SNMALLOC_RUST_DECLARE(bool, heap_dump, RustWriteFn, void*);
+SNMALLOC_RUST_DECLARE(void, thread_stats, RustThreadStats*);
+#ifdef SNMALLOC_COUNT_ALLOCATIONS
+SNMALLOC_RUST_DECLARE(void, thread_allocation_counts, size_t*, size_t*);
+#endif
+#ifdef SNMALLOC_HOOKS
+SNMALLOC_RUST_DECLARE(
+  void, set_alloc_hooks, void (*)(void*, size_t), void (*)(void*, size_t));
+SNMALLOC_RUST_DECLARE(void, set_alloc_hooks_min_size, size_t);
+#endif
+#ifdef SNMALLOC_DHAT
+SNMALLOC_RUST_DECLARE(bool, dhat_dump, const char*);
+#endif
+#ifdef SNMALLOC_PROFILING
+SNMALLOC_RUST_DECLARE(void, profile_set_rate, size_t);
+SNMALLOC_RUST_DECLARE(bool, profile_dump, const char*);
+SNMALLOC_RUST_DECLARE(bool, profile_dump_pprof, const char*);
+#endif
+#ifdef SNMALLOC_FAILURE_INJECTION
+SNMALLOC_RUST_DECLARE(void, fail_every, size_t);
+SNMALLOC_RUST_DECLARE(void, fail_nth, size_t);
+SNMALLOC_RUST_DECLARE(void, fail_random, size_t, uint64_t);
+SNMALLOC_RUST_DECLARE(void, fail_above, size_t);
+SNMALLOC_RUST_DECLARE(void, fail_scope_enter);
+SNMALLOC_RUST_DECLARE(void, fail_scope_exit);
+SNMALLOC_RUST_DECLARE(size_t, failures);
+SNMALLOC_RUST_DECLARE(void, fail_reset);
+#endif
+
+#define SNMALLOC_RUST_DISPATCH(name, ...) \
+  (use_checks() ? rust_checks_##name(__VA_ARGS__) : \
+                  rust_fast_##name(__VA_ARGS__))
+
+namespace
+{
+  enum Mode
+  {
+    Unselected,
+    Fast,
+    Checks,
+    System
+  };
+
+  std::atomic<Mode> mode{Unselected};
+
+  /**
+   * Fix the mode to `requested` unless it has already been fixed, and return
+   * the mode in effect.  The copy selected, which for the system allocator is
+   * the fast copy that serves the snmalloc-specific functions, is configured
+   * from the environment.
+   */
+  Mode select(Mode requested)
+  {
+    Mode expected = Unselected;
+    if (mode.compare_exchange_strong(expected, requested))
+    {
+      if (requested == Checks)
+        rust_checks_configure_from_env();
+      else
+        rust_fast_configure_from_env();
+      return requested;
+    }
+    return expected;
+  }
+
+  bool environment_flag(const char* name)
+  {
+    const char* env = getenv(name);
+    return (env != nullptr) && (*env != '\0') && (strcmp(env, "0") != 0);
+  }
+
+  SNMALLOC_SLOW_PATH Mode select_from_environment()
+  {
+    if (environment_flag("SNMALLOC_SYSTEM_ALLOCATOR"))
+      return select(System);
+    return select(environment_flag("SNMALLOC_CHECKS") ? Checks : Fast);
+  }
+
+  SNMALLOC_FAST_PATH Mode current_mode()
+  {
+    Mode m = mode.load(std::memory_order_relaxed);
+    if (unlikely(m == Unselected))
+      m = select_from_environment();
+    return m;
+  }
+
+  SNMALLOC_FAST_PATH bool use_checks()
+  {
+    return current_mode() == Checks;
+  }
+
+  SNMALLOC_FAST_PATH bool use_system()
+  {
+    return current_mode() == System;
+  }
+
+  /**
+   * Alignments that the system `malloc` already provides use it directly;
+   * larger ones use the platform's aligned allocator, which on Windows has
+   * its own free.  This depends only on the alignment, so deallocation makes
+   * the same choice as allocation did.
+   */
+  bool system_malloc_aligned(size_t alignment)
+  {
+    return alignment <= alignof(std::max_align_t);
+  }
+
+  void* system_alloc(size_t alignment, size_t size)
+  {
+    if (system_malloc_aligned(alignment))
+      return malloc(size);
+#ifdef _WIN32
+    return _aligned_malloc(size, alignment);
+#else
+    void* p;
+    return posix_memalign(&p, alignment, size) == 0 ? p : nullptr;
+#endif
+  }
+
+  void system_dealloc(void* ptr, size_t alignment)
+  {
+#ifdef _WIN32
+    if (!system_malloc_aligned(alignment))
+    {
+      _aligned_free(ptr);
+      return;
+    }
+#else
+    UNUSED(alignment);
+#endif
+    free(ptr);
+  }
+
+  void*
+  system_realloc(void* ptr, size_t alignment, size_t old_size, size_t new_size)
+  {
+    if (system_malloc_aligned(alignment))
+      return realloc(ptr, new_size);
+    void* p = system_alloc(alignment, new_size);
+    if (p)
+    {
+      memcpy(p, ptr, old_size < new_size ? old_size : new_size);
+      system_dealloc(ptr, alignment);
+    }
+    return p;
+  }
+} // namespace
+
+/**
+ * Select the hardened (`enable` is true) or fast copy of the allocator.
+ * Returns true if the requested copy is in use, which is always the case
+ * unless a different copy was selected, or used, earlier.
+ */
+extern "C" SNMALLOC_EXPORT bool rust_select_checks(bool enable)
+{
+  Mode requested = enable ? Checks : Fast;
+  return select(requested) == requested;
+}
+
+/**
+ * Returns true if the hardened copy of the allocator is in use.  This fixes
+ * the choice if it has not already been made.
+ */
+extern "C" SNMALLOC_EXPORT bool rust_checks_enabled()
+{
+  return use_checks();
+}
+
+/**
+ * Select the system allocator in place of snmalloc.  Returns true if it is in
+ * use, which is the case unless a copy of snmalloc was selected, or used,
+ * earlier.
+ */
+extern "C" SNMALLOC_EXPORT bool rust_select_system()
+{
+  return select(System) == System;
+}
+
+/**
+ * Returns true if the system allocator is in use.  This fixes the choice if
+ * it has not already been made.
+ */
+extern "C" SNMALLOC_EXPORT bool rust_system_enabled()
+{
+  return use_system();
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_alloc(size_t alignment, size_t size)
+{
+  if (use_system())
+    return system_alloc(alignment, size);
+  return SNMALLOC_RUST_DISPATCH(alloc, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_alloc_zeroed(size_t alignment, size_t size)
+{
+  if (use_system())
+  {
+    if (system_malloc_aligned(alignment))
+      return calloc(1, size);
+    void* p = system_alloc(alignment, size);
+    if (p)
+      memset(p, 0, size);
+    return p;
+  }
+  return SNMALLOC_RUST_DISPATCH(alloc_zeroed, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_alloc_filled(size_t alignment, size_t size, uint8_t byte)
+{
+  if (use_system())
+  {
+    void* p = system_alloc(alignment, size);
+    if (p)
+      memset(p, byte, size);
+    return p;
+  }
+  return SNMALLOC_RUST_DISPATCH(alloc_filled, alignment, size, byte);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_dealloc(void* ptr, size_t alignment, size_t size)
+{
+  if (use_system())
+  {
+    system_dealloc(ptr, alignment);
+    return;
+  }
+  SNMALLOC_RUST_DISPATCH(dealloc, ptr, alignment, size);
+}
+
+/**
+ * With the system allocator selected, handles are null and the handle
+ * functions use the system allocator.
+ */
+extern "C" SNMALLOC_EXPORT RustLocalHandle* rust_local_handle()
+{
+  if (use_system())
+    return nullptr;
+  return SNMALLOC_RUST_DISPATCH(local_handle);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_handle_alloc(RustLocalHandle* handle, size_t alignment, size_t size)
+{
+  if (use_system())
+    return system_alloc(alignment, size);
+  return SNMALLOC_RUST_DISPATCH(handle_alloc, handle, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_handle_dealloc(
+  RustLocalHandle* handle, void* ptr, size_t alignment, size_t size)
+{
+  if (use_system())
+  {
+    system_dealloc(ptr, alignment);
+    return;
+  }
+  SNMALLOC_RUST_DISPATCH(handle_dealloc, handle, ptr, alignment, size);
+}
+
+/**
+ * The system allocator has no isolated heaps, so with it selected heaps
+ * cannot be created.
+ */
+extern "C" SNMALLOC_EXPORT RustHeap* rust_heap_create(size_t capacity)
+{
+  if (use_system())
+    return nullptr;
+  return SNMALLOC_RUST_DISPATCH(heap_create, capacity);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_heap_alloc(RustHeap* heap, size_t alignment, size_t size)
+{
+  return SNMALLOC_RUST_DISPATCH(heap_alloc, heap, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_heap_dealloc(RustHeap* heap, void* ptr, size_t alignment, size_t size)
+{
+  SNMALLOC_RUST_DISPATCH(heap_dealloc, heap, ptr, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_heap_freeze(RustHeap* heap)
+{
+  return SNMALLOC_RUST_DISPATCH(heap_freeze, heap);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_heap_unfreeze(RustHeap* heap)
+{
+  SNMALLOC_RUST_DISPATCH(heap_unfreeze, heap);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_heap_destroy_all(RustHeap* heap)
+{
+  SNMALLOC_RUST_DISPATCH(heap_destroy_all, heap);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_heap_destroy(RustHeap* heap)
+{
+  SNMALLOC_RUST_DISPATCH(heap_destroy, heap);
+}
+
+extern "C" SNMALLOC_EXPORT RustArena* rust_arena_create(size_t chunk_size)
+{
+  return SNMALLOC_RUST_DISPATCH(arena_create, chunk_size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_arena_alloc(RustArena* arena, size_t alignment, size_t size)
+{
+  return SNMALLOC_RUST_DISPATCH(arena_alloc, arena, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_arena_reset(RustArena* arena)
+{
+  SNMALLOC_RUST_DISPATCH(arena_reset, arena);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_arena_usage(RustArena* arena, size_t* allocated, size_t* footprint)
+{
+  SNMALLOC_RUST_DISPATCH(arena_usage, arena, allocated, footprint);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_arena_destroy(RustArena* arena)
+{
+  SNMALLOC_RUST_DISPATCH(arena_destroy, arena);
+}
+
+extern "C" SNMALLOC_EXPORT RustPool*
+rust_pool_create(size_t alignment, size_t size, size_t batch)
+{
+  return SNMALLOC_RUST_DISPATCH(pool_create, alignment, size, batch);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_pool_acquire(RustPool* pool)
+{
+  return SNMALLOC_RUST_DISPATCH(pool_acquire, pool);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_pool_release(RustPool* pool, void* ptr)
+{
+  SNMALLOC_RUST_DISPATCH(pool_release, pool, ptr);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_pool_trim(RustPool* pool)
+{
+  return SNMALLOC_RUST_DISPATCH(pool_trim, pool);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_pool_destroy(RustPool* pool)
+{
+  SNMALLOC_RUST_DISPATCH(pool_destroy, pool);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_alloc_locked(size_t alignment, size_t size)
+{
+  if (use_system())
+    return nullptr;
+  return SNMALLOC_RUST_DISPATCH(alloc_locked, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_dealloc_locked(void* ptr, size_t alignment, size_t size)
+{
+  // With the system allocator selected, `rust_alloc_locked` returned null.
+  if (use_system())
+    return;
+  SNMALLOC_RUST_DISPATCH(dealloc_locked, ptr, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_realloc(void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  if (use_system())
+    return system_realloc(ptr, alignment, old_size, new_size);
+  return SNMALLOC_RUST_DISPATCH(realloc, ptr, alignment, old_size, new_size);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_realloc_zeroed(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  if (use_system())
+  {
+    auto p = static_cast<char*>(
+      system_realloc(ptr, alignment, old_size, new_size));
+    if (p && (new_size > old_size))
+      memset(p + old_size, 0, new_size - old_size);
+    return p;
+  }
+  return SNMALLOC_RUST_DISPATCH(
+    realloc_zeroed, ptr, alignment, old_size, new_size);
+}
+
+/**
+ * Resize without moving.  The system allocator can only shrink in place, by
+ * the caller using less of the allocation.
+ */
+extern "C" SNMALLOC_EXPORT bool rust_resize_in_place(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  if (use_system())
+    return new_size <= old_size;
+  return SNMALLOC_RUST_DISPATCH(
+    resize_in_place, ptr, alignment, old_size, new_size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_shrink(void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  if (use_system())
+    return system_realloc(ptr, alignment, old_size, new_size);
+  return SNMALLOC_RUST_DISPATCH(shrink, ptr, alignment, old_size, new_size);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_allocation_start(const void* ptr)
+{
+  if (use_system())
+    return nullptr;
+  return SNMALLOC_RUST_DISPATCH(allocation_start, ptr);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_owns(const void* ptr)
+{
+  if (use_system())
+    return false;
+  return SNMALLOC_RUST_DISPATCH(owns, ptr);
+}
+
+extern "C" SNMALLOC_EXPORT bool
+rust_allocation_bounds(const void* ptr, void** start, void** end)
+{
+  if (use_system())
+    return false;
+  return SNMALLOC_RUST_DISPATCH(allocation_bounds, ptr, start, end);
+}
+
+/**
+ * With the system allocator selected, bounds are unknown and copies are not
+ * checked.
+ */
+extern "C" SNMALLOC_EXPORT void*
+rust_memcpy_checked(void* dst, const void* src, size_t len)
+{
+  if (use_system())
+    return memcpy(dst, src, len);
+  return SNMALLOC_RUST_DISPATCH(memcpy_checked, dst, src, len);
+}
+
+/**
+ * Initialise the selected allocator.  This fixes the choice if it has not
+ * already been made.
+ */
+extern "C" SNMALLOC_EXPORT void rust_init()
+{
+  if (!use_system())
+    SNMALLOC_RUST_DISPATCH(init);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_thread_teardown()
+{
+  if (!use_system())
+    SNMALLOC_RUST_DISPATCH(thread_teardown);
+}
+
+#ifdef SNMALLOC_INIT_BEFORE_MAIN
+SNMALLOC_BEFORE_MAIN(rust_select_init_before_main, rust_init)
+#endif
+
+extern "C" SNMALLOC_EXPORT size_t
+rust_sizeclass_of(size_t size, size_t* capacity)
+{
+  return SNMALLOC_RUST_DISPATCH(sizeclass_of, size, capacity);
+}
+
+extern "C" SNMALLOC_EXPORT size_t
+rust_sizeclass_of_ptr(const void* ptr, size_t* capacity)
+{
+  return SNMALLOC_RUST_DISPATCH(sizeclass_of_ptr, ptr, capacity);
+}
+
+extern "C" SNMALLOC_EXPORT size_t
+rust_sizeclass_table(RustSizeclassInfo* info, size_t count)
+{
+  return SNMALLOC_RUST_DISPATCH(sizeclass_table, info, count);
+}
+
+/**
+ * The usable size of an allocation by the allocator in use.  The system
+ * allocator is only asked for the requested size.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+rust_usable_size(size_t alignment, size_t size)
+{
+  if (use_system())
+    return size;
+  return SNMALLOC_RUST_DISPATCH(usable_size, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+rust_alloc_excess(size_t alignment, size_t size, size_t* usable)
+{
+  if (use_system())
+  {
+    void* p = system_alloc(alignment, size);
+    *usable = (p == nullptr) ? 0 : size;
+    return p;
+  }
+  return SNMALLOC_RUST_DISPATCH(alloc_excess, alignment, size, usable);
+}
+
+/**
+ * The alignment guaranteed for every allocation by the allocator in use.
+ */
+extern "C" SNMALLOC_EXPORT size_t rust_min_alignment()
+{
+  if (use_system())
+    return alignof(std::max_align_t);
+  return SNMALLOC_RUST_DISPATCH(min_alignment);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_chunk_size()
+{
+  return SNMALLOC_RUST_DISPATCH(chunk_size);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_io_buffer_alloc(size_t len)
+{
+  return SNMALLOC_RUST_DISPATCH(io_buffer_alloc, len);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_io_buffer_dealloc(void* ptr, size_t len)
+{
+  SNMALLOC_RUST_DISPATCH(io_buffer_dealloc, ptr, len);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_set_pin_hooks(const RustPinHooks* hooks)
+{
+  SNMALLOC_RUST_DISPATCH(set_pin_hooks, hooks);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_pinned_alloc(size_t len)
+{
+  return SNMALLOC_RUST_DISPATCH(pinned_alloc, len);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_pinned_dealloc(void* ptr, size_t len)
+{
+  SNMALLOC_RUST_DISPATCH(pinned_dealloc, ptr, len);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_page_size()
+{
+  return SNMALLOC_RUST_DISPATCH(page_size);
+}
+
+extern "C" SNMALLOC_EXPORT void* rust_reserve(size_t alignment, size_t size)
+{
+  return SNMALLOC_RUST_DISPATCH(reserve, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_commit(void* ptr, size_t len)
+{
+  return SNMALLOC_RUST_DISPATCH(commit, ptr, len);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_decommit(void* ptr, size_t len)
+{
+  return SNMALLOC_RUST_DISPATCH(decommit, ptr, len);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_unreserve(void* ptr, size_t alignment, size_t size)
+{
+  SNMALLOC_RUST_DISPATCH(unreserve, ptr, alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_current_allocator_id()
+{
+  return SNMALLOC_RUST_DISPATCH(current_allocator_id);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_allocator_id_of(const void* ptr)
+{
+  return SNMALLOC_RUST_DISPATCH(allocator_id_of, ptr);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_remote_queue_depth(size_t* current_depth, size_t* peak_depth)
+{
+  SNMALLOC_RUST_DISPATCH(remote_queue_depth, current_depth, peak_depth);
+}
+
+extern "C" SNMALLOC_EXPORT size_t
+rust_remote_queue_info(RustRemoteQueueInfo* info, size_t count)
+{
+  return SNMALLOC_RUST_DISPATCH(remote_queue_info, info, count);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_set_remote_queue_alarm(size_t threshold, RustRemoteQueueAlarm alarm)
+{
+  SNMALLOC_RUST_DISPATCH(set_remote_queue_alarm, threshold, alarm);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_set_error_handler(RustErrorHandler handler)
+{
+  SNMALLOC_RUST_DISPATCH(set_error_handler, handler);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_set_oom_handler(RustOomHandler handler)
+{
+  SNMALLOC_RUST_DISPATCH(set_oom_handler, handler);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_decommit_strategy(size_t strategy)
+{
+  return SNMALLOC_RUST_DISPATCH(set_decommit_strategy, strategy);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_decommit_advice(size_t advice)
+{
+  return SNMALLOC_RUST_DISPATCH(set_decommit_advice, advice);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_page_move_threshold(size_t bytes)
+{
+  return SNMALLOC_RUST_DISPATCH(set_page_move_threshold, bytes);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_error_verbosity(size_t verbosity)
+{
+  return SNMALLOC_RUST_DISPATCH(set_error_verbosity, verbosity);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_stats_file(const char* path)
+{
+  return SNMALLOC_RUST_DISPATCH(set_stats_file, path);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_configure_from_env()
+{
+  SNMALLOC_RUST_DISPATCH(configure_from_env);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_set_check_failure_handler(RustCheckFailureHandler handler)
+{
+  SNMALLOC_RUST_DISPATCH(set_check_failure_handler, handler);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_slab_occupancy(
+  RustSlabOccupancy* info, size_t count, size_t* unused_slabs)
+{
+  return SNMALLOC_RUST_DISPATCH(slab_occupancy, info, count, unused_slabs);
+}
+
+extern "C" SNMALLOC_EXPORT size_t
+rust_large_cache_info(RustLargeCacheInfo* info, size_t count)
+{
+  return SNMALLOC_RUST_DISPATCH(large_cache_info, info, count);
+}
+
+extern "C" SNMALLOC_EXPORT bool
+rust_set_large_retention(size_t large_class, size_t count)
+{
+  return SNMALLOC_RUST_DISPATCH(set_large_retention, large_class, count);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_donate_range(void* base, size_t len)
+{
+  return SNMALLOC_RUST_DISPATCH(donate_range, base, len);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_set_memory_limit(size_t bytes)
+{
+  return SNMALLOC_RUST_DISPATCH(set_memory_limit, bytes);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_set_memory_limit_handler(RustMemoryLimitHandler handler)
+{
+  SNMALLOC_RUST_DISPATCH(set_memory_limit_handler, handler);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_memory_breakdown(size_t* reserved, size_t* committed, size_t* live)
+{
+  SNMALLOC_RUST_DISPATCH(memory_breakdown, reserved, committed, live);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_memory_released(size_t* bytes, size_t* count)
+{
+  SNMALLOC_RUST_DISPATCH(memory_released, bytes, count);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_release_free_memory()
+{
+  return SNMALLOC_RUST_DISPATCH(release_free_memory);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_start_memory_pressure_listener()
+{
+  return SNMALLOC_RUST_DISPATCH(start_memory_pressure_listener);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_cgroup_limits(size_t* max, size_t* high)
+{
+  return SNMALLOC_RUST_DISPATCH(cgroup_limits, max, high);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_huge_page_bytes()
+{
+  return SNMALLOC_RUST_DISPATCH(huge_page_bytes);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_flush()
+{
+  return SNMALLOC_RUST_DISPATCH(flush);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_debug_check_empty()
+{
+  return SNMALLOC_RUST_DISPATCH(debug_check_empty);
+}
+
+extern "C" SNMALLOC_EXPORT uint64_t rust_stats_refresh()
+{
+  return SNMALLOC_RUST_DISPATCH(stats_refresh);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_stats_read(
+  RustStats* stats, RustSizeclassStats* sizeclasses, size_t count)
+{
+  return SNMALLOC_RUST_DISPATCH(stats_read, stats, sizeclasses, count);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_heap_dump(RustWriteFn write, void* context)
+{
+  return SNMALLOC_RUST_DISPATCH(heap_dump, write, context);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_thread_stats(RustThreadStats* stats)
+{
+  SNMALLOC_RUST_DISPATCH(thread_stats, stats);
+}
+
+#ifdef SNMALLOC_FAILURE_INJECTION
+extern "C" SNMALLOC_EXPORT void rust_fail_every(size_t n)
+{
+  SNMALLOC_RUST_DISPATCH(fail_every, n);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_fail_nth(size_t n)
+{
+  SNMALLOC_RUST_DISPATCH(fail_nth, n);
+}
+
+extern "C" SNMALLOC_EXPORT void
+rust_fail_random(size_t per_million, uint64_t seed)
+{
+  SNMALLOC_RUST_DISPATCH(fail_random, per_million, seed);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_fail_above(size_t size)
+{
+  SNMALLOC_RUST_DISPATCH(fail_above, size);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_fail_scope_enter()
+{
+  SNMALLOC_RUST_DISPATCH(fail_scope_enter);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_fail_scope_exit()
+{
+  SNMALLOC_RUST_DISPATCH(fail_scope_exit);
+}
+
+extern "C" SNMALLOC_EXPORT size_t rust_failures()
+{
+  return SNMALLOC_RUST_DISPATCH(failures);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_fail_reset()
+{
+  SNMALLOC_RUST_DISPATCH(fail_reset);
+}
+#endif
+
+#ifdef SNMALLOC_HOOKS
+extern "C" SNMALLOC_EXPORT void rust_set_alloc_hooks(
+  void (*on_alloc)(void*, size_t), void (*on_dealloc)(void*, size_t))
+{
+  SNMALLOC_RUST_DISPATCH(set_alloc_hooks, on_alloc, on_dealloc);
+}
+
+extern "C" SNMALLOC_EXPORT void rust_set_alloc_hooks_min_size(size_t size)
+{
+  SNMALLOC_RUST_DISPATCH(set_alloc_hooks_min_size, size);
+}
+#endif
+
+#ifdef SNMALLOC_DHAT
+extern "C" SNMALLOC_EXPORT bool rust_dhat_dump(const char* path)
+{
+  return SNMALLOC_RUST_DISPATCH(dhat_dump, path);
+}
+#endif
+
+#ifdef SNMALLOC_PROFILING
+extern "C" SNMALLOC_EXPORT void rust_profile_set_rate(size_t rate)
+{
+  SNMALLOC_RUST_DISPATCH(profile_set_rate, rate);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_profile_dump(const char* path)
+{
+  return SNMALLOC_RUST_DISPATCH(profile_dump, path);
+}
+
+extern "C" SNMALLOC_EXPORT bool rust_profile_dump_pprof(const char* path)
+{
+  return SNMALLOC_RUST_DISPATCH(profile_dump_pprof, path);
+}
+#endif
+
+#ifdef SNMALLOC_COUNT_ALLOCATIONS
+extern "C" SNMALLOC_EXPORT void
+rust_thread_allocation_counts(size_t* allocations, size_t* deallocations)
+{
+  SNMALLOC_RUST_DISPATCH(thread_allocation_counts, allocations, deallocations);
+}
+#endif
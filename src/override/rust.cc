@@ -1,45 +1,1783 @@
-#define SNMALLOC_NAME_MANGLE(a) sn_##a
+/**
+ * The C allocation functions from `malloc.cc` are prefixed with `sn_`, unless
+ * `SNMALLOC_RUST_LIBC_API` is defined, in which case they replace the C
+ * library's `malloc`, `free` and so on.
+ */
+#if defined(SNMALLOC_RUST_LIBC_API) && defined(SNMALLOC_ASAN)
+#  error AddressSanitizer provides malloc, so SNMALLOC_ASAN cannot replace it
+#endif
+#ifndef SNMALLOC_NAME_MANGLE
+#  ifdef SNMALLOC_RUST_LIBC_API
+#    define SNMALLOC_NAME_MANGLE(a) a
+#  else
+#    define SNMALLOC_NAME_MANGLE(a) sn_##a
+#  endif
+#endif
+#include "../mem/objectpool.h"
 #include "malloc.cc"
+#include "runtime-config.h"
+#ifndef SNMALLOC_PASS_THROUGH
+#  include "../mem/arena.h"
+#  include "../mem/heap.h"
+#endif
 
+#include <atomic>
 #include <cstring>
 
 #ifndef SNMALLOC_EXPORT
 #  define SNMALLOC_EXPORT
 #endif
 
+/**
+ * The entry points are named `rust_*` unless this file is included with a
+ * different naming scheme, as `rust-select-fast.cc` and `rust-checked.cc` do.
+ */
+#ifndef SNMALLOC_RUST_NAME
+#  define SNMALLOC_RUST_NAME(a) rust_##a
+#endif
+
 using namespace snmalloc;
 
-extern "C" SNMALLOC_EXPORT void* rust_alloc(size_t alignment, size_t size)
+/**
+ * The size to request for an allocation of `size` bytes aligned to
+ * `alignment`.  Every sizeclass is a multiple of `MIN_ALIGNMENT`, so requests
+ * for no more than that alignment, which are most of them, need no rounding.
+ * Larger alignments round the size up so that its sizeclass is aligned; a
+ * size of zero is treated as one, as `aligned_size` would wrap it to zero and
+ * lose the alignment, for instance when `realloc` shrinks a block to nothing.
+ */
+static inline size_t request_size(size_t alignment, size_t size)
+{
+  if (likely(alignment <= MIN_ALIGNMENT))
+    return size;
+  return aligned_size(alignment, bits::max(size, size_t(1)));
+}
+
+/**
+ * Called with the size and alignment of an allocation that has failed, before
+ * null is returned for it, so that an application can log the failure or
+ * free caches and degrade gracefully; see `set_oom_handler`.
+ */
+using OomHandler = void (*)(size_t size, size_t alignment);
+
+static std::atomic<OomHandler> oom_handler{nullptr};
+
+static SNMALLOC_SLOW_PATH void* out_of_memory(size_t alignment, size_t size)
+{
+  auto handler = oom_handler.load(std::memory_order_relaxed);
+  if (handler != nullptr)
+    handler(size, alignment);
+  return nullptr;
+}
+
+static SNMALLOC_FAST_PATH void*
+alloc_with(Alloc* a, size_t alignment, size_t size)
+{
+  if (SNMALLOC_INJECT_FAILURE(size))
+    return out_of_memory(alignment, size);
+  void* p = a->alloc(request_size(alignment, size));
+  if (unlikely(p == nullptr))
+    return out_of_memory(alignment, size);
+  SNMALLOC_TRACE_RECORD(Alloc, p, nullptr, size, alignment);
+  SNMALLOC_COUNT_ALLOC(p);
+  SNMALLOC_PROFILE_ALLOC(p, size);
+  SNMALLOC_DHAT_ALLOC(p, size);
+  SNMALLOC_HOOK_ALLOC(p, size);
+  return p;
+}
+
+static SNMALLOC_FAST_PATH void
+dealloc_with(Alloc* a, void* ptr, size_t alignment, size_t size)
+{
+  SNMALLOC_TRACE_RECORD(Free, ptr, nullptr, size, alignment);
+  SNMALLOC_COUNT_DEALLOC();
+  SNMALLOC_PROFILE_DEALLOC(ptr);
+  SNMALLOC_DHAT_DEALLOC(ptr);
+  SNMALLOC_HOOK_DEALLOC(ptr, size);
+  a->dealloc(ptr, request_size(alignment, size));
+}
+
+/**
+ * With `SNMALLOC_CHECK_BOUNDS`, check that `len` bytes from `ptr` are within
+ * its allocation before the shim copies or clears that many bytes of it on
+ * the strength of a size passed by the caller.
+ */
+static SNMALLOC_FAST_PATH void check_bounds(void* ptr, size_t len)
+{
+#if defined(SNMALLOC_CHECK_BOUNDS) && !defined(SNMALLOC_PASS_THROUGH)
+  auto a = ThreadAlloc::get_noncachable();
+  check_client_bounds(
+    len <= pointer_diff(ptr, a->external_pointer<OnePastEnd>(ptr)),
+    "Size out of bounds of heap allocation",
+    ptr);
+#else
+  UNUSED(ptr);
+  UNUSED(len);
+#endif
+}
+
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(alloc)(size_t alignment, size_t size)
+{
+  return alloc_with(ThreadAlloc::get_noncachable(), alignment, size);
+}
+
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(alloc_zeroed)(size_t alignment, size_t size)
 {
-  return ThreadAlloc::get_noncachable()->alloc(aligned_size(alignment, size));
+  if (SNMALLOC_INJECT_FAILURE(size))
+    return out_of_memory(alignment, size);
+  void* p = ThreadAlloc::get_noncachable()->alloc<YesZero>(
+    request_size(alignment, size));
+  if (unlikely(p == nullptr))
+    return out_of_memory(alignment, size);
+  SNMALLOC_TRACE_RECORD(Alloc, p, nullptr, size, alignment);
+  SNMALLOC_COUNT_ALLOC(p);
+  SNMALLOC_PROFILE_ALLOC(p, size);
+  SNMALLOC_DHAT_ALLOC(p, size);
+  SNMALLOC_HOOK_ALLOC(p, size);
+  return p;
 }
 
+/**
+ * Allocate memory with every byte set to `byte`.  A zero fill uses the
+ * allocator's zeroing path, which avoids touching memory that is known to be
+ * freshly mapped.
+ */
 extern "C" SNMALLOC_EXPORT void*
-rust_alloc_zeroed(size_t alignment, size_t size)
+SNMALLOC_RUST_NAME(alloc_filled)(size_t alignment, size_t size, uint8_t byte)
+{
+  if (byte == 0)
+    return SNMALLOC_RUST_NAME(alloc_zeroed)(alignment, size);
+  void* p = SNMALLOC_RUST_NAME(alloc)(alignment, size);
+  if (p)
+    std::memset(p, byte, size);
+  return p;
+}
+
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(dealloc)(void* ptr, size_t alignment, size_t size)
+{
+  dealloc_with(ThreadAlloc::get_noncachable(), ptr, alignment, size);
+}
+
+/**
+ * The calling thread's allocator, for `handle_alloc` and `handle_dealloc`,
+ * which skip the thread-local lookup that `alloc` and `dealloc` make on every
+ * call.  A handle must only be used by the thread that obtained it, and only
+ * until that thread calls `thread_teardown` or exits.
+ */
+struct RustLocalHandle;
+
+extern "C" SNMALLOC_EXPORT RustLocalHandle* SNMALLOC_RUST_NAME(local_handle)()
+{
+  return reinterpret_cast<RustLocalHandle*>(ThreadAlloc::get());
+}
+
+/**
+ * As `alloc`, using the allocator from `local_handle`.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(handle_alloc)(
+  RustLocalHandle* handle, size_t alignment, size_t size)
+{
+  return alloc_with(reinterpret_cast<Alloc*>(handle), alignment, size);
+}
+
+/**
+ * As `dealloc`, using the allocator from `local_handle`.  The memory may have
+ * been allocated by any thread, with or without a handle.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(handle_dealloc)(
+  RustLocalHandle* handle, void* ptr, size_t alignment, size_t size)
+{
+  dealloc_with(reinterpret_cast<Alloc*>(handle), ptr, alignment, size);
+}
+
+/**
+ * A heap with its own allocator and memory, so that a tenant or request can
+ * be isolated from the rest of the program and everything it allocated freed
+ * at once.  A heap must only be used by one thread at a time, and its memory
+ * must only be freed with `heap_dealloc`.
+ */
+struct RustHeap;
+
+/**
+ * Create a heap that can allocate at least `capacity` bytes, or return null
+ * if the memory cannot be obtained.  Heaps are not available with
+ * `SNMALLOC_PASS_THROUGH`, so this always returns null there.
+ */
+extern "C" SNMALLOC_EXPORT RustHeap*
+SNMALLOC_RUST_NAME(heap_create)(size_t capacity)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(capacity);
+  return nullptr;
+#else
+  return reinterpret_cast<RustHeap*>(Heap::create(capacity));
+#endif
+}
+
+/**
+ * As `alloc`, from `heap`.  Returns null if the heap is full.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(heap_alloc)(
+  RustHeap* heap, size_t alignment, size_t size)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+  UNUSED(alignment);
+  UNUSED(size);
+  return nullptr;
+#else
+  return reinterpret_cast<Heap*>(heap)->alloc(request_size(alignment, size));
+#endif
+}
+
+/**
+ * As `dealloc`, for memory allocated from `heap`.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(heap_dealloc)(
+  RustHeap* heap, void* ptr, size_t alignment, size_t size)
 {
-  return ThreadAlloc::get_noncachable()->alloc<YesZero>(
-    aligned_size(alignment, size));
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+  UNUSED(ptr);
+  UNUSED(alignment);
+  UNUSED(size);
+#else
+  reinterpret_cast<Heap*>(heap)->dealloc(ptr, request_size(alignment, size));
+#endif
+}
+
+/**
+ * Make all of `heap`'s memory read-only, so that data built in it during
+ * initialisation cannot be corrupted later: writes to its objects fault.
+ * The heap must not be used to allocate, free or destroy until
+ * `heap_unfreeze` is called.  Returns false, leaving the heap writable, if
+ * the platform cannot protect pages.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(heap_freeze)(RustHeap* heap)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+  return false;
+#else
+  return reinterpret_cast<Heap*>(heap)->freeze();
+#endif
+}
+
+/**
+ * Make the memory of a heap frozen by `heap_freeze` writable again.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(heap_unfreeze)(RustHeap* heap)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+#else
+  reinterpret_cast<Heap*>(heap)->unfreeze();
+#endif
+}
+
+/**
+ * Free everything allocated from `heap`, leaving it empty and ready for
+ * reuse.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(heap_destroy_all)(RustHeap* heap)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+#else
+  reinterpret_cast<Heap*>(heap)->destroy_all();
+#endif
+}
+
+/**
+ * Free everything allocated from `heap` and the heap itself, returning its
+ * memory to the global allocator.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(heap_destroy)(RustHeap* heap)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(heap);
+#else
+  Heap::destroy(reinterpret_cast<Heap*>(heap));
+#endif
+}
+
+/**
+ * A region that objects are bump-allocated from and freed from all at once,
+ * for allocations with a common lifetime; see `Arena` in `arena.h`.  An
+ * arena must only be used by one thread at a time, and its memory must not
+ * be freed with `dealloc`.
+ */
+struct RustArena;
+
+/**
+ * Create an arena that takes chunks of at least `chunk_size` bytes from the
+ * allocator, or return null if the memory cannot be obtained.  Arenas are
+ * not available with `SNMALLOC_PASS_THROUGH`, so this always returns null
+ * there.
+ */
+extern "C" SNMALLOC_EXPORT RustArena*
+SNMALLOC_RUST_NAME(arena_create)(size_t chunk_size)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(chunk_size);
+  return nullptr;
+#else
+  void* p = ThreadAlloc::get()->alloc<sizeof(Arena)>();
+  if (p == nullptr)
+    return nullptr;
+  return reinterpret_cast<RustArena*>(new (p) Arena(chunk_size));
+#endif
+}
+
+/**
+ * As `alloc`, from `arena`.  Returns null if the memory cannot be obtained.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(arena_alloc)(
+  RustArena* arena, size_t alignment, size_t size)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(arena);
+  UNUSED(alignment);
+  UNUSED(size);
+  return nullptr;
+#else
+  return reinterpret_cast<Arena*>(arena)->alloc(size, alignment);
+#endif
 }
 
+/**
+ * Free everything allocated from `arena`, returning its chunks to the
+ * allocator, and leave it ready for reuse.
+ */
 extern "C" SNMALLOC_EXPORT void
-rust_dealloc(void* ptr, size_t alignment, size_t size)
+SNMALLOC_RUST_NAME(arena_reset)(RustArena* arena)
 {
-  ThreadAlloc::get_noncachable()->dealloc(ptr, aligned_size(alignment, size));
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(arena);
+#else
+  reinterpret_cast<Arena*>(arena)->reset();
+#endif
+}
+
+/**
+ * Report the bytes allocated from `arena` since it was created or last
+ * reset, and the bytes in the chunks that it holds.  Either pointer may be
+ * null.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(arena_usage)(
+  RustArena* arena, size_t* allocated, size_t* footprint)
+{
+  size_t a = 0;
+  size_t f = 0;
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(arena);
+#else
+  a = reinterpret_cast<Arena*>(arena)->allocated();
+  f = reinterpret_cast<Arena*>(arena)->footprint();
+#endif
+  if (allocated != nullptr)
+    *allocated = a;
+  if (footprint != nullptr)
+    *footprint = f;
 }
 
+/**
+ * Free everything allocated from `arena` and the arena itself.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(arena_destroy)(RustArena* arena)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(arena);
+#else
+  auto p = reinterpret_cast<Arena*>(arena);
+  p->~Arena();
+  ThreadAlloc::get()->dealloc<sizeof(Arena)>(p);
+#endif
+}
+
+/**
+ * A cache of free objects of one size, for fast allocation of many objects
+ * of the same type; see `FixedSizePoolTemplate` in `objectpool.h`.  A pool
+ * must only be used by one thread at a time.
+ */
+struct RustPool;
+
+/**
+ * Refills and trims pools like `alloc` and `dealloc`, so that their objects
+ * are traced, profiled and passed to the hooks when they are taken from the
+ * allocator and when they are returned to it, and failed refills call the
+ * handler from `set_oom_handler`.
+ */
+struct ShimPoolSource
+{
+  static void* alloc(size_t alignment, size_t size)
+  {
+    return alloc_with(ThreadAlloc::get_noncachable(), alignment, size);
+  }
+
+  static void dealloc(void* p, size_t alignment, size_t size)
+  {
+    dealloc_with(ThreadAlloc::get_noncachable(), p, alignment, size);
+  }
+};
+
+using ShimPool = FixedSizePoolTemplate<ShimPoolSource>;
+
+/**
+ * Create a pool of objects of `size` bytes aligned to `alignment`, refilled
+ * from the allocator `batch` objects at a time, or a slab's worth if `batch`
+ * is zero.  Returns null if the memory cannot be obtained.
+ */
+extern "C" SNMALLOC_EXPORT RustPool*
+SNMALLOC_RUST_NAME(pool_create)(size_t alignment, size_t size, size_t batch)
+{
+  void* p = ThreadAlloc::get()->alloc<sizeof(ShimPool)>();
+  if (p == nullptr)
+    return nullptr;
+  return reinterpret_cast<RustPool*>(new (p) ShimPool(size, alignment, batch));
+}
+
+/**
+ * Take an object from `pool`.  Returns null if the memory cannot be
+ * obtained.
+ */
 extern "C" SNMALLOC_EXPORT void*
-rust_realloc(void* ptr, size_t alignment, size_t old_size, size_t new_size)
+SNMALLOC_RUST_NAME(pool_acquire)(RustPool* pool)
+{
+  return reinterpret_cast<ShimPool*>(pool)->acquire();
+}
+
+/**
+ * Return to `pool` an object taken from it.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(pool_release)(RustPool* pool, void* ptr)
+{
+  reinterpret_cast<ShimPool*>(pool)->release(ptr);
+}
+
+/**
+ * Free the objects cached in `pool`.  Returns the number freed.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(pool_trim)(RustPool* pool)
 {
-  size_t aligned_old_size = aligned_size(alignment, old_size),
-         aligned_new_size = aligned_size(alignment, new_size);
+  return reinterpret_cast<ShimPool*>(pool)->trim();
+}
+
+/**
+ * Free the objects cached in `pool` and the pool itself.  Objects taken from
+ * it and not returned must be freed with `dealloc`, with the pool's size and
+ * alignment.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(pool_destroy)(RustPool* pool)
+{
+  auto p = reinterpret_cast<ShimPool*>(pool);
+  p->~ShimPool();
+  ThreadAlloc::get()->dealloc<sizeof(ShimPool)>(p);
+}
+
+/**
+ * Lock the pages of `size` bytes at `p`, which must be page aligned, into
+ * physical memory.  Returns false if they could not be locked or the
+ * platform cannot lock pages.
+ */
+template<typename PAL = snmalloc::Pal>
+static bool lock_pages(void* p, size_t size)
+{
+  if constexpr (pal_supports<PageLocking, PAL>)
+    return PAL::lock_pages(p, size);
+  UNUSED(p);
+  UNUSED(size);
+  return false;
+}
+
+template<typename PAL = snmalloc::Pal>
+static void unlock_pages(void* p, size_t size)
+{
+  if constexpr (pal_supports<PageLocking, PAL>)
+    PAL::unlock_pages(p, size);
+  UNUSED(p);
+  UNUSED(size);
+}
+
+/**
+ * The alignment and size to allocate for a locked allocation: whole pages,
+ * so that no other allocation shares them and unlocking one does not unlock
+ * its neighbours.
+ */
+static inline std::pair<size_t, size_t>
+locked_layout(size_t alignment, size_t size)
+{
+  return {bits::max(alignment, OS_PAGE_SIZE),
+          bits::align_up(bits::max(size, size_t(1)), OS_PAGE_SIZE)};
+}
+
+/**
+ * Allocate `size` bytes aligned to `alignment`, rounded out to whole pages
+ * and locked into physical memory, for key material and I/O buffers that
+ * must not be paged out.  Returns null if the memory cannot be obtained, if
+ * the platform cannot lock pages, or if locking fails, as it does when the
+ * process's limit on locked memory would be exceeded.
+ */
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(alloc_locked)(size_t alignment, size_t size)
+{
+  if (!pal_supports<PageLocking, Pal>)
+    return nullptr;
+  auto [a, s] = locked_layout(alignment, size);
+  void* p = alloc_with(ThreadAlloc::get_noncachable(), a, s);
+  if ((p != nullptr) && !lock_pages(p, s))
+  {
+    dealloc_with(ThreadAlloc::get_noncachable(), p, a, s);
+    return nullptr;
+  }
+  return p;
+}
+
+/**
+ * Free memory from `alloc_locked`, given the same alignment and size.  The
+ * memory is zeroed before it is unlocked, so its contents never reach swap.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(dealloc_locked)(
+  void* ptr, size_t alignment, size_t size)
+{
+  auto [a, s] = locked_layout(alignment, size);
+  check_bounds(ptr, s);
+  std::memset(ptr, 0, s);
+  unlock_pages(ptr, s);
+  dealloc_with(ThreadAlloc::get_noncachable(), ptr, a, s);
+}
+
+/**
+ * The smallest threshold accepted by `set_page_move_threshold`.  Each move
+ * splits the mappings around both allocations, so it only pays for blocks
+ * big enough that copying them costs far more.
+ */
+static constexpr size_t MIN_PAGE_MOVE_THRESHOLD = bits::one_at_bit(22);
+
+/**
+ * The smallest number of bytes that `move_large` moves rather than leaving
+ * to be copied, or zero, the default, to never move pages.
+ */
+static std::atomic<size_t> page_move_threshold{0};
+
+/**
+ * Move the first `size` bytes of the allocation at `from` to the allocation
+ * at `to` by remapping their pages instead of copying them, if this has been
+ * enabled with `set_page_move_threshold` for `size`, the platform can, and
+ * both are large allocations, which start on a chunk boundary.  Copying is
+ * left to the caller if this returns false.  The bytes after `size` up to
+ * the end of its last page are moved too.
+ */
+template<typename PAL = snmalloc::Pal>
+static bool move_large(void* to, void* from, size_t size)
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  if constexpr (pal_supports<PageMove, PAL>)
+  {
+    size_t threshold = page_move_threshold.load(std::memory_order_relaxed);
+    if (
+      (threshold != 0) && (size >= threshold) &&
+      (size > sizeclass_to_size(NUM_SIZECLASSES - 1)))
+      return PAL::move_pages(
+        from, to, bits::align_up(size, OS_PAGE_SIZE));
+  }
+#endif
+  UNUSED(to);
+  UNUSED(from);
+  UNUSED(size);
+  return false;
+}
+
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(realloc)(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  size_t aligned_old_size = request_size(alignment, old_size),
+         aligned_new_size = request_size(alignment, new_size);
   if (
     size_to_sizeclass(aligned_old_size) == size_to_sizeclass(aligned_new_size))
+  {
+    SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, new_size, alignment);
     return ptr;
+  }
+  if (SNMALLOC_INJECT_FAILURE(new_size))
+    return out_of_memory(alignment, new_size);
   void* p = ThreadAlloc::get_noncachable()->alloc(aligned_new_size);
-  if (p)
+  if (unlikely(p == nullptr))
+    return out_of_memory(alignment, new_size);
+  size_t kept = old_size < new_size ? old_size : new_size;
+  check_bounds(ptr, kept);
+  if (!move_large(p, ptr, kept))
+    std::memcpy(p, ptr, kept);
+  SNMALLOC_TRACE_RECORD(Realloc, p, ptr, new_size, alignment);
+  SNMALLOC_COUNT_ALLOC(p);
+  SNMALLOC_COUNT_DEALLOC();
+  SNMALLOC_PROFILE_ALLOC(p, new_size);
+  SNMALLOC_DHAT_ALLOC(p, new_size);
+  SNMALLOC_HOOK_ALLOC(p, new_size);
+  SNMALLOC_PROFILE_DEALLOC(ptr);
+  SNMALLOC_DHAT_DEALLOC(ptr);
+  SNMALLOC_HOOK_DEALLOC(ptr, old_size);
+  ThreadAlloc::get_noncachable()->dealloc(ptr, aligned_old_size);
+  return p;
+}
+
+/**
+ * As `realloc`, but the bytes from `old_size` to `new_size` are zeroed.  If
+ * the sizeclass is unchanged the block is resized in place and only that
+ * tail is cleared; otherwise the new block is allocated pre-zeroed, so only
+ * the old contents need to be copied.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(realloc_zeroed)(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+  size_t aligned_old_size = request_size(alignment, old_size),
+         aligned_new_size = request_size(alignment, new_size);
+  if (
+    size_to_sizeclass(aligned_old_size) == size_to_sizeclass(aligned_new_size))
   {
-    std::memcpy(p, ptr, old_size < new_size ? old_size : new_size);
-    ThreadAlloc::get_noncachable()->dealloc(ptr, aligned_old_size);
+    if (new_size > old_size)
+    {
+      check_bounds(ptr, new_size);
+      std::memset(static_cast<char*>(ptr) + old_size, 0, new_size - old_size);
+    }
+    SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, new_size, alignment);
+    return ptr;
   }
+  if (SNMALLOC_INJECT_FAILURE(new_size))
+    return out_of_memory(alignment, new_size);
+  void* p = ThreadAlloc::get_noncachable()->alloc<YesZero>(aligned_new_size);
+  if (unlikely(p == nullptr))
+    return out_of_memory(alignment, new_size);
+  // Moving pages also moves the stale bytes after `old_size` in the last
+  // one, which must be cleared.
+  size_t kept = old_size < new_size ? old_size : new_size;
+  check_bounds(ptr, kept);
+  if (!move_large(p, ptr, kept))
+    std::memcpy(p, ptr, kept);
+  else if (new_size > old_size)
+    std::memset(
+      static_cast<char*>(p) + old_size,
+      0,
+      bits::align_up(old_size, OS_PAGE_SIZE) - old_size);
+  SNMALLOC_TRACE_RECORD(Realloc, p, ptr, new_size, alignment);
+  SNMALLOC_COUNT_ALLOC(p);
+  SNMALLOC_COUNT_DEALLOC();
+  SNMALLOC_PROFILE_ALLOC(p, new_size);
+  SNMALLOC_DHAT_ALLOC(p, new_size);
+  SNMALLOC_HOOK_ALLOC(p, new_size);
+  SNMALLOC_PROFILE_DEALLOC(ptr);
+  SNMALLOC_DHAT_DEALLOC(ptr);
+  SNMALLOC_HOOK_DEALLOC(ptr, old_size);
+  ThreadAlloc::get_noncachable()->dealloc(ptr, aligned_old_size);
   return p;
 }
+
+/**
+ * Resize an allocation without moving it, which is possible if the new size
+ * rounds up to the same sizeclass or, for large allocations, the same chunk.
+ * Returns false, leaving the allocation unchanged, if it would have to move.
+ * On success, the allocation must subsequently be freed with `new_size`.
+ * With `SNMALLOC_PASS_THROUGH`, only shrinking succeeds.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(resize_in_place)(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  // The underlying allocator can only shrink in place, by the caller using
+  // less of the allocation.
+  UNUSED(alignment);
+  if (new_size > old_size)
+    return false;
+#else
+  if (
+    round_size(request_size(alignment, old_size)) !=
+    round_size(request_size(alignment, new_size)))
+    return false;
+#endif
+  UNUSED(ptr);
+  SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, new_size, alignment);
+  return true;
+}
+
+/**
+ * Shrink an allocation to `new_size` bytes, which must be no more than
+ * `old_size`.  Large allocations shrink in place and return the memory past
+ * the new size to the platform, so that long-lived buffers that shrink
+ * release memory; others are resized as by `realloc`.  The allocation must
+ * subsequently be freed with `new_size`.  A large allocation that shrinks is
+ * reported to the profilers and hooks as freed at its old size and allocated
+ * again at its new one.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(shrink)(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size)
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  size_t aligned_new_size = request_size(alignment, new_size);
+  if (aligned_new_size > sizeclass_to_size(NUM_SIZECLASSES - 1))
+  {
+    SNMALLOC_PROFILE_DEALLOC(ptr);
+    SNMALLOC_DHAT_DEALLOC(ptr);
+    SNMALLOC_HOOK_DEALLOC(ptr, old_size);
+    ThreadAlloc::get_noncachable()->large_shrink(
+      ptr, request_size(alignment, old_size), aligned_new_size);
+    SNMALLOC_TRACE_RECORD(Realloc, ptr, ptr, new_size, alignment);
+    SNMALLOC_PROFILE_ALLOC(ptr, new_size);
+    SNMALLOC_DHAT_ALLOC(ptr, new_size);
+    SNMALLOC_HOOK_ALLOC(ptr, new_size);
+    return ptr;
+  }
+#endif
+  return SNMALLOC_RUST_NAME(realloc)(ptr, alignment, old_size, new_size);
+}
+
+/**
+ * Return the start of the allocation containing `ptr`, which may point
+ * anywhere inside it, or null if snmalloc did not allocate the memory.  The
+ * result is only meaningful while the allocation is live.
+ */
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(allocation_start)(const void* ptr)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  UNUSED(ptr);
+  return nullptr;
+#else
+  return ThreadAlloc::get_noncachable()->external_pointer<Start>(
+    const_cast<void*>(ptr));
+#endif
+}
+
+/**
+ * Return true if `ptr` points into memory that snmalloc is using, so that a
+ * program mixing allocators can tell whether to free it with `dealloc`.
+ * This only checks the pagemap, not that the allocation is live.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(owns)(const void* ptr)
+{
+  return ThreadAlloc::get_noncachable()->owns(ptr);
+}
+
+/**
+ * As `allocation_start`, but also set `end` to one past the end of the
+ * allocation's usable space.  Returns false, leaving `start` and `end`
+ * unchanged, if snmalloc did not allocate the memory.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(allocation_bounds)(
+  const void* ptr, void** start, void** end)
+{
+  void* s = SNMALLOC_RUST_NAME(allocation_start)(ptr);
+  if (s == nullptr)
+    return false;
+  *start = s;
+  *end = ThreadAlloc::get_noncachable()->external_pointer<OnePastEnd>(
+    const_cast<void*>(ptr));
+  return true;
+}
+
+/**
+ * As `memcpy`, but reports a fatal error instead of copying if `dst` or `src`
+ * points into memory from snmalloc and `len` bytes from it would run past the
+ * end of its allocation.  Pointers to other memory, such as the stack, are
+ * not checked.  Nothing is checked with `SNMALLOC_PASS_THROUGH`.
+ */
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(memcpy_checked)(void* dst, const void* src, size_t len)
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  auto a = ThreadAlloc::get_noncachable();
+  if (len > pointer_diff(dst, a->external_pointer<OnePastEnd>(dst)))
+    error("memcpy destination out of bounds of heap allocation");
+  void* s = const_cast<void*>(src);
+  if (len > pointer_diff(s, a->external_pointer<OnePastEnd>(s)))
+    error("memcpy source out of bounds of heap allocation");
+#endif
+  return memcpy(dst, src, len);
+}
+
+/**
+ * Initialise the allocator and the calling thread's allocator ahead of the
+ * first allocation.  With `SNMALLOC_INIT_BEFORE_MAIN`, this is done for the
+ * main thread before `main` and before most static constructors.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(init)()
+{
+  SNMALLOC_NAME_MANGLE(snmalloc_init)();
+}
+
+/**
+ * Flush the calling thread's allocator and return it to the pool, so that
+ * runtimes managing their own threads, or plugins about to be unloaded, can
+ * release a thread's allocator state deterministically.  The thread may
+ * allocate again afterwards, which calls `init` implicitly.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(thread_teardown)()
+{
+  SNMALLOC_NAME_MANGLE(snmalloc_thread_teardown)();
+}
+
+/**
+ * Return the index of the sizeclass used for a request of `size` bytes (with
+ * no more than the minimum alignment), and set `capacity` to the usable size
+ * of such an allocation.  Small and medium classes are numbered first, in
+ * increasing order of size, followed by the large classes, one per power of
+ * two.  Sizes too large to allocate return the number of classes and a zero
+ * capacity.
+ *
+ * This is the reference for reimplementations of the rounding, such as a
+ * compile-time function in the Rust crate, to test against.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(sizeclass_of)(size_t size, size_t* capacity)
+{
+  if (size <= sizeclass_to_size(NUM_SIZECLASSES - 1))
+  {
+    sizeclass_t sc = size_to_sizeclass(size == 0 ? 1 : size);
+    *capacity = sizeclass_to_size(sc);
+    return sc;
+  }
+
+  size_t size_bits = bits::next_pow2_bits(size);
+  if (size_bits >= bits::ADDRESS_BITS)
+  {
+    *capacity = 0;
+    return NUM_SIZECLASSES + NUM_LARGE_CLASSES;
+  }
+  *capacity = bits::one_at_bit(size_bits);
+  return NUM_SIZECLASSES + size_bits - SUPERSLAB_BITS;
+}
+
+/**
+ * Return the index of the sizeclass of the allocation containing `ptr`, which
+ * may point anywhere inside it, numbered as by `sizeclass_of`, and set
+ * `capacity` to its usable size.  Memory not allocated by snmalloc returns
+ * the number of classes and a zero capacity.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(sizeclass_of_ptr)(const void* ptr, size_t* capacity)
+{
+  void* start;
+  void* end;
+  if (!SNMALLOC_RUST_NAME(allocation_bounds)(ptr, &start, &end))
+  {
+    *capacity = 0;
+    return NUM_SIZECLASSES + NUM_LARGE_CLASSES;
+  }
+  return SNMALLOC_RUST_NAME(sizeclass_of)(pointer_diff(start, end), capacity);
+}
+
+struct RustSizeclassInfo
+{
+  size_t object_size;
+  size_t slab_size;
+  size_t objects_per_slab;
+};
+
+/**
+ * Fill `info` with up to `count` entries of the sizeclass table, indexed as
+ * by `sizeclass_of`, and return the total number of classes.  Each entry
+ * gives the usable size of the class, the size of the slabs that hold its
+ * objects, and how many objects fit in a slab.  A large class has a slab per
+ * object; the first slab in each superslab of small objects holds fewer, as
+ * it also holds the superslab's header.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(sizeclass_table)(RustSizeclassInfo* info, size_t count)
+{
+  constexpr size_t total = NUM_SIZECLASSES + NUM_LARGE_CLASSES;
+  for (size_t i = 0; i < total && i < count; i++)
+  {
+    if (i < NUM_SIZECLASSES)
+    {
+      auto sc = static_cast<sizeclass_t>(i);
+      info[i] = {
+        sizeclass_to_size(sc),
+        (i < NUM_SMALL_CLASSES) ? SLAB_SIZE : SUPERSLAB_SIZE,
+        sizeclass_to_slab_capacity(sc)};
+    }
+    else
+    {
+      size_t size = bits::one_at_bit(SUPERSLAB_BITS + i - NUM_SIZECLASSES);
+      info[i] = {size, size, 1};
+    }
+  }
+  return total;
+}
+
+/**
+ * Return the usable size of an allocation of `size` bytes aligned to
+ * `alignment`.  All of it may be used, and any size from `size` up to it may
+ * be passed back to `dealloc` and `realloc` for the allocation, so an
+ * implementation of Rust's `Allocator` trait can return it from `allocate`.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(usable_size)(size_t alignment, size_t size)
+{
+#ifdef SNMALLOC_PASS_THROUGH
+  // The underlying allocator does not promise to round the size up.
+  UNUSED(alignment);
+  return size;
+#else
+  return round_size(request_size(alignment, size));
+#endif
+}
+
+/**
+ * As `alloc`, but also set `usable` to the usable size of the allocation, or
+ * to zero if it fails, saving a call to `usable_size`.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(alloc_excess)(
+  size_t alignment, size_t size, size_t* usable)
+{
+  void* p = SNMALLOC_RUST_NAME(alloc)(alignment, size);
+  *usable =
+    (p == nullptr) ? 0 : SNMALLOC_RUST_NAME(usable_size)(alignment, size);
+  return p;
+}
+
+/**
+ * Every allocation is aligned to at least this, so a global allocator can
+ * treat smaller alignments as no constraint at all.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(min_alignment)()
+{
+  return MIN_ALIGNMENT;
+}
+
+/**
+ * The size of the chunks that the allocator takes from the platform, which
+ * is chosen when the shim is built.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(chunk_size)()
+{
+  return SUPERSLAB_SIZE;
+}
+
+/**
+ * Size class request used for I/O buffers of `len` bytes: whole pages, page
+ * aligned, so that a buffer shares no page with any other allocation.
+ */
+static inline size_t io_buffer_size(size_t len)
+{
+  return aligned_size(OS_PAGE_SIZE, bits::align_up(len, OS_PAGE_SIZE));
+}
+
+/**
+ * Allocate a buffer suitable for pinning, for example with io_uring's
+ * `IORING_REGISTER_BUFFERS`.  The buffer is page aligned and no other
+ * allocation shares its pages, so pinning it cannot pin unrelated memory and
+ * copy-on-write after `fork` cannot separate it from the kernel's view.  It
+ * must be freed with `io_buffer_dealloc` and the same `len`, after it has been
+ * unregistered.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(io_buffer_alloc)(size_t len)
+{
+  if (SNMALLOC_INJECT_FAILURE(len))
+    return out_of_memory(OS_PAGE_SIZE, len);
+  void* p = ThreadAlloc::get_noncachable()->alloc(io_buffer_size(len));
+  if (unlikely(p == nullptr))
+    return out_of_memory(OS_PAGE_SIZE, len);
+  SNMALLOC_COUNT_ALLOC(p);
+  SNMALLOC_PROFILE_ALLOC(p, len);
+  SNMALLOC_DHAT_ALLOC(p, len);
+  SNMALLOC_HOOK_ALLOC(p, len);
+  return p;
+}
+
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(io_buffer_dealloc)(void* ptr, size_t len)
+{
+  SNMALLOC_COUNT_DEALLOC();
+  SNMALLOC_PROFILE_DEALLOC(ptr);
+  SNMALLOC_DHAT_DEALLOC(ptr);
+  SNMALLOC_HOOK_DEALLOC(ptr, len);
+  ThreadAlloc::get_noncachable()->dealloc(ptr, io_buffer_size(len));
+}
+
+/**
+ * Callbacks that pin memory from `pinned_alloc`, for example with
+ * `cudaHostRegister`, and unpin it again before it is freed.  Each is passed
+ * the buffer rounded out to whole pages.  `register_memory` returns false if
+ * the memory could not be pinned, in which case `pinned_alloc` fails.
+ */
+struct RustPinHooks
+{
+  bool (*register_memory)(void* ptr, size_t len, void* context);
+  void (*unregister_memory)(void* ptr, size_t len, void* context);
+  void* context;
+};
+
+static std::atomic<const RustPinHooks*> pin_hooks{nullptr};
+
+/**
+ * Install `hooks`, which must remain valid while installed, or remove them if
+ * `hooks` is null.  This should be done before the first pinned allocation:
+ * a buffer is unpinned with the hooks installed when it is freed.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_pin_hooks)(const RustPinHooks* hooks)
+{
+  pin_hooks.store(hooks, std::memory_order_release);
+}
+
+/**
+ * Allocate an I/O buffer and pin it with the installed hooks, if any.
+ * Returns null if allocation or pinning fails.  Free with `pinned_dealloc`.
+ */
+extern "C" SNMALLOC_EXPORT void* SNMALLOC_RUST_NAME(pinned_alloc)(size_t len)
+{
+  void* p = SNMALLOC_RUST_NAME(io_buffer_alloc)(len);
+  auto hooks = pin_hooks.load(std::memory_order_acquire);
+  if ((p != nullptr) && (hooks != nullptr))
+  {
+    size_t pinned_len = bits::align_up(len, OS_PAGE_SIZE);
+    if (!hooks->register_memory(p, pinned_len, hooks->context))
+    {
+      SNMALLOC_RUST_NAME(io_buffer_dealloc)(p, len);
+      return nullptr;
+    }
+  }
+  return p;
+}
+
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(pinned_dealloc)(void* ptr, size_t len)
+{
+  auto hooks = pin_hooks.load(std::memory_order_acquire);
+  if (hooks != nullptr)
+  {
+    size_t pinned_len = bits::align_up(len, OS_PAGE_SIZE);
+    hooks->unregister_memory(ptr, pinned_len, hooks->context);
+  }
+  SNMALLOC_RUST_NAME(io_buffer_dealloc)(ptr, len);
+}
+
+/**
+ * The granularity of `commit` and `decommit`.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(page_size)()
+{
+  return OS_PAGE_SIZE;
+}
+
+/**
+ * Address space reserved for a reservation of `size` bytes aligned to
+ * `alignment`: whole pages, and at least the alignment, as reservations are
+ * aligned to the next power of two at or above their size.  Returns zero if
+ * that is larger than the largest allocation.
+ */
+static inline size_t reservation_size(size_t alignment, size_t size)
+{
+  constexpr size_t max_size =
+    bits::one_at_bit(SUPERSLAB_BITS + NUM_LARGE_CLASSES - 1);
+  if ((size > max_size) || (alignment > max_size))
+    return 0;
+  return bits::max(
+    bits::align_up(bits::max(size, size_t(1)), OS_PAGE_SIZE), alignment);
+}
+
+/**
+ * Reserve `size` bytes of address space aligned to `alignment`, without
+ * committing any of it, for large structures such as ring buffers and sparse
+ * arrays whose pages are committed as they are needed.  Pages must be
+ * committed with `commit` before they are used, and read as zero the first
+ * time.  The reservation is not an allocation: it must be returned with
+ * `unreserve` and the same `alignment` and `size`.  Returns null if the
+ * address space is exhausted.
+ */
+extern "C" SNMALLOC_EXPORT void*
+SNMALLOC_RUST_NAME(reserve)(size_t alignment, size_t size)
+{
+  size_t rsize = reservation_size(alignment, size);
+  void* p = (rsize == 0) ? nullptr : reserve_address_space(rsize);
+  if (p == nullptr)
+    return out_of_memory(alignment, size);
+  return p;
+}
+
+/**
+ * Commit the `len` bytes at `ptr`, within a reservation, so that they can be
+ * used.  Pages that were decommitted have unspecified contents.  Returns
+ * false, doing nothing, if the range is not page aligned.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(commit)(void* ptr, size_t len)
+{
+  if (!is_aligned_block<OS_PAGE_SIZE>(ptr, len))
+    return false;
+  Pal::notify_using<NoZero>(ptr, len);
+  return true;
+}
+
+/**
+ * Decommit the `len` bytes at `ptr`, within a reservation, which must not be
+ * used again until they are committed.  Whether and when the platform
+ * reclaims the pages depends on it and, on Linux, on
+ * `set_decommit_advice`.  Returns false, doing nothing, if the range is not
+ * page aligned.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(decommit)(void* ptr, size_t len)
+{
+  if (!is_aligned_block<OS_PAGE_SIZE>(ptr, len))
+    return false;
+  Pal::notify_not_using(ptr, len);
+  return true;
+}
+
+/**
+ * Return a reservation, committed or not, to the allocator, which may reuse
+ * its address space.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(unreserve)(void* ptr, size_t alignment, size_t size)
+{
+  unreserve_address_space(ptr, reservation_size(alignment, size));
+}
+
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(current_allocator_id)()
+{
+  return ThreadAlloc::get()->get_trunc_id();
+}
+
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(allocator_id_of)(const void* ptr)
+{
+  return ThreadAlloc::get_noncachable()->get_owner_trunc_id(ptr);
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(remote_queue_depth)(
+  size_t* current_depth, size_t* peak_depth)
+{
+  auto depth = ThreadAlloc::get()->remote_queue_depth();
+  *current_depth = depth.first;
+  *peak_depth = depth.second;
+}
+
+struct RustRemoteQueueInfo
+{
+  size_t allocator_id;
+  size_t current_depth;
+  size_t peak_depth;
+};
+
+/**
+ * Fill `info` with the message queue depths of up to `count` allocators and
+ * return the total number of allocators, which may be greater than `count`.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(remote_queue_info)(RustRemoteQueueInfo* info, size_t count)
+{
+  size_t n = 0;
+  current_alloc_pool()->for_each_allocator([&](Alloc* a) {
+    if (n < count)
+    {
+      auto depth = a->remote_queue_depth();
+      info[n] = {a->get_trunc_id(), depth.first, depth.second};
+    }
+    n++;
+  });
+  return n;
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(set_remote_queue_alarm)(
+  size_t threshold, RemoteQueueAlarm alarm)
+{
+  set_remote_queue_alarm(threshold, alarm);
+}
+
+/**
+ * Install `handler` to be called with the message of any fatal error, before
+ * the process is aborted, so that it can be logged; see `ErrorHandler` in
+ * `pal.h`.  Null removes it.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_error_handler)(ErrorHandler handler)
+{
+  set_error_handler(handler);
+}
+
+/**
+ * Install `handler` to be called with the size and alignment of each failed
+ * allocation, before null is returned for it; see `OomHandler`.  Null removes
+ * it.  The handler may allocate, but an allocation that fails inside it calls
+ * it again.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_oom_handler)(OomHandler handler)
+{
+  oom_handler.store(handler, std::memory_order_relaxed);
+}
+
+/**
+ * Install `handler` to be called when a client check, such as the check for
+ * an invalid or double free, fails, before the process is aborted; see
+ * `ClientCheckReport` in `allocconfig.h`.  Null removes it.  Checks are only
+ * made in builds with `CHECK_CLIENT`.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_check_failure_handler)(ClientCheckReport handler)
+{
+  set_client_check_report(handler);
+}
+
+/**
+ * Set when freed chunks are returned to the OS: 0 never, 1 as soon as they
+ * are freed, or 2 only for chunks larger than one superslab, keeping the
+ * others until `release_free_memory` or a low-memory notification.  These
+ * are the `DecommitStrategy` values in `allocconfig.h`.  This replaces any
+ * limits set with `set_large_retention`.  Returns false if `strategy` is out
+ * of range.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_decommit_strategy)(size_t strategy)
+{
+  if (strategy > DecommitSuperLazy)
+    return false;
+  set_decommit_strategy(static_cast<DecommitStrategy>(strategy));
+  return true;
+}
+
+/**
+ * Set how the pages of decommitted chunks are returned to the OS: 0 not at
+ * all, which is the default, 1 when the OS is short of memory
+ * (`MADV_FREE`), or 2 immediately (`MADV_DONTNEED`).  Returns false if
+ * `advice` is out of range or the platform, which is currently only Linux,
+ * does not offer the choice.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_decommit_advice)(size_t advice)
+{
+  if (advice > AdviseDontNeed)
+    return false;
+  return set_decommit_advice(static_cast<DecommitAdvice>(advice));
+}
+
+/**
+ * Make `realloc` and `realloc_zeroed` move the pages of a large allocation
+ * of at least `bytes` bytes, when they have to move it, instead of copying
+ * them, or with zero, the default, always copy.  Every move leaves more
+ * mappings behind, and a process can only have so many (`vm.max_map_count`
+ * on Linux), so a nonzero `bytes` below 4 MiB is rejected.  Returns false if
+ * `bytes` is rejected or the platform, which is currently only Linux, cannot
+ * move pages.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_page_move_threshold)(size_t bytes)
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  if constexpr (pal_supports<PageMove, snmalloc::Pal>)
+  {
+    if ((bytes != 0) && (bytes < MIN_PAGE_MOVE_THRESHOLD))
+      return false;
+    page_move_threshold.store(bytes, std::memory_order_relaxed);
+    return true;
+  }
+#endif
+  UNUSED(bytes);
+  return false;
+}
+
+/**
+ * Set what is printed on a fatal error before the process is aborted: 0
+ * nothing, 1 the message, or 2 the message and a stack trace, which is the
+ * default.  Returns false if `verbosity` is out of range.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_error_verbosity)(size_t verbosity)
+{
+  if (verbosity > ErrorBacktrace)
+    return false;
+  set_error_verbosity(static_cast<ErrorVerbosity>(verbosity));
+  return true;
+}
+
+/**
+ * Write a heap dump, as `heap_dump` does, to the file at `path` when the
+ * process exits, or stop doing so if `path` is null or empty.  Returns false
+ * if `path` is too long.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_stats_file)(const char* path)
+{
+  return runtime_config::set_stats_file(path);
+}
+
+/**
+ * Apply the settings in the `SNMALLOC_*` environment variables; see
+ * `runtime-config.h`.  This is done before `main`, so is only needed if the
+ * environment is changed afterwards.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(configure_from_env)()
+{
+  runtime_config::apply();
+}
+
+/**
+ * Report the memory limits, `memory.max` and `memory.high`, of the cgroup
+ * that the process is in, or zero for those it does not have; see
+ * `cgroup.h`.  Either pointer may be null.  Returns false if there are no
+ * limits, as when not in a cgroup2 hierarchy or not on Linux.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(cgroup_limits)(size_t* max, size_t* high)
+{
+  cgroup::Limits limits = cgroup::current();
+  if (max != nullptr)
+    *max = limits.max;
+  if (high != nullptr)
+    *high = limits.high;
+  return (limits.max != 0) || (limits.high != 0);
+}
+
+// The copies in `rust-select.cc` are configured when one is selected.
+#ifndef SNMALLOC_RUST_SELECT_COPY
+SNMALLOC_BEFORE_MAIN(
+  SNMALLOC_RUST_NAME(configure_before_main),
+  SNMALLOC_RUST_NAME(configure_from_env))
+#endif
+
+struct RustSlabOccupancy
+{
+  size_t object_size;
+  size_t full;
+  size_t partial;
+};
+
+/**
+ * Fill `info` with the slab occupancy of up to `count` sizeclasses of the
+ * current thread's allocator, in increasing order of object size, and return
+ * the total number of sizeclasses.  If `unused_slabs` is not null, it is set
+ * to the number of empty slabs the allocator retains in its superslabs.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(slab_occupancy)(
+  RustSlabOccupancy* info, size_t count, size_t* unused_slabs)
+{
+  auto a = ThreadAlloc::get();
+  for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES && sc < count; sc++)
+  {
+    auto occupancy = a->slab_occupancy(sc);
+    info[sc] = {sizeclass_to_size(sc), occupancy.full, occupancy.partial};
+  }
+  if (unused_slabs != nullptr)
+    *unused_slabs = a->unused_slabs();
+  return NUM_SIZECLASSES;
+}
+
+struct RustLargeCacheInfo
+{
+  size_t chunk_size;
+  size_t retained_committed;
+  size_t retention_limit;
+  size_t hits;
+  size_t misses;
+};
+
+/**
+ * Fill `info` with statistics for the caches of freed chunks of up to `count`
+ * large classes, in increasing order of chunk size, and return the total
+ * number of large classes.
+ */
+extern "C" SNMALLOC_EXPORT size_t
+SNMALLOC_RUST_NAME(large_cache_info)(RustLargeCacheInfo* info, size_t count)
+{
+  for (size_t lc = 0; lc < NUM_LARGE_CLASSES && lc < count; lc++)
+  {
+    auto stats = large_cache_stats(lc);
+    info[lc] = {bits::one_at_bit(SUPERSLAB_BITS) << lc,
+                stats.retained_committed,
+                stats.retention_limit,
+                stats.hits,
+                stats.misses};
+  }
+  return NUM_LARGE_CLASSES;
+}
+
+/**
+ * Set how many freed chunks of `large_class` (an index into the results of
+ * `large_cache_info`) are kept committed for reuse rather than returned to
+ * the OS.  Returns false if `large_class` is out of range.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_large_retention)(size_t large_class, size_t count)
+{
+  if (large_class >= NUM_LARGE_CLASSES)
+    return false;
+  set_large_retention(large_class, count);
+  return true;
+}
+
+/**
+ * Make allocations fail once the memory that the allocator has taken for its
+ * heap, the `peak` in `RustStats`, would exceed `bytes`, or remove the limit
+ * if `bytes` is zero; see `set_memory_limit` in `globalalloc.h`.  Failed
+ * allocations return null, after calling the handler from `set_oom_handler`.
+ * Returns false if built with `SNMALLOC_PASS_THROUGH`, which cannot enforce
+ * a limit.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(set_memory_limit)(size_t bytes)
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  set_memory_limit(bytes);
+  return true;
+#else
+  UNUSED(bytes);
+  return false;
+#endif
+}
+
+/**
+ * Install `handler` to be called with the size of the chunk needed and the
+ * limit when an allocation would exceed the memory limit, or remove it if
+ * null.  It may raise the limit with `set_memory_limit` and return true to
+ * try again, or return false to fail the allocation.  It must not allocate.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_memory_limit_handler)(MemoryLimitHandler handler)
+{
+  set_memory_limit_handler(handler);
+}
+
+/**
+ * Give the allocator `len` bytes at `base` to allocate from before it maps
+ * memory of its own, for applications that map memory themselves, such as
+ * from hugetlbfs or a memfd; see `donate_range` in `globalalloc.h`.  The
+ * memory must be readable and writable, and must never be unmapped or used
+ * for anything else.  Returns false if it was not accepted.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(donate_range)(void* base, size_t len)
+{
+  return donate_range(base, len);
+}
+
+/**
+ * Report the address space reserved, the memory committed, and the bytes in
+ * live objects.  The last is only precise if built with USE_SNMALLOC_STATS.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(memory_breakdown)(
+  size_t* reserved, size_t* committed, size_t* live)
+{
+  auto breakdown = memory_breakdown();
+  *reserved = breakdown.reserved;
+  *committed = breakdown.committed;
+  *live = breakdown.live;
+}
+
+/**
+ * Report the total memory returned to the OS so far, and the number of
+ * operations that returned it.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(memory_released)(size_t* bytes, size_t* count)
+{
+  auto breakdown = memory_breakdown();
+  *bytes = breakdown.released;
+  *count = breakdown.releases;
+}
+
+/**
+ * Report the bytes of the heap backed by huge pages, transparent or explicit.
+ * This is zero on platforms that cannot tell.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(huge_page_bytes)()
+{
+  return huge_page_bytes();
+}
+
+/**
+ * Return as much free memory to the OS as possible, like `malloc_trim(0)`;
+ * see `release_free_memory` in `threadalloc.h`.  Returns the bytes released.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(release_free_memory)()
+{
+  return release_free_memory();
+}
+
+/**
+ * Return free memory to the OS, as `release_free_memory` does, whenever the
+ * OS reports that it is short of memory: on Linux with PSI, from a
+ * background thread, and on macOS from a dispatch source; on Windows this is
+ * always done.  See `memory-pressure.h`.  Only the first call has any
+ * effect.  Returns false if the platform cannot report memory pressure.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(start_memory_pressure_listener)()
+{
+  return memory_pressure::start();
+}
+
+/**
+ * Process the frees waiting for the calling thread's allocator and post the
+ * ones it holds for other threads; see `flush_remote` in `threadalloc.h`.
+ * Returns the number of frees processed.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(flush)()
+{
+  return flush_remote();
+}
+
+/**
+ * Returns true if every allocation made by any thread has been freed, for
+ * test harnesses that check for leaks; see `debug_check_empty` in
+ * `globalalloc.h`.  Frees pending in other threads' caches are delivered
+ * first.  No other thread may be allocating or freeing during the call.
+ * This always returns true if built with `SNMALLOC_PASS_THROUGH`.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(debug_check_empty)()
+{
+  bool result = true;
+  current_alloc_pool()->debug_check_empty(&result);
+  return result;
+}
+
+struct RustStats
+{
+  uint64_t epoch;
+  size_t reserved;
+  size_t committed;
+  size_t live;
+  size_t peak;
+  size_t released;
+  size_t allocators;
+  size_t remote_queue_depth;
+  size_t allocations;
+  size_t deallocations;
+  size_t bytes_allocated;
+  size_t bytes_freed;
+};
+
+struct RustSizeclassStats
+{
+  size_t object_size;
+  size_t live;
+  size_t total;
+  size_t slabs;
+  size_t free;
+};
+
+namespace
+{
+  /**
+   * The statistics gathered by the last `stats_refresh`, which `stats_read`
+   * returns, so that a series of reads is consistent.
+   */
+  std::atomic_flag stats_lock = ATOMIC_FLAG_INIT;
+  uint64_t stats_epoch = 0;
+  StatsSnapshot stats_cached{};
+}
+
+/**
+ * Gather the statistics returned by `stats_read` and return the new epoch.
+ * As with jemalloc's `epoch`, the statistics only change when refreshed.
+ */
+extern "C" SNMALLOC_EXPORT uint64_t SNMALLOC_RUST_NAME(stats_refresh)()
+{
+  StatsSnapshot snapshot;
+  stats_snapshot(snapshot);
+
+  FlagLock f(stats_lock);
+  stats_cached = snapshot;
+  return ++stats_epoch;
+}
+
+/**
+ * Fill `stats` with the statistics from the last refresh, and `sizeclasses`
+ * with those of up to `count` sizeclasses in increasing order of object size,
+ * and return the total number of sizeclasses.  Everything is zero before the
+ * first refresh.  The sizeclass object counts are only tracked if built with
+ * `USE_SNMALLOC_STATS`; see `SizeclassStats`.
+ */
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(stats_read)(
+  RustStats* stats, RustSizeclassStats* sizeclasses, size_t count)
+{
+  FlagLock f(stats_lock);
+  stats->epoch = stats_epoch;
+  stats->reserved = stats_cached.memory.reserved;
+  stats->committed = stats_cached.memory.committed;
+  stats->live = stats_cached.memory.live;
+  stats->peak = stats_cached.peak;
+  stats->released = stats_cached.memory.released;
+  stats->allocators = stats_cached.allocators;
+  stats->remote_queue_depth = stats_cached.remote_queue_depth;
+  stats->allocations = stats_cached.totals.allocations;
+  stats->deallocations = stats_cached.totals.deallocations;
+  stats->bytes_allocated = stats_cached.totals.bytes_allocated;
+  stats->bytes_freed = stats_cached.totals.bytes_freed;
+  for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES && sc < count; sc++)
+  {
+    auto& s = stats_cached.sizeclasses[sc];
+    sizeclasses[sc] = {
+      sizeclass_to_size(sc), s.live, s.total, s.slabs, s.free};
+  }
+  return NUM_SIZECLASSES;
+}
+
+/**
+ * Write a snapshot of the heap as JSON to `write`, a buffer at a time; see
+ * `heapdump.h`.  Returns false if `write` did.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(heap_dump)(heapdump::WriteFn write, void* context)
+{
+  return heapdump::dump(write, context);
+}
+
+struct RustThreadStats
+{
+  size_t bytes_allocated;
+  size_t bytes_freed;
+  size_t remote_frees_sent;
+  size_t remote_frees_received;
+};
+
+/**
+ * Fill `stats` with the counters of the calling thread; see `threadstats.h`.
+ * These are all zero unless built with `SNMALLOC_THREAD_STATS`.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(thread_stats)(RustThreadStats* stats)
+{
+  auto current = ThreadStats::current();
+  stats->bytes_allocated = current.bytes_allocated;
+  stats->bytes_freed = current.bytes_freed;
+  stats->remote_frees_sent = current.remote_frees_sent;
+  stats->remote_frees_received = current.remote_frees_received;
+}
+
+#ifdef SNMALLOC_PROFILING
+/**
+ * Set the mean number of bytes between sampled allocations, or disable
+ * sampling if `rate` is zero; see `profile.h`.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(profile_set_rate)(size_t rate)
+{
+  profile::set_rate(rate);
+}
+
+/**
+ * Write a heap profile of the live sampled allocations to `path`, which
+ * `pprof` can read.  Returns false if the file cannot be written.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(profile_dump)(const char* path)
+{
+  return profile::dump(path);
+}
+
+/**
+ * As `profile_dump`, but in `pprof`'s protocol buffer format, with the
+ * samples scaled up to estimate the whole heap.
+ */
+extern "C" SNMALLOC_EXPORT bool
+SNMALLOC_RUST_NAME(profile_dump_pprof)(const char* path)
+{
+  return profile::dump_pprof(path);
+}
+#endif
+
+#ifdef SNMALLOC_HOOKS
+/**
+ * Install callbacks on each allocation and deallocation; see `hooks.h`.
+ * Either may be null.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(set_alloc_hooks)(
+  void (*on_alloc)(void*, size_t), void (*on_dealloc)(void*, size_t))
+{
+  hooks::set(on_alloc, on_dealloc);
+}
+
+/**
+ * Only call the hooks for blocks of at least `size` bytes, for example to
+ * emit `tracing` events for allocations above a threshold.
+ */
+extern "C" SNMALLOC_EXPORT void
+SNMALLOC_RUST_NAME(set_alloc_hooks_min_size)(size_t size)
+{
+  hooks::set_min_size(size);
+}
+#endif
+
+#ifdef SNMALLOC_DHAT
+/**
+ * Write the allocation totals for each call stack so far as DHAT JSON; see
+ * `dhat.h`.  Returns false if the file cannot be written.
+ */
+extern "C" SNMALLOC_EXPORT bool SNMALLOC_RUST_NAME(dhat_dump)(const char* path)
+{
+  return dhat::dump(path);
+}
+#endif
+
+#ifdef SNMALLOC_FAILURE_INJECTION
+/**
+ * Configure failure injection; see `failure.h`.  A failed allocation returns
+ * null, which Rust reports through `handle_alloc_error` or, for fallible
+ * APIs such as `Vec::try_reserve`, as an error.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_every)(size_t n)
+{
+  failure::injector.fail_every(n);
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_nth)(size_t n)
+{
+  failure::injector.fail_nth(n);
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_random)(
+  size_t per_million, uint64_t seed)
+{
+  failure::injector.fail_random(per_million, seed);
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_above)(size_t size)
+{
+  failure::injector.fail_above(size);
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_scope_enter)()
+{
+  failure::Injector::enter_scope();
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_scope_exit)()
+{
+  failure::Injector::exit_scope();
+}
+
+extern "C" SNMALLOC_EXPORT size_t SNMALLOC_RUST_NAME(failures)()
+{
+  return failure::injector.failures();
+}
+
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(fail_reset)()
+{
+  failure::injector.reset();
+}
+#endif
+
+#ifdef SNMALLOC_COUNT_ALLOCATIONS
+/**
+ * Report the allocations and deallocations made by the current thread so
+ * far; see `counting.h`.  A counting global allocator wrapper can compare
+ * these before and after a closure to check that it did not allocate.
+ */
+extern "C" SNMALLOC_EXPORT void SNMALLOC_RUST_NAME(thread_allocation_counts)(
+  size_t* allocations, size_t* deallocations)
+{
+  *allocations = counting::thread_counts.allocations;
+  *deallocations = counting::thread_counts.deallocations;
+}
+#endif
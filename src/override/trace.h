@@ -0,0 +1,223 @@
+#pragma once
+
+/**
+ * Recording of allocation traces.
+ *
+ * If the shims are built with `SNMALLOC_TRACE` defined and the
+ * `SNMALLOC_TRACE_FILE` environment variable names a file when the first
+ * allocation is made, every operation through the shims is appended to that
+ * file.  Otherwise, `SNMALLOC_TRACE_RECORD` expands to nothing and its
+ * arguments are not evaluated.
+ *
+ * The file starts with the eight bytes `SNTRACE1`, followed by
+ * `trace::Record`s in native byte order.  Records are collected in a single
+ * buffer of `trace::BUFFER_RECORDS` entries, which is written out whenever it
+ * fills, when `trace::flush` is called, and at process exit.  Recording is
+ * serialised by a lock, so it is intended for capturing workloads rather than
+ * for measuring them.
+ *
+ * The format is available without `SNMALLOC_TRACE`, for tools that read
+ * traces, such as the `replay` benchmark.
+ */
+#include <cstddef>
+#include <cstdint>
+
+namespace snmalloc::trace
+{
+  /**
+   * The bytes at the start of every trace file.
+   */
+  static constexpr char HEADER[] = "SNTRACE1";
+  static constexpr size_t HEADER_SIZE = sizeof(HEADER) - 1;
+
+  enum Op : uint8_t
+  {
+    /**
+     * `address` was allocated with `size` bytes.
+     */
+    Alloc = 0,
+    /**
+     * `address` was freed.  `size` is zero if the caller did not give one.
+     */
+    Free = 1,
+    /**
+     * `old_address` was resized to `size` bytes at `address`.
+     */
+    Realloc = 2,
+  };
+
+  struct Record
+  {
+    /**
+     * Nanoseconds since an arbitrary point fixed for the process.
+     */
+    uint64_t timestamp;
+    uint64_t address;
+    uint64_t old_address;
+    uint64_t size;
+    /**
+     * Small integer identifying the calling thread, in order of first use.
+     */
+    uint32_t thread;
+    uint8_t op;
+    /**
+     * Log2 of the requested alignment, or zero if none was requested.
+     */
+    uint8_t alignment_bits;
+    uint16_t reserved;
+  };
+  static_assert(sizeof(Record) == 40, "Trace records must not be padded");
+} // namespace snmalloc::trace
+
+#ifdef SNMALLOC_TRACE
+#  ifdef _WIN32
+#    error SNMALLOC_TRACE is only supported on POSIX platforms
+#  endif
+
+#  include "../ds/flaglock.h"
+
+#  include <atomic>
+#  include <chrono>
+#  include <cstdlib>
+#  include <fcntl.h>
+#  include <unistd.h>
+
+namespace snmalloc::trace
+{
+  static constexpr size_t BUFFER_RECORDS = 4096;
+
+  class Recorder
+  {
+    enum State
+    {
+      Uninitialised = 0,
+      Disabled,
+      Enabled
+    };
+
+    std::atomic<State> state{Uninitialised};
+    std::atomic_flag lock = ATOMIC_FLAG_INIT;
+    int fd = -1;
+    size_t count = 0;
+    std::atomic<uint32_t> next_thread{0};
+    Record buffer[BUFFER_RECORDS] = {};
+
+    static inline thread_local uint32_t thread_id = 0;
+
+    /**
+     * Write out the buffer.  Must be called with the lock held.  Errors are
+     * ignored: a truncated trace is preferable to failing allocations.
+     */
+    void write_buffer()
+    {
+      auto p = reinterpret_cast<const char*>(buffer);
+      size_t len = count * sizeof(Record);
+      while (len > 0)
+      {
+        ssize_t written = ::write(fd, p, len);
+        if (written <= 0)
+          break;
+        p += written;
+        len -= static_cast<size_t>(written);
+      }
+      count = 0;
+    }
+
+    SNMALLOC_SLOW_PATH bool initialise()
+    {
+      FlagLock f(lock);
+      State s = state.load(std::memory_order_relaxed);
+      if (s != Uninitialised)
+        return s == Enabled;
+
+      const char* path = getenv("SNMALLOC_TRACE_FILE");
+      if ((path != nullptr) && (*path != '\0'))
+        fd = ::open(path, O_WRONLY | O_CREAT | O_TRUNC | O_CLOEXEC, 0644);
+      if (
+        (fd < 0) ||
+        (::write(fd, HEADER, HEADER_SIZE) !=
+         static_cast<ssize_t>(HEADER_SIZE)))
+      {
+        state.store(Disabled, std::memory_order_release);
+        return false;
+      }
+      state.store(Enabled, std::memory_order_release);
+      return true;
+    }
+
+    static uint64_t now()
+    {
+      return static_cast<uint64_t>(
+        std::chrono::duration_cast<std::chrono::nanoseconds>(
+          std::chrono::steady_clock::now().time_since_epoch())
+          .count());
+    }
+
+  public:
+    /**
+     * The recorder must be constant initialised, because allocations may be
+     * recorded before dynamic initialisers run.
+     */
+    constexpr Recorder() = default;
+
+    void record(
+      Op op, void* address, void* old_address, size_t size, size_t alignment)
+    {
+      State s = state.load(std::memory_order_acquire);
+      if (likely(s == Disabled))
+        return;
+      if ((s == Uninitialised) && !initialise())
+        return;
+
+      if (thread_id == 0)
+        thread_id = ++next_thread;
+
+      Record r = {now(),
+                  static_cast<uint64_t>(address_cast(address)),
+                  static_cast<uint64_t>(address_cast(old_address)),
+                  static_cast<uint64_t>(size),
+                  thread_id,
+                  op,
+                  static_cast<uint8_t>(
+                    alignment == 0 ? 0 : bits::next_pow2_bits(alignment)),
+                  0};
+
+      FlagLock f(lock);
+      buffer[count++] = r;
+      if (count == BUFFER_RECORDS)
+        write_buffer();
+    }
+
+    void flush()
+    {
+      if (state.load(std::memory_order_acquire) != Enabled)
+        return;
+      FlagLock f(lock);
+      write_buffer();
+    }
+
+    ~Recorder()
+    {
+      flush();
+      // Drop anything freed by later destructors rather than buffering it.
+      state.store(Disabled, std::memory_order_release);
+    }
+  };
+
+  inline Recorder recorder;
+
+  /**
+   * Write out any buffered records.
+   */
+  inline void flush()
+  {
+    recorder.flush();
+  }
+} // namespace snmalloc::trace
+
+#  define SNMALLOC_TRACE_RECORD(op, address, old_address, size, alignment) \
+    snmalloc::trace::recorder.record( \
+      snmalloc::trace::op, address, old_address, size, alignment)
+#else
+#  define SNMALLOC_TRACE_RECORD(op, address, old_address, size, alignment)
+#endif
@@ -4,6 +4,8 @@
 #include "pal_concept.h"
 #include "pal_consts.h"
 
+#include <atomic>
+
 // If simultating OE, then we need the underlying platform
 #if defined(OPEN_ENCLAVE)
 #  include "pal_open_enclave.h"
@@ -11,6 +13,7 @@
 #if !defined(OPEN_ENCLAVE) || defined(OPEN_ENCLAVE_SIMULATION)
 #  include "pal_apple.h"
 #  include "pal_dragonfly.h"
+#  include "pal_emscripten.h"
 #  include "pal_freebsd.h"
 #  include "pal_freebsd_kernel.h"
 #  include "pal_haiku.h"
@@ -21,6 +24,8 @@
 #  include "pal_solaris.h"
 #  include "pal_windows.h"
 #endif
+#include "pal_fixed_region.h"
+#include "pal_hooks.h"
 #include "pal_plain.h"
 
 namespace snmalloc
@@ -39,6 +44,8 @@ namespace snmalloc
     PALFreeBSD;
 #  elif defined(__HAIKU__)
     PALHaiku;
+#  elif defined(__EMSCRIPTEN__)
+    PALEmscripten;
 #  elif defined(__NetBSD__)
     PALNetBSD;
 #  elif defined(__OpenBSD__)
@@ -55,15 +62,71 @@ namespace snmalloc
   using Pal =
 #if defined(SNMALLOC_MEMORY_PROVIDER)
     PALPlainMixin<SNMALLOC_MEMORY_PROVIDER>;
+#elif defined(SNMALLOC_PAL)
+    SNMALLOC_PAL;
 #elif defined(OPEN_ENCLAVE)
     PALPlainMixin<PALOpenEnclave>;
 #else
     DefaultPal;
 #endif
 
+  /**
+   * Callback invoked with the message of a fatal error before the platform
+   * reports it and aborts, so that it can be sent to a log or crash reporter
+   * rather than only to standard output.  The allocator may be in an
+   * inconsistent state, so the handler should avoid allocating.
+   */
+  using ErrorHandler = void (*)(const char* message);
+
+  inline std::atomic<ErrorHandler> error_handler{nullptr};
+
+  /**
+   * Install `handler` to be called on fatal errors, or remove it if null.
+   */
+  inline void set_error_handler(ErrorHandler handler)
+  {
+    error_handler.store(handler, std::memory_order_relaxed);
+  }
+
+  /**
+   * Set how much the platform prints about a fatal error.  This does not
+   * affect the error handler, which is always called with the message.
+   */
+  inline void set_error_verbosity(ErrorVerbosity verbosity)
+  {
+    error_verbosity.store(verbosity, std::memory_order_relaxed);
+  }
+
+  /**
+   * Set how decommitted pages are returned to the operating system.  Returns
+   * false, changing nothing, if the platform does not support the choice.
+   */
+  inline bool set_decommit_advice(DecommitAdvice advice)
+  {
+    if constexpr (!pal_supports<AdvisedDecommit, Pal>)
+    {
+      UNUSED(advice);
+      return false;
+    }
+    else
+    {
+      decommit_advice.store(advice, std::memory_order_relaxed);
+      return true;
+    }
+  }
+
+  /**
+   * Report a fatal error: pass the message to the error handler, if one is
+   * installed, then have the platform print it and abort.
+   */
   [[noreturn]] SNMALLOC_SLOW_PATH inline SNMALLOC_COLD void
   error(const char* const str)
   {
+    // Remove the handler before calling it, so that an error inside it does
+    // not recurse.
+    auto handler = error_handler.exchange(nullptr, std::memory_order_relaxed);
+    if (handler != nullptr)
+      handler(str);
     Pal::error(str);
   }
 
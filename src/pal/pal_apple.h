@@ -28,7 +28,7 @@ namespace snmalloc
      * The features exported by this PAL.
      */
     static constexpr uint64_t pal_features =
-      AlignedAllocation | LazyCommit | Entropy;
+      AlignedAllocation | LazyCommit | Entropy | PageLocking | PageProtection;
 
     /*
      * `page_size`
@@ -239,7 +239,11 @@ namespace snmalloc
 
       if (unlikely(kr != KERN_SUCCESS))
       {
-        error("Failed to allocate memory\n");
+#  ifdef SNMALLOC_OOM_RETURNS_NULL
+        return nullptr;
+#  else
+        snmalloc::error("Failed to allocate memory\n");
+#  endif
       }
 
       return reinterpret_cast<void*>(addr);
@@ -276,7 +280,7 @@ namespace snmalloc
         CCRandomGenerateBytes(
           reinterpret_cast<void*>(&result), sizeof(result)) != kCCSuccess)
       {
-        error("Failed to get system randomness");
+        snmalloc::error("Failed to get system randomness");
       }
 
       return result;
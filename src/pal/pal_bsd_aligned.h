@@ -46,7 +46,13 @@ namespace snmalloc
         0);
 
       if (p == MAP_FAILED)
-        PALBSD<OS>::error("Out of memory");
+      {
+#ifdef SNMALLOC_OOM_RETURNS_NULL
+        return nullptr;
+#else
+        snmalloc::error("Out of memory");
+#endif
+      }
 
       return p;
     }
@@ -86,6 +86,44 @@ namespace snmalloc
     { PAL::get_entropy64() } -> ConceptSame<uint64_t>;
   };
 
+  /**
+   * Some PALs can move pages between ranges without copying them.
+   */
+  template<typename PAL>
+  concept ConceptPAL_move_pages = requires(void* p, std::size_t sz)
+  {
+    { PAL::move_pages(p, p, sz) } noexcept -> ConceptSame<bool>;
+  };
+
+  /**
+   * Some PALs can report how much of their memory is backed by huge pages.
+   */
+  template<typename PAL>
+  concept ConceptPAL_huge_page_bytes = requires()
+  {
+    { PAL::huge_page_bytes() } noexcept -> ConceptSame<std::size_t>;
+  };
+
+  /**
+   * Some PALs can lock pages into physical memory.
+   */
+  template<typename PAL>
+  concept ConceptPAL_lock_pages = requires(void* p, std::size_t sz)
+  {
+    { PAL::lock_pages(p, sz) } noexcept -> ConceptSame<bool>;
+    { PAL::unlock_pages(p, sz) } noexcept -> ConceptSame<void>;
+  };
+
+  /**
+   * Some PALs can make pages read-only.
+   */
+  template<typename PAL>
+  concept ConceptPAL_protect_pages = requires(void* p, std::size_t sz)
+  {
+    { PAL::make_read_only(p, sz) } noexcept -> ConceptSame<bool>;
+    { PAL::make_read_write(p, sz) } noexcept -> ConceptSame<void>;
+  };
+
   /**
    * PALs ascribe to the conjunction of several concepts.  These are broken
    * out by the shape of the requires() quantifiers required and by any
@@ -101,6 +139,14 @@ namespace snmalloc
       ConceptPAL_get_entropy64<PAL>) &&
     (!pal_supports<LowMemoryNotification, PAL> ||
       ConceptPAL_mem_low_notify<PAL>) &&
+    (!pal_supports<PageMove, PAL> ||
+      ConceptPAL_move_pages<PAL>) &&
+    (!pal_supports<HugePageQuery, PAL> ||
+      ConceptPAL_huge_page_bytes<PAL>) &&
+    (!pal_supports<PageLocking, PAL> ||
+      ConceptPAL_lock_pages<PAL>) &&
+    (!pal_supports<PageProtection, PAL> ||
+      ConceptPAL_protect_pages<PAL>) &&
     (pal_supports<NoAllocation, PAL> ||
      (pal_supports<AlignedAllocation, PAL> &&
         ConceptPAL_reserve_aligned<PAL>) ||
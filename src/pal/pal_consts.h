@@ -45,7 +45,86 @@ namespace snmalloc
      * This Pal provides a source of Entropy
      */
     Entropy = (1 << 4),
+    /**
+     * This PAL can move committed pages from one range to another without
+     * copying them.  It must implement a `move_pages()` method that takes the
+     * source, the destination and the size, and returns false if the pages
+     * could not be moved.
+     */
+    PageMove = (1 << 5),
+    /**
+     * This PAL can report how much of the memory that it has reserved is
+     * backed by huge pages.  It must implement a `huge_page_bytes()` method
+     * that returns that amount in bytes.
+     */
+    HugePageQuery = (1 << 6),
+    /**
+     * This PAL returns decommitted pages to the operating system in the way
+     * selected by `decommit_advice`.  Other PALs ignore it.
+     */
+    AdvisedDecommit = (1 << 7),
+    /**
+     * This PAL can lock pages into physical memory, so that they are never
+     * paged out.  It must implement a `lock_pages()` method that takes a
+     * page-aligned range and returns false if it could not be locked, and an
+     * `unlock_pages()` method that unlocks one.
+     */
+    PageLocking = (1 << 8),
+    /**
+     * This PAL can make pages read-only.  It must implement a
+     * `make_read_only()` method that takes a page-aligned range of committed
+     * pages and returns false if it could not be protected, and a
+     * `make_read_write()` method that makes one writable again.
+     */
+    PageProtection = (1 << 9),
+  };
+  /**
+   * How much a PAL reports about a fatal error before aborting.
+   */
+  enum ErrorVerbosity
+  {
+    /**
+     * Abort without printing anything.
+     */
+    ErrorSilent,
+    /**
+     * Print the message.
+     */
+    ErrorMessage,
+    /**
+     * Print the message and, where the platform supports it, a stack trace.
+     */
+    ErrorBacktrace
   };
+
+  inline std::atomic<ErrorVerbosity> error_verbosity{ErrorBacktrace};
+
+  /**
+   * How a PAL that supports `AdvisedDecommit` tells the operating system that
+   * decommitted pages are no longer needed.
+   */
+  enum DecommitAdvice
+  {
+    /**
+     * Leave the pages resident.  They are reused when the memory is next
+     * committed, and the process's resident set does not shrink.
+     */
+    AdviseNothing,
+    /**
+     * Let the operating system reclaim the pages when it is short of memory
+     * (`MADV_FREE`).  This is cheap, but the resident set only shrinks under
+     * pressure.
+     */
+    AdviseFree,
+    /**
+     * Discard the pages immediately (`MADV_DONTNEED`), so that the resident
+     * set shrinks at once, at the cost of faulting in zeroed pages on reuse.
+     */
+    AdviseDontNeed
+  };
+
+  inline std::atomic<DecommitAdvice> decommit_advice{AdviseNothing};
+
   /**
    * Flag indicating whether requested memory should be zeroed.
    */
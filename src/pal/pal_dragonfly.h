@@ -35,7 +35,7 @@ namespace snmalloc
     {
       uint64_t result;
       if (getrandom(&result, sizeof(result), 0) != sizeof(result))
-        error("Failed to get system randomness");
+        snmalloc::error("Failed to get system randomness");
       return result;
     }
   };
@@ -0,0 +1,57 @@
+#pragma once
+
+#if defined(__EMSCRIPTEN__)
+#  include "pal_posix.h"
+
+namespace snmalloc
+{
+  /**
+   * Platform abstraction layer for Emscripten.  Its `mmap` is emulated by
+   * allocating from WebAssembly linear memory, so memory is committed as
+   * soon as it is reserved and cannot be returned to the host.
+   */
+  class PALEmscripten : public PALPOSIX<PALEmscripten>
+  {
+  public:
+    /**
+     * Bitmap of PalFeatures flags indicating the optional features that this
+     * PAL supports.
+     *
+     * Memory is not committed lazily, so this does not include `LazyCommit`.
+     */
+    static constexpr uint64_t pal_features =
+      PALPOSIX::pal_features & ~LazyCommit;
+
+    /**
+     * Reserve exactly the memory requested, as the generic POSIX PAL's
+     * over-allocation would be committed immediately.
+     */
+    static std::pair<void*, size_t> reserve_at_least(size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(bits::is_pow2(size));
+
+      void* p = mmap(
+        nullptr,
+        size,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+        0);
+
+      if (p == MAP_FAILED)
+        return {nullptr, 0};
+
+      return {p, size};
+    }
+
+    /**
+     * `mmap` cannot replace existing memory, so always clear it directly.
+     */
+    template<bool page_aligned = false>
+    static void zero(void* p, size_t size) noexcept
+    {
+      memset(p, 0, size);
+    }
+  };
+} // namespace snmalloc
+#endif
@@ -0,0 +1,88 @@
+#pragma once
+
+#include "../ds/address.h"
+#include "../ds/flaglock.h"
+
+#include <cstdlib>
+#include <cstring>
+
+namespace snmalloc
+{
+  /**
+   * Platform abstraction layer for running over a single region of memory
+   * provided by the caller, with no interaction with the operating system,
+   * for embedded and `no_std` targets.  It is used by defining
+   * `SNMALLOC_MEMORY_PROVIDER` as `PALFixedRegion`, and the region must be
+   * provided with `set_region` before the first allocation.
+   *
+   * This is a generalisation of `PALOpenEnclave`, which gets its region from
+   * the enclave runtime.
+   */
+  class PALFixedRegion
+  {
+    /// Base of the region, or null if it has been handed out.
+    static inline void* region_base = nullptr;
+
+    /// Size of the region still to be handed out.
+    static inline size_t region_size = 0;
+
+    /// Set once a region has been provided.
+    static inline bool region_set = false;
+
+    // This is infrequently used code, a spin lock simplifies the code
+    // considerably, and should never be on the fast path.
+    static inline std::atomic_flag spin_lock;
+
+  public:
+    /**
+     * Provide the region of `size` bytes at `base` to allocate from.  Returns
+     * false, ignoring the region, if one has already been provided.
+     */
+    static bool set_region(void* base, size_t size)
+    {
+      FlagLock lock(spin_lock);
+      if (region_set)
+        return false;
+
+      region_base = base;
+      region_size = size;
+      region_set = true;
+      return true;
+    }
+
+    /**
+     * Bitmap of PalFeatures flags indicating the optional features that this
+     * PAL supports.
+     */
+    static constexpr uint64_t pal_features = 0;
+
+    static constexpr size_t page_size = Aal::smallest_page_size;
+
+    [[noreturn]] static void error(const char* const str)
+    {
+      UNUSED(str);
+      abort();
+    }
+
+    static std::pair<void*, size_t>
+    reserve_at_least(size_t request_size) noexcept
+    {
+      // First call returns the entire region
+      // subsequent calls return {nullptr, 0}
+      FlagLock lock(spin_lock);
+      if (request_size > region_size)
+        return {nullptr, 0};
+
+      auto result = std::make_pair(region_base, region_size);
+      region_base = nullptr;
+      region_size = 0;
+      return result;
+    }
+
+    template<bool page_aligned = false>
+    static void zero(void* p, size_t size) noexcept
+    {
+      memset(p, 0, size);
+    }
+  };
+} // namespace snmalloc
@@ -48,7 +48,7 @@ namespace snmalloc
       int flags = M_WAITOK | ((zero_mem == YesZero) ? M_ZERO : 0);
       if (kmem_back(kernel_object, addr, size, flags) != KERN_SUCCESS)
       {
-        error("Out of memory");
+        snmalloc::error("Out of memory");
       }
     }
 
@@ -0,0 +1,127 @@
+#pragma once
+
+#include "../ds/address.h"
+#include "../ds/flaglock.h"
+
+#include <cstdlib>
+#include <cstring>
+
+namespace snmalloc
+{
+  /**
+   * Platform operations supplied by the embedding program as function
+   * pointers, for platforms, hypervisors or test harnesses that snmalloc has
+   * no PAL for.  Only `reserve` is required; the others may be null, in which
+   * case they do nothing, or use `memset` and `abort` for `zero` and `error`.
+   */
+  struct PalHooks
+  {
+    /**
+     * Reserve at least `size` bytes of address space, returning null if it
+     * cannot be reserved.  The memory is not used until it is committed.
+     */
+    void* (*reserve)(size_t size);
+
+    /**
+     * Make `size` bytes from `p`, which are page aligned, usable.
+     */
+    void (*commit)(void* p, size_t size);
+
+    /**
+     * Tell the platform that `size` bytes from `p`, which are page aligned,
+     * are no longer in use.  They are committed again before they are reused.
+     */
+    void (*decommit)(void* p, size_t size);
+
+    /**
+     * Set `size` bytes from `p` to zero.
+     */
+    void (*zero)(void* p, size_t size);
+
+    /**
+     * Report a fatal error.  This must not return.
+     */
+    void (*error)(const char* message);
+  };
+
+  /**
+   * Platform abstraction layer that forwards to the `PalHooks` provided with
+   * `set_hooks`, which must be called before the first allocation.  It is
+   * used by defining `SNMALLOC_PAL` as `PALHooks`.
+   */
+  class PALHooks
+  {
+    static inline PalHooks hooks{};
+
+    static inline std::atomic_flag spin_lock;
+
+  public:
+    /**
+     * Use `h` for all platform operations.  Returns false, ignoring `h`, if
+     * hooks have already been provided or `h` has no `reserve` hook.
+     */
+    static bool set_hooks(const PalHooks& h)
+    {
+      FlagLock lock(spin_lock);
+      if ((hooks.reserve != nullptr) || (h.reserve == nullptr))
+        return false;
+
+      hooks = h;
+      return true;
+    }
+
+    /**
+     * Bitmap of PalFeatures flags indicating the optional features that this
+     * PAL supports.
+     */
+    static constexpr uint64_t pal_features = 0;
+
+    static constexpr size_t page_size = Aal::smallest_page_size;
+
+    [[noreturn]] static void error(const char* const str)
+    {
+      if (hooks.error != nullptr)
+        hooks.error(str);
+      abort();
+    }
+
+    static std::pair<void*, size_t>
+    reserve_at_least(size_t request_size) noexcept
+    {
+      if (hooks.reserve == nullptr)
+        return {nullptr, 0};
+
+      void* p = hooks.reserve(request_size);
+      if (p == nullptr)
+        return {nullptr, 0};
+      return {p, request_size};
+    }
+
+    static void notify_not_using(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      if (hooks.decommit != nullptr)
+        hooks.decommit(p, size);
+    }
+
+    template<ZeroMem zero_mem>
+    static void notify_using(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      if (hooks.commit != nullptr)
+        hooks.commit(p, size);
+
+      if constexpr (zero_mem == YesZero)
+        zero<true>(p, size);
+    }
+
+    template<bool page_aligned = false>
+    static void zero(void* p, size_t size) noexcept
+    {
+      if (hooks.zero != nullptr)
+        hooks.zero(p, size);
+      else
+        memset(p, 0, size);
+    }
+  };
+} // namespace snmalloc
@@ -4,8 +4,11 @@
 #  include "../ds/bits.h"
 #  include "pal_posix.h"
 
+#  include <atomic>
+#  include <fcntl.h>
 #  include <string.h>
 #  include <sys/mman.h>
+#  include <unistd.h>
 
 extern "C" int puts(const char* str);
 
@@ -13,17 +16,112 @@ namespace snmalloc
 {
   class PALLinux : public PALPOSIX<PALLinux>
   {
+    /**
+     * The ranges returned by `reserve_at_least`, so that `huge_page_bytes`
+     * can tell the heap's mappings from others.  Reservations are at least
+     * 4 GiB on 64-bit platforms unless memory is short, so few are made;
+     * any past the last slot are not counted.
+     */
+    static constexpr size_t max_reservations = 64;
+    static inline std::atomic<size_t> reservation_count{0};
+    static inline std::atomic<address_t> reservation_base[max_reservations];
+    static inline std::atomic<size_t> reservation_size[max_reservations];
+
+    static void record_reservation(void* p, size_t size) noexcept
+    {
+      size_t i = reservation_count.fetch_add(1, std::memory_order_relaxed);
+      if (i >= max_reservations)
+        return;
+      reservation_size[i].store(size, std::memory_order_relaxed);
+      reservation_base[i].store(address_cast(p), std::memory_order_release);
+    }
+
+    static bool is_reserved(address_t a) noexcept
+    {
+      size_t count = bits::min(
+        reservation_count.load(std::memory_order_relaxed), max_reservations);
+      for (size_t i = 0; i < count; i++)
+      {
+        address_t base = reservation_base[i].load(std::memory_order_acquire);
+        if (
+          (base != 0) && (a >= base) &&
+          (a - base < reservation_size[i].load(std::memory_order_relaxed)))
+          return true;
+      }
+      return false;
+    }
+
+    /**
+     * Parses the number at the start of `s` in the given base, skipping
+     * leading spaces.
+     */
+    static size_t parse_number(const char* s, size_t base) noexcept
+    {
+      while (*s == ' ')
+        s++;
+      size_t result = 0;
+      for (;; s++)
+      {
+        size_t digit;
+        if ((*s >= '0') && (*s <= '9'))
+          digit = static_cast<size_t>(*s - '0');
+        else if ((base == 16) && (*s >= 'a') && (*s <= 'f'))
+          digit = static_cast<size_t>(*s - 'a' + 10);
+        else
+          return result;
+        result = result * base + digit;
+      }
+    }
+
+#  ifdef SNMALLOC_HUGEPAGES_EXPLICIT
+    /**
+     * Reserves memory from the pool of explicit huge pages, halving the
+     * request down to `size` until the pool can hold it.  The mapping
+     * reserves its pages from the pool, so faulting them in cannot fail.
+     * Returns a null pointer if the pool cannot hold `size` bytes.
+     */
+    static std::pair<void*, size_t> reserve_huge(size_t size) noexcept
+    {
+      constexpr size_t min_size =
+        bits::is64() ? bits::one_at_bit(32) : bits::one_at_bit(28);
+      size_t huge_size = bits::max(size, huge_page_size);
+
+      for (size_t size_request = bits::max(huge_size, min_size);
+           size_request >= huge_size;
+           size_request = size_request / 2)
+      {
+        void* p = mmap(
+          nullptr,
+          size_request,
+          PROT_READ | PROT_WRITE,
+          MAP_PRIVATE | MAP_ANONYMOUS | MAP_HUGETLB,
+          -1,
+          0);
+
+        if (p != MAP_FAILED)
+          return {p, size_request};
+      }
+      return {nullptr, 0};
+    }
+#  endif
+
   public:
     /**
      * Bitmap of PalFeatures flags indicating the optional features that this
      * PAL supports.
      *
-     * Linux does not support any features other than those in a generic POSIX
-     * platform.  This field is declared explicitly to remind anyone who
-     * extends this PAL that they may need to extend the set of advertised
-     * features.
+     * Linux can move pages with `mremap`, report the heap's huge pages from
+     * `/proc/self/smaps`, and choose how decommitted pages are returned, in
+     * addition to the features of a generic POSIX platform.
+     */
+    static constexpr uint64_t pal_features =
+      PALPOSIX::pal_features | PageMove | HugePageQuery | AdvisedDecommit;
+
+    /**
+     * The size of the huge pages used with `SNMALLOC_HUGEPAGES`, which is
+     * the size of a PMD mapping on x86-64 and AArch64 with 4 KiB pages.
      */
-    static constexpr uint64_t pal_features = PALPOSIX::pal_features;
+    static constexpr size_t huge_page_size = bits::one_at_bit(21);
 
     static constexpr size_t page_size =
       Aal::aal_name == PowerPC ? 0x10000 : PALPOSIX::page_size;
@@ -57,13 +155,151 @@ namespace snmalloc
         // Only use this on large allocations as memset faster, and doesn't
         // introduce IPI so faster for small allocations.
         SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
-        madvise(p, size, MADV_DONTNEED);
+        // Explicit huge pages can only be dropped in whole huge pages, so
+        // fall back to memset if the range is not made of them.
+        if (madvise(p, size, MADV_DONTNEED) == 0)
+          return;
       }
-      else
 #  endif
+      ::memset(p, 0, size);
+    }
+
+    /**
+     * Notify platform that we will not be using these pages.
+     *
+     * By default this does nothing, as on any POSIX platform, because
+     * returning the pages costs more than it saves for most programs.
+     * `decommit_advice` can select `MADV_FREE`, which falls back to
+     * `MADV_DONTNEED` on kernels and headers that predate it, or
+     * `MADV_DONTNEED`.
+     */
+    static void notify_not_using(void* p, size_t size) noexcept
+    {
+      PALPOSIX::notify_not_using(p, size);
+
+      auto advice = decommit_advice.load(std::memory_order_relaxed);
+#  ifdef MADV_FREE
+      if ((advice == AdviseFree) && (madvise(p, size, MADV_FREE) == 0))
+        return;
+#  endif
+      if (advice != AdviseNothing)
+        madvise(p, size, MADV_DONTNEED);
+    }
+
+    /**
+     * Reserve memory, as on any POSIX platform.
+     *
+     * With `SNMALLOC_HUGEPAGES`, the memory is advised with `MADV_HUGEPAGE`
+     * so that the kernel backs it with transparent huge pages wherever it
+     * can, even if they are only enabled on request.  With
+     * `SNMALLOC_HUGEPAGES_EXPLICIT`, the memory is first sought from the pool
+     * of explicit huge pages (`/proc/sys/vm/nr_hugepages`), falling back to
+     * transparent huge pages when the pool is too small.
+     */
+    static std::pair<void*, size_t> reserve_at_least(size_t size) noexcept
+    {
+      std::pair<void*, size_t> result{nullptr, 0};
+#  ifdef SNMALLOC_HUGEPAGES_EXPLICIT
+      result = reserve_huge(size);
+#  endif
+      if (result.first == nullptr)
       {
-        ::memset(p, 0, size);
+        result = PALPOSIX::reserve_at_least(size);
+#  if defined(SNMALLOC_HUGEPAGES) && defined(MADV_HUGEPAGE)
+        madvise(result.first, result.second, MADV_HUGEPAGE);
+#  endif
       }
+      if (result.first != nullptr)
+        record_reservation(result.first, result.second);
+      return result;
+    }
+
+    /**
+     * Returns the bytes of the memory reserved by this PAL that are backed
+     * by huge pages, transparent or explicit, according to
+     * `/proc/self/smaps`.  Returns zero if that cannot be read.
+     *
+     * This reads the file with a fixed buffer, as it may be called from
+     * inside `malloc`.
+     */
+    static size_t huge_page_bytes() noexcept
+    {
+      int fd = open("/proc/self/smaps", O_RDONLY | O_CLOEXEC);
+      if (fd < 0)
+        return 0;
+
+      size_t total = 0;
+      bool in_heap = false;
+      char buffer[4096];
+      // Only the start of each line is needed, so longer ones are truncated.
+      char line[64];
+      size_t length = 0;
+      ssize_t n;
+      while ((n = read(fd, buffer, sizeof(buffer))) > 0)
+      {
+        for (ssize_t i = 0; i < n; i++)
+        {
+          if (buffer[i] != '\n')
+          {
+            if (length < sizeof(line) - 1)
+              line[length++] = buffer[i];
+            continue;
+          }
+          line[length] = '\0';
+          length = 0;
+
+          // Each mapping starts with a line giving its range in lower case
+          // hex, followed by lines of fields with capitalised names.
+          if (
+            ((line[0] >= '0') && (line[0] <= '9')) ||
+            ((line[0] >= 'a') && (line[0] <= 'f')))
+          {
+            in_heap = is_reserved(parse_number(line, 16));
+            continue;
+          }
+          if (!in_heap)
+            continue;
+          for (const char* field : {"AnonHugePages:", "Private_Hugetlb:"})
+          {
+            size_t field_length = strlen(field);
+            if (strncmp(line, field, field_length) == 0)
+              total += parse_number(line + field_length, 10) * 1024;
+          }
+        }
+      }
+      close(fd);
+      return total;
+    }
+
+    /**
+     * Move the pages of `size` bytes at `from` to `to`, replacing whatever
+     * was mapped there, without copying them.  `from` is left mapped, reading
+     * as zero.  Both ranges must be page aligned.  Each move can split the
+     * mappings around both ranges, and a process may only have
+     * `vm.max_map_count` of them, so this is only worth doing for large
+     * ranges.
+     *
+     * Returns false if `mremap` fails, for example because the kernel
+     * predates `MREMAP_DONTUNMAP` (Linux 5.7) or `from` spans more than one
+     * mapping, so the caller must copy instead.
+     */
+    static bool move_pages(void* from, void* to, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(from, size));
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(to, size));
+#  ifdef MREMAP_DONTUNMAP
+      return mremap(
+               from,
+               size,
+               size,
+               MREMAP_MAYMOVE | MREMAP_FIXED | MREMAP_DONTUNMAP,
+               to) != MAP_FAILED;
+#  else
+      UNUSED(from);
+      UNUSED(to);
+      UNUSED(size);
+      return false;
+#  endif
     }
   };
 } // namespace snmalloc
@@ -75,7 +75,7 @@ namespace snmalloc
     {
       uint64_t result = 0;
       if (oe_random(&result, sizeof(result)) != OE_OK)
-        error("Failed to get system randomness");
+        snmalloc::error("Failed to get system randomness");
       return result;
     }
   };
@@ -117,10 +117,12 @@ namespace snmalloc
      * Bitmap of PalFeatures flags indicating the optional features that this
      * PAL supports.
      *
-     * POSIX systems are assumed to support lazy commit. The build system checks
-     * getentropy is available, only then this PAL supports Entropy.
+     * POSIX systems are assumed to support lazy commit, `mlock` and
+     * `mprotect`. The build system checks getentropy is available, only then
+     * this PAL supports Entropy.
      */
-    static constexpr uint64_t pal_features = LazyCommit
+    static constexpr uint64_t pal_features = LazyCommit | PageLocking
+      | PageProtection
 #if defined(SNMALLOC_PLATFORM_HAS_GETENTROPY)
       | Entropy
 #endif
@@ -146,8 +148,11 @@ namespace snmalloc
      */
     [[noreturn]] static void error(const char* const str) noexcept
     {
-      puts(str);
-      print_stack_trace();
+      auto verbosity = error_verbosity.load(std::memory_order_relaxed);
+      if (verbosity >= ErrorMessage)
+        puts(str);
+      if (verbosity >= ErrorBacktrace)
+        print_stack_trace();
       abort();
     }
 
@@ -197,6 +202,45 @@ namespace snmalloc
         zero<true>(p, size);
     }
 
+    /**
+     * Lock the pages in a page-aligned range into physical memory, faulting
+     * them in.  Returns false if they could not all be locked, as when
+     * `RLIMIT_MEMLOCK` would be exceeded.
+     */
+    static bool lock_pages(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<OS::page_size>(p, size));
+      return mlock(p, size) == 0;
+    }
+
+    /**
+     * Allow the pages in a page-aligned range to be paged out again.
+     */
+    static void unlock_pages(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<OS::page_size>(p, size));
+      munlock(p, size);
+    }
+
+    /**
+     * Make the pages in a page-aligned range read-only, so that writes to
+     * them fault.  Returns false if they could not be protected.
+     */
+    static bool make_read_only(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<OS::page_size>(p, size));
+      return mprotect(p, size, PROT_READ) == 0;
+    }
+
+    /**
+     * Make the pages in a page-aligned range writable again.
+     */
+    static void make_read_write(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<OS::page_size>(p, size));
+      mprotect(p, size, PROT_READ | PROT_WRITE);
+    }
+
     /**
      * OS specific function for zeroing memory.
      *
@@ -273,7 +317,11 @@ namespace snmalloc
           return {p, size_request};
       }
 
-      OS::error("Out of memory");
+#ifdef SNMALLOC_OOM_RETURNS_NULL
+      return {nullptr, 0};
+#else
+      snmalloc::error("Out of memory");
+#endif
     }
 
     /**
@@ -298,11 +346,12 @@ namespace snmalloc
 #ifdef SNMALLOC_PLATFORM_HAS_GETENTROPY
         uint64_t result;
         if (getentropy(&result, sizeof(result)) != 0)
-          error("Failed to get system randomness");
+          snmalloc::error("Failed to get system randomness");
         return result;
 #endif
       }
-      error("Entropy requested on platform that does not provide entropy");
+      snmalloc::error(
+        "Entropy requested on platform that does not provide entropy");
     }
   };
 } // namespace snmalloc
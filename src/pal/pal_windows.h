@@ -53,6 +53,7 @@ namespace snmalloc
      * PAL supports.  This PAL supports low-memory notifications.
      */
     static constexpr uint64_t pal_features = LowMemoryNotification | Entropy
+      | PageLocking | PageProtection
 #  if defined(PLATFORM_HAS_VIRTUALALLOC2) && !defined(USE_SYSTEMATIC_TESTING)
       | AlignedAllocation
 #  endif
@@ -109,8 +110,11 @@ namespace snmalloc
 
     [[noreturn]] static void error(const char* const str)
     {
-      puts(str);
-      fflush(stdout);
+      if (error_verbosity.load(std::memory_order_relaxed) >= ErrorMessage)
+      {
+        puts(str);
+        fflush(stdout);
+      }
       abort();
     }
 
@@ -122,7 +126,7 @@ namespace snmalloc
       BOOL ok = VirtualFree(p, size, MEM_DECOMMIT);
 
       if (!ok)
-        error("VirtualFree failed");
+        snmalloc::error("VirtualFree failed");
     }
 
     /// Notify platform that we will be using these pages
@@ -135,7 +139,38 @@ namespace snmalloc
       void* r = VirtualAlloc(p, size, MEM_COMMIT, PAGE_READWRITE);
 
       if (r == nullptr)
-        error("out of memory");
+        snmalloc::error("out of memory");
+    }
+
+    /// Lock committed pages into the working set.  This fails if the
+    /// process's minimum working set size is too small to hold them.
+    static bool lock_pages(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      return VirtualLock(p, size) != 0;
+    }
+
+    /// Allow locked pages to be paged out again
+    static void unlock_pages(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      VirtualUnlock(p, size);
+    }
+
+    /// Make committed pages read-only
+    static bool make_read_only(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      DWORD old;
+      return VirtualProtect(p, size, PAGE_READONLY, &old) != 0;
+    }
+
+    /// Make read-only pages writable again
+    static void make_read_write(void* p, size_t size) noexcept
+    {
+      SNMALLOC_ASSERT(is_aligned_block<page_size>(p, size));
+      DWORD old;
+      VirtualProtect(p, size, PAGE_READWRITE, &old);
     }
 
     /// OS specific function for zeroing memory
@@ -208,10 +243,12 @@ namespace snmalloc
 
       void* ret = VirtualAlloc2FromApp(
         nullptr, nullptr, size, flags, PAGE_READWRITE, &param, 1);
+#    ifndef SNMALLOC_OOM_RETURNS_NULL
       if (ret == nullptr)
       {
-        error("Failed to allocate memory\n");
+        snmalloc::error("Failed to allocate memory\n");
       }
+#    endif
       return ret;
     }
 #  else
@@ -234,7 +271,11 @@ namespace snmalloc
           return std::pair(ret, size_request);
         }
       }
-      error("Failed to allocate memory\n");
+#    ifdef SNMALLOC_OOM_RETURNS_NULL
+      return {nullptr, 0};
+#    else
+      snmalloc::error("Failed to allocate memory\n");
+#    endif
     }
 #  endif
 
@@ -250,7 +291,7 @@ namespace snmalloc
           reinterpret_cast<PUCHAR>(&result),
           sizeof(result),
           BCRYPT_USE_SYSTEM_PREFERRED_RNG) != 0)
-        error("Failed to get entropy.");
+        snmalloc::error("Failed to get entropy.");
       return result;
     }
   };
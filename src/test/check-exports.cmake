@@ -0,0 +1,67 @@
+# Checks that a Rust shim built with SNMALLOC_RUST_LIBC_API defines the C
+# allocation functions and no other C symbols, which could collide with
+# those of the program that it is linked into.  C++ symbols are mangled, so
+# are not checked.
+#
+# Usage: cmake -DNM=<nm> -DLIBRARY=<library> [-DPREFIX=_] -P check-exports.cmake
+#
+# PREFIX is the platform's prefix for C symbols, such as `_` on macOS.
+
+set(REQUIRED
+  malloc free calloc realloc posix_memalign aligned_alloc memalign
+  malloc_usable_size)
+
+set(ALLOWED ${REQUIRED}
+  reallocarray reallocf cfree valloc pvalloc malloc_size malloc_good_size
+  free_sized free_aligned_sized __malloc_end_pointer _malloc_prefork
+  _malloc_postfork _malloc_first_thread mallctl)
+
+execute_process(
+  COMMAND ${NM} -g ${LIBRARY}
+  OUTPUT_VARIABLE SYMBOLS
+  RESULT_VARIABLE RESULT)
+if(NOT RESULT EQUAL 0)
+  message(FATAL_ERROR "${NM} failed on ${LIBRARY}")
+endif()
+
+string(REPLACE "\n" ";" LINES "${SYMBOLS}")
+set(DEFINED)
+set(UNEXPECTED)
+foreach(LINE ${LINES})
+  # Defined symbols have an address; undefined ones only a type and name.
+  if(NOT LINE MATCHES "^[0-9a-fA-F]+ ([A-Za-z]) (.+)$")
+    continue()
+  endif()
+  set(NAME ${CMAKE_MATCH_2})
+  if(PREFIX)
+    string(REGEX REPLACE "^${PREFIX}" "" NAME ${NAME})
+  endif()
+  if(NAME MATCHES "^(_Z|DW\\.ref\\.|\\.)")
+    continue()
+  endif()
+  list(APPEND DEFINED ${NAME})
+  if(NAME MATCHES "^(rust_|snmalloc_)")
+    continue()
+  endif()
+  list(FIND ALLOWED ${NAME} INDEX)
+  if(INDEX EQUAL -1)
+    list(APPEND UNEXPECTED ${NAME})
+  endif()
+endforeach()
+
+set(MISSING)
+foreach(NAME ${REQUIRED})
+  list(FIND DEFINED ${NAME} INDEX)
+  if(INDEX EQUAL -1)
+    list(APPEND MISSING ${NAME})
+  endif()
+endforeach()
+
+if(MISSING)
+  message(FATAL_ERROR "${LIBRARY} does not export: ${MISSING}")
+endif()
+if(UNEXPECTED)
+  list(REMOVE_DUPLICATES UNEXPECTED)
+  message(FATAL_ERROR "${LIBRARY} unexpectedly exports: ${UNEXPECTED}")
+endif()
+message(STATUS "${LIBRARY} exports the C allocation functions")
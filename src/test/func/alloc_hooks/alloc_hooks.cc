@@ -0,0 +1,119 @@
+/**
+ * Checks that, when built with SNMALLOC_HOOKS, the malloc and Rust shims
+ * call the installed callbacks with the pointer and size of each allocation
+ * and deallocation, that allocations made by a callback are not reported to
+ * it, that the callbacks can be limited to large blocks, and that pool refills
+ * and trims are reported.
+ */
+
+#define SNMALLOC_HOOKS
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+struct Event
+{
+  void* p;
+  size_t size;
+};
+
+size_t allocs = 0;
+size_t deallocs = 0;
+Event last_alloc;
+Event last_dealloc;
+
+void on_alloc(void* p, size_t size)
+{
+  allocs++;
+  last_alloc = {p, size};
+  // Allocating from a callback must not recurse.
+  sn_free(sn_malloc(32));
+}
+
+void on_dealloc(void* p, size_t size)
+{
+  deallocs++;
+  last_dealloc = {p, size};
+}
+
+int main()
+{
+  setup();
+
+  // Nothing is reported until hooks are installed.
+  sn_free(sn_malloc(16));
+  SNMALLOC_CHECK(allocs == 0 && deallocs == 0);
+
+  sn_snmalloc_set_alloc_hooks(on_alloc, on_dealloc);
+
+  void* p = sn_malloc(100);
+  SNMALLOC_CHECK(allocs == 1);
+  SNMALLOC_CHECK(last_alloc.p == p && last_alloc.size == 100);
+
+  sn_free(p);
+  SNMALLOC_CHECK(deallocs == 1);
+  SNMALLOC_CHECK(last_dealloc.p == p);
+  SNMALLOC_CHECK(last_dealloc.size >= 100);
+
+  sn_free(nullptr);
+  SNMALLOC_CHECK(deallocs == 1);
+
+  void* q = sn_calloc(3, 10);
+  SNMALLOC_CHECK(last_alloc.p == q && last_alloc.size == 30);
+  sn_free_sized(q, 30);
+  SNMALLOC_CHECK(last_dealloc.p == q && last_dealloc.size == 30);
+
+  void* r = rust_alloc(64, 200);
+  SNMALLOC_CHECK(last_alloc.p == r && last_alloc.size == 200);
+
+  // Moving reports a deallocation of the old block and an allocation of
+  // the new one.
+  size_t before = allocs;
+  void* s = rust_realloc(r, 64, 200, 5000);
+  SNMALLOC_CHECK(allocs == before + 1);
+  SNMALLOC_CHECK(last_alloc.p == s && last_alloc.size == 5000);
+  SNMALLOC_CHECK(last_dealloc.p == r && last_dealloc.size == 200);
+
+  rust_dealloc(s, 64, 5000);
+  SNMALLOC_CHECK(last_dealloc.p == s && last_dealloc.size == 5000);
+
+  // The callbacks' own allocations were not counted.
+  SNMALLOC_CHECK(allocs == 4);
+  SNMALLOC_CHECK(deallocs == 4);
+
+  // Only blocks of at least the minimum size are reported.
+  sn_snmalloc_set_alloc_hooks_min_size(4096);
+  sn_free(sn_malloc(3000));
+  SNMALLOC_CHECK(allocs == 4 && deallocs == 4);
+  void* big = rust_alloc(8, 1 << 20);
+  SNMALLOC_CHECK(last_alloc.p == big && last_alloc.size == 1 << 20);
+  rust_dealloc(big, 8, 1 << 20);
+  SNMALLOC_CHECK(last_dealloc.p == big);
+  SNMALLOC_CHECK(allocs == 5 && deallocs == 5);
+
+  // A block whose requested size is below the minimum, but whose usable
+  // size is not, is reported both when allocated and when freed.
+  sn_snmalloc_set_alloc_hooks_min_size(round_size(3900));
+  void* rounded = sn_malloc(3900);
+  SNMALLOC_CHECK(allocs == 6 && last_alloc.p == rounded);
+  sn_free(rounded);
+  SNMALLOC_CHECK(deallocs == 6 && last_dealloc.p == rounded);
+  sn_snmalloc_set_alloc_hooks_min_size(0);
+
+  // Pool objects are reported when the pool takes them from the allocator
+  // and when it gives them back, not when they are acquired and released.
+  RustPool* pool = rust_pool_create(16, 24, 4);
+  void* o = rust_pool_acquire(pool);
+  SNMALLOC_CHECK(allocs == 10 && last_alloc.size == 24);
+  rust_pool_release(pool, o);
+  SNMALLOC_CHECK(allocs == 10 && deallocs == 6);
+  SNMALLOC_CHECK(rust_pool_trim(pool) == 4);
+  SNMALLOC_CHECK(deallocs == 10 && last_dealloc.size == 24);
+  rust_pool_destroy(pool);
+
+  sn_snmalloc_set_alloc_hooks(nullptr, nullptr);
+  sn_free(sn_malloc(16));
+  SNMALLOC_CHECK(allocs == 10 && deallocs == 10);
+
+  return 0;
+}
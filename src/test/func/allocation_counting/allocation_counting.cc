@@ -0,0 +1,68 @@
+/**
+ * Checks that, when built with SNMALLOC_COUNT_ALLOCATIONS, the malloc and
+ * Rust shims count each thread's allocations and deallocations.
+ */
+
+#define SNMALLOC_COUNT_ALLOCATIONS
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <thread>
+
+int main()
+{
+  setup();
+
+  size_t allocations, deallocations;
+  {
+    counting::Scope scope;
+    SNMALLOC_CHECK(scope.allocations() == 0);
+
+    void* p = sn_malloc(16);
+    void* q = sn_calloc(4, 16);
+    void* r = rust_alloc(64, 100);
+    SNMALLOC_CHECK(scope.allocations() == 3);
+
+#ifndef SNMALLOC_PASS_THROUGH
+    // Resizing within the sizeclass is not an allocation.
+    p = sn_realloc(p, 15);
+    r = rust_realloc(r, 64, 100, 110);
+    SNMALLOC_CHECK(scope.allocations() == 3);
+#endif
+    p = sn_realloc(p, 5000);
+    SNMALLOC_CHECK(scope.allocations() == 4);
+    SNMALLOC_CHECK(scope.deallocations() == 1);
+
+    sn_free(nullptr);
+    SNMALLOC_CHECK(scope.deallocations() == 1);
+
+    SNMALLOC_CHECK(sn_malloc(SIZE_MAX / 2) == nullptr);
+    SNMALLOC_CHECK(sn_calloc(SIZE_MAX / 2, 1) == nullptr);
+    SNMALLOC_CHECK(scope.allocations() == 4);
+
+    // Other threads' allocations are not counted.
+    std::thread t([] { sn_free(sn_malloc(32)); });
+    t.join();
+    SNMALLOC_CHECK(scope.allocations() == 4);
+
+    sn_free(p);
+    sn_free(q);
+    rust_dealloc(r, 64, 100);
+    SNMALLOC_CHECK(scope.deallocations() == 4);
+
+    sn_snmalloc_thread_allocation_counts(&allocations, &deallocations);
+    SNMALLOC_CHECK(allocations >= 4);
+  }
+
+  {
+    counting::Scope scope;
+    size_t a, d;
+    rust_io_buffer_dealloc(rust_io_buffer_alloc(100), 100);
+    rust_thread_allocation_counts(&a, &d);
+    SNMALLOC_CHECK(a == allocations + 1);
+    SNMALLOC_CHECK(d == deallocations + 1);
+    SNMALLOC_CHECK(scope.allocations() == 1);
+  }
+
+  return 0;
+}
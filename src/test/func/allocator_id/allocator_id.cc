@@ -0,0 +1,52 @@
+/**
+ * Checks that the owner of an allocation can be recovered from a pointer to
+ * it, and that it matches the ID of the allocator that allocated it.
+ */
+
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+#include <thread>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+  size_t id = a->get_trunc_id();
+  SNMALLOC_CHECK(id != 0);
+  SNMALLOC_CHECK(id == ThreadAlloc::get()->get_trunc_id());
+
+  void* small = a->alloc(16);
+  void* medium = a->alloc(SLAB_SIZE * 2);
+  void* large = a->alloc(SUPERSLAB_SIZE * 2);
+  int on_stack = 0;
+
+  SNMALLOC_CHECK(a->get_owner_trunc_id(small) == id);
+  SNMALLOC_CHECK(a->get_owner_trunc_id(pointer_offset(small, 8)) == id);
+  SNMALLOC_CHECK(a->get_owner_trunc_id(medium) == id);
+  SNMALLOC_CHECK(a->get_owner_trunc_id(large) == 0);
+  SNMALLOC_CHECK(a->get_owner_trunc_id(&on_stack) == 0);
+
+  size_t other_id = 0;
+  size_t other_view = 0;
+  std::thread t([&]() {
+    auto b = ThreadAlloc::get();
+    other_id = b->get_trunc_id();
+    other_view = b->get_owner_trunc_id(small);
+  });
+  t.join();
+
+  SNMALLOC_CHECK(other_id != 0);
+  SNMALLOC_CHECK(other_id != id);
+  SNMALLOC_CHECK(other_view == id);
+
+  a->dealloc(small);
+  a->dealloc(medium);
+  a->dealloc(large);
+#endif
+  return 0;
+}
@@ -0,0 +1,107 @@
+/**
+ * Checks that arenas bump-allocate aligned objects, take larger chunks for
+ * large objects, and return their chunks for reuse when reset.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+#ifndef SNMALLOC_PASS_THROUGH
+struct Node
+{
+  Node* left;
+  Node* right;
+  int value;
+
+  Node(int value) : left(nullptr), right(nullptr), value(value) {}
+};
+
+size_t reserved()
+{
+  size_t reserved, committed, live;
+  rust_memory_breakdown(&reserved, &committed, &live);
+  return reserved;
+}
+
+/**
+ * Fill `arena` with a tree and a large buffer.
+ */
+void fill(Arena& arena)
+{
+  Node* root = arena.make<Node>(0);
+  SNMALLOC_CHECK(root != nullptr);
+  Node* n = root;
+  for (int i = 1; i < 1000; i++)
+  {
+    n->left = arena.make<Node>(i);
+    SNMALLOC_CHECK(n->left != nullptr);
+    SNMALLOC_CHECK(pointer_align_up(n->left, alignof(Node)) == n->left);
+    n = n->left;
+  }
+
+  auto buffer = static_cast<char*>(arena.alloc(SUPERSLAB_SIZE * 2, 4096));
+  SNMALLOC_CHECK(buffer != nullptr);
+  SNMALLOC_CHECK(pointer_align_up(buffer, 4096) == buffer);
+  memset(buffer, 1, SUPERSLAB_SIZE * 2);
+
+  int sum = 0;
+  for (n = root; n != nullptr; n = n->left)
+    sum += n->value;
+  SNMALLOC_CHECK(sum == 999 * 1000 / 2);
+}
+#endif
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH
+  Arena arena;
+  SNMALLOC_CHECK(arena.footprint() == 0);
+  void* a = arena.alloc(1, 1);
+  void* b = arena.alloc(1, 1);
+  SNMALLOC_CHECK(b == pointer_offset(a, 1));
+  SNMALLOC_CHECK(arena.alloc(0, 1) != nullptr);
+  SNMALLOC_CHECK(arena.footprint() == SUPERSLAB_SIZE);
+
+  fill(arena);
+  SNMALLOC_CHECK(arena.footprint() > SUPERSLAB_SIZE * 2);
+  SNMALLOC_CHECK(arena.allocated() >= SUPERSLAB_SIZE * 2);
+
+  // Chunks returned on reset are reused rather than new ones reserved.
+  arena.reset();
+  SNMALLOC_CHECK((arena.footprint() == 0) && (arena.allocated() == 0));
+  size_t before = reserved();
+  for (int i = 0; i < 4; i++)
+  {
+    fill(arena);
+    arena.reset();
+  }
+  SNMALLOC_CHECK(reserved() == before);
+
+  SNMALLOC_CHECK(arena.alloc(SIZE_MAX / 2, 1) == nullptr);
+
+  RustArena* ra = rust_arena_create(SUPERSLAB_SIZE * 4);
+  SNMALLOC_CHECK(ra != nullptr);
+  for (size_t i = 0; i < 100; i++)
+  {
+    void* p = rust_arena_alloc(ra, 32, 100);
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(pointer_align_up(p, 32) == p);
+  }
+  size_t allocated, footprint;
+  rust_arena_usage(ra, &allocated, &footprint);
+  SNMALLOC_CHECK(allocated == 100 * 100);
+  SNMALLOC_CHECK(footprint == SUPERSLAB_SIZE * 4);
+  rust_arena_reset(ra);
+  rust_arena_usage(ra, &allocated, &footprint);
+  SNMALLOC_CHECK((allocated == 0) && (footprint == 0));
+  rust_arena_alloc(ra, 1, 1);
+  rust_arena_destroy(ra);
+#else
+  SNMALLOC_CHECK(rust_arena_create(0) == nullptr);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,66 @@
+/**
+ * Checks that, with `SNMALLOC_ASAN`, freed small and medium objects are
+ * poisoned apart from their header, and unpoisoned when they are allocated
+ * again.  The ASan interface is replaced by functions that record their last
+ * call, so this does not need the ASan runtime.
+ */
+
+#define SNMALLOC_ASAN
+
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+
+using namespace snmalloc;
+
+void* poisoned = nullptr;
+size_t poisoned_size = 0;
+void* unpoisoned = nullptr;
+size_t unpoisoned_size = 0;
+
+extern "C" void
+__asan_poison_memory_region(void const volatile* addr, size_t size)
+{
+  poisoned = const_cast<void*>(addr);
+  poisoned_size = size;
+}
+
+extern "C" void
+__asan_unpoison_memory_region(void const volatile* addr, size_t size)
+{
+  unpoisoned = const_cast<void*>(addr);
+  unpoisoned_size = size;
+}
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+void check_object(size_t size)
+{
+  auto a = ThreadAlloc::get();
+  size_t rsize = round_size(size);
+
+  void* p = a->alloc(size);
+  SNMALLOC_CHECK(unpoisoned == p);
+  SNMALLOC_CHECK(unpoisoned_size == rsize);
+
+  a->dealloc(p);
+  SNMALLOC_CHECK(poisoned == pointer_offset(p, sizeof(Remote)));
+  SNMALLOC_CHECK(poisoned_size == rsize - sizeof(Remote));
+
+  void* q = a->alloc(size);
+  SNMALLOC_CHECK(unpoisoned == q);
+  SNMALLOC_CHECK(unpoisoned_size == rsize);
+  a->dealloc(q);
+}
+#endif
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  // A small and a medium sizeclass.
+  check_object(48);
+  check_object(SLAB_SIZE * 2);
+#endif
+  return 0;
+}
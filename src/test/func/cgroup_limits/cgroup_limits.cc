@@ -0,0 +1,92 @@
+/**
+ * Checks that cgroup memory limits are parsed, and that the lowest of those
+ * on a cgroup and its ancestors is found.
+ */
+
+#ifdef _WIN32
+/*
+ * The test builds a fake cgroup hierarchy with POSIX calls.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include "../../../override/rust.cc"
+
+#  include <sys/stat.h>
+#  include <test/setup.h>
+#  include <unistd.h>
+
+char root[] = "/tmp/snmalloc-cgroup-XXXXXX";
+
+void make_dir(const char* path)
+{
+  char dir[256];
+  snprintf(dir, sizeof(dir), "%s%s", root, path);
+  SNMALLOC_CHECK(mkdir(dir, 0700) == 0);
+}
+
+void write_file(const char* path, const char* contents)
+{
+  char file[256];
+  snprintf(file, sizeof(file), "%s%s", root, path);
+  FILE* f = fopen(file, "w");
+  SNMALLOC_CHECK(f != nullptr);
+  fputs(contents, f);
+  fclose(f);
+}
+
+int main()
+{
+  setup();
+
+  size_t limit = 1;
+  SNMALLOC_CHECK(cgroup::parse_limit("max\n", limit) && (limit == 0));
+  SNMALLOC_CHECK(cgroup::parse_limit("4096\n", limit) && (limit == 4096));
+  SNMALLOC_CHECK(!cgroup::parse_limit("", limit));
+  SNMALLOC_CHECK(!cgroup::parse_limit("12k\n", limit));
+
+  SNMALLOC_CHECK(mkdtemp(root) != nullptr);
+  make_dir("/a");
+  make_dir("/a/b");
+  make_dir("/a/b/c");
+  write_file("/a/memory.max", "1073741824\n");
+  write_file("/a/memory.high", "max\n");
+  write_file("/a/b/memory.max", "max\n");
+  write_file("/a/b/memory.high", "536870912\n");
+  write_file("/a/b/c/memory.max", "2147483648\n");
+
+  cgroup::Limits limits = cgroup::read_limits(root, "/a/b/c");
+  SNMALLOC_CHECK(limits.max == 1073741824);
+  SNMALLOC_CHECK(limits.high == 536870912);
+  limits = cgroup::read_limits(root, "/a/b/c/");
+  SNMALLOC_CHECK(limits.max == 1073741824);
+  limits = cgroup::read_limits(root, "/");
+  SNMALLOC_CHECK((limits.max == 0) && (limits.high == 0));
+  limits = cgroup::read_limits(root, "/missing");
+  SNMALLOC_CHECK((limits.max == 0) && (limits.high == 0));
+
+  size_t max, high;
+  bool limited = rust_cgroup_limits(&max, &high);
+  SNMALLOC_CHECK(limited == ((max != 0) || (high != 0)));
+
+  const char* files[] = {"/a/b/c/memory.max",
+                         "/a/b/memory.max",
+                         "/a/b/memory.high",
+                         "/a/memory.max",
+                         "/a/memory.high",
+                         "/a/b/c",
+                         "/a/b",
+                         "/a",
+                         ""};
+  for (auto file : files)
+  {
+    char path[256];
+    snprintf(path, sizeof(path), "%s%s", root, file);
+    remove(path);
+  }
+
+  return 0;
+}
+#endif
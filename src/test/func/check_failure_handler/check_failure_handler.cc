@@ -0,0 +1,41 @@
+/**
+ * Checks that a failed client check calls the installed handler with the
+ * offending pointer, its claimed sizeclass and the reason, before aborting.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <cstring>
+#include <test/setup.h>
+
+#if defined(CHECK_CLIENT) && !defined(SNMALLOC_PASS_THROUGH)
+const void* expected_ptr;
+size_t expected_sizeclass;
+
+void handler(const void* p, size_t sizeclass, const char* reason)
+{
+  SNMALLOC_CHECK(p == expected_ptr);
+  SNMALLOC_CHECK(sizeclass == expected_sizeclass);
+  SNMALLOC_CHECK(strstr(reason, "start of an object") != nullptr);
+  // The process would be aborted on return.
+  _Exit(0);
+}
+#endif
+
+int main()
+{
+  setup();
+
+#if defined(CHECK_CLIENT) && !defined(SNMALLOC_PASS_THROUGH)
+  auto p = static_cast<char*>(rust_alloc(1, 64));
+  size_t capacity;
+  expected_sizeclass = rust_sizeclass_of(64, &capacity);
+  expected_ptr = p + 16;
+
+  rust_set_check_failure_handler(handler);
+  rust_dealloc(p + 16, 1, 64);
+  SNMALLOC_CHECK(false);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,35 @@
+/**
+ * Checks that the chunk size follows the chunk size defines, and that the
+ * memory committed by a process that allocates little is about one chunk
+ * plus the allocator's metadata, so shrinks with the chunk size.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  size_t expected =
+#if defined(SNMALLOC_USE_SMALL_CHUNKS)
+    256 * 1024;
+#elif defined(SNMALLOC_USE_LARGE_CHUNKS)
+    bits::is64() ? 16 * 1024 * 1024 : 1024 * 1024;
+#else
+    1024 * 1024;
+#endif
+  SNMALLOC_CHECK(rust_chunk_size() == expected);
+
+#ifndef SNMALLOC_PASS_THROUGH
+  void* p = rust_alloc(8, 16);
+  size_t reserved, committed, live;
+  rust_memory_breakdown(&reserved, &committed, &live);
+  SNMALLOC_CHECK(committed >= rust_chunk_size());
+  SNMALLOC_CHECK(committed <= rust_chunk_size() + 256 * 1024);
+  rust_dealloc(p, 8, 16);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,77 @@
+/**
+ * Checks that decommitted chunks stay resident by default on Linux, and that
+ * `MADV_DONTNEED` advice returns their pages immediately.
+ */
+
+#if !defined(__linux__)
+/*
+ * Only Linux offers a choice of advice.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include "../../../override/rust.cc"
+
+#  include <sys/mman.h>
+#  include <test/setup.h>
+
+#  ifndef SNMALLOC_PASS_THROUGH
+/**
+ * Returns the number of resident pages in the `size` bytes at `p`.
+ */
+size_t resident_pages(void* p, size_t size)
+{
+  static unsigned char vec[1 << 16];
+  size_t pages = size / OS_PAGE_SIZE;
+  SNMALLOC_CHECK(pages <= sizeof(vec));
+  SNMALLOC_CHECK(mincore(p, size, vec) == 0);
+  size_t count = 0;
+  for (size_t i = 0; i < pages; i++)
+    count += vec[i] & 1;
+  return count;
+}
+
+/**
+ * Allocate and touch a large allocation, free it, and return the number of
+ * its pages, apart from the first, that are still resident.
+ */
+size_t resident_after_free()
+{
+  constexpr size_t size = SUPERSLAB_SIZE * 2;
+  auto p = static_cast<char*>(rust_alloc(1, size));
+  SNMALLOC_CHECK(p != nullptr);
+  memset(p, 1, size);
+  rust_dealloc(p, 1, size);
+  return resident_pages(p + OS_PAGE_SIZE, size - OS_PAGE_SIZE);
+}
+#  endif
+
+int main()
+{
+  setup();
+
+  SNMALLOC_CHECK(!rust_set_decommit_advice(3));
+
+#  ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(rust_set_decommit_strategy(DecommitSuper));
+
+  SNMALLOC_CHECK(rust_set_decommit_advice(AdviseNothing));
+  SNMALLOC_CHECK(resident_after_free() > 0);
+
+  SNMALLOC_CHECK(rust_set_decommit_advice(AdviseDontNeed));
+  SNMALLOC_CHECK(resident_after_free() == 0);
+
+  // MADV_FREE only returns pages under pressure, so can only be checked to
+  // leave the memory usable.
+  SNMALLOC_CHECK(rust_set_decommit_advice(AdviseFree));
+  resident_after_free();
+  auto p = static_cast<char*>(rust_alloc(1, SUPERSLAB_SIZE * 2));
+  memset(p, 1, SUPERSLAB_SIZE * 2);
+  rust_dealloc(p, 1, SUPERSLAB_SIZE * 2);
+#  endif
+
+  return 0;
+}
+#endif
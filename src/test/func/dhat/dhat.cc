@@ -0,0 +1,135 @@
+/**
+ * Checks that the DHAT output attributes allocations to their call stacks,
+ * with the totals, peaks and end state that the DHAT viewer shows.
+ */
+
+#ifdef _WIN32
+/*
+ * DHAT output is only supported on POSIX platforms.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  define SNMALLOC_DHAT
+#  define SNMALLOC_NAME_MANGLE(a) our_##a
+#  include "../../../override/malloc.cc"
+
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <test/setup.h>
+
+constexpr size_t batch = 10;
+void* temporary[batch];
+void* kept[batch];
+
+SNMALLOC_SLOW_PATH void alloc_temporary()
+{
+  for (auto& p : temporary)
+    p = our_malloc(100);
+}
+
+SNMALLOC_SLOW_PATH void alloc_kept()
+{
+  for (auto& p : kept)
+    p = our_calloc(1, 1000);
+}
+
+struct Totals
+{
+  size_t pps = 0;
+  size_t total_bytes = 0;
+  size_t total_blocks = 0;
+  size_t max_bytes = 0;
+  size_t peak_bytes = 0;
+  size_t end_bytes = 0;
+  size_t end_blocks = 0;
+  bool root = false;
+};
+
+Totals read_dhat(const char* path)
+{
+  SNMALLOC_CHECK(our_snmalloc_dhat_dump(path) == 0);
+
+  static char json[1 << 20];
+  FILE* f = fopen(path, "r");
+  SNMALLOC_CHECK(f != nullptr);
+  size_t len = fread(json, 1, sizeof(json) - 1, f);
+  json[len] = '\0';
+  fclose(f);
+
+  Totals t;
+  SNMALLOC_CHECK(strncmp(json, "{\"dhatFileVersion\":2,", 21) == 0);
+  for (char* p = strstr(json, "{\"tb\":"); p != nullptr;
+       p = strstr(p + 1, "{\"tb\":"))
+  {
+    size_t tb, tbk, tl, mb, mbk, gb, gbk, eb, ebk;
+    SNMALLOC_CHECK(
+      sscanf(
+        p,
+        "{\"tb\":%zu,\"tbk\":%zu,\"tl\":%zu,\"mb\":%zu,\"mbk\":%zu,"
+        "\"gb\":%zu,\"gbk\":%zu,\"eb\":%zu,\"ebk\":%zu,\"fs\":[",
+        &tb,
+        &tbk,
+        &tl,
+        &mb,
+        &mbk,
+        &gb,
+        &gbk,
+        &eb,
+        &ebk) == 9);
+    t.pps++;
+    t.total_bytes += tb;
+    t.total_blocks += tbk;
+    t.max_bytes += mb;
+    t.peak_bytes += gb;
+    t.end_bytes += eb;
+    t.end_blocks += ebk;
+  }
+  t.root = strstr(json, "\"ftbl\":[\n\"[root]\"") != nullptr;
+  return t;
+}
+
+int main()
+{
+  setup();
+
+  char path[] = "/tmp/snmalloc_dhat_XXXXXX";
+  int fd = mkstemp(path);
+  SNMALLOC_CHECK(fd >= 0);
+  close(fd);
+
+  alloc_temporary();
+  for (auto p : temporary)
+    our_free(p);
+  alloc_kept();
+
+  auto t = read_dhat(path);
+  SNMALLOC_CHECK(t.pps >= 2);
+  SNMALLOC_CHECK(t.total_bytes == batch * (100 + 1000));
+  SNMALLOC_CHECK(t.total_blocks == 2 * batch);
+  SNMALLOC_CHECK(t.max_bytes == batch * (100 + 1000));
+  SNMALLOC_CHECK(t.peak_bytes == batch * 1000);
+  SNMALLOC_CHECK(t.end_bytes == batch * 1000);
+  SNMALLOC_CHECK(t.end_blocks == batch);
+  SNMALLOC_CHECK(t.root);
+
+  // Moving reallocations are attributed to the reallocating call stack.
+  for (auto& p : kept)
+    p = our_realloc(p, 5000);
+  t = read_dhat(path);
+  SNMALLOC_CHECK(t.total_blocks == 3 * batch);
+  SNMALLOC_CHECK(t.end_bytes == batch * 5000);
+  // The peak is reached while the last block is being moved, so includes it.
+  SNMALLOC_CHECK(t.peak_bytes == (batch * 5000) + 1000);
+
+  for (auto p : kept)
+    our_free(p);
+  t = read_dhat(path);
+  SNMALLOC_CHECK(t.end_blocks == 0);
+
+  unlink(path);
+  return 0;
+}
+#endif
@@ -0,0 +1,79 @@
+/**
+ * Checks that memory donated through the Rust shim is counted as reserved
+ * and used to serve allocations, which are zeroed if asked even though the
+ * donated memory was not.
+ */
+
+#ifdef _WIN32
+/*
+ * The test maps the memory to donate with `mmap`.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include "../../../override/rust.cc"
+
+#  include <sys/mman.h>
+#  include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  // Start the allocator, so that its own reservation is already made.
+  rust_dealloc(rust_alloc(1, 1), 1, 1);
+
+  constexpr size_t length = SUPERSLAB_SIZE * 16;
+  auto base = static_cast<char*>(mmap(
+    nullptr,
+    length,
+    PROT_READ | PROT_WRITE,
+    MAP_PRIVATE | MAP_ANONYMOUS | MAP_NORESERVE,
+    -1,
+    0));
+  SNMALLOC_CHECK(base != MAP_FAILED);
+
+  SNMALLOC_CHECK(!rust_donate_range(base + 1, OS_PAGE_SIZE));
+
+#  ifndef SNMALLOC_PASS_THROUGH
+  // Donated memory need not be zero.
+  memset(base, 0xa5, length);
+
+  size_t reserved, committed, live;
+  rust_memory_breakdown(&reserved, &committed, &live);
+  SNMALLOC_CHECK(rust_donate_range(base, length));
+  size_t reserved_after;
+  rust_memory_breakdown(&reserved_after, &committed, &live);
+  SNMALLOC_CHECK(reserved_after == reserved + length);
+
+  // Donated blocks are preferred over those of the same size left over from
+  // the allocator's own reservations, so one of these lands in the range.
+  constexpr size_t count = 32;
+  void* objects[count];
+  bool donated = false;
+  for (auto& p : objects)
+  {
+    p = rust_alloc_zeroed(1, SUPERSLAB_SIZE);
+    SNMALLOC_CHECK(p != nullptr);
+    auto c = static_cast<char*>(p);
+    if ((c >= base) && (c + SUPERSLAB_SIZE <= base + length))
+    {
+      donated = true;
+      for (size_t i = 0; i < SUPERSLAB_SIZE; i++)
+        SNMALLOC_CHECK(c[i] == 0);
+      memset(c, 1, SUPERSLAB_SIZE);
+    }
+  }
+  SNMALLOC_CHECK(donated);
+  for (auto p : objects)
+    rust_dealloc(p, 1, SUPERSLAB_SIZE);
+#  else
+  SNMALLOC_CHECK(!rust_donate_range(base, length));
+  munmap(base, length);
+#  endif
+
+  return 0;
+}
+#endif
@@ -0,0 +1,56 @@
+/**
+ * Checks that a fatal error is passed to the installed error handler before
+ * the process is aborted, including one raised inside the platform layer.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <cstring>
+#include <test/setup.h>
+#ifdef __linux__
+#  include <sys/resource.h>
+#  include <sys/wait.h>
+#  include <unistd.h>
+#endif
+
+const char* expected = "Not allocated by this allocator";
+
+void handler(const char* message)
+{
+  SNMALLOC_CHECK(strcmp(message, expected) == 0);
+  // The process would be aborted on return.
+  _Exit(0);
+}
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific errors
+#  ifdef __linux__
+  // The platform reports running out of address space as a fatal error.
+  pid_t pid = fork();
+  SNMALLOC_CHECK(pid >= 0);
+  if (pid == 0)
+  {
+    expected = "Out of memory";
+    rust_set_error_handler(handler);
+    rlimit limit = {size_t(1) << 33, size_t(1) << 33};
+    setrlimit(RLIMIT_AS, &limit);
+    rust_alloc(8, size_t(1) << 36);
+    _Exit(1);
+  }
+  int status;
+  SNMALLOC_CHECK(waitpid(pid, &status, 0) == pid);
+  SNMALLOC_CHECK(WIFEXITED(status) && (WEXITSTATUS(status) == 0));
+#  endif
+
+  rust_set_error_handler(handler);
+
+  int on_stack = 0;
+  sn_malloc_usable_size(&on_stack);
+  SNMALLOC_CHECK(false);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,141 @@
+/**
+ * Checks that, when built with SNMALLOC_FAILURE_INJECTION, the malloc and
+ * Rust shims fail the allocations requested, and only those.
+ */
+
+#define SNMALLOC_FAILURE_INJECTION
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+void test_every()
+{
+  sn_snmalloc_fail_every(3);
+  for (size_t i = 1; i <= 9; i++)
+  {
+    errno = 0;
+    void* p = sn_malloc(16);
+    if ((i % 3) == 0)
+    {
+      SNMALLOC_CHECK(p == nullptr);
+      SNMALLOC_CHECK(errno == ENOMEM);
+    }
+    else
+    {
+      SNMALLOC_CHECK(p != nullptr);
+      sn_free(p);
+    }
+  }
+  SNMALLOC_CHECK(sn_snmalloc_failures() == 3);
+  sn_snmalloc_fail_reset();
+  SNMALLOC_CHECK(sn_snmalloc_failures() == 0);
+}
+
+void test_nth()
+{
+  sn_snmalloc_fail_nth(4);
+  for (size_t i = 1; i <= 8; i++)
+  {
+    void* p = sn_malloc(16);
+    SNMALLOC_CHECK((p == nullptr) == (i == 4));
+    sn_free(p);
+  }
+  SNMALLOC_CHECK(sn_snmalloc_failures() == 1);
+
+  rust_fail_nth(2);
+  void* p = rust_alloc(8, 16);
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(rust_alloc(8, 16) == nullptr);
+  rust_dealloc(p, 8, 16);
+  sn_snmalloc_fail_reset();
+}
+
+size_t random_failures(size_t per_million, uint64_t seed, bool* failed)
+{
+  sn_snmalloc_fail_random(per_million, seed);
+  size_t n = 0;
+  for (size_t i = 0; i < 1000; i++)
+  {
+    void* p = sn_malloc(16);
+    failed[i] = (p == nullptr);
+    if (p == nullptr)
+      n++;
+    sn_free(p);
+  }
+  sn_snmalloc_fail_reset();
+  return n;
+}
+
+void test_random()
+{
+  static bool first[1000];
+  static bool second[1000];
+
+  SNMALLOC_CHECK(random_failures(0, 1, first) == 0);
+  SNMALLOC_CHECK(random_failures(1000000, 1, first) == 1000);
+
+  size_t n = random_failures(250000, 42, first);
+  SNMALLOC_CHECK((n > 150) && (n < 350));
+  SNMALLOC_CHECK(random_failures(250000, 42, second) == n);
+  for (size_t i = 0; i < 1000; i++)
+    SNMALLOC_CHECK(first[i] == second[i]);
+}
+
+void test_above()
+{
+  sn_snmalloc_fail_above(1024);
+  void* p = sn_malloc(1024);
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(sn_malloc(1025) == nullptr);
+  SNMALLOC_CHECK(sn_calloc(2, 1000) == nullptr);
+  SNMALLOC_CHECK(sn_memalign(64, 2000) == nullptr);
+  void* q = nullptr;
+  SNMALLOC_CHECK(sn_posix_memalign(&q, 64, 2000) == ENOMEM);
+  SNMALLOC_CHECK(sn_realloc(p, 4096) == nullptr);
+  p = sn_realloc(p, 512);
+  SNMALLOC_CHECK(p != nullptr);
+  sn_free(p);
+
+  void* r = rust_alloc(8, 100);
+  SNMALLOC_CHECK(rust_alloc(8, 5000) == nullptr);
+  SNMALLOC_CHECK(rust_alloc_zeroed(8, 5000) == nullptr);
+  SNMALLOC_CHECK(rust_realloc(r, 8, 100, 5000) == nullptr);
+  // The original block is untouched by a failed reallocation.
+  rust_dealloc(r, 8, 100);
+
+  SNMALLOC_CHECK(sn_snmalloc_failures() == 8);
+  sn_snmalloc_fail_reset();
+
+  p = sn_malloc(4096);
+  SNMALLOC_CHECK(p != nullptr);
+  sn_free(p);
+}
+
+void test_scope()
+{
+  {
+    failure::Scope s;
+    SNMALLOC_CHECK(sn_malloc(16) == nullptr);
+    rust_fail_scope_enter();
+    SNMALLOC_CHECK(rust_alloc(8, 16) == nullptr);
+    rust_fail_scope_exit();
+    SNMALLOC_CHECK(sn_malloc(16) == nullptr);
+  }
+  void* p = sn_malloc(16);
+  SNMALLOC_CHECK(p != nullptr);
+  sn_free(p);
+  sn_snmalloc_fail_reset();
+}
+
+int main()
+{
+  setup();
+
+  test_every();
+  test_nth();
+  test_random();
+  test_above();
+  test_scope();
+
+  return 0;
+}
@@ -0,0 +1,102 @@
+/**
+ * Builds a linked list in a file-backed heap, using offsets rather than
+ * pointers, and checks that it can be read back from the file after the heap
+ * is closed.
+ */
+
+#ifdef SNMALLOC_PASS_THROUGH
+/*
+ * This test does not make sense with malloc pass-through, skip it.
+ */
+int main()
+{
+  return 0;
+}
+#elif defined(_WIN32)
+/*
+ * File-backed heaps are only supported on POSIX platforms.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include <mem/fileheap.h>
+#  include <snmalloc.h>
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <test/setup.h>
+
+using namespace snmalloc;
+
+struct Node
+{
+  FileOffset<Node> next;
+  size_t value;
+};
+
+int main()
+{
+  setup();
+
+  char path[] = "/tmp/snmalloc_file_heap_XXXXXX";
+  int fd = mkstemp(path);
+  SNMALLOC_CHECK(fd >= 0);
+  close(fd);
+
+  SNMALLOC_CHECK(FileHeap::open(path, SUPERSLAB_SIZE) == nullptr);
+
+  constexpr size_t size =
+    bits::align_up(FileHeap::min_size() + 4 * SUPERSLAB_SIZE, OS_PAGE_SIZE);
+  auto heap = FileHeap::open(path, size);
+  SNMALLOC_CHECK(heap != nullptr);
+
+  // Build the list back to front, so that it reads in order.
+  constexpr size_t count = 1000;
+  Node* head = nullptr;
+  for (size_t i = count; i > 0; i--)
+  {
+    auto n = static_cast<Node*>(heap->alloc(sizeof(Node)));
+    SNMALLOC_CHECK(heap->contains(n));
+    n->next = heap->offset_of(head);
+    n->value = i - 1;
+    head = n;
+  }
+  heap->set_root(head);
+  SNMALLOC_CHECK(heap->root<Node>() == head);
+  SNMALLOC_CHECK(heap->resolve(head->next)->value == 1);
+
+  // Freed memory is reused by the heap.
+  void* large = heap->alloc(SUPERSLAB_SIZE);
+  SNMALLOC_CHECK(heap->contains(large));
+  heap->dealloc(large);
+  SNMALLOC_CHECK(heap->alloc(size) == nullptr);
+  SNMALLOC_CHECK(heap->sync());
+  FileHeap::close(heap);
+
+  // Read the file back and walk the list without the heap.
+  FILE* f = fopen(path, "rb");
+  SNMALLOC_CHECK(f != nullptr);
+  auto base = malloc(size);
+  SNMALLOC_CHECK(fread(base, 1, size, f) == size);
+  fclose(f);
+
+  auto header = static_cast<FileHeap::Header*>(base);
+  SNMALLOC_CHECK(
+    memcmp(header->magic, FileHeap::Header::MAGIC, sizeof(header->magic)) == 0);
+  SNMALLOC_CHECK(header->size == size);
+
+  size_t seen = 0;
+  for (auto n = FileOffset<Node>(header->root).get(base); n != nullptr;
+       n = n->next.get(base))
+  {
+    SNMALLOC_CHECK(n->value == seen);
+    seen++;
+  }
+  SNMALLOC_CHECK(seen == count);
+
+  free(base);
+  unlink(path);
+  return 0;
+}
+#endif
@@ -0,0 +1,46 @@
+/**
+ * Checks that `flush_remote` posts the objects a thread has freed for
+ * another allocator, which would otherwise wait in its remote cache, and
+ * that the owner's `flush_remote` then processes all of them.
+ */
+
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  // Few enough bytes that the freeing thread's remote cache does not post
+  // them by itself.
+  constexpr size_t object_size = 64;
+  constexpr size_t count = 100;
+
+  auto a = ThreadAlloc::get();
+  std::vector<void*> objects;
+  for (size_t i = 0; i < count; i++)
+    objects.push_back(a->alloc(object_size));
+
+  std::thread t([&]() {
+    auto b = ThreadAlloc::get();
+    for (auto p : objects)
+      b->dealloc(p);
+    // A new allocator posts on its first remote free, and caches the rest.
+    SNMALLOC_CHECK(a->remote_queue_depth().first < count);
+    SNMALLOC_CHECK(flush_remote() == 0);
+    SNMALLOC_CHECK(a->remote_queue_depth().first == count);
+  });
+  t.join();
+
+  SNMALLOC_CHECK(flush_remote() == count);
+  SNMALLOC_CHECK(a->remote_queue_depth().first == 0);
+  SNMALLOC_CHECK(flush_remote() == 0);
+#endif
+  return 0;
+}
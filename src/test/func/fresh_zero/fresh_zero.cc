@@ -0,0 +1,64 @@
+/**
+ * Checks that zeroed large allocations from fresh memory are not cleared
+ * again, that this is counted, and that reused memory is still zeroed.
+ */
+
+#include <snmalloc.h>
+#include <test/setup.h>
+
+using namespace snmalloc;
+
+#ifdef USE_SNMALLOC_STATS
+size_t fresh_zero_count()
+{
+  Stats stats;
+  current_alloc_pool()->aggregate_stats(stats);
+  return stats.fresh_zero_count;
+}
+#endif
+
+void check_zero(void* p, size_t size)
+{
+  auto bytes = static_cast<char*>(p);
+  for (size_t i = 0; i < size; i += OS_PAGE_SIZE / 2)
+    SNMALLOC_CHECK(bytes[i] == 0);
+  SNMALLOC_CHECK(bytes[size - 1] == 0);
+}
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+
+  // Use a class that nothing else in the process allocates.
+  const size_t size = bits::one_at_bit(SUPERSLAB_BITS) << 3;
+
+#  ifdef USE_SNMALLOC_STATS
+  size_t before = fresh_zero_count();
+#  endif
+  void* p = a->alloc<YesZero>(size);
+  check_zero(p, size);
+#  ifdef USE_SNMALLOC_STATS
+  SNMALLOC_CHECK(fresh_zero_count() == before + 1);
+#  endif
+
+  // Dirty the memory, then reuse it both retained and decommitted.
+  memset(p, 0xff, size);
+  for (size_t retain : {size_t(1), size_t(0)})
+  {
+    set_large_retention(3, retain);
+    a->dealloc(p, size);
+    p = a->alloc<YesZero>(size);
+    check_zero(p, size);
+    memset(p, 0xff, size);
+  }
+#  ifdef USE_SNMALLOC_STATS
+  SNMALLOC_CHECK(fresh_zero_count() == before + 1);
+#  endif
+  a->dealloc(p, size);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,116 @@
+/**
+ * Checks that the Rust shim's heap dump is well-formed JSON and describes the
+ * chunks of live allocations, giving only the kind of another thread's
+ * chunks.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <thread>
+
+static char output[1 << 22];
+static size_t output_len = 0;
+
+bool append(const char* data, size_t len, void* context)
+{
+  SNMALLOC_CHECK(context == &output_len);
+  SNMALLOC_CHECK(output_len + len < sizeof(output));
+  memcpy(output + output_len, data, len);
+  output_len += len;
+  output[output_len] = '\0';
+  return true;
+}
+
+bool refuse(const char*, size_t, void*)
+{
+  return false;
+}
+
+/**
+ * Checks that brackets balance outside strings, which with the fixed layout
+ * of the output is enough to catch a broken writer.
+ */
+bool balanced(const char* s)
+{
+  size_t depth = 0;
+  bool in_string = false;
+  for (; *s != '\0'; s++)
+  {
+    if (*s == '"')
+      in_string = !in_string;
+    else if (in_string)
+      continue;
+    else if ((*s == '{') || (*s == '['))
+      depth++;
+    else if ((*s == '}') || (*s == ']'))
+    {
+      if (depth == 0)
+        return false;
+      depth--;
+    }
+  }
+  return (depth == 0) && !in_string;
+}
+
+int main()
+{
+  setup();
+
+  void* small = rust_alloc(8, 48);
+  void* medium = rust_alloc(8, SLAB_SIZE * 2);
+  void* large = rust_alloc(8, SUPERSLAB_SIZE * 4);
+  void* other = nullptr;
+  std::thread([&]() { other = rust_alloc(8, SLAB_SIZE * 2); }).join();
+
+  SNMALLOC_CHECK(rust_heap_dump(append, &output_len));
+  SNMALLOC_CHECK(strncmp(output, "{\"version\":1,", 13) == 0);
+  SNMALLOC_CHECK(strcmp(output + output_len - 2, "}\n") == 0);
+  SNMALLOC_CHECK(balanced(output));
+  SNMALLOC_CHECK(strstr(output, "\"sizeclasses\":[") != nullptr);
+  SNMALLOC_CHECK(strstr(output, "\"large_cache\":[") != nullptr);
+
+#ifndef SNMALLOC_PASS_THROUGH // Chunks are not tracked with pass-through
+  char entry[128];
+  snprintf(
+    entry,
+    sizeof(entry),
+    "{\"address\":%zu,\"kind\":\"large\",\"size\":%zu}",
+    static_cast<size_t>(address_cast(large)),
+    SUPERSLAB_SIZE * 4);
+  SNMALLOC_CHECK(strstr(output, entry) != nullptr);
+
+  snprintf(
+    entry,
+    sizeof(entry),
+    "{\"address\":%zu,\"kind\":\"medium\",\"object_size\":%zu,",
+    static_cast<size_t>(
+      bits::align_down(address_cast(medium), SUPERSLAB_SIZE)),
+    rust_usable_size(8, SLAB_SIZE * 2));
+  SNMALLOC_CHECK(strstr(output, entry) != nullptr);
+
+  snprintf(
+    entry,
+    sizeof(entry),
+    "{\"address\":%zu,\"kind\":\"superslab\",",
+    static_cast<size_t>(
+      bits::align_down(address_cast(small), SUPERSLAB_SIZE)));
+  SNMALLOC_CHECK(strstr(output, entry) != nullptr);
+
+  snprintf(
+    entry,
+    sizeof(entry),
+    "{\"address\":%zu,\"kind\":\"medium\"}",
+    static_cast<size_t>(
+      bits::align_down(address_cast(other), SUPERSLAB_SIZE)));
+  SNMALLOC_CHECK(strstr(output, entry) != nullptr);
+#endif
+
+  SNMALLOC_CHECK(!rust_heap_dump(refuse, nullptr));
+
+  rust_dealloc(small, 8, 48);
+  rust_dealloc(medium, 8, SLAB_SIZE * 2);
+  rust_dealloc(large, 8, SUPERSLAB_SIZE * 4);
+  rust_dealloc(other, 8, SLAB_SIZE * 2);
+  return 0;
+}
@@ -0,0 +1,236 @@
+/**
+ * Checks that the heap profiler records live sampled allocations by call
+ * stack, forgets them when they are freed, and writes profiles that `pprof`
+ * can read, in both its text and protocol buffer formats.
+ */
+
+#ifdef _WIN32
+/*
+ * The heap profiler is only supported on POSIX platforms.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  define SNMALLOC_PROFILING
+#  define SNMALLOC_NAME_MANGLE(a) our_##a
+#  include "../../../override/malloc.cc"
+
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <test/setup.h>
+
+constexpr size_t batch = 100;
+void* small[batch];
+void* large[batch];
+
+SNMALLOC_SLOW_PATH void alloc_small()
+{
+  for (auto& p : small)
+    p = our_malloc(1000);
+}
+
+SNMALLOC_SLOW_PATH void alloc_large()
+{
+  for (auto& p : large)
+    p = our_calloc(1, 20000);
+}
+
+struct Profile
+{
+  size_t objects = 0;
+  size_t bytes = 0;
+  size_t rate = 0;
+  size_t stacks = 0;
+  size_t stack_objects = 0;
+  bool mapped = false;
+};
+
+Profile read_profile(const char* path)
+{
+  SNMALLOC_CHECK(our_snmalloc_profile_dump(path) == 0);
+
+  Profile result;
+  FILE* f = fopen(path, "r");
+  SNMALLOC_CHECK(f != nullptr);
+  SNMALLOC_CHECK(
+    fscanf(
+      f,
+      "heap profile: %zu: %zu [0: 0] @ heap_v2/%zu\n",
+      &result.objects,
+      &result.bytes,
+      &result.rate) == 3);
+
+  char line[4096];
+  while (fgets(line, sizeof(line), f) != nullptr)
+  {
+    size_t objects;
+    size_t bytes;
+    if (sscanf(line, "%zu: %zu [0: 0] @ 0x", &objects, &bytes) == 2)
+    {
+      result.stacks++;
+      result.stack_objects += objects;
+    }
+    else if (strcmp(line, "MAPPED_LIBRARIES:\n") == 0)
+      result.mapped = true;
+  }
+  fclose(f);
+  return result;
+}
+
+struct Reader
+{
+  const unsigned char* p;
+  const unsigned char* end;
+
+  uint64_t varint()
+  {
+    uint64_t v = 0;
+    for (size_t shift = 0; p < end; shift += 7)
+    {
+      uint64_t c = *p++;
+      v |= (c & 0x7f) << shift;
+      if (c < 0x80)
+        break;
+    }
+    return v;
+  }
+
+  /**
+   * Read the next field, returning its number, and its value or contents.
+   */
+  uint64_t field(uint64_t& value, Reader& contents)
+  {
+    uint64_t key = varint();
+    if ((key & 7) == 0)
+      value = varint();
+    else
+    {
+      SNMALLOC_CHECK((key & 7) == 2);
+      size_t len = varint();
+      SNMALLOC_CHECK(len <= static_cast<size_t>(end - p));
+      contents = {p, p + len};
+      p += len;
+    }
+    return key >> 3;
+  }
+};
+
+/**
+ * Sum the values of the samples in a `pprof` protocol buffer.
+ */
+Profile read_pprof(const char* path)
+{
+  SNMALLOC_CHECK(our_snmalloc_profile_dump_pprof(path) == 0);
+
+  static unsigned char data[1 << 20];
+  FILE* f = fopen(path, "rb");
+  SNMALLOC_CHECK(f != nullptr);
+  size_t len = fread(data, 1, sizeof(data), f);
+  fclose(f);
+
+  Profile result;
+  size_t strings = 0;
+  Reader r{data, data + len};
+  while (r.p < r.end)
+  {
+    uint64_t value = 0;
+    Reader contents{nullptr, nullptr};
+    switch (r.field(value, contents))
+    {
+      case 2:
+      {
+        result.stacks++;
+        while (contents.p < contents.end)
+        {
+          Reader packed{nullptr, nullptr};
+          if (contents.field(value, packed) == 2)
+          {
+            result.objects += packed.varint();
+            result.bytes += packed.varint();
+          }
+        }
+        break;
+      }
+      case 3:
+        result.mapped = true;
+        break;
+      case 6:
+        if (strings++ == 0)
+          SNMALLOC_CHECK(contents.p == contents.end);
+        break;
+      case 12:
+        result.rate = value;
+        break;
+    }
+  }
+  result.stack_objects = result.objects;
+  return result;
+}
+
+int main()
+{
+  setup();
+
+  char path[] = "/tmp/snmalloc_heap_profile_XXXXXX";
+  int fd = mkstemp(path);
+  SNMALLOC_CHECK(fd >= 0);
+  close(fd);
+
+  // With a mean of one byte between samples, every allocation is sampled.
+  our_snmalloc_profile_set_rate(1);
+  alloc_small();
+  alloc_large();
+
+  auto p = read_profile(path);
+  SNMALLOC_CHECK(p.rate == 1);
+  SNMALLOC_CHECK(p.objects == 2 * batch);
+  SNMALLOC_CHECK(p.bytes == batch * (1000 + 20000));
+  SNMALLOC_CHECK(p.stacks >= 2);
+  SNMALLOC_CHECK(p.stack_objects == p.objects);
+#  ifdef __linux__
+  SNMALLOC_CHECK(p.mapped);
+#  endif
+
+  // At this rate, the samples are not scaled.
+  auto pb = read_pprof(path);
+  SNMALLOC_CHECK(pb.rate == 1);
+  SNMALLOC_CHECK(pb.objects == p.objects);
+  SNMALLOC_CHECK(pb.bytes == p.bytes);
+  SNMALLOC_CHECK(pb.stacks == p.stacks);
+#  ifdef __linux__
+  SNMALLOC_CHECK(pb.mapped);
+#  endif
+
+  for (auto q : large)
+    our_free(q);
+  p = read_profile(path);
+  SNMALLOC_CHECK(p.objects == batch);
+  SNMALLOC_CHECK(p.bytes == batch * 1000);
+
+  // Moving reallocations are tracked at the new address and size.
+  for (auto& q : small)
+    q = our_realloc(q, 5000);
+  p = read_profile(path);
+  SNMALLOC_CHECK(p.objects == batch);
+  SNMALLOC_CHECK(p.bytes == batch * 5000);
+
+  // Once disabled, nothing more is sampled.
+  our_snmalloc_profile_set_rate(0);
+  alloc_large();
+  p = read_profile(path);
+  SNMALLOC_CHECK(p.rate == 0);
+  SNMALLOC_CHECK(p.objects == batch);
+
+  for (auto q : small)
+    our_free(q);
+  for (auto q : large)
+    our_free(q);
+  p = read_profile(path);
+  SNMALLOC_CHECK(p.objects == 0);
+
+  unlink(path);
+  return 0;
+}
+#endif
@@ -0,0 +1,52 @@
+/**
+ * Checks that, with `SNMALLOC_HUGEPAGES`, `huge_page_bytes` counts the huge
+ * pages that back the heap, and no others.  Whether the kernel provides huge
+ * pages at all depends on its configuration, so this only checks that they
+ * are counted if it does.
+ */
+
+#define SNMALLOC_HUGEPAGES
+
+#include <snmalloc.h>
+#include <string.h>
+#include <test/setup.h>
+
+#if defined(__linux__)
+#  include <sys/mman.h>
+#endif
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+
+  // Large allocations start on a chunk boundary, so this spans whole huge
+  // pages.
+  const size_t size = bits::one_at_bit(24);
+  void* p = a->alloc(size);
+  memset(p, 0x7e, size);
+
+  size_t heap = huge_page_bytes();
+  printf("Huge pages backing the heap: %zu bytes\n", heap);
+  SNMALLOC_CHECK(heap <= memory_breakdown().reserved);
+  SNMALLOC_CHECK(heap % bits::one_at_bit(21) == 0);
+
+#  if defined(__linux__) && defined(MADV_HUGEPAGE)
+  // Huge pages outside the heap are not counted.
+  void* q = mmap(
+    nullptr, size, PROT_READ | PROT_WRITE, MAP_PRIVATE | MAP_ANONYMOUS, -1, 0);
+  SNMALLOC_CHECK(q != MAP_FAILED);
+  madvise(q, size, MADV_HUGEPAGE);
+  memset(q, 0x7e, size);
+  SNMALLOC_CHECK(huge_page_bytes() == heap);
+  munmap(q, size);
+#  endif
+
+  a->dealloc(p, size);
+#endif
+  return 0;
+}
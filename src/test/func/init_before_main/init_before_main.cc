@@ -0,0 +1,40 @@
+/**
+ * Checks that, when built with SNMALLOC_INIT_BEFORE_MAIN, the main thread's
+ * allocator is set up before main, and that the explicit initialisation
+ * function sets up the calling thread's allocator.
+ */
+
+#define SNMALLOC_INIT_BEFORE_MAIN
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <thread>
+
+bool initialised()
+{
+  return !needs_initialisation(ThreadAlloc::get_reference());
+}
+
+int main()
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(initialised());
+#endif
+
+  setup();
+
+  std::thread t([] {
+#ifndef SNMALLOC_PASS_THROUGH
+    SNMALLOC_CHECK(!initialised());
+    rust_init();
+    SNMALLOC_CHECK(initialised());
+#endif
+    rust_init();
+    void* p = rust_alloc(8, 64);
+    SNMALLOC_CHECK(p != nullptr);
+    rust_dealloc(p, 8, 64);
+  });
+  t.join();
+
+  return 0;
+}
@@ -0,0 +1,76 @@
+/**
+ * Checks that the number of freed large chunks kept committed follows the
+ * run-time retention limit, and that reuse is counted as cache hits.
+ */
+
+#include <snmalloc.h>
+#include <test/setup.h>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+
+  // Use a class that nothing else in the process allocates.
+  const size_t large_class = 2;
+  const size_t size = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+  const size_t n = 6;
+  const size_t limit = 2;
+
+  set_large_retention(large_class, limit);
+  SNMALLOC_CHECK(large_cache_stats(large_class).retention_limit == limit);
+
+  void* p[n];
+  auto before = large_cache_stats(large_class);
+  for (size_t i = 0; i < n; i++)
+    p[i] = a->alloc(size);
+  auto after_alloc = large_cache_stats(large_class);
+  SNMALLOC_CHECK(after_alloc.misses == before.misses + n);
+
+  auto breakdown = memory_breakdown();
+  for (size_t i = 0; i < n; i++)
+    a->dealloc(p[i], size);
+  auto after_free = large_cache_stats(large_class);
+  SNMALLOC_CHECK(after_free.retained_committed == limit);
+
+  // The chunks beyond the limit have been returned to the OS, apart from the
+  // first page of each.
+  size_t released = (n - limit) * (size - OS_PAGE_SIZE);
+  auto after_breakdown = memory_breakdown();
+  SNMALLOC_CHECK(after_breakdown.committed == breakdown.committed - released);
+  SNMALLOC_CHECK(after_breakdown.released == breakdown.released + released);
+  SNMALLOC_CHECK(after_breakdown.releases == breakdown.releases + (n - limit));
+
+  // Reuse every chunk, committed or not, and check the contents are usable.
+  for (size_t i = 0; i < n; i++)
+  {
+    p[i] = a->alloc<YesZero>(size);
+    auto bytes = static_cast<char*>(p[i]);
+    SNMALLOC_CHECK(bytes[0] == 0 && bytes[size - 1] == 0);
+    bytes[size / 2] = 1;
+  }
+  auto after_reuse = large_cache_stats(large_class);
+  SNMALLOC_CHECK(after_reuse.hits == after_free.hits + n);
+  SNMALLOC_CHECK(after_reuse.retained_committed == 0);
+
+  // With no limit, every chunk is retained committed.
+  set_large_retention(large_class, SIZE_MAX);
+  for (size_t i = 0; i < n; i++)
+    a->dealloc(p[i], size);
+  SNMALLOC_CHECK(large_cache_stats(large_class).retained_committed == n);
+
+  // With a limit of zero, nothing is retained committed.
+  set_large_retention(large_class, 0);
+  for (size_t i = 0; i < n; i++)
+    p[i] = a->alloc(size);
+  for (size_t i = 0; i < n; i++)
+    a->dealloc(p[i], size);
+  SNMALLOC_CHECK(large_cache_stats(large_class).retained_committed == 0);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,109 @@
+/**
+ * Checks that locked allocations are whole pages, aligned as requested,
+ * locked while live and unlocked when freed, and that a request beyond the
+ * process's limit on locked memory fails cleanly.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <vector>
+
+#ifdef __linux__
+#  include <sys/resource.h>
+#  include <unistd.h>
+#endif
+
+#ifdef __linux__
+/**
+ * The locked memory of this process in KiB, from `/proc/self/status`.
+ */
+size_t locked_kib()
+{
+  FILE* f = fopen("/proc/self/status", "r");
+  SNMALLOC_CHECK(f != nullptr);
+  char line[256];
+  size_t kib = 0;
+  while (fgets(line, sizeof(line), f) != nullptr)
+  {
+    if (strncmp(line, "VmLck:", 6) == 0)
+      kib = strtoull(line + 6, nullptr, 10);
+  }
+  fclose(f);
+  return kib;
+}
+#endif
+
+int main()
+{
+  setup();
+
+  if (!pal_supports<PageLocking, Pal>)
+  {
+    SNMALLOC_CHECK(rust_alloc_locked(16, 32) == nullptr);
+    return 0;
+  }
+
+  struct Layout
+  {
+    size_t alignment;
+    size_t size;
+  };
+  std::vector<Layout> layouts = {{1, 1},
+                                 {16, 32},
+                                 {8, OS_PAGE_SIZE - 1},
+                                 {8, OS_PAGE_SIZE},
+                                 {64, OS_PAGE_SIZE + 1},
+                                 {OS_PAGE_SIZE * 4, OS_PAGE_SIZE * 3},
+                                 {16, 0}};
+
+  std::vector<void*> live;
+#ifdef __linux__
+  size_t before = locked_kib();
+#endif
+  for (auto& l : layouts)
+  {
+    auto p = static_cast<char*>(rust_alloc_locked(l.alignment, l.size));
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(is_aligned_block<OS_PAGE_SIZE>(p, OS_PAGE_SIZE));
+    SNMALLOC_CHECK(
+      (address_cast(p) % bits::max(l.alignment, OS_PAGE_SIZE)) == 0);
+    memset(p, 0xa5, bits::align_up(bits::max(l.size, size_t(1)), OS_PAGE_SIZE));
+    live.push_back(p);
+  }
+
+#ifdef __linux__
+  // Seven allocations of ten pages in all.
+  size_t pages = 10;
+  SNMALLOC_CHECK(locked_kib() >= before + (pages * OS_PAGE_SIZE) / 1024);
+#endif
+
+  for (size_t i = 0; i < layouts.size(); i++)
+    rust_dealloc_locked(live[i], layouts[i].alignment, layouts[i].size);
+
+#ifdef __linux__
+  SNMALLOC_CHECK(locked_kib() == before);
+
+  // Unprivileged processes may only lock up to `RLIMIT_MEMLOCK`.
+  rlimit limit;
+  if (
+    (geteuid() != 0) && (getrlimit(RLIMIT_MEMLOCK, &limit) == 0) &&
+    (limit.rlim_cur != RLIM_INFINITY))
+  {
+    size_t size = bits::align_up(
+      static_cast<size_t>(limit.rlim_cur) + OS_PAGE_SIZE, OS_PAGE_SIZE);
+    SNMALLOC_CHECK(rust_alloc_locked(8, size) == nullptr);
+    SNMALLOC_CHECK(locked_kib() == before);
+  }
+#endif
+
+  // Freed locked memory is reused like any other.
+  for (size_t i = 0; i < 100; i++)
+  {
+    void* p = rust_alloc_locked(8, 100);
+    SNMALLOC_CHECK(p != nullptr);
+    rust_dealloc_locked(p, 8, 100);
+  }
+
+  return 0;
+}
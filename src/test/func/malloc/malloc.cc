@@ -203,6 +203,69 @@ int main(int argc, char** argv)
     test_posix_memalign(0, align + 1, EINVAL, true);
   }
 
+  fprintf(stderr, "malloc_good_size\n");
+  for (size_t size = 0; size <= SUPERSLAB_SIZE * 4; size += (size >> 3) + 1)
+  {
+    const size_t good_size = our_malloc_good_size(size);
+    if (good_size < size)
+      abort();
+    void* p = our_malloc(good_size);
+    if (our_malloc_usable_size(p) < good_size)
+      abort();
+#ifndef SNMALLOC_PASS_THROUGH
+    if (our_malloc_good_size(good_size) != good_size)
+      abort();
+    if (our_malloc_usable_size(p) != good_size)
+      abort();
+#endif
+    our_free(p);
+  }
+
+  fprintf(stderr, "malloc_size\n");
+  for (size_t size = 1; size <= SUPERSLAB_SIZE * 4; size += (size >> 3) + 1)
+  {
+    void* p = our_malloc(size);
+    if (our_malloc_size(p) != our_malloc_usable_size(p))
+      abort();
+    our_free(p);
+  }
+
+  fprintf(stderr, "reallocf\n");
+  {
+    void* p = our_reallocf(nullptr, 16);
+    if (p == nullptr)
+      abort();
+    p = our_reallocf(p, 5000);
+    if ((p == nullptr) || (our_malloc_usable_size(p) < 5000))
+      abort();
+    // A failed reallocf frees the original block.
+    errno = 0;
+    if ((our_reallocf(p, (size_t)-1) != nullptr) || (errno != ENOMEM))
+      abort();
+    if (our_reallocf(our_malloc(16), 0) != nullptr)
+      abort();
+  }
+
+  fprintf(stderr, "free_sized\n");
+  our_free_sized(nullptr, 0);
+  our_free_sized(our_malloc(0), 0);
+  our_free_sized(our_calloc(3, 5), 15);
+  for (size_t size = 1; size <= SUPERSLAB_SIZE * 4; size += (size >> 3) + 1)
+    our_free_sized(our_malloc(size), size);
+
+  fprintf(stderr, "free_aligned_sized\n");
+  our_free_aligned_sized(nullptr, 0, 0);
+  for (size_t align = sizeof(uintptr_t); align <= SUPERSLAB_SIZE * 8;
+       align <<= 1)
+  {
+    our_free_aligned_sized(our_memalign(align, 0), align, 0);
+    for (sizeclass_t sc = 0; sc < NUM_SIZECLASSES; sc++)
+    {
+      const size_t size = sizeclass_to_size(sc);
+      our_free_aligned_sized(our_memalign(align, size), align, size);
+    }
+  }
+
   current_alloc_pool()->debug_check_empty();
   return 0;
 }
@@ -0,0 +1,83 @@
+/**
+ * Checks that the Rust shim's memory limit fails allocations that would take
+ * the heap over it, and that its handler can raise it.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+size_t peak()
+{
+  RustStats stats;
+  rust_stats_refresh();
+  rust_stats_read(&stats, nullptr, 0);
+  return stats.peak;
+}
+
+size_t oom_calls = 0;
+
+void on_oom(size_t, size_t)
+{
+  oom_calls++;
+}
+
+size_t limit_calls = 0;
+
+bool raise_limit(size_t size, size_t limit)
+{
+  limit_calls++;
+  rust_set_memory_limit(limit + size);
+  return true;
+}
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH
+  rust_set_oom_handler(on_oom);
+
+  constexpr size_t size = SUPERSLAB_SIZE * 2;
+  constexpr size_t count = 16;
+  void* objects[count] = {};
+
+  // Leave room for a few allocations, beyond any chunks already cached.
+  size_t limit = peak() + size * 4;
+  SNMALLOC_CHECK(rust_set_memory_limit(limit));
+  size_t allocated = 0;
+  for (auto& p : objects)
+  {
+    p = rust_alloc(1, size);
+    if (p == nullptr)
+      break;
+    allocated++;
+  }
+  SNMALLOC_CHECK(allocated < count);
+  SNMALLOC_CHECK(oom_calls == 1);
+  SNMALLOC_CHECK(peak() <= limit);
+
+  // The handler can raise the limit and retry.
+  rust_set_memory_limit_handler(raise_limit);
+  void* extra = rust_alloc(1, size);
+  SNMALLOC_CHECK(extra != nullptr);
+  SNMALLOC_CHECK(limit_calls == 1);
+  rust_dealloc(extra, 1, size);
+  rust_set_memory_limit_handler(nullptr);
+
+  // Without a limit, allocations succeed again.
+  SNMALLOC_CHECK(rust_set_memory_limit(0));
+  for (auto& p : objects)
+  {
+    if (p == nullptr)
+      p = rust_alloc(1, size);
+    SNMALLOC_CHECK(p != nullptr);
+  }
+  for (auto p : objects)
+    rust_dealloc(p, 1, size);
+#else
+  SNMALLOC_CHECK(!rust_set_memory_limit(1));
+#endif
+
+  return 0;
+}
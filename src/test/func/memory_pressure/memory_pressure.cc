@@ -0,0 +1,51 @@
+/**
+ * Checks that a purge for memory pressure returns cached chunks to the OS,
+ * and that the listener is only started once.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  bool started = rust_start_memory_pressure_listener();
+  SNMALLOC_CHECK(rust_start_memory_pressure_listener() == started);
+
+#ifndef SNMALLOC_PASS_THROUGH
+#  if defined(_WIN32)
+  SNMALLOC_CHECK(started);
+#  endif
+
+  // Keep freed superslab-sized chunks committed, as the pressure purge
+  // should decommit them.
+  SNMALLOC_CHECK(rust_set_decommit_strategy(DecommitSuperLazy));
+  constexpr size_t count = 4;
+  void* objects[count];
+  for (auto& p : objects)
+  {
+    p = rust_alloc(1, SUPERSLAB_SIZE);
+    SNMALLOC_CHECK(p != nullptr);
+    memset(p, 1, SUPERSLAB_SIZE);
+  }
+  for (auto p : objects)
+    rust_dealloc(p, 1, SUPERSLAB_SIZE);
+
+  size_t purges = memory_pressure::purges;
+  SNMALLOC_CHECK(memory_pressure::purge() > 0);
+  SNMALLOC_CHECK(memory_pressure::purges == purges + 1);
+  SNMALLOC_CHECK(memory_pressure::purge() == 0);
+
+  // The purged chunks are reused.
+  void* p = rust_alloc_zeroed(1, SUPERSLAB_SIZE);
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(static_cast<char*>(p)[SUPERSLAB_SIZE - 1] == 0);
+  rust_dealloc(p, 1, SUPERSLAB_SIZE);
+#else
+  SNMALLOC_CHECK(!started);
+#endif
+
+  return 0;
+}
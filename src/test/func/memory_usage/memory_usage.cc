@@ -13,6 +13,25 @@
 
 using namespace snmalloc;
 
+/**
+ * Check that the breakdown is ordered from address space down to live objects.
+ */
+void check_memory_breakdown()
+{
+  malloc_info_v2 breakdown;
+  get_malloc_info_v2(&breakdown);
+
+  if (
+    (breakdown.reserved_address_space < breakdown.committed_memory) ||
+    (breakdown.committed_memory < breakdown.live_bytes))
+  {
+    std::cout << "Inconsistent breakdown (" << breakdown.reserved_address_space
+              << ", " << breakdown.committed_memory << ", "
+              << breakdown.live_bytes << ")" << std::endl;
+    abort();
+  }
+}
+
 bool print_memory_usage()
 {
   static malloc_info_v1 last_memory_usage;
@@ -30,6 +49,7 @@ bool print_memory_usage()
               << next_memory_usage.current_memory_usage << ", "
               << next_memory_usage.peak_memory_usage << ")" << std::endl;
     last_memory_usage = next_memory_usage;
+    check_memory_breakdown();
     return true;
   }
   return false;
@@ -0,0 +1,37 @@
+/**
+ * Checks that every allocation is aligned to at least MIN_ALIGNMENT, which
+ * can be raised with USE_MIN_ALIGNMENT, and that the Rust shim's requests
+ * with smaller alignments are freed correctly without rounding.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  SNMALLOC_CHECK(rust_min_alignment() == MIN_ALIGNMENT);
+  SNMALLOC_CHECK(bits::is_pow2(MIN_ALIGNMENT));
+
+  for (size_t size = 1; size < 8192; size += 7)
+  {
+    void* p = sn_malloc(size);
+    SNMALLOC_CHECK((address_cast(p) & (MIN_ALIGNMENT - 1)) == 0);
+    sn_free(p);
+
+    for (size_t align = 1; align <= MIN_ALIGNMENT * 2; align *= 2)
+    {
+      void* r = rust_alloc(align, size);
+      SNMALLOC_CHECK(
+        (address_cast(r) & (bits::max(align, MIN_ALIGNMENT) - 1)) == 0);
+      r = rust_realloc(r, align, size, size + 1);
+      SNMALLOC_CHECK(
+        (address_cast(r) & (bits::max(align, MIN_ALIGNMENT) - 1)) == 0);
+      rust_dealloc(r, align, size + 1);
+    }
+  }
+
+  return 0;
+}
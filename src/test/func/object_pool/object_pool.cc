@@ -0,0 +1,95 @@
+/**
+ * Checks that object pools construct and destroy their objects, refill a
+ * batch at a time, reuse released objects, and hand out ordinary
+ * allocations.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+size_t live = 0;
+
+struct Entity
+{
+  size_t id;
+  double position[3];
+
+  Entity(size_t id) : id(id), position{0, 0, 0}
+  {
+    live++;
+  }
+
+  ~Entity()
+  {
+    live--;
+  }
+};
+
+struct alignas(64) Aligned
+{
+  char data[72];
+};
+
+int main()
+{
+  setup();
+
+  {
+    ObjectPool<Entity> pool(8);
+    Entity* e = pool.acquire(size_t(42));
+    SNMALLOC_CHECK(e != nullptr);
+    SNMALLOC_CHECK((e->id == 42) && (live == 1));
+    SNMALLOC_CHECK(pool.cached() == 7);
+#ifndef SNMALLOC_PASS_THROUGH
+    SNMALLOC_CHECK(
+      ThreadAlloc::get()->alloc_size(e) == round_size(sizeof(Entity)));
+#endif
+
+    pool.release(e);
+    SNMALLOC_CHECK((live == 0) && (pool.cached() == 8));
+    Entity* again = pool.acquire(size_t(43));
+    SNMALLOC_CHECK(again == e);
+    pool.release(again);
+
+    Entity* many[20];
+    for (size_t i = 0; i < 20; i++)
+      many[i] = pool.acquire(i);
+    SNMALLOC_CHECK(live == 20);
+    for (auto p : many)
+      pool.release(p);
+    SNMALLOC_CHECK(pool.cached() == 24);
+    SNMALLOC_CHECK(pool.trim() == 24);
+    SNMALLOC_CHECK(pool.cached() == 0);
+  }
+
+  ObjectPool<Aligned> aligned;
+  for (size_t i = 0; i < 4; i++)
+  {
+    Aligned* a = aligned.acquire();
+    SNMALLOC_CHECK(pointer_align_up(a, 64) == a);
+    aligned.release(a);
+  }
+
+  RustPool* pool = rust_pool_create(16, 24, 0);
+  SNMALLOC_CHECK(pool != nullptr);
+  void* objects[100];
+  for (auto& p : objects)
+  {
+    p = rust_pool_acquire(pool);
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(pointer_align_up(p, 16) == p);
+    memset(p, 1, 24);
+  }
+  for (auto p : objects)
+    rust_pool_release(pool, p);
+  SNMALLOC_CHECK(rust_pool_trim(pool) >= 100);
+  SNMALLOC_CHECK(rust_pool_trim(pool) == 0);
+
+  // Objects still out when the pool is destroyed are freed normally.
+  void* kept = rust_pool_acquire(pool);
+  rust_pool_destroy(pool);
+  rust_dealloc(kept, 16, 24);
+
+  return 0;
+}
@@ -0,0 +1,57 @@
+/**
+ * Checks that, when built with SNMALLOC_OOM_RETURNS_NULL, a request the
+ * platform cannot satisfy returns null instead of aborting, and that the
+ * Rust shim's out-of-memory handler sees each failure.
+ */
+
+#define SNMALLOC_OOM_RETURNS_NULL
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+// The allocator reserves twice this, to align it, which is more address
+// space than current platforms give a process.
+static constexpr size_t huge = bits::one_at_bit(bits::is64() ? 47 : 31);
+
+size_t calls = 0;
+size_t last_size = 0;
+size_t last_alignment = 0;
+
+void on_oom(size_t size, size_t alignment)
+{
+  calls++;
+  last_size = size;
+  last_alignment = alignment;
+}
+
+int main()
+{
+  setup();
+
+  SNMALLOC_CHECK(sn_malloc(huge) == nullptr);
+  SNMALLOC_CHECK(sn_malloc(SIZE_MAX / 2) == nullptr);
+
+  SNMALLOC_CHECK(rust_alloc(8, huge) == nullptr);
+
+  rust_set_oom_handler(on_oom);
+  SNMALLOC_CHECK(rust_alloc(16, huge) == nullptr);
+  SNMALLOC_CHECK(calls == 1);
+  SNMALLOC_CHECK((last_size == huge) && (last_alignment == 16));
+
+  SNMALLOC_CHECK(rust_alloc_zeroed(8, huge) == nullptr);
+  SNMALLOC_CHECK(calls == 2);
+
+  auto p = static_cast<char*>(rust_alloc(8, 100));
+  SNMALLOC_CHECK(p != nullptr);
+  p[0] = 42;
+  SNMALLOC_CHECK(rust_realloc(p, 8, 100, huge) == nullptr);
+  SNMALLOC_CHECK(calls == 3);
+  SNMALLOC_CHECK(p[0] == 42);
+  rust_dealloc(p, 8, 100);
+
+  rust_set_oom_handler(nullptr);
+  SNMALLOC_CHECK(rust_alloc(8, huge) == nullptr);
+  SNMALLOC_CHECK(calls == 3);
+
+  return 0;
+}
@@ -0,0 +1,59 @@
+/**
+ * Checks that `release_free_memory` decommits the freed chunks kept for
+ * reuse, whatever the retention limit, and that they can still be reused.
+ */
+
+#include <snmalloc.h>
+#include <test/setup.h>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+
+  // Use a class that nothing else in the process allocates.
+  const size_t large_class = 2;
+  const size_t size = bits::one_at_bit(SUPERSLAB_BITS) << large_class;
+  const size_t n = 4;
+
+  set_large_retention(large_class, SIZE_MAX);
+
+  void* p[n];
+  for (size_t i = 0; i < n; i++)
+    p[i] = a->alloc(size);
+  for (size_t i = 0; i < n; i++)
+    a->dealloc(p[i], size);
+  SNMALLOC_CHECK(large_cache_stats(large_class).retained_committed == n);
+
+  // Every retained chunk is returned to the OS, apart from its first page.
+  auto before = memory_breakdown();
+  size_t released = release_free_memory();
+  auto after = memory_breakdown();
+  SNMALLOC_CHECK(released >= n * (size - OS_PAGE_SIZE));
+  SNMALLOC_CHECK(large_cache_stats(large_class).retained_committed == 0);
+  SNMALLOC_CHECK(after.committed == before.committed - released);
+  SNMALLOC_CHECK(after.released == before.released + released);
+
+  // Nothing more is released until more memory is freed.
+  SNMALLOC_CHECK(release_free_memory() == 0);
+
+  // The decommitted chunks are reused, and are usable.
+  auto stats = large_cache_stats(large_class);
+  for (size_t i = 0; i < n; i++)
+  {
+    p[i] = a->alloc<YesZero>(size);
+    auto bytes = static_cast<char*>(p[i]);
+    SNMALLOC_CHECK(bytes[0] == 0 && bytes[size - 1] == 0);
+    bytes[size / 2] = 1;
+  }
+  SNMALLOC_CHECK(large_cache_stats(large_class).hits == stats.hits + n);
+  for (size_t i = 0; i < n; i++)
+    a->dealloc(p[i], size);
+#endif
+
+  return 0;
+}
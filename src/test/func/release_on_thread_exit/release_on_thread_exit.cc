@@ -0,0 +1,65 @@
+/**
+ * Checks that, with `SNMALLOC_RELEASE_ON_THREAD_EXIT`, an exiting thread
+ * returns the memory cached by its allocator, and posts the objects it freed
+ * for other allocators, instead of leaving them with the unused allocator.
+ */
+
+#define SNMALLOC_RELEASE_ON_THREAD_EXIT
+
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+  auto before = memory_breakdown().reclaimed;
+
+  // The first superslab always holds the allocator's message queue stub, so
+  // allocate enough to spill into a second.  After everything is freed, the
+  // slab in use is still held by the fast free list and the bump allocator,
+  // so that superslab is only empty once these caches have been flushed.
+  std::thread t1([]() {
+    auto b = ThreadAlloc::get();
+    std::vector<void*> objects;
+    for (size_t i = 0; i < SUPERSLAB_SIZE / 64; i++)
+      objects.push_back(b->alloc(64));
+    for (auto p : objects)
+      b->dealloc(p);
+    SNMALLOC_CHECK(b->unused_slabs() > 0);
+  });
+  t1.join();
+
+  auto reclaimed = memory_breakdown().reclaimed - before;
+  printf("Reclaimed on thread exit: %zu\n", reclaimed);
+  SNMALLOC_CHECK(reclaimed >= SUPERSLAB_SIZE);
+
+  // A few remote frees stay in the remote cache of the freeing thread, until
+  // it exits.
+  constexpr size_t count = 16;
+  std::vector<void*> objects;
+  for (size_t i = 0; i < count; i++)
+    objects.push_back(a->alloc(1024));
+
+  std::thread t2([&]() {
+    auto b = ThreadAlloc::get();
+    for (auto p : objects)
+      b->dealloc(p);
+  });
+  t2.join();
+
+  SNMALLOC_CHECK(a->remote_queue_depth().first == count);
+
+  // Process the queue, medium allocations always check for messages.
+  a->dealloc(a->alloc(SLAB_SIZE * 2));
+  SNMALLOC_CHECK(a->remote_queue_depth().first == 0);
+#endif
+  return 0;
+}
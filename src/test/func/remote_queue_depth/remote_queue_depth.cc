@@ -0,0 +1,72 @@
+/**
+ * Frees a batch of objects from a thread that did not allocate them and
+ * checks that the owning allocator's message queue depth, its high-water
+ * mark, and the alarm callback reflect the posted messages.
+ */
+
+#include <atomic>
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+using namespace snmalloc;
+
+std::atomic<size_t> alarm_id{0};
+std::atomic<size_t> alarm_depth{0};
+
+void alarm(size_t id, size_t depth)
+{
+  alarm_id = id;
+  alarm_depth = depth;
+}
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  constexpr size_t object_size = 1024;
+  // Enough bytes to overflow the remote cache of the freeing thread, so that
+  // the messages are posted.
+  constexpr size_t count = (2 * REMOTE_CACHE) / object_size;
+  constexpr size_t threshold = 16;
+
+  auto a = ThreadAlloc::get();
+  SNMALLOC_CHECK(a->remote_queue_depth().first == 0);
+
+  std::vector<void*> objects;
+  for (size_t i = 0; i < count; i++)
+    objects.push_back(a->alloc(object_size));
+
+  set_remote_queue_alarm(threshold, alarm);
+
+  std::thread t([&]() {
+    auto b = ThreadAlloc::get();
+    for (auto p : objects)
+      b->dealloc(p);
+  });
+  t.join();
+
+  auto depth = a->remote_queue_depth();
+  printf(
+    "Depth after remote frees: %zu (peak %zu)\n", depth.first, depth.second);
+  SNMALLOC_CHECK(depth.first >= threshold);
+  SNMALLOC_CHECK(depth.second >= depth.first);
+  SNMALLOC_CHECK(alarm_id == a->get_trunc_id());
+  SNMALLOC_CHECK(alarm_depth >= threshold);
+
+  // Process the queue, medium allocations always check for messages.
+  a->dealloc(a->alloc(SLAB_SIZE * 2));
+
+  auto after = a->remote_queue_depth();
+  printf(
+    "Depth after processing: %zu (peak %zu)\n", after.first, after.second);
+  SNMALLOC_CHECK(after.first < depth.first);
+  SNMALLOC_CHECK(after.second == depth.second);
+
+  set_remote_queue_alarm(0, nullptr);
+#endif
+  return 0;
+}
@@ -0,0 +1,106 @@
+/**
+ * Checks that the Rust shim applies the settings in the environment, and
+ * ignores values that it does not recognise.
+ */
+
+#ifdef _WIN32
+/*
+ * The test uses `setenv` and `mkstemp`, which are POSIX.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include "../../../override/rust.cc"
+
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <test/setup.h>
+#  include <unistd.h>
+
+bool retains(size_t large_class)
+{
+  return large_cache_stats(large_class).retention_limit != 0;
+}
+
+void test_decommit()
+{
+  setenv("SNMALLOC_DECOMMIT", "eager", 1);
+  rust_configure_from_env();
+  for (size_t lc = 0; lc < NUM_LARGE_CLASSES; lc++)
+    SNMALLOC_CHECK(!retains(lc));
+
+  setenv("SNMALLOC_DECOMMIT", "lazy", 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(retains(0));
+  SNMALLOC_CHECK(!retains(1));
+
+  setenv("SNMALLOC_DECOMMIT", "sometimes", 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(retains(0));
+
+  SNMALLOC_CHECK(rust_set_decommit_strategy(DecommitNone));
+  SNMALLOC_CHECK(retains(0) && retains(1));
+  SNMALLOC_CHECK(!rust_set_decommit_strategy(3));
+  unsetenv("SNMALLOC_DECOMMIT");
+}
+
+void test_verbosity()
+{
+  setenv("SNMALLOC_ERROR_VERBOSITY", "silent", 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(error_verbosity == ErrorSilent);
+
+  setenv("SNMALLOC_ERROR_VERBOSITY", "1", 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(error_verbosity == ErrorMessage);
+
+  setenv("SNMALLOC_ERROR_VERBOSITY", "loud", 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(error_verbosity == ErrorMessage);
+
+  SNMALLOC_CHECK(!rust_set_error_verbosity(3));
+  SNMALLOC_CHECK(rust_set_error_verbosity(ErrorBacktrace));
+  unsetenv("SNMALLOC_ERROR_VERBOSITY");
+}
+
+void test_stats_file()
+{
+  char path[] = "/tmp/snmalloc_stats_XXXXXX";
+  int fd = mkstemp(path);
+  SNMALLOC_CHECK(fd >= 0);
+  close(fd);
+
+  setenv("SNMALLOC_STATS_FILE", path, 1);
+  rust_configure_from_env();
+  SNMALLOC_CHECK(strcmp(runtime_config::stats_file, path) == 0);
+
+  // Write the dump that would be written at exit.
+  runtime_config::dump_at_exit();
+  FILE* f = fopen(path, "r");
+  SNMALLOC_CHECK(f != nullptr);
+  char start[16] = {};
+  SNMALLOC_CHECK(fread(start, 1, 11, f) == 11);
+  fclose(f);
+  SNMALLOC_CHECK(strcmp(start, "{\"version\":") == 0);
+
+  static char too_long[sizeof(runtime_config::stats_file) + 1];
+  memset(too_long, 'a', sizeof(too_long) - 1);
+  SNMALLOC_CHECK(!rust_set_stats_file(too_long));
+  SNMALLOC_CHECK(rust_set_stats_file(nullptr));
+  unsetenv("SNMALLOC_STATS_FILE");
+  unlink(path);
+}
+
+int main()
+{
+  setup();
+
+  test_decommit();
+  test_verbosity();
+  test_stats_file();
+
+  return 0;
+}
+#endif
@@ -0,0 +1,38 @@
+/**
+ * Checks that `rust_alloc_filled` fills every byte, for zero and non-zero
+ * fills, across small, medium and large sizes, including reused memory.
+ */
+
+#include <test/setup.h>
+
+#include "../../../override/rust.cc"
+
+void check_filled(size_t alignment, size_t size, uint8_t byte)
+{
+  auto p = static_cast<uint8_t*>(rust_alloc_filled(alignment, size, byte));
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK((address_cast(p) & (alignment - 1)) == 0);
+  for (size_t i = 0; i < size; i++)
+    SNMALLOC_CHECK(p[i] == byte);
+  // Dirty the block so that a reused block cannot pass by accident.
+  memset(p, ~byte, size);
+  rust_dealloc(p, alignment, size);
+}
+
+int main()
+{
+  setup();
+
+  size_t sizes[] = {1, 16, 100, 4096, 100000, 4 * 1024 * 1024};
+  for (size_t size : sizes)
+  {
+    for (int round = 0; round < 2; round++)
+    {
+      check_filled(8, size, 0);
+      check_filled(8, size, 0xa5);
+      check_filled(64, size, 0xff);
+    }
+  }
+
+  return 0;
+}
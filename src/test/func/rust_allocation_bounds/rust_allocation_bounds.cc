@@ -0,0 +1,44 @@
+/**
+ * Checks that interior pointers into small, medium and large allocations are
+ * mapped back to the bounds of the allocation, and that other memory is
+ * reported as not allocated by snmalloc.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  // Small, medium and large sizes.
+  const size_t sizes[] = {16, 1000, 100000, bits::one_at_bit(24)};
+  for (size_t size : sizes)
+  {
+    auto p = static_cast<char*>(rust_alloc(1, size));
+    size_t usable = rust_usable_size(1, size);
+    for (size_t offset : {size_t(0), size / 2, size - 1})
+    {
+      SNMALLOC_CHECK(rust_allocation_start(p + offset) == p);
+
+      void* start = nullptr;
+      void* end = nullptr;
+      SNMALLOC_CHECK(rust_allocation_bounds(p + offset, &start, &end));
+      SNMALLOC_CHECK(start == p);
+      SNMALLOC_CHECK(end == p + usable);
+    }
+    rust_dealloc(p, 1, size);
+  }
+
+  int on_stack = 0;
+  SNMALLOC_CHECK(rust_allocation_start(&on_stack) == nullptr);
+  void* start = &on_stack;
+  void* end = &on_stack;
+  SNMALLOC_CHECK(!rust_allocation_bounds(&on_stack, &start, &end));
+  SNMALLOC_CHECK(start == &on_stack && end == &on_stack);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,48 @@
+/**
+ * Checks that, with `SNMALLOC_CHECK_BOUNDS`, the Rust shim accepts sizes
+ * within an allocation and reports an old size passed to `rust_realloc`
+ * that would read past its end, rather than copying it.
+ */
+
+#define SNMALLOC_CHECK_BOUNDS
+#include "../../../override/rust.cc"
+
+#include <cstring>
+#include <test/setup.h>
+
+#ifndef SNMALLOC_PASS_THROUGH
+const void* expected_ptr;
+
+void handler(const void* p, size_t, const char* reason)
+{
+  SNMALLOC_CHECK(p == expected_ptr);
+  SNMALLOC_CHECK(strstr(reason, "out of bounds") != nullptr);
+  // The process would be aborted on return.
+  _Exit(0);
+}
+#endif
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH
+  // Sizes within the allocation are accepted.
+  auto p = static_cast<char*>(rust_alloc(8, 60));
+  memset(p, 0x3c, 60);
+  p = static_cast<char*>(rust_realloc_zeroed(p, 8, 60, 64));
+  SNMALLOC_CHECK(p[59] == 0x3c && p[63] == 0);
+  p = static_cast<char*>(rust_realloc(p, 8, 64, 10000));
+  SNMALLOC_CHECK(p[59] == 0x3c);
+  p = static_cast<char*>(rust_realloc(p, 8, 10000, 64));
+  SNMALLOC_CHECK(p[59] == 0x3c);
+
+  // An old size larger than the allocation is reported.
+  rust_set_check_failure_handler(handler);
+  expected_ptr = p;
+  rust_realloc(p, 8, 5000, 10000);
+  SNMALLOC_CHECK(false);
+#endif
+
+  return 0;
+}
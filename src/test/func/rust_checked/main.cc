@@ -0,0 +1,42 @@
+/**
+ * Checks that the hardened copy of the Rust shim can be linked alongside the
+ * plain one, and that each uses its own heap.
+ */
+
+#include <cstddef>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <pal/pal.h>
+
+extern "C" void* rust_alloc(size_t alignment, size_t size);
+extern "C" void rust_dealloc(void* ptr, size_t alignment, size_t size);
+extern "C" bool rust_owns(const void* ptr);
+extern "C" void* rust_checked_alloc(size_t alignment, size_t size);
+extern "C" void* rust_checked_realloc(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size);
+extern "C" void rust_checked_dealloc(void* ptr, size_t alignment, size_t size);
+extern "C" bool rust_checked_owns(const void* ptr);
+
+int main()
+{
+  auto plain = static_cast<char*>(rust_alloc(16, 100));
+  auto hardened = static_cast<char*>(rust_checked_alloc(16, 100));
+  SNMALLOC_CHECK(plain != nullptr && hardened != nullptr);
+  memset(hardened, 0x5a, 100);
+
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(rust_owns(plain) && !rust_checked_owns(plain));
+  SNMALLOC_CHECK(rust_checked_owns(hardened) && !rust_owns(hardened));
+#endif
+
+  hardened = static_cast<char*>(rust_checked_realloc(hardened, 16, 100, 5000));
+  SNMALLOC_CHECK(hardened != nullptr);
+  for (size_t i = 0; i < 100; i++)
+    SNMALLOC_CHECK(hardened[i] == 0x5a);
+
+  rust_checked_dealloc(hardened, 16, 5000);
+  rust_dealloc(plain, 16, 100);
+
+  return 0;
+}
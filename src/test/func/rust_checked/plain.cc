@@ -0,0 +1 @@
+#include "../../../override/rust.cc"
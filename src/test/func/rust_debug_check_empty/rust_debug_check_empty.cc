@@ -0,0 +1,42 @@
+/**
+ * Checks that `rust_debug_check_empty` reports live allocations, including
+ * ones freed by another thread whose frees have not yet been delivered, and
+ * that the allocator can still be used afterwards.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <thread>
+
+int main()
+{
+  setup();
+
+  SNMALLOC_CHECK(rust_debug_check_empty());
+
+#ifndef SNMALLOC_PASS_THROUGH
+  void* p = rust_alloc(16, 48);
+  void* q = rust_alloc(8, 1 << 20);
+  SNMALLOC_CHECK(!rust_debug_check_empty());
+
+  rust_dealloc(p, 16, 48);
+  SNMALLOC_CHECK(!rust_debug_check_empty());
+
+  // Frees made by another thread are delivered before checking.
+  void* r = rust_alloc(8, 100);
+  std::thread([q, r]() {
+    rust_dealloc(q, 8, 1 << 20);
+    rust_dealloc(r, 8, 100);
+  }).join();
+  SNMALLOC_CHECK(rust_debug_check_empty());
+
+  // The allocator is still usable.
+  p = rust_alloc(16, 48);
+  SNMALLOC_CHECK(!rust_debug_check_empty());
+  rust_dealloc(p, 16, 48);
+  SNMALLOC_CHECK(rust_debug_check_empty());
+#endif
+
+  return 0;
+}
@@ -0,0 +1,58 @@
+/**
+ * Checks that the fixed-region Rust shim serves every allocation from the
+ * region it is given, and fails cleanly once the region is exhausted.
+ */
+
+#include "../../../override/rust-fixed.cc"
+
+#include <test/setup.h>
+
+#if defined(SNMALLOC_PASS_THROUGH) || defined(_WIN32)
+int main()
+{
+  return 0;
+}
+#else
+bool in_region(void* p, void* base, size_t len)
+{
+  return (p >= base) && (p < pointer_offset(base, len));
+}
+
+int main()
+{
+  setup();
+
+  // The region is allocated with the system allocator, as this test links
+  // snmalloc with prefixed names.
+  constexpr size_t len = bits::one_at_bit(28);
+  void* base = aligned_alloc(SUPERSLAB_SIZE, len);
+  SNMALLOC_CHECK(base != nullptr);
+
+  SNMALLOC_CHECK(rust_init_with_region(base, len));
+  SNMALLOC_CHECK(!rust_init_with_region(base, len));
+
+  constexpr size_t count = 1000;
+  void* objects[count];
+  for (size_t i = 0; i < count; i++)
+  {
+    size_t size = 16 << (i % 16);
+    objects[i] = rust_alloc(16, size);
+    SNMALLOC_CHECK(objects[i] != nullptr);
+    SNMALLOC_CHECK(in_region(objects[i], base, len));
+    memset(objects[i], 0x5a, size);
+  }
+  for (size_t i = 0; i < count; i++)
+    rust_dealloc(objects[i], 16, 16 << (i % 16));
+
+  size_t total = 0;
+  while (void* p = rust_alloc(16, SUPERSLAB_SIZE))
+  {
+    SNMALLOC_CHECK(in_region(p, base, len));
+    total += SUPERSLAB_SIZE;
+  }
+  SNMALLOC_CHECK(total > 0);
+  SNMALLOC_CHECK(total < len);
+
+  return 0;
+}
+#endif
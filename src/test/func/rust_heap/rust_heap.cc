@@ -0,0 +1,72 @@
+/**
+ * Checks that an isolated heap allocates only from its own memory, that
+ * `heap_destroy_all` frees everything at once and leaves the heap reusable,
+ * and that a destroyed heap's memory can be reused.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+#ifndef SNMALLOC_PASS_THROUGH
+/**
+ * Allocate from `heap` until it is full, checking that every object is in
+ * the heap, and return the number of objects allocated.
+ */
+size_t fill(RustHeap* heap, size_t size)
+{
+  auto h = reinterpret_cast<Heap*>(heap);
+  size_t count = 0;
+  while (void* p = rust_heap_alloc(heap, 16, size))
+  {
+    SNMALLOC_CHECK(h->contains(p));
+    SNMALLOC_CHECK(rust_owns(p));
+    memset(p, 0x5a, size);
+    count++;
+  }
+  return count;
+}
+#endif
+
+int main()
+{
+  setup();
+
+  constexpr size_t capacity = 4 * 1024 * 1024;
+  auto heap = rust_heap_create(capacity);
+#ifdef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(heap == nullptr);
+#else
+  SNMALLOC_CHECK(heap != nullptr);
+  auto h = reinterpret_cast<Heap*>(heap);
+
+  void* outside = rust_alloc(16, 1024);
+  SNMALLOC_CHECK(!h->contains(outside));
+
+  void* first = rust_heap_alloc(heap, 16, 1024);
+  SNMALLOC_CHECK(first != nullptr);
+  rust_heap_dealloc(heap, first, 16, 1024);
+
+  SNMALLOC_CHECK(fill(heap, 1024) * 1024 >= capacity);
+
+  void* last = rust_heap_alloc(heap, 16, 1024);
+  SNMALLOC_CHECK(last == nullptr);
+
+  rust_heap_destroy_all(heap);
+  SNMALLOC_CHECK(rust_owns(outside));
+  SNMALLOC_CHECK(fill(heap, 1024) * 1024 >= capacity);
+
+  rust_heap_destroy(heap);
+  SNMALLOC_CHECK(rust_owns(outside));
+  rust_dealloc(outside, 16, 1024);
+
+  auto again = rust_heap_create(capacity);
+  SNMALLOC_CHECK(again == heap);
+  SNMALLOC_CHECK(fill(again, 1024) * 1024 >= capacity);
+  rust_heap_destroy(again);
+
+  SNMALLOC_CHECK(rust_heap_create(SIZE_MAX) == nullptr);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,63 @@
+/**
+ * Checks that a frozen heap can be read but not written, and that it can be
+ * used as before once it is unfrozen.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#ifdef __unix__
+#  include <sys/wait.h>
+#  include <unistd.h>
+#endif
+
+int main()
+{
+  setup();
+
+  auto heap = rust_heap_create(4 * 1024 * 1024);
+#ifdef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(heap == nullptr);
+#else
+  auto table = static_cast<size_t*>(rust_heap_alloc(heap, 16, 4096));
+  for (size_t i = 0; i < 512; i++)
+    table[i] = i;
+
+  if (!rust_heap_freeze(heap))
+  {
+    SNMALLOC_CHECK((!pal_supports<PageProtection, Pal>));
+    rust_heap_destroy(heap);
+    return 0;
+  }
+
+  size_t sum = 0;
+  for (size_t i = 0; i < 512; i++)
+    sum += table[i];
+  SNMALLOC_CHECK(sum == 511 * 512 / 2);
+
+#  ifdef __unix__
+  pid_t pid = fork();
+  SNMALLOC_CHECK(pid >= 0);
+  if (pid == 0)
+  {
+    signal(SIGSEGV, SIG_DFL);
+    signal(SIGBUS, SIG_DFL);
+    table[7] = 0;
+    _Exit(0);
+  }
+  int status;
+  SNMALLOC_CHECK(waitpid(pid, &status, 0) == pid);
+  SNMALLOC_CHECK(WIFSIGNALED(status));
+#  endif
+
+  rust_heap_unfreeze(heap);
+  table[7] = 0;
+  rust_heap_dealloc(heap, table, 16, 4096);
+  void* p = rust_heap_alloc(heap, 16, 1024);
+  SNMALLOC_CHECK(p != nullptr);
+  rust_heap_dealloc(heap, p, 16, 1024);
+  rust_heap_destroy(heap);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,47 @@
+/**
+ * Checks that I/O buffers are page aligned and never share a page with
+ * another allocation, for buffers from one byte to several pages.
+ */
+
+#include <test/setup.h>
+#include <vector>
+
+#include "../../../override/rust.cc"
+
+int main()
+{
+  setup();
+
+  size_t lens[] = {1, 100, OS_PAGE_SIZE, OS_PAGE_SIZE + 1, 5 * OS_PAGE_SIZE};
+  for (size_t len : lens)
+  {
+    std::vector<void*> bufs;
+    for (size_t i = 0; i < 16; i++)
+    {
+      void* p = rust_io_buffer_alloc(len);
+      SNMALLOC_CHECK(p != nullptr);
+      SNMALLOC_CHECK((address_cast(p) % OS_PAGE_SIZE) == 0);
+      memset(p, 0xab, len);
+      bufs.push_back(p);
+    }
+
+    // Small allocations must not land in the pages of any buffer.
+    for (size_t i = 0; i < 64; i++)
+    {
+      void* q = rust_alloc(8, 16);
+      address_t page = bits::align_down(address_cast(q), OS_PAGE_SIZE);
+      for (void* p : bufs)
+      {
+        address_t start = address_cast(p);
+        address_t end = bits::align_up(start + len, OS_PAGE_SIZE);
+        SNMALLOC_CHECK((page < start) || (page >= end));
+      }
+      rust_dealloc(q, 8, 16);
+    }
+
+    for (void* p : bufs)
+      rust_io_buffer_dealloc(p, len);
+  }
+
+  return 0;
+}
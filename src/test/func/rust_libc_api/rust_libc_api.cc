@@ -0,0 +1,66 @@
+/**
+ * Checks that the Rust shim built with `SNMALLOC_RUST_LIBC_API` replaces the
+ * C allocation functions, so that they allocate from the same heap as the
+ * `rust_*` entry points.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#if defined(SNMALLOC_PASS_THROUGH) || defined(_WIN32)
+/*
+ * With pass-through the shim's `malloc` would call itself, and on Windows the
+ * C runtime's allocator is replaced by `crt.cc` instead, so skip this test.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  define SNMALLOC_RUST_LIBC_API
+#  include "../../../override/rust.cc"
+
+#  include <test/setup.h>
+
+void check_owned(void* p, size_t alignment)
+{
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(rust_owns(p));
+  SNMALLOC_CHECK((address_cast(p) % alignment) == 0);
+}
+
+int main()
+{
+  setup();
+
+  void* p = malloc(100);
+  check_owned(p, 16);
+  SNMALLOC_CHECK(malloc_usable_size(p) >= 100);
+  p = realloc(p, 5000);
+  check_owned(p, 16);
+  free(p);
+
+  p = calloc(10, 10);
+  check_owned(p, 16);
+  free(p);
+
+  p = aligned_alloc(4096, 4096);
+  check_owned(p, 4096);
+  free(p);
+
+  void* q = nullptr;
+  SNMALLOC_CHECK(posix_memalign(&q, 256, 100) == 0);
+  check_owned(q, 256);
+  free(q);
+
+  // Memory moves freely between the C and Rust interfaces.
+  p = rust_alloc(64, 100);
+  SNMALLOC_CHECK(malloc_usable_size(p) >= 100);
+  p = realloc(p, 200);
+  check_owned(p, 16);
+  free(p);
+
+  return 0;
+}
+#endif
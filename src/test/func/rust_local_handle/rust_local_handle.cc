@@ -0,0 +1,48 @@
+/**
+ * Checks that allocations through a local handle use the calling thread's
+ * allocator, and interoperate with the ordinary entry points.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+#include <thread>
+
+int main()
+{
+  setup();
+
+  auto handle = rust_local_handle();
+  SNMALLOC_CHECK(handle != nullptr);
+
+  constexpr size_t count = 100;
+  void* objects[count];
+  for (size_t i = 0; i < count; i++)
+  {
+    size_t size = 16 << (i % 12);
+    objects[i] = rust_handle_alloc(handle, 16, size);
+    SNMALLOC_CHECK(objects[i] != nullptr);
+    SNMALLOC_CHECK((address_cast(objects[i]) % 16) == 0);
+#ifndef SNMALLOC_PASS_THROUGH
+    if (size < SUPERSLAB_SIZE)
+      SNMALLOC_CHECK(
+        rust_allocator_id_of(objects[i]) == rust_current_allocator_id());
+#endif
+  }
+
+  // Memory from a handle can be freed normally, and vice versa.
+  for (size_t i = 0; i < count; i += 2)
+    rust_dealloc(objects[i], 16, 16 << (i % 12));
+  for (size_t i = 0; i < count; i += 2)
+    objects[i] = rust_alloc(16, 16 << (i % 12));
+
+  // Another thread frees through its own handle.
+  std::thread t([&]() {
+    auto other = rust_local_handle();
+    for (size_t i = 0; i < count; i++)
+      rust_handle_dealloc(other, objects[i], 16, 16 << (i % 12));
+  });
+  t.join();
+
+  return 0;
+}
@@ -0,0 +1,55 @@
+/**
+ * Checks that checked copies within heap allocations succeed, and that a copy
+ * past the end of a heap allocation is reported as a fatal error.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <cstring>
+#include <test/setup.h>
+
+void handler(const char* message)
+{
+  SNMALLOC_CHECK(
+    strcmp(message, "memcpy destination out of bounds of heap allocation") ==
+      0);
+  // The process would be aborted on return.
+  _Exit(0);
+}
+
+int main()
+{
+  setup();
+
+  char on_stack[256];
+  memset(on_stack, 'a', sizeof(on_stack));
+
+  const size_t sizes[] = {16, 100, 4096, 100000, 4 * SUPERSLAB_SIZE};
+  for (size_t size : sizes)
+  {
+    auto p = static_cast<char*>(rust_alloc(16, size));
+    size_t usable = sn_malloc_usable_size(p);
+    size_t len = size < sizeof(on_stack) ? size : sizeof(on_stack);
+
+    SNMALLOC_CHECK(rust_memcpy_checked(p, on_stack, len) == p);
+    SNMALLOC_CHECK(
+      rust_memcpy_checked(p + usable - len, on_stack, len) == p + usable - len);
+    SNMALLOC_CHECK(p[usable - 1] == 'a');
+    rust_memcpy_checked(on_stack, p + usable - len, len);
+    rust_dealloc(p, 16, size);
+  }
+
+  char other[256];
+  rust_memcpy_checked(other, on_stack, sizeof(other));
+  SNMALLOC_CHECK(memcmp(other, on_stack, sizeof(other)) == 0);
+
+#ifndef SNMALLOC_PASS_THROUGH // Bounds are not known with pass-through
+  rust_set_error_handler(handler);
+
+  auto p = static_cast<char*>(rust_alloc(16, 64));
+  rust_memcpy_checked(p + 1, on_stack, sn_malloc_usable_size(p));
+  SNMALLOC_CHECK(false);
+#endif
+
+  return 0;
+}
@@ -0,0 +1,40 @@
+/**
+ * Checks that the Rust shim recognises memory it allocated, and only while
+ * it is allocated.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <stdlib.h>
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  // Small, medium and large sizes.
+  const size_t sizes[] = {16, 1000, 100000, bits::one_at_bit(24)};
+  for (size_t size : sizes)
+  {
+    auto p = static_cast<char*>(rust_alloc(1, size));
+    SNMALLOC_CHECK(rust_owns(p));
+    SNMALLOC_CHECK(rust_owns(p + size - 1));
+    rust_dealloc(p, 1, size);
+  }
+
+  // A freed large allocation's chunks are no longer in use.
+  size_t large = bits::one_at_bit(24);
+  auto p = rust_alloc(1, large);
+  rust_dealloc(p, 1, large);
+  SNMALLOC_CHECK(!rust_owns(p));
+
+  int on_stack = 0;
+  SNMALLOC_CHECK(!rust_owns(&on_stack));
+  auto system = malloc(100);
+  SNMALLOC_CHECK(!rust_owns(system));
+  free(system);
+#endif
+
+  return 0;
+}
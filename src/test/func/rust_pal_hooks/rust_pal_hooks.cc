@@ -0,0 +1,97 @@
+/**
+ * Checks that the Rust shim built with `PALHooks` gets all of its memory
+ * through the hooks it is given, and commits memory before using it.
+ */
+
+#include "../../../override/rust-pal.cc"
+
+#include <test/setup.h>
+
+#if defined(SNMALLOC_PASS_THROUGH) || defined(_WIN32)
+int main()
+{
+  return 0;
+}
+#else
+/**
+ * The hooks hand out memory from a single block from the system allocator,
+ * as this test links snmalloc with prefixed names.  Each medium sizeclass
+ * takes a whole chunk, so the block is sized in chunks.
+ */
+constexpr size_t region_size =
+  bits::max(bits::one_at_bit(28), 64 * SUPERSLAB_SIZE);
+void* region;
+size_t region_used;
+size_t reserves;
+size_t commits;
+size_t decommits;
+
+bool in_region(void* p, size_t size = 1)
+{
+  return (p >= region) &&
+    (pointer_offset(p, size) <= pointer_offset(region, region_size));
+}
+
+void* test_reserve(size_t size)
+{
+  reserves++;
+  if (size > region_size - region_used)
+    return nullptr;
+  void* p = pointer_offset(region, region_used);
+  region_used += size;
+  return p;
+}
+
+void test_commit(void* p, size_t size)
+{
+  SNMALLOC_CHECK(in_region(p, size));
+  commits++;
+}
+
+void test_decommit(void* p, size_t size)
+{
+  SNMALLOC_CHECK(in_region(p, size));
+  decommits++;
+}
+
+int main()
+{
+  setup();
+
+  region = aligned_alloc(SUPERSLAB_SIZE, region_size);
+  SNMALLOC_CHECK(region != nullptr);
+
+  PalHooks hooks{};
+  SNMALLOC_CHECK(!rust_init_with_pal(&hooks));
+
+  hooks.reserve = test_reserve;
+  hooks.commit = test_commit;
+  hooks.decommit = test_decommit;
+  SNMALLOC_CHECK(rust_init_with_pal(&hooks));
+  SNMALLOC_CHECK(!rust_init_with_pal(&hooks));
+
+  constexpr size_t count = 1000;
+  void* objects[count];
+  for (size_t i = 0; i < count; i++)
+  {
+    size_t size = 16 << (i % 16);
+    objects[i] = rust_alloc(16, size);
+    SNMALLOC_CHECK(objects[i] != nullptr);
+    SNMALLOC_CHECK(in_region(objects[i], size));
+    memset(objects[i], 0x5a, size);
+  }
+  SNMALLOC_CHECK(reserves > 0);
+  SNMALLOC_CHECK(commits > 0);
+
+  for (size_t i = 0; i < count; i++)
+    rust_dealloc(objects[i], 16, 16 << (i % 16));
+
+  void* p = rust_alloc_zeroed(16, 4 * SUPERSLAB_SIZE);
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(static_cast<char*>(p)[SUPERSLAB_SIZE] == 0);
+  rust_dealloc(p, 16, 4 * SUPERSLAB_SIZE);
+  SNMALLOC_CHECK(decommits > 0);
+
+  return 0;
+}
+#endif
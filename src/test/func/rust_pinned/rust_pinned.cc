@@ -0,0 +1,69 @@
+/**
+ * Checks that pinned allocations are registered and unregistered through the
+ * installed hooks with whole pages, and that a failed registration fails the
+ * allocation.
+ */
+
+#include <test/setup.h>
+
+#include "../../../override/rust.cc"
+
+struct PinState
+{
+  size_t pinned = 0;
+  size_t pinned_bytes = 0;
+  bool fail = false;
+};
+
+bool pin(void* ptr, size_t len, void* context)
+{
+  auto state = static_cast<PinState*>(context);
+  SNMALLOC_CHECK((address_cast(ptr) % OS_PAGE_SIZE) == 0);
+  SNMALLOC_CHECK((len % OS_PAGE_SIZE) == 0);
+  if (state->fail)
+    return false;
+  state->pinned++;
+  state->pinned_bytes += len;
+  return true;
+}
+
+void unpin(void* ptr, size_t len, void* context)
+{
+  auto state = static_cast<PinState*>(context);
+  SNMALLOC_CHECK((address_cast(ptr) % OS_PAGE_SIZE) == 0);
+  state->pinned--;
+  state->pinned_bytes -= len;
+}
+
+int main()
+{
+  setup();
+
+  // Without hooks, pinned memory is a plain I/O buffer.
+  void* p = rust_pinned_alloc(100);
+  SNMALLOC_CHECK(p != nullptr);
+  rust_pinned_dealloc(p, 100);
+
+  PinState state;
+  RustPinHooks hooks = {pin, unpin, &state};
+  rust_set_pin_hooks(&hooks);
+
+  void* a = rust_pinned_alloc(100);
+  void* b = rust_pinned_alloc(3 * OS_PAGE_SIZE + 1);
+  SNMALLOC_CHECK((a != nullptr) && (b != nullptr));
+  SNMALLOC_CHECK(state.pinned == 2);
+  SNMALLOC_CHECK(state.pinned_bytes == 5 * OS_PAGE_SIZE);
+
+  state.fail = true;
+  SNMALLOC_CHECK(rust_pinned_alloc(100) == nullptr);
+  SNMALLOC_CHECK(state.pinned == 2);
+  state.fail = false;
+
+  rust_pinned_dealloc(a, 100);
+  rust_pinned_dealloc(b, 3 * OS_PAGE_SIZE + 1);
+  SNMALLOC_CHECK(state.pinned == 0);
+  SNMALLOC_CHECK(state.pinned_bytes == 0);
+
+  rust_set_pin_hooks(nullptr);
+  return 0;
+}
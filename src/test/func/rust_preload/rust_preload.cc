@@ -0,0 +1,57 @@
+/**
+ * Checks that the preloadable Rust shim replaces the C allocation functions,
+ * including for allocations made inside the C library, and shares its heap
+ * with the `rust_*` entry points.
+ */
+
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+
+#if defined(SNMALLOC_PASS_THROUGH) || defined(_WIN32)
+/*
+ * With pass-through the shim's `malloc` would call itself, and Windows has no
+ * `LD_PRELOAD`, so skip this test.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include "../../../override/rust-preload.cc"
+
+#  include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  void* p = malloc(100);
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(rust_owns(p));
+  p = realloc(p, 10000);
+  SNMALLOC_CHECK(rust_owns(p));
+  free(p);
+
+  p = calloc(10, 10);
+  SNMALLOC_CHECK(rust_owns(p));
+  free(p);
+
+  void* q = nullptr;
+  SNMALLOC_CHECK(posix_memalign(&q, 256, 100) == 0);
+  SNMALLOC_CHECK(rust_owns(q));
+  free(q);
+
+  // The C library's own allocations use the replaced functions.
+  char* s = strdup("preload");
+  SNMALLOC_CHECK(rust_owns(s));
+  free(s);
+
+  // Memory can move between the C and Rust interfaces.
+  p = rust_alloc(16, 100);
+  SNMALLOC_CHECK(malloc_usable_size(p) >= 100);
+  free(p);
+
+  return 0;
+}
+#endif
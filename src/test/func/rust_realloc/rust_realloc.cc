@@ -0,0 +1,226 @@
+/**
+ * Checks that the Rust resize entry points resize in place when the
+ * sizeclass does not change, and that `rust_realloc_zeroed` zeroes the new
+ * tail whether or not the block moves, and that over-aligned blocks keep
+ * their alignment when they move, and that large blocks keep their contents
+ * when their pages are moved rather than copied, which only happens when
+ * enabled, so that reallocating does not pile up kernel mappings.
+ */
+
+#include <test/setup.h>
+
+#include "../../../override/rust.cc"
+
+#include <fstream>
+#include <string>
+
+void check_zero(char* p, size_t from, size_t to)
+{
+  for (size_t i = from; i < to; i++)
+    SNMALLOC_CHECK(p[i] == 0);
+}
+
+void check_fill(char* p, size_t to, char c)
+{
+  for (size_t i = 0; i < to; i++)
+    SNMALLOC_CHECK(p[i] == c);
+}
+
+void check_aligned(void* p, size_t alignment)
+{
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK((address_cast(p) & (alignment - 1)) == 0);
+}
+
+/**
+ * Resize a block aligned to `alignment` through small, medium and large
+ * sizes, growing and then shrinking, including to and from zero.
+ */
+void check_realloc_aligned(size_t alignment)
+{
+  static constexpr size_t sizes[] = {
+    0, 1, 48, 100, 4096, 5000, 65536, 300000, 1 << 20, 3 << 20, 64, 0};
+
+  auto p = static_cast<char*>(rust_alloc(alignment, sizes[0]));
+  check_aligned(p, alignment);
+  size_t size = sizes[0];
+  for (size_t i = 1; i < sizeof(sizes) / sizeof(sizes[0]); i++)
+  {
+    size_t new_size = sizes[i];
+    size_t kept = size < new_size ? size : new_size;
+    memset(p, 0x3c, size);
+    p = static_cast<char*>(rust_realloc(p, alignment, size, new_size));
+    check_aligned(p, alignment);
+    check_fill(p, kept, 0x3c);
+    memset(p, 0x3c, new_size);
+    size = new_size;
+
+    // Grow zeroed back to the next size, and shrink back again.
+    size_t next = sizes[(i + 1) % (sizeof(sizes) / sizeof(sizes[0]))];
+    if (next > size)
+    {
+      p = static_cast<char*>(rust_realloc_zeroed(p, alignment, size, next));
+      check_aligned(p, alignment);
+      check_fill(p, size, 0x3c);
+      check_zero(p, size, next);
+      p = static_cast<char*>(rust_realloc(p, alignment, next, size));
+      check_aligned(p, alignment);
+    }
+  }
+  rust_dealloc(p, alignment, size);
+}
+
+/**
+ * Resize large blocks, whose pages are moved rather than copied on platforms
+ * that support it once enabled, checking the contents survive and that the
+ * stale end of the last page moved is zeroed by `rust_realloc_zeroed`.
+ */
+void check_realloc_large()
+{
+#ifndef SNMALLOC_PASS_THROUGH
+  if constexpr (pal_supports<PageMove, snmalloc::Pal>)
+  {
+    // Moved pages leave zeroes behind.
+    size_t size = 4 * OS_PAGE_SIZE;
+    auto from = static_cast<char*>(
+      snmalloc::Pal::reserve_at_least(2 * size).first);
+    auto to = from + size;
+    snmalloc::Pal::notify_using<NoZero>(from, 2 * size);
+    memset(from, 0x7e, size);
+    SNMALLOC_CHECK(snmalloc::Pal::move_pages(from, to, size));
+    check_fill(to, size, 0x7e);
+    check_zero(from, 0, size);
+  }
+#endif
+
+  size_t old_size = (5 << 20) + 5;
+  auto p = static_cast<char*>(rust_alloc(8, old_size));
+  memset(p, 0x6b, old_size);
+  // Leave stale bytes at the end of the last page.
+  memset(p + old_size, 0x6b, bits::align_up(old_size, 4096) - old_size);
+
+  size_t new_size = 40 << 20;
+  p = static_cast<char*>(rust_realloc_zeroed(p, 8, old_size, new_size));
+  SNMALLOC_CHECK(p != nullptr);
+  check_fill(p, old_size, 0x6b);
+  check_zero(p, old_size, new_size);
+
+  memset(p, 0x2d, new_size);
+  p = static_cast<char*>(rust_realloc(p, 8, new_size, old_size));
+  SNMALLOC_CHECK(p != nullptr);
+  check_fill(p, old_size, 0x2d);
+
+  p = static_cast<char*>(rust_realloc(p, 8, old_size, new_size));
+  SNMALLOC_CHECK(p != nullptr);
+  check_fill(p, old_size, 0x2d);
+  rust_dealloc(p, 8, new_size);
+}
+
+/**
+ * Returns the number of mappings in the process, or zero if they cannot be
+ * read.
+ */
+size_t map_count()
+{
+  std::ifstream maps("/proc/self/maps");
+  std::string line;
+  size_t count = 0;
+  while (std::getline(maps, line))
+    count++;
+  return count;
+}
+
+/**
+ * Reallocate many large blocks of different sizes, growing and shrinking
+ * them, checking that the number of mappings stays bounded.  Moving pages
+ * for all of them would split the mappings into thousands.
+ */
+void check_map_count()
+{
+  constexpr size_t count = 128;
+  size_t sizes[count];
+  void* blocks[count];
+  for (size_t i = 0; i < count; i++)
+  {
+    sizes[i] = (1 << 20) + i * 3 * OS_PAGE_SIZE;
+    blocks[i] = rust_alloc(8, sizes[i]);
+    SNMALLOC_CHECK(blocks[i] != nullptr);
+    memset(blocks[i], 0x11, sizes[i]);
+  }
+
+  size_t before = map_count();
+  for (size_t round = 0; round < 8; round++)
+  {
+    for (size_t i = 0; i < count; i++)
+    {
+      size_t size =
+        (round % 2 == 0) ? sizes[i] * 2 : sizes[i] / 2 + OS_PAGE_SIZE;
+      blocks[i] = rust_realloc(blocks[i], 8, sizes[i], size);
+      SNMALLOC_CHECK(blocks[i] != nullptr);
+      memset(blocks[i], 0x22, OS_PAGE_SIZE);
+      sizes[i] = size;
+    }
+  }
+#ifndef USE_POSIX_COMMIT_CHECKS
+  // Commit checks protect decommitted chunks, which splits mappings anyway.
+  SNMALLOC_CHECK(map_count() <= before + 64);
+#else
+  UNUSED(before);
+#endif
+
+  for (size_t i = 0; i < count; i++)
+    rust_dealloc(blocks[i], 8, sizes[i]);
+}
+
+int main()
+{
+  setup();
+
+  // Within one sizeclass, resizing must not move (and so not copy) the block.
+  sizeclass_t sc = size_to_sizeclass(100);
+  size_t big = sizeclass_to_size(sc);
+  size_t small = sizeclass_to_size(sc - 1) + 1;
+  auto p = static_cast<char*>(rust_alloc(8, big));
+  memset(p, 0x5a, big);
+  SNMALLOC_CHECK(rust_realloc(p, 8, big, small) == p);
+  SNMALLOC_CHECK(rust_realloc(p, 8, small, big) == p);
+
+  // The bytes past the shrunk size are stale, so growing zeroed must clear
+  // them even though the block does not move.
+  SNMALLOC_CHECK(rust_realloc(p, 8, big, small) == p);
+  SNMALLOC_CHECK(rust_realloc_zeroed(p, 8, small, big) == p);
+  check_fill(p, small, 0x5a);
+  check_zero(p, small, big);
+
+  // Moving to a larger sizeclass copies the contents and zeroes the rest.
+  size_t bigger = big * 4;
+  auto q = static_cast<char*>(rust_realloc_zeroed(p, 8, big, bigger));
+  SNMALLOC_CHECK(q != nullptr);
+  check_fill(q, small, 0x5a);
+  check_zero(q, small, bigger);
+
+  // Shrinking across sizeclasses preserves the prefix.
+  auto r = static_cast<char*>(rust_realloc_zeroed(q, 8, bigger, 16));
+  SNMALLOC_CHECK(r != nullptr);
+  check_fill(r, 16, 0x5a);
+  rust_dealloc(r, 8, 16);
+
+  check_realloc_aligned(64);
+  check_realloc_aligned(4096);
+
+  // Pages are copied by default; moving them is enabled for blocks of at
+  // least 4 MiB.
+  check_map_count();
+  check_realloc_large();
+  SNMALLOC_CHECK(!rust_set_page_move_threshold(1 << 20));
+  bool can_move = rust_set_page_move_threshold(4 << 20);
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK((can_move == pal_supports<PageMove, snmalloc::Pal>));
+#else
+  SNMALLOC_CHECK(!can_move);
+#endif
+  check_realloc_large();
+  SNMALLOC_CHECK(rust_set_page_move_threshold(0) == can_move);
+
+  return 0;
+}
@@ -0,0 +1,58 @@
+/**
+ * Checks that reservations from the Rust shim are aligned, that their pages
+ * can be committed and decommitted independently, and that address space
+ * handed out again after it is returned reads as zero.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  size_t page = rust_page_size();
+  SNMALLOC_CHECK(page == OS_PAGE_SIZE);
+
+  // A sparse reservation, of which only a few pages are used.
+  constexpr size_t size = bits::one_at_bit(30);
+  auto p = static_cast<char*>(rust_reserve(1, size));
+  SNMALLOC_CHECK(p != nullptr);
+  SNMALLOC_CHECK(is_aligned_block<OS_PAGE_SIZE>(p, size));
+
+  char* middle = p + size / 2;
+  SNMALLOC_CHECK(rust_commit(middle, page * 2));
+  SNMALLOC_CHECK(middle[0] == 0 && middle[page * 2 - 1] == 0);
+  memset(middle, 1, page * 2);
+  SNMALLOC_CHECK(rust_commit(p, page));
+  p[0] = 1;
+
+  SNMALLOC_CHECK(!rust_commit(middle + 1, page));
+  SNMALLOC_CHECK(!rust_decommit(middle, page - 1));
+  SNMALLOC_CHECK(rust_decommit(middle, page * 2));
+  SNMALLOC_CHECK(rust_commit(middle, page));
+  middle[0] = 2;
+  rust_unreserve(p, 1, size);
+
+  // Alignments larger than the size are honoured.
+  constexpr size_t alignment = bits::one_at_bit(24);
+  auto q = static_cast<char*>(rust_reserve(alignment, page));
+  SNMALLOC_CHECK(q != nullptr);
+  SNMALLOC_CHECK(pointer_align_up(q, alignment) == q);
+  SNMALLOC_CHECK(rust_commit(q, page));
+  SNMALLOC_CHECK(q[0] == 0);
+  q[0] = 1;
+  rust_unreserve(q, alignment, page);
+
+  // This may reuse the address space just returned.
+  auto r = static_cast<char*>(rust_reserve(alignment, page));
+  SNMALLOC_CHECK(r != nullptr);
+  SNMALLOC_CHECK(rust_commit(r, page));
+  SNMALLOC_CHECK(r[0] == 0);
+  rust_unreserve(r, alignment, page);
+
+  SNMALLOC_CHECK(rust_reserve(1, SIZE_MAX) == nullptr);
+
+  return 0;
+}
@@ -0,0 +1,45 @@
+/**
+ * Checks that the Rust shim resizes an allocation in place exactly when the
+ * new size fits the same sizeclass or chunk, and that the allocation can then
+ * be used and freed at its new size.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  for (size_t alignment = 1; alignment <= 4096; alignment *= 8)
+  {
+    for (size_t size = 1; size <= 4 * SUPERSLAB_SIZE;
+         size += (size >> 2) + 1)
+    {
+      size_t usable = rust_usable_size(alignment, size);
+
+      // Growing to the usable size, and shrinking back, stay in place.
+      auto p = static_cast<char*>(rust_alloc(alignment, size));
+      SNMALLOC_CHECK(rust_resize_in_place(p, alignment, size, usable));
+      memset(p, 0x5a, usable);
+      SNMALLOC_CHECK(rust_resize_in_place(p, alignment, usable, size));
+
+      // Growing beyond the usable size needs a move.
+      SNMALLOC_CHECK(!rust_resize_in_place(p, alignment, size, usable + 1));
+
+      // Shrinking to half stays in place only if the sizeclass is unchanged.
+      size_t half = (size + 1) / 2;
+      bool shrunk = rust_resize_in_place(p, alignment, size, half);
+#ifdef SNMALLOC_PASS_THROUGH
+      SNMALLOC_CHECK(shrunk);
+#else
+      SNMALLOC_CHECK(shrunk == (rust_usable_size(alignment, half) == usable));
+#endif
+
+      rust_dealloc(p, alignment, shrunk ? half : size);
+    }
+  }
+
+  return 0;
+}
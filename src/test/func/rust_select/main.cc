@@ -0,0 +1,50 @@
+/**
+ * Checks that the runtime-selectable Rust shim routes every call to the
+ * copy of the allocator selected before first use.
+ */
+
+#include <cstddef>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <pal/pal.h>
+
+extern "C" bool rust_select_checks(bool enable);
+extern "C" bool rust_checks_enabled();
+extern "C" void* rust_alloc(size_t alignment, size_t size);
+extern "C" void* rust_alloc_zeroed(size_t alignment, size_t size);
+extern "C" void rust_dealloc(void* ptr, size_t alignment, size_t size);
+extern "C" void*
+rust_realloc(void* ptr, size_t alignment, size_t old_size, size_t new_size);
+extern "C" size_t rust_fast_allocator_id_of(const void* ptr);
+extern "C" size_t rust_checks_allocator_id_of(const void* ptr);
+
+int main()
+{
+  SNMALLOC_CHECK(rust_select_checks(true));
+  SNMALLOC_CHECK(rust_select_checks(true));
+  SNMALLOC_CHECK(!rust_select_checks(false));
+  SNMALLOC_CHECK(rust_checks_enabled());
+
+  auto p = static_cast<char*>(rust_alloc(16, 100));
+  SNMALLOC_CHECK(p != nullptr);
+  memset(p, 0x5a, 100);
+
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(rust_checks_allocator_id_of(p) != 0);
+  SNMALLOC_CHECK(rust_fast_allocator_id_of(p) == 0);
+#endif
+
+  p = static_cast<char*>(rust_realloc(p, 16, 100, 5000));
+  SNMALLOC_CHECK(p != nullptr);
+  for (size_t i = 0; i < 100; i++)
+    SNMALLOC_CHECK(p[i] == 0x5a);
+  rust_dealloc(p, 16, 5000);
+
+  auto z = static_cast<char*>(rust_alloc_zeroed(8, 64));
+  for (size_t i = 0; i < 64; i++)
+    SNMALLOC_CHECK(z[i] == 0);
+  rust_dealloc(z, 8, 64);
+
+  return 0;
+}
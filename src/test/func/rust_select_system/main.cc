@@ -0,0 +1,79 @@
+/**
+ * Checks that the runtime-selectable Rust shim can route ordinary allocations
+ * to the system allocator, while snmalloc-specific buffers still come from
+ * snmalloc.
+ */
+
+#include <cstddef>
+#include <cstdint>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <pal/pal.h>
+
+extern "C" bool rust_select_system();
+extern "C" bool rust_select_checks(bool enable);
+extern "C" bool rust_system_enabled();
+extern "C" bool rust_checks_enabled();
+extern "C" void* rust_alloc(size_t alignment, size_t size);
+extern "C" void* rust_alloc_zeroed(size_t alignment, size_t size);
+extern "C" void rust_dealloc(void* ptr, size_t alignment, size_t size);
+extern "C" void*
+rust_realloc(void* ptr, size_t alignment, size_t old_size, size_t new_size);
+extern "C" void* rust_realloc_zeroed(
+  void* ptr, size_t alignment, size_t old_size, size_t new_size);
+extern "C" void* rust_io_buffer_alloc(size_t len);
+extern "C" void rust_io_buffer_dealloc(void* ptr, size_t len);
+extern "C" void* rust_alloc_locked(size_t alignment, size_t size);
+extern "C" void rust_dealloc_locked(void* ptr, size_t alignment, size_t size);
+extern "C" size_t rust_fast_allocator_id_of(const void* ptr);
+extern "C" size_t rust_checks_allocator_id_of(const void* ptr);
+
+int main()
+{
+  SNMALLOC_CHECK(rust_select_system());
+  SNMALLOC_CHECK(!rust_select_checks(false));
+  SNMALLOC_CHECK(rust_system_enabled());
+  SNMALLOC_CHECK(!rust_checks_enabled());
+
+  for (size_t alignment = 1; alignment <= 4096; alignment <<= 1)
+  {
+    auto p = static_cast<char*>(rust_alloc(alignment, 100));
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK((reinterpret_cast<uintptr_t>(p) % alignment) == 0);
+    memset(p, 0x5a, 100);
+
+#ifndef SNMALLOC_PASS_THROUGH
+    SNMALLOC_CHECK(rust_fast_allocator_id_of(p) == 0);
+    SNMALLOC_CHECK(rust_checks_allocator_id_of(p) == 0);
+#endif
+
+    p = static_cast<char*>(rust_realloc_zeroed(p, alignment, 100, 5000));
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK((reinterpret_cast<uintptr_t>(p) % alignment) == 0);
+    for (size_t i = 0; i < 5000; i++)
+      SNMALLOC_CHECK(p[i] == (i < 100 ? 0x5a : 0));
+
+    p = static_cast<char*>(rust_realloc(p, alignment, 5000, 10));
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(p[9] == 0x5a);
+    rust_dealloc(p, alignment, 10);
+
+    auto z = static_cast<char*>(rust_alloc_zeroed(alignment, 64));
+    for (size_t i = 0; i < 64; i++)
+      SNMALLOC_CHECK(z[i] == 0);
+    rust_dealloc(z, alignment, 64);
+  }
+
+  void* b = rust_io_buffer_alloc(100);
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(rust_fast_allocator_id_of(b) != 0);
+#endif
+  rust_io_buffer_dealloc(b, 100);
+
+  void* l = rust_alloc_locked(16, 100);
+  SNMALLOC_CHECK(l == nullptr);
+  rust_dealloc_locked(l, 16, 100);
+
+  return 0;
+}
@@ -0,0 +1 @@
+#include "../../../override/rust-select.cc"
@@ -0,0 +1,112 @@
+/**
+ * Checks that `rust_shrink` shrinks large allocations in place, returning
+ * the chunks no longer needed to the platform, and that the allocation can
+ * then be used and freed at its new size.  Smaller allocations are resized
+ * as by `rust_realloc`.  The per-thread statistics and the hooks see the
+ * new size.
+ */
+
+#define SNMALLOC_THREAD_STATS
+#define SNMALLOC_HOOKS
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+void check_fill(char* p, size_t to, char c)
+{
+  for (size_t i = 0; i < to; i += 4093)
+    SNMALLOC_CHECK(p[i] == c);
+  SNMALLOC_CHECK(p[to - 1] == c);
+}
+
+size_t hooked_size = 0;
+
+void on_alloc(void*, size_t size)
+{
+  hooked_size = size;
+}
+
+void on_dealloc(void*, size_t)
+{
+  hooked_size = 0;
+}
+
+/**
+ * The bytes that this thread has allocated and not freed.
+ */
+size_t thread_live()
+{
+  auto stats = ThreadStats::current();
+  return stats.bytes_allocated - stats.bytes_freed;
+}
+
+size_t released()
+{
+  size_t bytes, count;
+  rust_memory_released(&bytes, &count);
+  return bytes;
+}
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH
+  rust_set_alloc_hooks(on_alloc, on_dealloc);
+  for (size_t alignment = 8; alignment <= 4096; alignment *= 512)
+  {
+    // Shrinking across large classes splits off and decommits every chunk
+    // past the new one.
+    size_t old_size = 8 * SUPERSLAB_SIZE;
+    size_t new_size = 2 * SUPERSLAB_SIZE - 5;
+    size_t live = thread_live();
+    auto p = static_cast<char*>(rust_alloc(alignment, old_size));
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(thread_live() - live == old_size);
+    memset(p, 0x4e, old_size);
+
+    size_t before = released();
+    SNMALLOC_CHECK(rust_shrink(p, alignment, old_size, new_size) == p);
+    SNMALLOC_CHECK(
+      released() - before >= old_size - 2 * SUPERSLAB_SIZE - 2 * OS_PAGE_SIZE);
+    check_fill(p, new_size, 0x4e);
+    SNMALLOC_CHECK(thread_live() - live == 2 * SUPERSLAB_SIZE);
+    SNMALLOC_CHECK(hooked_size == new_size);
+    SNMALLOC_CHECK(rust_usable_size(alignment, new_size) >= new_size);
+
+    // The memory split off can be reused.
+    auto q = static_cast<char*>(rust_alloc(alignment, 2 * SUPERSLAB_SIZE));
+    SNMALLOC_CHECK(q != nullptr);
+    memset(q, 0x11, 2 * SUPERSLAB_SIZE);
+    check_fill(p, new_size, 0x4e);
+    rust_dealloc(q, alignment, 2 * SUPERSLAB_SIZE);
+
+    // Shrinking within a chunk zeroes the pages that are no longer used.
+    size_t smaller = SUPERSLAB_SIZE + 3;
+    memset(p, 0x4e, new_size);
+    SNMALLOC_CHECK(rust_shrink(p, alignment, new_size, smaller) == p);
+    check_fill(p, smaller, 0x4e);
+    size_t kept = bits::align_up(smaller, OS_PAGE_SIZE);
+    check_fill(p + kept, new_size - kept, 0);
+
+    SNMALLOC_CHECK(hooked_size == smaller);
+
+    rust_dealloc(p, alignment, smaller);
+    SNMALLOC_CHECK(thread_live() == live);
+  }
+  rust_set_alloc_hooks(nullptr, nullptr);
+#endif
+
+  // Small and medium allocations move, or stay put, as with realloc.
+  for (size_t size = 64; size <= 4 * SUPERSLAB_SIZE; size *= 4)
+  {
+    auto p = static_cast<char*>(rust_alloc(16, size));
+    memset(p, 0x3d, size);
+    p = static_cast<char*>(rust_shrink(p, 16, size, 40));
+    SNMALLOC_CHECK(p != nullptr);
+    check_fill(p, 40, 0x3d);
+    rust_dealloc(p, 16, 40);
+  }
+
+  return 0;
+}
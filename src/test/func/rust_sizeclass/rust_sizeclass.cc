@@ -0,0 +1,74 @@
+/**
+ * Checks that the Rust shim's sizeclass lookup agrees with the rounding the
+ * allocator applies, that its indices are increasing and dense, and that the
+ * sizeclass table and lookup by pointer agree with it.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  size_t capacity;
+  SNMALLOC_CHECK(rust_sizeclass_of(0, &capacity) == 0);
+  SNMALLOC_CHECK(capacity == MIN_ALLOC_SIZE);
+
+  size_t prev_index = 0, prev_capacity = capacity;
+  for (size_t size = 1; size <= 4 * SUPERSLAB_SIZE; size += (size >> 4) + 1)
+  {
+    size_t index = rust_sizeclass_of(size, &capacity);
+    SNMALLOC_CHECK(capacity >= size);
+    SNMALLOC_CHECK(capacity == round_size(size));
+    if (index == prev_index)
+    {
+      SNMALLOC_CHECK(capacity == prev_capacity);
+    }
+    else
+    {
+      SNMALLOC_CHECK((index == prev_index + 1) && (capacity > prev_capacity));
+    }
+    prev_index = index;
+    prev_capacity = capacity;
+
+#ifndef SNMALLOC_PASS_THROUGH
+    void* p = rust_alloc(1, size);
+    SNMALLOC_CHECK(sn_malloc_usable_size(p) == capacity);
+    size_t ptr_capacity;
+    SNMALLOC_CHECK(rust_sizeclass_of_ptr(p, &ptr_capacity) == index);
+    SNMALLOC_CHECK(ptr_capacity == capacity);
+    SNMALLOC_CHECK(
+      rust_sizeclass_of_ptr(pointer_offset(p, size - 1), &ptr_capacity) ==
+        index);
+    rust_dealloc(p, 1, size);
+#endif
+  }
+
+  static RustSizeclassInfo table[NUM_SIZECLASSES + NUM_LARGE_CLASSES];
+  size_t total = rust_sizeclass_table(table, 1);
+  SNMALLOC_CHECK(total == NUM_SIZECLASSES + NUM_LARGE_CLASSES);
+  SNMALLOC_CHECK(rust_sizeclass_table(table, total) == total);
+  for (size_t i = 0; i < total; i++)
+  {
+    SNMALLOC_CHECK(rust_sizeclass_of(table[i].object_size, &capacity) == i);
+    SNMALLOC_CHECK(capacity == table[i].object_size);
+    SNMALLOC_CHECK(table[i].objects_per_slab > 0);
+    SNMALLOC_CHECK(
+      table[i].objects_per_slab * table[i].object_size <= table[i].slab_size);
+  }
+
+  int local;
+  SNMALLOC_CHECK(
+    rust_sizeclass_of_ptr(&local, &capacity) ==
+      NUM_SIZECLASSES + NUM_LARGE_CLASSES);
+  SNMALLOC_CHECK(capacity == 0);
+
+  SNMALLOC_CHECK(
+    rust_sizeclass_of(SIZE_MAX, &capacity) ==
+      NUM_SIZECLASSES + NUM_LARGE_CLASSES);
+  SNMALLOC_CHECK(capacity == 0);
+
+  return 0;
+}
@@ -0,0 +1,110 @@
+/**
+ * Checks that the Rust shim's statistics only change when refreshed, and
+ * that they reflect allocations made between refreshes, that
+ * `stats_delta` reports the allocations made between two snapshots, and that
+ * statistics can be gathered while another thread allocates.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <atomic>
+#include <test/setup.h>
+#include <thread>
+
+int main()
+{
+  setup();
+
+  RustStats stats;
+  RustSizeclassStats sizeclasses[NUM_SIZECLASSES];
+
+  size_t n = rust_stats_read(&stats, sizeclasses, NUM_SIZECLASSES);
+  SNMALLOC_CHECK(n == NUM_SIZECLASSES);
+  SNMALLOC_CHECK(stats.epoch == 0);
+  SNMALLOC_CHECK(stats.reserved == 0);
+
+  constexpr size_t count = 100;
+  constexpr size_t size = 48;
+  void* objects[count];
+  for (auto& p : objects)
+    p = rust_alloc(1, size);
+
+  SNMALLOC_CHECK(rust_stats_refresh() == 1);
+  rust_stats_read(&stats, sizeclasses, NUM_SIZECLASSES);
+  SNMALLOC_CHECK(stats.epoch == 1);
+#ifndef SNMALLOC_PASS_THROUGH // Memory is not tracked with pass-through
+  SNMALLOC_CHECK(stats.reserved >= stats.committed);
+  SNMALLOC_CHECK(stats.live >= count * size);
+  SNMALLOC_CHECK(stats.peak > 0);
+  SNMALLOC_CHECK(stats.allocators >= 1);
+#endif
+
+  sizeclass_t sc = size_to_sizeclass(size);
+  SNMALLOC_CHECK(sizeclasses[sc].object_size == sizeclass_to_size(sc));
+#ifndef SNMALLOC_PASS_THROUGH
+  SNMALLOC_CHECK(
+    sizeclasses[sc].slabs * sizeclass_to_slab_capacity(sc) >= count);
+#endif
+#if defined(USE_SNMALLOC_STATS) && !defined(SNMALLOC_PASS_THROUGH)
+  SNMALLOC_CHECK(sizeclasses[sc].live >= count);
+  SNMALLOC_CHECK(sizeclasses[sc].total >= count);
+  SNMALLOC_CHECK(
+    sizeclasses[sc].live + sizeclasses[sc].free <=
+      sizeclasses[sc].slabs * sizeclass_to_slab_capacity(sc));
+#endif
+  size_t live = sizeclasses[sc].live;
+  RustStats before = stats;
+
+  // Freeing does not change the statistics until the next refresh.
+  for (auto p : objects)
+    rust_dealloc(p, 1, size);
+  rust_stats_read(&stats, sizeclasses, NUM_SIZECLASSES);
+  SNMALLOC_CHECK(sizeclasses[sc].live == live);
+
+  SNMALLOC_CHECK(rust_stats_refresh() == 2);
+  rust_stats_read(&stats, sizeclasses, sc + 1);
+#if defined(USE_SNMALLOC_STATS) && !defined(SNMALLOC_PASS_THROUGH)
+  SNMALLOC_CHECK(sizeclasses[sc].live == live - count);
+  SNMALLOC_CHECK(stats.deallocations - before.deallocations >= count);
+  SNMALLOC_CHECK(stats.bytes_freed - before.bytes_freed >= count * size);
+#endif
+  SNMALLOC_CHECK(stats.allocations >= before.allocations);
+  SNMALLOC_CHECK(stats.allocations >= stats.deallocations);
+
+  // A delta between snapshots covers the phase between them.
+  StatsSnapshot first;
+  stats_snapshot(first);
+  for (auto& p : objects)
+    p = rust_alloc(1, size);
+  StatsSnapshot second;
+  stats_snapshot(second);
+  StatsDelta delta = stats_delta(first, second);
+#if defined(USE_SNMALLOC_STATS) && !defined(SNMALLOC_PASS_THROUGH)
+  SNMALLOC_CHECK(delta.totals.allocations >= count);
+  SNMALLOC_CHECK(delta.totals.bytes_allocated >= count * size);
+  SNMALLOC_CHECK(delta.live >= static_cast<ptrdiff_t>(count * size));
+#else
+  SNMALLOC_CHECK(delta.totals.allocations == 0);
+#endif
+  for (auto p : objects)
+    rust_dealloc(p, 1, size);
+
+  // Another thread's counters can be read while it updates them.
+  std::atomic<bool> done{false};
+  std::thread churn([&]() {
+    while (!done)
+    {
+      void* local[count];
+      for (auto& p : local)
+        p = rust_alloc(1, size);
+      for (auto p : local)
+        rust_dealloc(p, 1, size);
+    }
+  });
+  for (size_t i = 0; i < 100; i++)
+    rust_stats_refresh();
+  done = true;
+  churn.join();
+
+  return 0;
+}
@@ -0,0 +1,50 @@
+/**
+ * Checks that the usable size reported by the Rust shim can all be written,
+ * and can be passed back as the size of the allocation, as an implementation
+ * of the `Allocator` trait does after returning it from `allocate`, and that
+ * `rust_alloc_excess` reports the same size.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <test/setup.h>
+
+int main()
+{
+  setup();
+
+  for (size_t alignment = 1; alignment <= 4096; alignment *= 4)
+  {
+    // Rust does not pass zero-sized layouts to the allocator.
+    for (size_t size = 1; size <= 4 * SUPERSLAB_SIZE;
+         size += (size >> 2) + 1)
+    {
+      size_t usable = rust_usable_size(alignment, size);
+      SNMALLOC_CHECK(usable >= size);
+
+      auto p = static_cast<char*>(rust_alloc(alignment, size));
+      SNMALLOC_CHECK(p != nullptr);
+      SNMALLOC_CHECK((reinterpret_cast<uintptr_t>(p) & (alignment - 1)) == 0);
+      memset(p, 0xa5, usable);
+
+      // Growing into the usable size stays in place, except for large
+      // allocations, which are always moved.
+      auto q = static_cast<char*>(rust_realloc(p, alignment, size, usable));
+      SNMALLOC_CHECK(q != nullptr);
+      if (usable <= sizeclass_to_size(NUM_SIZECLASSES - 1))
+        SNMALLOC_CHECK(q == p);
+      p = q;
+
+      // Freeing with the usable size rather than the requested size.
+      rust_dealloc(p, alignment, usable);
+
+      size_t excess;
+      p = static_cast<char*>(rust_alloc_excess(alignment, size, &excess));
+      SNMALLOC_CHECK(p != nullptr);
+      SNMALLOC_CHECK(excess == usable);
+      rust_dealloc(p, alignment, excess);
+    }
+  }
+
+  return 0;
+}
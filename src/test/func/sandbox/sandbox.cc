@@ -129,6 +129,28 @@ namespace
         real_state->push_large_stack(slab, large_class);
       }
 
+      /**
+       * Decide whether a freed chunk stays committed, proxies to the real
+       * implementation.
+       *
+       * This method must be implemented for `LargeAlloc` to work.
+       */
+      bool retain_committed(size_t large_class)
+      {
+        return real_state->retain_committed(large_class);
+      }
+
+      /**
+       * Report whether fresh memory is known to be zero, proxies to the real
+       * implementation.
+       *
+       * This method must be implemented for `LargeAlloc` to work.
+       */
+      bool fresh_memory_is_zero()
+      {
+        return real_state->fresh_memory_is_zero();
+      }
+
       /**
        * Reserve (and optionally commit) memory for a large sizeclass, proxies
        * to the real implementation.
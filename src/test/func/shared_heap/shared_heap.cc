@@ -0,0 +1,153 @@
+/**
+ * Builds a linked list in a shared heap, and checks that a child process
+ * that attaches to it can read the list, free it, allocate a list of its own
+ * for the parent, and allocate and free concurrently with the parent.
+ */
+
+#ifdef SNMALLOC_PASS_THROUGH
+/*
+ * This test does not make sense with malloc pass-through, skip it.
+ */
+int main()
+{
+  return 0;
+}
+#elif defined(_WIN32)
+/*
+ * The test uses `fork`.
+ */
+int main()
+{
+  return 0;
+}
+#else
+#  include <mem/sharedheap.h>
+#  include <snmalloc.h>
+#  include <stdio.h>
+#  include <stdlib.h>
+#  include <sys/wait.h>
+#  include <test/setup.h>
+#  include <unistd.h>
+
+using namespace snmalloc;
+
+struct Node
+{
+  Node* next;
+  size_t value;
+};
+
+Node* build(SharedHeap* heap, size_t count, size_t first)
+{
+  Node* head = nullptr;
+  for (size_t i = 0; i < count; i++)
+  {
+    auto n = static_cast<Node*>(heap->alloc(sizeof(Node)));
+    SNMALLOC_CHECK(n != nullptr);
+    SNMALLOC_CHECK(heap->contains(n));
+    n->next = head;
+    n->value = first + i;
+    head = n;
+  }
+  return head;
+}
+
+/**
+ * Check that the list at `head` holds `count` values from `first`, and free
+ * it.
+ */
+void consume(SharedHeap* heap, Node* head, size_t count, size_t first)
+{
+  size_t sum = 0;
+  size_t seen = 0;
+  while (head != nullptr)
+  {
+    Node* next = head->next;
+    sum += head->value;
+    seen++;
+    heap->dealloc(head);
+    head = next;
+  }
+  SNMALLOC_CHECK(seen == count);
+  SNMALLOC_CHECK(sum == count * first + count * (count - 1) / 2);
+}
+
+/**
+ * Allocate and free objects of varied sizes, including large ones.
+ */
+void churn(SharedHeap* heap)
+{
+  void* objects[64] = {};
+  for (size_t i = 0; i < 2000; i++)
+  {
+    size_t slot = (i * 7) % 64;
+    if (objects[slot] != nullptr)
+      heap->dealloc(objects[slot]);
+    size_t size = (i % 50 == 0) ? SUPERSLAB_SIZE : 16 + (i % 200) * 8;
+    objects[slot] = heap->alloc(size);
+    SNMALLOC_CHECK(objects[slot] != nullptr);
+    memset(objects[slot], static_cast<int>(i), 16);
+  }
+  for (auto p : objects)
+    if (p != nullptr)
+      heap->dealloc(p);
+}
+
+int main()
+{
+  setup();
+
+  char name[64];
+  snprintf(name, sizeof(name), "/snmalloc-shared-heap-%d", getpid());
+
+  SNMALLOC_CHECK(!SharedHeapHandle::create(name, SUPERSLAB_SIZE));
+  SNMALLOC_CHECK(!SharedHeapHandle::attach(name));
+
+  size_t size = SharedHeap::min_size() + 16 * SUPERSLAB_SIZE;
+  SharedHeapHandle heap = SharedHeapHandle::create(name, size);
+  SNMALLOC_CHECK(static_cast<bool>(heap));
+  SNMALLOC_CHECK(!SharedHeapHandle::create(name, size));
+
+  heap->set_root(build(heap.get(), 1000, 0));
+  void* base = heap.get();
+
+  pid_t child = fork();
+  SNMALLOC_CHECK(child >= 0);
+  if (child == 0)
+  {
+    // Drop the inherited mapping, so that the segment is attached afresh.
+    heap.detach();
+    SharedHeapHandle mine = SharedHeapHandle::attach(name);
+    SNMALLOC_CHECK(static_cast<bool>(mine));
+    SNMALLOC_CHECK(mine.get() == base);
+    consume(mine.get(), mine->root<Node>(), 1000, 0);
+    mine->set_root(build(mine.get(), 500, 1000));
+    churn(mine.get());
+    mine.detach();
+    _exit(0);
+  }
+
+  churn(heap.get());
+  int status;
+  SNMALLOC_CHECK(waitpid(child, &status, 0) == child);
+  SNMALLOC_CHECK(WIFEXITED(status) && (WEXITSTATUS(status) == 0));
+  consume(heap.get(), heap->root<Node>(), 500, 1000);
+
+  // The memory freed by both processes is reused.
+  for (size_t i = 0; i < 8; i++)
+    churn(heap.get());
+
+  SNMALLOC_CHECK(SharedHeapHandle::remove(name));
+  SNMALLOC_CHECK(!SharedHeapHandle::attach(name));
+  heap.detach();
+  SNMALLOC_CHECK(!heap);
+
+#  ifdef __linux__
+  SharedHeapHandle anon = SharedHeapHandle::create_anonymous(size);
+  SNMALLOC_CHECK(static_cast<bool>(anon));
+  churn(anon.get());
+#  endif
+
+  return 0;
+}
+#endif
@@ -0,0 +1,85 @@
+/**
+ * Checks that the per-sizeclass slab occupancy reported by an allocator
+ * follows slabs from full, to partially full, to returned to the superslab.
+ */
+
+#include <snmalloc.h>
+#include <stdio.h>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+using namespace snmalloc;
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+void test_occupancy()
+{
+  auto a = ThreadAlloc::get();
+  constexpr size_t size = 64;
+  constexpr size_t slabs = 3;
+  sizeclass_t sc = size_to_sizeclass(size);
+
+  // Keep the superslab alive once the slabs below are returned to it.
+  void* anchor = a->alloc(16);
+
+  auto before = a->slab_occupancy(sc);
+  SNMALLOC_CHECK(before.full == 0 && before.partial == 0);
+
+  std::vector<void*> objects;
+  for (size_t i = 0; i < slabs * (SLAB_SIZE / size); i++)
+    objects.push_back(a->alloc(size));
+
+  auto allocated = a->slab_occupancy(sc);
+  SNMALLOC_CHECK(allocated.full >= slabs);
+  SNMALLOC_CHECK(allocated.partial == 0);
+
+  // Free all but the first object seen in each slab.
+  std::vector<void*> kept;
+  std::vector<address_t> seen;
+  for (auto p : objects)
+  {
+    address_t slab = address_align_down<SLAB_SIZE>(address_cast(p));
+    bool found = false;
+    for (auto s : seen)
+      found |= (s == slab);
+    if (found)
+    {
+      a->dealloc(p, size);
+    }
+    else
+    {
+      seen.push_back(slab);
+      kept.push_back(p);
+    }
+  }
+
+  auto sparse = a->slab_occupancy(sc);
+  SNMALLOC_CHECK(sparse.partial == seen.size());
+  SNMALLOC_CHECK(sparse.full + sparse.partial == allocated.full);
+
+  size_t unused_before = a->unused_slabs();
+  for (auto p : kept)
+    a->dealloc(p, size);
+
+  auto after = a->slab_occupancy(sc);
+  SNMALLOC_CHECK(after.full == 0 && after.partial == 0);
+  SNMALLOC_CHECK(a->unused_slabs() == unused_before + seen.size());
+
+  auto out_of_range = a->slab_occupancy(NUM_SIZECLASSES);
+  SNMALLOC_CHECK(out_of_range.full == 0 && out_of_range.partial == 0);
+
+  a->dealloc(anchor);
+}
+#endif
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  // Use a fresh allocator so that earlier allocations do not interfere.
+  std::thread t(test_occupancy);
+  t.join();
+#endif
+  return 0;
+}
@@ -0,0 +1,91 @@
+/**
+ * Checks that each thread's counters record the memory it allocates and
+ * frees, including objects freed by another thread.
+ */
+
+#define SNMALLOC_THREAD_STATS
+#include <snmalloc.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+using namespace snmalloc;
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  constexpr size_t count = 10;
+  // Large allocations are counted with the size of their large class, so
+  // one that is not a power of two frees as many bytes as it allocates.
+  const size_t sizes[] = {48,
+                          sizeclass_to_size(NUM_SMALL_CLASSES),
+                          4 * SUPERSLAB_SIZE,
+                          3 * SUPERSLAB_SIZE + 1};
+
+  auto a = ThreadAlloc::get();
+  auto start = ThreadStats::current();
+
+  std::vector<void*> objects;
+  size_t bytes = 0;
+  for (auto size : sizes)
+  {
+    for (size_t i = 0; i < count; i++)
+      objects.push_back(a->alloc(size));
+    bytes += count * round_size(size);
+  }
+
+  auto allocated = ThreadStats::current();
+  SNMALLOC_CHECK(allocated.bytes_allocated - start.bytes_allocated == bytes);
+  SNMALLOC_CHECK(allocated.bytes_freed == start.bytes_freed);
+
+  // Free half locally and half on another thread, whose counters are its own.
+  size_t half = objects.size() / 2;
+  for (size_t i = 0; i < half; i++)
+    a->dealloc(objects[i]);
+
+  ThreadStats other;
+  std::thread t([&]() {
+    auto b = ThreadAlloc::get();
+    for (size_t i = half; i < objects.size(); i++)
+      b->dealloc(objects[i]);
+    b->flush();
+    other = ThreadStats::current();
+  });
+  t.join();
+
+  auto freed = ThreadStats::current();
+  size_t local_bytes = 0;
+  size_t remote_bytes = 0;
+  size_t remote_objects = 0;
+  for (size_t i = 0; i < objects.size(); i++)
+  {
+    size_t size = sizes[i / count];
+    if (i < half)
+      local_bytes += round_size(size);
+    else
+    {
+      remote_bytes += round_size(size);
+      // Large allocations are returned directly rather than sent back.
+      if (size <= sizeclass_to_size(NUM_SIZECLASSES - 1))
+        remote_objects++;
+    }
+  }
+  SNMALLOC_CHECK(freed.bytes_freed - allocated.bytes_freed == local_bytes);
+  SNMALLOC_CHECK(other.bytes_allocated == 0);
+  SNMALLOC_CHECK(other.bytes_freed == remote_bytes);
+  SNMALLOC_CHECK(other.remote_frees_sent == remote_objects);
+
+  // Flushing processes the frees that the other thread posted.
+  a->flush();
+  auto received = ThreadStats::current();
+  SNMALLOC_CHECK(
+    received.remote_frees_received - start.remote_frees_received ==
+      remote_objects);
+#endif
+
+  return 0;
+}
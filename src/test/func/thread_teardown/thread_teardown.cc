@@ -0,0 +1,72 @@
+/**
+ * Checks that tearing down a thread's allocator posts its remote frees and
+ * returns it to the pool for another thread, that the memory it releases is
+ * reported as reclaimed, and that the thread can still allocate afterwards.
+ */
+
+#include "../../../override/rust.cc"
+
+#include <atomic>
+#include <test/setup.h>
+#include <thread>
+#include <vector>
+
+int main()
+{
+  setup();
+
+#ifndef SNMALLOC_PASS_THROUGH // Depends on snmalloc specific features
+  auto a = ThreadAlloc::get();
+
+  constexpr size_t count = 16;
+  void* objects[count];
+  for (auto& p : objects)
+    p = a->alloc(1024);
+
+  std::atomic<bool> torn_down{false};
+  std::atomic<bool> reused{false};
+  size_t id = 0;
+  size_t reclaimed = memory_breakdown().reclaimed;
+
+  std::thread t1([&]() {
+    rust_init();
+    id = rust_current_allocator_id();
+    for (auto p : objects)
+      rust_dealloc(p, 1, 1024);
+    // Fill more than the superslab holding the allocator's message queue, so
+    // that the superslab of its bump allocator and free list is only
+    // released on teardown.
+    std::vector<void*> filler(2 * SUPERSLAB_SIZE / 48);
+    for (auto& p : filler)
+      p = rust_alloc(1, 48);
+    for (auto p : filler)
+      rust_dealloc(p, 1, 48);
+    rust_thread_teardown();
+    SNMALLOC_CHECK(memory_breakdown().reclaimed > reclaimed);
+    SNMALLOC_CHECK(ThreadAlloc::get_reference() == get_GlobalPlaceHolder());
+    torn_down = true;
+
+    // Keep this thread alive until its allocator has been reused.
+    while (!reused)
+      std::this_thread::yield();
+
+    auto p = rust_alloc(1, 64);
+    SNMALLOC_CHECK(p != nullptr);
+    SNMALLOC_CHECK(rust_allocator_id_of(p) == rust_current_allocator_id());
+    rust_dealloc(p, 1, 64);
+  });
+
+  while (!torn_down)
+    std::this_thread::yield();
+  SNMALLOC_CHECK(a->remote_queue_depth().first == count);
+
+  std::thread t2([&]() {
+    SNMALLOC_CHECK(rust_current_allocator_id() == id);
+    reused = true;
+  });
+  t2.join();
+  t1.join();
+#endif
+
+  return 0;
+}
@@ -0,0 +1,71 @@
+/**
+ * Checks that, when built with SNMALLOC_TRACE, the malloc shim records each
+ * call to the file named by SNMALLOC_TRACE_FILE.
+ */
+
+#define SNMALLOC_TRACE
+#define SNMALLOC_NAME_MANGLE(a) our_##a
+#include "../../../override/malloc.cc"
+
+#include <cstdio>
+#include <test/setup.h>
+#include <vector>
+
+int main()
+{
+  setup();
+
+  char path[] = "/tmp/snmalloc-trace-XXXXXX";
+  int fd = mkstemp(path);
+  SNMALLOC_CHECK(fd >= 0);
+  close(fd);
+  // Must be set before the first call through the shim.
+  setenv("SNMALLOC_TRACE_FILE", path, 1);
+
+  void* a = our_malloc(100);
+  void* b = our_memalign(256, 1000);
+  void* c = our_realloc(a, 5000);
+  our_free(b);
+  our_free(c);
+  trace::flush();
+
+  FILE* f = fopen(path, "rb");
+  SNMALLOC_CHECK(f != nullptr);
+  char header[8];
+  SNMALLOC_CHECK(fread(header, 1, 8, f) == 8);
+  SNMALLOC_CHECK(memcmp(header, "SNTRACE1", 8) == 0);
+  std::vector<trace::Record> records;
+  trace::Record r;
+  while (fread(&r, sizeof(r), 1, f) == 1)
+    records.push_back(r);
+  fclose(f);
+  unlink(path);
+
+  SNMALLOC_CHECK(records.size() == 5);
+  for (size_t i = 0; i < records.size(); i++)
+  {
+    SNMALLOC_CHECK(records[i].thread == 1);
+    if (i > 0)
+      SNMALLOC_CHECK(records[i].timestamp >= records[i - 1].timestamp);
+  }
+
+  SNMALLOC_CHECK(records[0].op == trace::Alloc);
+  SNMALLOC_CHECK(records[0].address == address_cast(a));
+  SNMALLOC_CHECK(records[0].size == 100);
+  SNMALLOC_CHECK(records[0].alignment_bits == 0);
+
+  SNMALLOC_CHECK(records[1].op == trace::Alloc);
+  SNMALLOC_CHECK(records[1].address == address_cast(b));
+  SNMALLOC_CHECK(records[1].alignment_bits == 8);
+
+  SNMALLOC_CHECK(records[2].op == trace::Realloc);
+  SNMALLOC_CHECK(records[2].address == address_cast(c));
+  SNMALLOC_CHECK(records[2].old_address == address_cast(a));
+  SNMALLOC_CHECK(records[2].size == 5000);
+
+  SNMALLOC_CHECK(records[3].op == trace::Free);
+  SNMALLOC_CHECK(records[3].address == address_cast(b));
+  SNMALLOC_CHECK(records[4].address == address_cast(c));
+
+  return 0;
+}
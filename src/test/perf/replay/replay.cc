@@ -0,0 +1,185 @@
+/**
+ * Replays an allocation trace recorded with SNMALLOC_TRACE and reports the
+ * throughput and peak memory, for comparing builds on a real workload:
+ *
+ *   perf-replay-1 --trace app.trace [--use_malloc]
+ *
+ * Without `--trace`, a synthetic trace with cross-thread frees is replayed.
+ */
+
+#include "test/opt.h"
+#include "test/replay.h"
+#include "test/setup.h"
+#include "test/usage.h"
+#include "test/xoroshiro.h"
+
+#include <iostream>
+#include <snmalloc.h>
+
+using namespace snmalloc;
+
+struct SnmallocAllocator
+{
+  static void* alloc(size_t size, size_t alignment)
+  {
+    return ThreadAlloc::get_noncachable()->alloc(
+      alignment == 0 ? size : aligned_size(alignment, size));
+  }
+
+  static void dealloc(void* p, size_t size, size_t alignment)
+  {
+    ThreadAlloc::get_noncachable()->dealloc(
+      p, alignment == 0 ? size : aligned_size(alignment, size));
+  }
+
+  static void* realloc(
+    void* p,
+    size_t old_size,
+    size_t old_alignment,
+    size_t new_size,
+    size_t new_alignment)
+  {
+    void* n = alloc(new_size, new_alignment);
+    if (n != nullptr)
+    {
+      memcpy(n, p, bits::min(old_size, new_size));
+      dealloc(p, old_size, old_alignment);
+    }
+    return n;
+  }
+};
+
+struct SystemAllocator
+{
+  static void* alloc(size_t size, size_t alignment)
+  {
+    if (alignment <= alignof(max_align_t))
+      return malloc(size);
+    return aligned_alloc(alignment, bits::align_up(size, alignment));
+  }
+
+  static void dealloc(void* p, size_t, size_t)
+  {
+    free(p);
+  }
+
+  static void* realloc(
+    void* p,
+    size_t old_size,
+    size_t old_alignment,
+    size_t new_size,
+    size_t new_alignment)
+  {
+    size_t max_alignment = bits::max(old_alignment, new_alignment);
+    if (max_alignment <= alignof(max_align_t))
+      return ::realloc(p, new_size);
+    void* n = alloc(new_size, new_alignment);
+    if (n != nullptr)
+    {
+      memcpy(n, p, bits::min(old_size, new_size));
+      free(p);
+    }
+    return n;
+  }
+};
+
+/**
+ * Build a trace in which each thread allocates, reallocates, and frees blocks
+ * allocated by any thread, including by reallocating them to zero bytes.
+ */
+void synthesise(replay::Trace& trace, size_t threads, size_t count)
+{
+  xoroshiro::p128r32 r;
+  std::vector<uint64_t> live;
+  uint64_t next_address = 0;
+
+  for (size_t i = 0; i < count; i++)
+  {
+    trace::Record rec = {};
+    rec.timestamp = i;
+    rec.thread = static_cast<uint32_t>(r.next() % threads) + 1;
+
+    size_t action = r.next() % 8;
+    if ((live.size() > 1024) || ((action < 3) && !live.empty()))
+    {
+      size_t victim = r.next() % live.size();
+      rec.op = trace::Free;
+      rec.address = live[victim];
+      live[victim] = live.back();
+      live.pop_back();
+    }
+    else if ((action == 3) && !live.empty())
+    {
+      size_t victim = r.next() % live.size();
+      rec.op = trace::Realloc;
+      rec.old_address = live[victim];
+      if ((r.next() % 16) == 0)
+      {
+        // `realloc(p, 0)` frees `p` and returns null.
+        live[victim] = live.back();
+        live.pop_back();
+      }
+      else
+      {
+        rec.address = live[victim] = (next_address += 16);
+        rec.size = 16 + (r.next() % 4096);
+      }
+    }
+    else
+    {
+      rec.op = trace::Alloc;
+      rec.address = (next_address += 16);
+      rec.size = 16 + (r.next() % 4096);
+      if (action == 7)
+        rec.alignment_bits = 6;
+      live.push_back(rec.address);
+    }
+    trace.add(rec);
+  }
+
+  trace.prepare();
+}
+
+int main(int argc, char** argv)
+{
+  setup();
+
+  opt::Opt opt(argc, argv);
+  const char* path = opt.is("--trace", static_cast<const char*>(nullptr));
+  bool use_malloc = opt.has("--use_malloc");
+
+  replay::Trace trace;
+  if (path == nullptr)
+  {
+    synthesise(
+      trace,
+      opt.is<size_t>("--threads", 4),
+      opt.is<size_t>("--count", 1 << 20));
+  }
+  else if (!trace.load(path))
+  {
+    std::cout << "Cannot read trace " << path << std::endl;
+    return 1;
+  }
+
+  std::cout << "Allocator is " << (use_malloc ? "System" : "snmalloc")
+            << std::endl;
+
+  auto result = use_malloc ? replay::run<SystemAllocator>(trace) :
+                             replay::run<SnmallocAllocator>(trace);
+
+  double seconds = static_cast<double>(result.nanoseconds) / 1e9;
+  std::cout << "Replayed " << result.operations << " operations on "
+            << trace.threads.size() << " threads in " << seconds << " s ("
+            << static_cast<double>(result.operations) / seconds / 1e6
+            << " Mops/s)" << std::endl
+            << "Peak live bytes in trace: " << trace.peak_live << std::endl
+            << "Peak resident bytes: " << usage::peak_memory() << std::endl;
+
+#ifndef NDEBUG
+  if (!use_malloc)
+    current_alloc_pool()->debug_check_empty();
+#endif
+
+  return 0;
+}
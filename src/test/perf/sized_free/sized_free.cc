@@ -0,0 +1,64 @@
+/**
+ * Compares `free` with the C23 sized `free_sized`, which can skip looking up
+ * the sizeclass in the pagemap.  Each run frees the same blocks in the same
+ * (shuffled) order, so the difference is the cost of the lookup.
+ */
+
+#include "test/measuretime.h"
+#include "test/setup.h"
+#include "test/xoroshiro.h"
+
+#include <vector>
+
+#define SNMALLOC_NAME_MANGLE(a) our_##a
+#include "../../../override/malloc.cc"
+
+using namespace snmalloc;
+
+void test_free(size_t count, size_t size, bool sized)
+{
+  std::vector<void*> blocks(count);
+  xoroshiro::p128r64 r;
+
+  for (auto& p : blocks)
+    p = our_malloc(size);
+
+  // Free in a random order, so that consecutive frees do not hit the same
+  // pagemap entries.
+  for (size_t i = count - 1; i > 0; i--)
+    std::swap(blocks[i], blocks[r.next() % (i + 1)]);
+
+  MeasureTime m;
+  m << "Count: " << std::setw(7) << count << ", Size: " << std::setw(6)
+    << size << ", " << (sized ? "free_sized" : "free      ");
+
+  if (sized)
+  {
+    for (auto p : blocks)
+      our_free_sized(p, size);
+  }
+  else
+  {
+    for (auto p : blocks)
+      our_free(p);
+  }
+}
+
+int main(int, char**)
+{
+  setup();
+
+  for (size_t size = 16; size <= 1 << 16; size <<= 2)
+  {
+    size_t count = (size_t{64} << 20) / size;
+    if (count > (1 << 20))
+      count = 1 << 20;
+
+    // Warm up, so that neither run pays for fetching fresh memory.
+    test_free(count, size, false);
+    test_free(count, size, false);
+    test_free(count, size, true);
+  }
+
+  return 0;
+}
@@ -0,0 +1,293 @@
+/**
+ * Configurable stress and soak test.
+ *
+ * A number of threads allocate, reallocate and free blocks through the
+ * malloc API for a fixed duration.  Every block is filled with a pattern
+ * derived from a per-block seed, and the pattern is checked before the block
+ * is reallocated or freed, so heap corruption is reported close to where it
+ * happens.  Some fraction of blocks are handed to other threads to free.
+ *
+ * Run with --help for the options.  The seed is printed at start up, so a
+ * failing configuration can be reproduced.
+ */
+
+#include "test/opt.h"
+#include "test/setup.h"
+#include "test/xoroshiro.h"
+
+#include <atomic>
+#include <chrono>
+#include <cstring>
+#include <iostream>
+#include <thread>
+#include <vector>
+
+#define SNMALLOC_NAME_MANGLE(a) our_##a
+#include "../../../override/malloc.cc"
+
+using namespace snmalloc;
+
+/**
+ * Header at the start of every block.  The rest of the block is filled from
+ * `seed`.
+ */
+struct Block
+{
+  size_t size;
+  uint64_t seed;
+};
+
+struct Config
+{
+  size_t threads;
+  size_t duration_ms;
+  size_t min_size;
+  size_t max_size;
+  bool log_sizes;
+  size_t remote_percent;
+  size_t realloc_percent;
+  size_t live;
+  uint64_t seed;
+};
+
+Config config;
+
+/**
+ * Blocks in transit between threads.  A thread frees whatever block it
+ * displaces, so most frees through this are remote.
+ */
+std::atomic<Block*>* in_transit;
+size_t in_transit_size;
+
+std::atomic<size_t> total_ops{0};
+std::atomic<size_t> total_remote{0};
+std::atomic<size_t> total_reallocs{0};
+
+uint8_t pattern(uint64_t seed, size_t i)
+{
+  return static_cast<uint8_t>(((seed + i) * 0x9E3779B97F4A7C15) >> 56);
+}
+
+void fill(Block* b, size_t from)
+{
+  auto* bytes = reinterpret_cast<uint8_t*>(b);
+  for (size_t i = bits::max(from, sizeof(Block)); i < b->size; i++)
+    bytes[i] = pattern(b->seed, i);
+}
+
+void verify(Block* b, size_t size, const char* when)
+{
+  auto* bytes = reinterpret_cast<uint8_t*>(b);
+  for (size_t i = sizeof(Block); i < size; i++)
+  {
+    if (bytes[i] != pattern(b->seed, i))
+    {
+      std::cout << "Corruption detected " << when << ": block " << b
+                << " of size " << b->size << " differs at offset " << i
+                << " (seed " << config.seed << ")" << std::endl;
+      abort();
+    }
+  }
+}
+
+size_t random_size(xoroshiro::p128r64& r)
+{
+  size_t min = config.min_size;
+  size_t max = config.max_size;
+  if (config.log_sizes)
+  {
+    // Pick a power of two range uniformly, then a size within it.
+    size_t min_bits = bits::next_pow2_bits(min);
+    size_t max_bits = bits::next_pow2_bits(max);
+    size_t b = min_bits + (r.next() % (max_bits - min_bits + 1));
+    max = bits::min(max, bits::one_at_bit(b));
+    min = bits::max(min, bits::one_at_bit(b) >> 1);
+  }
+  return min + (r.next() % (max - min + 1));
+}
+
+Block* allocate(xoroshiro::p128r64& r)
+{
+  size_t size = random_size(r);
+  auto* b = static_cast<Block*>(our_malloc(size));
+  if (b == nullptr)
+  {
+    std::cout << "Allocation of " << size << " bytes failed" << std::endl;
+    abort();
+  }
+  b->size = size;
+  b->seed = r.next();
+  fill(b, 0);
+  return b;
+}
+
+void release(Block* b, const char* when)
+{
+  verify(b, b->size, when);
+  our_free(b);
+}
+
+Block* reallocate(Block* b, xoroshiro::p128r64& r)
+{
+  size_t old_size = b->size;
+  size_t new_size = random_size(r);
+  verify(b, old_size, "before realloc");
+
+  auto* n = static_cast<Block*>(our_realloc(b, new_size));
+  if (n == nullptr)
+  {
+    std::cout << "Reallocation to " << new_size << " bytes failed"
+              << std::endl;
+    abort();
+  }
+
+  // The contents up to the smaller size must have been preserved.
+  verify(n, bits::min(old_size, new_size), "after realloc");
+  n->size = new_size;
+  fill(n, old_size);
+  return n;
+}
+
+void worker(size_t id)
+{
+  xoroshiro::p128r64 r(config.seed + id + 1);
+  std::vector<Block*> live(config.live, nullptr);
+  size_t ops = 0;
+  size_t remote = 0;
+  size_t reallocs = 0;
+
+  auto deadline = std::chrono::steady_clock::now() +
+    std::chrono::milliseconds(config.duration_ms);
+
+  while (std::chrono::steady_clock::now() < deadline)
+  {
+    // Check the time in batches; the clock is slow relative to malloc.
+    for (size_t i = 0; i < 1024; i++, ops++)
+    {
+      Block*& slot = live[r.next() % live.size()];
+      if (slot == nullptr)
+      {
+        slot = allocate(r);
+        continue;
+      }
+
+      size_t action = r.next() % 100;
+      if (action < config.remote_percent)
+      {
+        auto& e = in_transit[r.next() % in_transit_size];
+        Block* out = e.exchange(slot, std::memory_order_acq_rel);
+        if (out != nullptr)
+          release(out, "before remote free");
+        slot = nullptr;
+        remote++;
+      }
+      else if (action < config.remote_percent + config.realloc_percent)
+      {
+        slot = reallocate(slot, r);
+        reallocs++;
+      }
+      else
+      {
+        release(slot, "before free");
+        slot = nullptr;
+      }
+    }
+  }
+
+  for (auto b : live)
+  {
+    if (b != nullptr)
+      release(b, "at thread exit");
+  }
+
+  total_ops += ops;
+  total_remote += remote;
+  total_reallocs += reallocs;
+}
+
+void usage()
+{
+  std::cout
+    << "Options:" << std::endl
+    << "  --threads N          worker threads (default 4)" << std::endl
+    << "  --duration MS        run time in milliseconds (default 1000)"
+    << std::endl
+    << "  --min-size N         smallest allocation (default 16)" << std::endl
+    << "  --max-size N         largest allocation (default 65536)"
+    << std::endl
+    << "  --sizes uniform|log  size distribution (default log)" << std::endl
+    << "  --remote PERCENT     operations freeing on another thread "
+    << "(default 25)" << std::endl
+    << "  --realloc PERCENT    operations that reallocate (default 10)"
+    << std::endl
+    << "  --live N             live blocks per thread (default 4096)"
+    << std::endl
+    << "  --seed N             random seed (default 1)" << std::endl;
+}
+
+int main(int argc, char** argv)
+{
+  setup();
+
+  opt::Opt opt(argc, argv);
+  if (opt.has("--help"))
+  {
+    usage();
+    return 0;
+  }
+
+  config.threads = opt.is<size_t>("--threads", 4);
+  config.duration_ms = opt.is<size_t>("--duration", 1000);
+  config.min_size = opt.is<size_t>("--min-size", 16);
+  config.max_size = opt.is<size_t>("--max-size", 65536);
+  config.log_sizes = strcmp(opt.is("--sizes", "log"), "uniform") != 0;
+  config.remote_percent = opt.is<size_t>("--remote", 25);
+  config.realloc_percent = opt.is<size_t>("--realloc", 10);
+  config.live = opt.is<size_t>("--live", 4096);
+  config.seed = opt.is<uint64_t>("--seed", 1);
+
+  config.min_size = bits::max(config.min_size, sizeof(Block));
+  config.max_size = bits::max(config.max_size, config.min_size);
+  config.threads = bits::max<size_t>(config.threads, 1);
+  config.live = bits::max<size_t>(config.live, 1);
+  if (config.remote_percent + config.realloc_percent > 100)
+  {
+    std::cout << "--remote and --realloc must sum to at most 100" << std::endl;
+    return 1;
+  }
+
+  std::cout << "Stress: " << config.threads << " threads, "
+            << config.duration_ms << "ms, sizes " << config.min_size << "-"
+            << config.max_size << (config.log_sizes ? " log" : " uniform")
+            << ", remote " << config.remote_percent << "%, realloc "
+            << config.realloc_percent << "%, live " << config.live
+            << ", seed " << config.seed << std::endl;
+
+  in_transit_size = config.threads * 64;
+  in_transit = new std::atomic<Block*>[in_transit_size];
+  for (size_t i = 0; i < in_transit_size; i++)
+    in_transit[i] = nullptr;
+
+  std::vector<std::thread> threads;
+  for (size_t i = 0; i < config.threads; i++)
+    threads.emplace_back(worker, i);
+  for (auto& t : threads)
+    t.join();
+
+  for (size_t i = 0; i < in_transit_size; i++)
+  {
+    Block* b = in_transit[i];
+    if (b != nullptr)
+      release(b, "at exit");
+  }
+  delete[] in_transit;
+
+  std::cout << "Completed " << total_ops << " operations, " << total_remote
+            << " cross-thread frees, " << total_reallocs << " reallocs"
+            << std::endl;
+
+#if !defined(NDEBUG) && !defined(SNMALLOC_PASS_THROUGH)
+  current_alloc_pool()->debug_check_empty();
+#endif
+  return 0;
+}
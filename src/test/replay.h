@@ -0,0 +1,257 @@
+#pragma once
+
+#include "../override/trace.h"
+
+#include <algorithm>
+#include <atomic>
+#include <chrono>
+#include <cstdio>
+#include <cstdlib>
+#include <cstring>
+#include <memory>
+#include <thread>
+#include <unordered_map>
+#include <vector>
+
+/**
+ * Replay of allocation traces recorded with `SNMALLOC_TRACE`.
+ *
+ * Each thread in the trace is replayed on its own thread, in the order it
+ * made its calls.  A block freed or reallocated by a different thread from the
+ * one that allocated it is passed between the replay threads, so remote
+ * deallocation is exercised as in the original workload.  A thread that
+ * reaches such a call before the block has been allocated waits for it; the
+ * recorded timestamps are otherwise ignored, and the trace is replayed as fast
+ * as possible.
+ */
+namespace replay
+{
+  using snmalloc::trace::Record;
+
+  static constexpr size_t NONE = SIZE_MAX;
+
+  class Trace
+  {
+  public:
+    std::vector<Record> records;
+    /**
+     * For each record that frees or reallocates a block, the index of the
+     * record that produced the block, or NONE if it was allocated before
+     * recording started.
+     */
+    std::vector<size_t> source;
+    /**
+     * The indices of the records made by each thread, in order.
+     */
+    std::vector<std::vector<size_t>> threads;
+    /**
+     * Records producing blocks that are never freed in the trace.  These are
+     * freed after the replay.
+     */
+    std::vector<size_t> leaked;
+    /**
+     * The largest total of requested sizes live at any point in the trace.
+     */
+    size_t peak_live = 0;
+
+    void add(const Record& r)
+    {
+      records.push_back(r);
+    }
+
+    /**
+     * Load the trace in `path`.  Returns false if it cannot be read or is not
+     * a trace.
+     */
+    bool load(const char* path)
+    {
+      FILE* f = fopen(path, "rb");
+      if (f == nullptr)
+        return false;
+
+      char header[snmalloc::trace::HEADER_SIZE];
+      bool ok = (fread(header, 1, sizeof(header), f) == sizeof(header)) &&
+        (memcmp(header, snmalloc::trace::HEADER, sizeof(header)) == 0);
+      Record r;
+      while (ok && (fread(&r, sizeof(r), 1, f) == 1))
+        add(r);
+      fclose(f);
+      if (ok)
+        prepare();
+      return ok;
+    }
+
+    /**
+     * Match each deallocation to its allocation and split the records by
+     * thread.  Must be called after the last `add`.
+     */
+    void prepare()
+    {
+      std::unordered_map<uint64_t, size_t> live;
+      std::unordered_map<uint32_t, size_t> thread_index;
+      size_t live_bytes = 0;
+
+      source.assign(records.size(), NONE);
+      threads.clear();
+      leaked.clear();
+      peak_live = 0;
+
+      for (size_t i = 0; i < records.size(); i++)
+      {
+        const Record& r = records[i];
+
+        auto t = thread_index.emplace(r.thread, threads.size());
+        if (t.second)
+          threads.emplace_back();
+        threads[t.first->second].push_back(i);
+
+        // A reallocation to zero bytes frees its block, while one that failed
+        // leaves it live.
+        bool to_zero = (r.op == snmalloc::trace::Realloc) && (r.size == 0);
+        bool frees = (r.op == snmalloc::trace::Free) || to_zero ||
+          ((r.op == snmalloc::trace::Realloc) && (r.address != 0));
+        uint64_t freed = !frees                 ? 0 :
+          (r.op == snmalloc::trace::Realloc) ? r.old_address :
+                                               r.address;
+        auto it = live.find(freed);
+        if ((freed != 0) && (it != live.end()))
+        {
+          source[i] = it->second;
+          live_bytes -= records[it->second].size;
+          live.erase(it);
+        }
+
+        if ((r.op != snmalloc::trace::Free) && (r.address != 0) && !to_zero)
+        {
+          live[r.address] = i;
+          live_bytes += r.size;
+          peak_live = std::max(peak_live, live_bytes);
+        }
+      }
+
+      for (auto& l : live)
+        leaked.push_back(l.second);
+    }
+  };
+
+  struct Result
+  {
+    size_t operations;
+    uint64_t nanoseconds;
+  };
+
+  /**
+   * Replay `trace` with `Allocator`, which provides static `alloc(size,
+   * alignment)`, `dealloc(p, size, alignment)` and `realloc(p, old_size,
+   * old_alignment, new_size, new_alignment)`.  A zero alignment means none
+   * was requested.  A reallocation to zero bytes is replayed as a `dealloc`.
+   */
+  template<typename Allocator>
+  Result run(const Trace& trace)
+  {
+    size_t n = trace.records.size();
+    std::unique_ptr<std::atomic<void*>[]> blocks(new std::atomic<void*>[n]());
+    std::atomic<size_t> ready{0};
+    std::atomic<bool> go{false};
+
+    auto alignment = [](const Record& r) {
+      return r.alignment_bits == 0 ? 0 : size_t(1) << r.alignment_bits;
+    };
+
+    auto wait_for = [&](size_t i) {
+      void* p;
+      while ((p = blocks[i].load(std::memory_order_acquire)) == nullptr)
+        std::this_thread::yield();
+      return p;
+    };
+
+    auto produced = [&](size_t i, void* p) {
+      if (p == nullptr)
+      {
+        printf("Allocation failed during replay\n");
+        abort();
+      }
+      blocks[i].store(p, std::memory_order_release);
+    };
+
+    auto replay_thread = [&](const std::vector<size_t>& indices) {
+      ready++;
+      while (!go.load(std::memory_order_acquire))
+        std::this_thread::yield();
+
+      for (size_t i : indices)
+      {
+        const Record& r = trace.records[i];
+        size_t s = trace.source[i];
+        switch (r.op)
+        {
+          case snmalloc::trace::Alloc:
+            if (r.address != 0)
+              produced(i, Allocator::alloc(r.size, alignment(r)));
+            break;
+
+          case snmalloc::trace::Free:
+            if (s != NONE)
+            {
+              const Record& a = trace.records[s];
+              Allocator::dealloc(wait_for(s), a.size, alignment(a));
+            }
+            break;
+
+          case snmalloc::trace::Realloc:
+            if (r.size == 0)
+            {
+              if (s != NONE)
+              {
+                const Record& a = trace.records[s];
+                Allocator::dealloc(wait_for(s), a.size, alignment(a));
+              }
+              break;
+            }
+            if (r.address == 0)
+              break;
+            if (s == NONE)
+            {
+              produced(i, Allocator::alloc(r.size, alignment(r)));
+            }
+            else
+            {
+              const Record& a = trace.records[s];
+              produced(
+                i,
+                Allocator::realloc(
+                  wait_for(s), a.size, alignment(a), r.size, alignment(r)));
+            }
+            break;
+
+          default:
+            break;
+        }
+      }
+    };
+
+    std::vector<std::thread> threads;
+    for (auto& indices : trace.threads)
+      threads.emplace_back(replay_thread, std::cref(indices));
+    while (ready.load() != threads.size())
+      std::this_thread::yield();
+
+    auto start = std::chrono::steady_clock::now();
+    go.store(true, std::memory_order_release);
+    for (auto& t : threads)
+      t.join();
+    auto finish = std::chrono::steady_clock::now();
+
+    for (size_t i : trace.leaked)
+    {
+      const Record& a = trace.records[i];
+      Allocator::dealloc(blocks[i].load(), a.size, alignment(a));
+    }
+
+    return {n,
+            static_cast<uint64_t>(
+              std::chrono::duration_cast<std::chrono::nanoseconds>(
+                finish - start)
+                .count())};
+  }
+} // namespace replay
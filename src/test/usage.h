@@ -8,6 +8,8 @@
 #  include <windows.h>
 // Needs to be included after windows.h
 #  include <psapi.h>
+#else
+#  include <sys/resource.h>
 #endif
 
 #include <iomanip>
@@ -39,6 +41,29 @@ namespace usage
               << "\tPagefileUsage: " << pmc.PagefileUsage << std::endl
               << "\tPeakPagefileUsage: " << pmc.PeakPagefileUsage << std::endl
               << "\tPrivateUsage: " << pmc.PrivateUsage << std::endl;
+#endif
+  }
+
+  /**
+   * The peak resident memory of the process in bytes, or zero if unknown.
+   */
+  size_t peak_memory()
+  {
+#if defined(_WIN32)
+    PROCESS_MEMORY_COUNTERS pmc;
+    if (!GetProcessMemoryInfo(GetCurrentProcess(), &pmc, sizeof(pmc)))
+      return 0;
+    return pmc.PeakWorkingSetSize;
+#else
+    struct rusage ru;
+    if (getrusage(RUSAGE_SELF, &ru) != 0)
+      return 0;
+#  if defined(__APPLE__)
+    return static_cast<size_t>(ru.ru_maxrss);
+#  else
+    // Linux and the BSDs report kilobytes.
+    return static_cast<size_t>(ru.ru_maxrss) * 1024;
+#  endif
 #endif
   }
 };